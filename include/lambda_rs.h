@@ -0,0 +1,32 @@
+/* C interface to lambda_rs, see src/ffi.rs */
+#ifndef LAMBDA_RS_H
+#define LAMBDA_RS_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct LambdaTerm LambdaTerm;
+
+/* functions returning a pointer return NULL on failure and, if error is not
+   NULL, store a message there to be released with lambda_string_free */
+
+/* parse every language level, prelude names resolved */
+LambdaTerm *lambda_parse(const char *source, char **error);
+
+/* normal form under normal order within fuel steps, steps may be NULL */
+LambdaTerm *lambda_normalize(const LambdaTerm *term, size_t fuel, size_t *steps, char **error);
+
+/* pretty-printed, release with lambda_string_free */
+char *lambda_print(const LambdaTerm *term);
+
+void lambda_free(LambdaTerm *term);
+void lambda_string_free(char *text);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
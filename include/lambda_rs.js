@@ -0,0 +1,55 @@
+// glue for the wasm32 build of lambda_rs, see the wasm module in src/api.rs
+//
+//     cargo build --lib --release --target wasm32-unknown-unknown
+//
+//     import { load } from "./lambda_rs.js";
+//     const lambda = await load(fetch("lambda_rs.wasm"));
+//     lambda.normalizeWithFuel("<\\x.{x}|y>", 1000);
+//     // { ok: true, normal: "$y", term: "y", steps: 1 }, or { ok: false, error }
+//
+// every entry point takes source text and answers a plain object, errors of
+// the source included. the module needs no imports
+
+const encoder = new TextEncoder();
+const decoder = new TextDecoder();
+
+// `source` is a Response, a promise of one, or the bytes of the module
+export async function load(source) {
+  const resolved = await source;
+  const { instance } =
+    resolved instanceof ArrayBuffer || ArrayBuffer.isView(resolved)
+      ? await WebAssembly.instantiate(resolved)
+      : await WebAssembly.instantiateStreaming(resolved);
+  return wrap(instance.exports);
+}
+
+// copy `text` in, call `entry` with its pointer, length and `args`, read the
+// length-prefixed JSON answer and give both buffers back
+function call(exports, entry, text, ...args) {
+  const bytes = encoder.encode(text);
+  const input = exports.alloc(bytes.length);
+  new Uint8Array(exports.memory.buffer, input, bytes.length).set(bytes);
+  const output = exports[entry](input, bytes.length, ...args);
+  exports.dealloc(input, bytes.length);
+  // memory may have grown during the call, take the buffer afresh
+  const length = new DataView(exports.memory.buffer).getUint32(output, true);
+  const json = decoder.decode(
+    new Uint8Array(exports.memory.buffer, output + 4, length),
+  );
+  exports.dealloc(output, length + 4);
+  return JSON.parse(json);
+}
+
+export function wrap(exports) {
+  return {
+    parse: (source) => call(exports, "parse", source),
+    normalizeWithFuel: (source, fuel) =>
+      call(exports, "normalize_with_fuel", source, fuel),
+    // "normal" or "applicative"
+    trace: (source, strategy, steps) =>
+      call(exports, "trace", source, strategy === "applicative" ? 1 : 0, steps),
+    pretty: (source) => call(exports, "pretty", source),
+    typeOf: (source) => call(exports, "type_of", source),
+    highlight: (source) => call(exports, "highlight", source),
+  };
+}
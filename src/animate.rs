@@ -0,0 +1,249 @@
+// a reduction sequence (see graph::sequence) as an animated SVG, for slides
+// and course pages: one frame per term, drawn as the tree of tui with the
+// redex about to be contracted shaded and its root in bold
+//
+// the frames are switched by SMIL, which browsers play in an <img> too, and
+// the text is monospaced so the tree lines up without measuring the font
+use crate::graph::{self, Graph};
+use crate::pretty_printer::PrettyPrinter;
+use crate::reduce;
+use crate::symbol::Symbol;
+use crate::tui::{self, TreeLine};
+
+const FONT_SIZE: f64 = 14.0;
+const LINE: f64 = 18.0;
+const CHAR: f64 = 8.4; // the advance of a monospace font at FONT_SIZE
+const MARGIN: f64 = 12.0;
+const HEADER: f64 = 2.0 * LINE; // the step and the term as text
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// the tree lines of a frame, each marked when it is in the redex, and the
+// redex root drawn in bold
+struct Frame {
+    title: String,
+    caption: String,
+    lines: Vec<TreeLine>,
+    root: Option<usize>,
+}
+
+// the column a tree line starts at and its text: the connector to its parent,
+// then its label. the rails above are drawn as lines, see `rails`, so the
+// text stays as long as the label however deep the node is
+fn placed(line: &TreeLine) -> (usize, String) {
+    match line.depth {
+        0 => (0, line.label.clone()),
+        depth => {
+            let connector = if line.last { "└─ " } else { "├─ " };
+            (3 * (depth - 1), format!("{}{}", connector, line.label))
+        }
+    }
+}
+
+// the rail from each parent down to its last child, as the column and the
+// rows of its first and last child: in pre-order a first child comes right
+// after its parent
+fn rails(lines: &[TreeLine]) -> Vec<(usize, usize, usize)> {
+    let mut first: Vec<usize> = Vec::new();
+    let mut found = Vec::new();
+    for (row, line) in lines.iter().enumerate().skip(1) {
+        if lines[row - 1].depth + 1 == line.depth {
+            first.truncate(line.depth - 1);
+            first.push(row);
+        }
+        if line.last && first[line.depth - 1] < row {
+            found.push((3 * (line.depth - 1), first[line.depth - 1], row));
+        }
+    }
+    found
+}
+
+fn frames(steps: &Graph, free: &[Symbol]) -> Vec<Frame> {
+    let last = steps.nodes.len() - 1;
+    steps
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(index, term)| {
+            let path = steps.edges.get(index).map(|edge| edge.path.as_slice());
+            let mut lines: Vec<TreeLine> = tui::tree_lines(term, free, path).collect();
+            // pre-order, so the redex is its root's line and the size - 1 after it
+            let root = lines.iter().position(|line| line.marked);
+            if let (Some(root), Some(path)) = (root, path) {
+                let size = reduce::subterm(term, path).size();
+                for line in &mut lines[root..root + size] {
+                    line.marked = true;
+                }
+            }
+            let title = if index < last {
+                format!("step {} of {}", index, last)
+            } else if steps.frontier[index] {
+                format!("stopped after {} steps", last)
+            } else {
+                format!("normal form after {} steps", last)
+            };
+            Frame {
+                title,
+                caption: graph::label(PrettyPrinter::new().format(term, free)),
+                lines,
+                root,
+            }
+        })
+        .collect()
+}
+
+// `seconds` per frame, the animation loops
+pub fn svg(steps: &Graph, free: &[Symbol], seconds: f64) -> Result<String, String> {
+    let frames = frames(steps, free);
+    let columns = frames
+        .iter()
+        .flat_map(|frame| {
+            let lines = frame.lines.iter().map(|line| {
+                let (column, text) = placed(line);
+                column + text.chars().count()
+            });
+            lines.chain([frame.title.chars().count(), frame.caption.chars().count()])
+        })
+        .max()
+        .unwrap_or(0);
+    let rows = frames
+        .iter()
+        .map(|frame| frame.lines.len())
+        .max()
+        .unwrap_or(0);
+    let width = 2.0 * MARGIN + columns as f64 * CHAR;
+    let height = 2.0 * MARGIN + HEADER + rows as f64 * LINE;
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" \
+         viewBox=\"0 0 {:.0} {:.0}\" font-family=\"monospace\" font-size=\"{}\">\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n",
+        width, height, width, height, FONT_SIZE
+    );
+    let count = frames.len();
+    let total = seconds * count as f64;
+    for (index, frame) in frames.iter().enumerate() {
+        out.push_str(&visibility(index, count, total));
+        let baseline = |row: f64| MARGIN + row * LINE + FONT_SIZE;
+        out.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" fill=\"#555\">{}</text>\n",
+            MARGIN,
+            baseline(0.0),
+            escape(&frame.title)
+        ));
+        out.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\">{}</text>\n",
+            MARGIN,
+            baseline(1.0),
+            escape(&frame.caption)
+        ));
+        let top = |row: usize| MARGIN + HEADER + row as f64 * LINE;
+        let left = |column: usize| MARGIN + column as f64 * CHAR;
+        for (row, line) in frame.lines.iter().enumerate() {
+            let (column, text) = placed(line);
+            if line.marked {
+                out.push_str(&format!(
+                    "<rect x=\"{:.1}\" y=\"{}\" width=\"{:.1}\" height=\"{}\" fill=\"#ffe9a8\"/>\n",
+                    left(column) - 2.0,
+                    top(row),
+                    text.chars().count() as f64 * CHAR + 4.0,
+                    LINE
+                ));
+            }
+            let weight = if frame.root == Some(row) {
+                " font-weight=\"bold\""
+            } else {
+                ""
+            };
+            out.push_str(&format!(
+                "<text x=\"{:.1}\" y=\"{}\" xml:space=\"preserve\"{}>{}</text>\n",
+                left(column),
+                top(row) + FONT_SIZE,
+                weight,
+                escape(&text)
+            ));
+        }
+        // from the first child's line down to the middle of the last one's
+        for (column, first, last) in rails(&frame.lines) {
+            let x = left(column) + CHAR / 2.0;
+            out.push_str(&format!(
+                "<line x1=\"{:.1}\" y1=\"{}\" x2=\"{:.1}\" y2=\"{}\" stroke=\"black\"/>\n",
+                x,
+                top(first),
+                x,
+                top(last) + LINE / 2.0
+            ));
+        }
+        out.push_str("</g>\n");
+    }
+    out.push_str("</svg>\n");
+    Ok(out)
+}
+
+// the opening of frame `index`, shown during its share of `total` seconds
+fn visibility(index: usize, count: usize, total: f64) -> String {
+    if count == 1 {
+        return "<g>\n".to_string();
+    }
+    let time = |frame: usize| format!("{:.4}", frame as f64 / count as f64);
+    let (values, times) = if index == 0 {
+        ("visible;hidden", format!("0;{}", time(1)))
+    } else if index + 1 == count {
+        ("hidden;visible", format!("0;{}", time(index)))
+    } else {
+        (
+            "hidden;visible;hidden",
+            format!("0;{};{}", time(index), time(index + 1)),
+        )
+    };
+    format!(
+        "<g visibility=\"hidden\">\n<animate attributeName=\"visibility\" values=\"{}\" \
+         keyTimes=\"{}\" dur=\"{}s\" calcMode=\"discrete\" repeatCount=\"indefinite\"/>\n",
+        values, times, total
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{self, ParserConfig};
+    use crate::reduce::Strategy;
+
+    #[test]
+    fn one_frame_per_term_with_the_redex_shaded() {
+        let (term, free) =
+            parser::parse_with_config(r"<\x.{x}|y>", ParserConfig::default()).unwrap();
+        let steps = graph::sequence(&term, Strategy::Normal, 20);
+        let svg = svg(&steps, &free, 1.0).unwrap();
+        assert_eq!(svg.matches("<g").count(), 2);
+        assert!(svg.contains("step 0 of 1"));
+        assert!(svg.contains("normal form after 1 steps"));
+        // the redex is the whole first tree, the normal form has no redex
+        assert_eq!(svg.matches("fill=\"#ffe9a8\"").count(), 4);
+        assert_eq!(svg.matches("font-weight=\"bold\"").count(), 1);
+    }
+
+    #[test]
+    fn deep_terms_are_drawn_in_linear_size() {
+        let n = 25_000;
+        let source = format!("{}x{}", r"\x.{".repeat(n), "}".repeat(n));
+        let (term, free) = parser::parse_with_config(&source, ParserConfig::default()).unwrap();
+        let steps = graph::sequence(&term, Strategy::Normal, 20);
+        let svg = svg(&steps, &free, 1.0).unwrap();
+        assert_eq!(svg.matches("<text").count(), n + 3);
+        assert!(svg.len() < 100 * n);
+    }
+
+    #[test]
+    fn rails_run_from_the_first_child_to_the_last() {
+        let (term, free) =
+            parser::parse_with_config(r"<\x.{<x|y>}|z>", ParserConfig::default()).unwrap();
+        let lines: Vec<TreeLine> = tui::tree_lines(&term, &free, None).collect();
+        // the root's children on rows 1 and 5, the inner @'s on 3 and 4
+        assert_eq!(rails(&lines), [(6, 3, 4), (0, 1, 5)]);
+    }
+}
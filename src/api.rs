@@ -0,0 +1,333 @@
+// string in, JSON out: the entry points for embedding the engine, with every
+// error turned into data. on wasm32 they are exported to the host as well
+//
+// answers are objects with "ok": true and the result, or "ok": false and an
+// "error" message
+use std::time::Instant;
+
+use crate::graph;
+use crate::highlight::{self, Class};
+use crate::json::Json;
+use crate::parser::{self, ParserConfig, Term};
+use crate::prelude;
+use crate::pretty_printer::PrettyPrinter;
+use crate::reduce::{self, Strategy};
+use crate::symbol::Symbol;
+use crate::types;
+use crate::unparse::unparse;
+
+// every language level, prelude names resolved, as in the REPL. nothing on
+// the way recurses per node, so deep terms are fine on a host's small stack
+pub(crate) fn parse_source(source: &str) -> Result<(Term, Vec<Symbol>), String> {
+    let config = ParserConfig {
+        system_f: true,
+        ..Default::default()
+    };
+    let (term, free) = parser::parse_with_config(source, config)?;
+    Ok((prelude::resolve(&term, &free), free))
+}
+
+fn failure(error: String) -> Json {
+    Json::object([("ok", false.into()), ("error", error.into())])
+}
+
+// the term in source syntax, its free names and size
+pub fn parse(source: &str) -> Json {
+    match parse_source(source) {
+        Ok((term, free)) => Json::object([
+            ("ok", true.into()),
+            ("term", unparse(&term, &free).into()),
+            (
+                "free",
+                Json::Array(free.into_iter().map(Json::from).collect()),
+            ),
+            ("size", term.size().into()),
+        ]),
+        Err(error) => failure(error),
+    }
+}
+
+// normal order in at most `fuel` steps: the normal form printed, in source
+// syntax and the steps taken
+pub fn normalize_with_fuel(source: &str, fuel: usize) -> Json {
+    normalize_with(source, Some(fuel), Strategy::Normal)
+}
+
+// same under any strategy, applicative order stops at a value. without fuel
+// the term gets as many steps as in the REPL
+pub fn normalize_with(source: &str, fuel: Option<usize>, strategy: Strategy) -> Json {
+    normalize_within(source, fuel, strategy, &Limits::default())
+}
+
+// what a reduction may take besides steps: the nodes of the terms on the way
+// and the time until it is given up, for a server the requests share
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Limits {
+    pub max_size: Option<usize>,
+    pub deadline: Option<Instant>,
+}
+
+// normalize_with, failing once a term on the way or the time taken goes past
+// `limits`
+pub(crate) fn normalize_within(
+    source: &str,
+    fuel: Option<usize>,
+    strategy: Strategy,
+    limits: &Limits,
+) -> Json {
+    let (term, free) = match parse_source(source) {
+        Ok(parsed) => parsed,
+        Err(error) => return failure(error),
+    };
+    let fuel = fuel.unwrap_or_else(|| reduce::default_fuel(reduce::termination(&term)));
+    let mut current = term;
+    for steps in 0..=fuel {
+        if let Some(max) = limits.max_size
+            && current.size() > max
+        {
+            return failure(format!(
+                "the term grew past {} nodes after {} steps",
+                max, steps
+            ));
+        }
+        if limits
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return failure(format!("out of time after {} steps", steps));
+        }
+        match reduce::step_with(&current, strategy) {
+            Some(next) => current = next,
+            None => {
+                return Json::object([
+                    ("ok", true.into()),
+                    (
+                        "normal",
+                        PrettyPrinter::new().format(&current, &free).into(),
+                    ),
+                    ("term", unparse(&current, &free).into()),
+                    ("steps", steps.into()),
+                ]);
+            }
+        }
+    }
+    failure(format!("no normal form within {} steps", fuel))
+}
+
+// the fields of a normalize request: "term", and optionally "strategy",
+// normal or applicative, and "fuel"
+pub(crate) fn normalize_params(params: &Json) -> Result<(&str, Option<usize>, Strategy), String> {
+    let source = params
+        .get("term")
+        .and_then(Json::as_str)
+        .ok_or("\"term\" must be a string")?;
+    let strategy = match params.get("strategy").map(Json::as_str) {
+        None | Some(Some("normal")) => Strategy::Normal,
+        Some(Some("applicative")) => Strategy::Applicative,
+        _ => return Err("\"strategy\" must be \"normal\" or \"applicative\"".to_string()),
+    };
+    let fuel = match params.get("fuel") {
+        None => None,
+        Some(fuel) => Some(
+            fuel.as_usize()
+                .ok_or("\"fuel\" must be a non-negative integer")?,
+        ),
+    };
+    Ok((source, fuel, strategy))
+}
+
+// at most `steps` contractions under `strategy`: each term pretty-printed with
+// the redex contracted next, null for the last, and whether the last term is
+// where the strategy stops
+pub fn trace(source: &str, strategy: Strategy, steps: usize) -> Json {
+    let (term, free) = match parse_source(source) {
+        Ok(parsed) => parsed,
+        Err(error) => return failure(error),
+    };
+    let sequence = graph::sequence(&term, strategy, steps);
+    let terms = sequence
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| {
+            let redex = sequence.edges.get(index).map(|edge| {
+                let binders = reduce::binders_along(node, &edge.path);
+                PrettyPrinter::new().format_under(
+                    reduce::subterm(node, &edge.path),
+                    &binders,
+                    &free,
+                )
+            });
+            Json::object([
+                ("term", PrettyPrinter::new().format(node, &free).into()),
+                ("redex", redex.map_or(Json::Null, Json::from)),
+            ])
+        })
+        .collect();
+    let last = sequence.nodes.len() - 1;
+    Json::object([
+        ("ok", true.into()),
+        ("steps", Json::Array(terms)),
+        ("done", (!sequence.frontier[last]).into()),
+    ])
+}
+
+pub fn pretty(source: &str) -> Json {
+    match parse_source(source) {
+        Ok((term, free)) => Json::object([
+            ("ok", true.into()),
+            ("pretty", PrettyPrinter::new().format(&term, &free).into()),
+        ]),
+        Err(error) => failure(error),
+    }
+}
+
+// the classified spans of highlight::classify as byte offsets, with "binder"
+// set on binders and bound variables
+pub fn highlight(source: &str) -> Json {
+    let config = ParserConfig {
+        system_f: true,
+        ..Default::default()
+    };
+    let spans = highlight::classify(source, config)
+        .into_iter()
+        .map(|(span, class)| {
+            let (name, binder) = match class {
+                Class::Binder(id) => ("binder", Some(id)),
+                Class::Bound(id) => ("bound", Some(id)),
+                Class::Free => ("free", None),
+                Class::Keyword => ("keyword", None),
+                Class::Name => ("name", None),
+                Class::Number => ("number", None),
+                Class::Punctuation => ("punctuation", None),
+                Class::Comment => ("comment", None),
+            };
+            let mut fields = vec![
+                ("start", span.start.into()),
+                ("end", span.end.into()),
+                ("class", name.into()),
+            ];
+            fields.extend(binder.map(|id| ("binder", id.into())));
+            Json::object(fields)
+        })
+        .collect();
+    Json::object([("ok", true.into()), ("spans", Json::Array(spans))])
+}
+
+// the principal type, as :type shows it in the REPL
+pub fn type_of(source: &str) -> Json {
+    match parse_source(source) {
+        Ok((term, _)) => match types::type_of(&term) {
+            Ok(ty) => Json::object([("ok", true.into()), ("type", ty.to_string().into())]),
+            Err(error) => failure(error.to_string()),
+        },
+        Err(error) => failure(error),
+    }
+}
+
+// without wasm-bindgen the host does the marshalling: it copies UTF-8 source
+// into a buffer from `alloc`, passes pointer and length, and gets back a
+// pointer to a little-endian u32 length followed by the JSON text. both go
+// back through `dealloc`, the answer with its length plus 4. the entry points
+// do not panic, errors of the source come back as answers there too.
+// include/lambda_rs.js does this for JS callers, as lambda_rs.h declares ffi
+// for C, and playground.html carries its own copy
+#[cfg(target_arch = "wasm32")]
+mod wasm {
+    use super::{Json, Strategy};
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn alloc(len: usize) -> *mut u8 {
+        Box::into_raw(vec![0u8; len].into_boxed_slice()) as *mut u8
+    }
+
+    // SAFETY: `ptr` and `len` must come from alloc or an answer
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn dealloc(ptr: *mut u8, len: usize) {
+        drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)) });
+    }
+
+    fn answer(json: Json) -> *mut u8 {
+        let text = json.to_string();
+        let mut out = Vec::with_capacity(4 + text.len());
+        out.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        out.extend_from_slice(text.as_bytes());
+        Box::into_raw(out.into_boxed_slice()) as *mut u8
+    }
+
+    // SAFETY: `ptr` must point to `len` readable bytes
+    unsafe fn run(ptr: *const u8, len: usize, entry: impl FnOnce(&str) -> Json) -> *mut u8 {
+        let bytes = unsafe { std::slice::from_raw_parts(ptr, len) };
+        answer(match std::str::from_utf8(bytes) {
+            Ok(source) => entry(source),
+            Err(_) => super::failure("source is not UTF-8".to_string()),
+        })
+    }
+
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn parse(ptr: *const u8, len: usize) -> *mut u8 {
+        unsafe { run(ptr, len, super::parse) }
+    }
+
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn normalize_with_fuel(
+        ptr: *const u8,
+        len: usize,
+        fuel: usize,
+    ) -> *mut u8 {
+        unsafe { run(ptr, len, |source| super::normalize_with_fuel(source, fuel)) }
+    }
+
+    // normal order for a zero `applicative`, applicative order otherwise
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn trace(
+        ptr: *const u8,
+        len: usize,
+        applicative: u32,
+        steps: usize,
+    ) -> *mut u8 {
+        let strategy = match applicative {
+            0 => Strategy::Normal,
+            _ => Strategy::Applicative,
+        };
+        unsafe { run(ptr, len, |source| super::trace(source, strategy, steps)) }
+    }
+
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn pretty(ptr: *const u8, len: usize) -> *mut u8 {
+        unsafe { run(ptr, len, super::pretty) }
+    }
+
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn type_of(ptr: *const u8, len: usize) -> *mut u8 {
+        unsafe { run(ptr, len, super::type_of) }
+    }
+
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn highlight(ptr: *const u8, len: usize) -> *mut u8 {
+        unsafe { run(ptr, len, super::highlight) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok(answer: &Json) -> bool {
+        answer.get("ok") == Some(&Json::Bool(true))
+    }
+
+    #[test]
+    fn deep_terms_are_answered() {
+        let n = 20_000;
+        // a redex under the lambdas, one step from the normal form
+        let source = format!("{}<\\y.{{y}}|x>{}", r"\x.{".repeat(n), "}".repeat(n));
+        assert!(ok(&parse(&source)));
+        let normal = normalize_with_fuel(&source, 10);
+        assert_eq!(normal.get("steps").and_then(Json::as_usize), Some(1));
+        assert!(ok(&trace(&source, Strategy::Normal, 10)));
+        assert!(ok(&pretty(&source)));
+        assert!(ok(&highlight(&source)));
+        assert!(ok(&type_of(&source)));
+    }
+}
@@ -0,0 +1,78 @@
+// normalize many independent terms at once, spread over all cores
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::parser::Term;
+use crate::reduce::{self, Strategy};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EvalError {
+    OutOfFuel(usize), // no normal form within that many steps
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalError::OutOfFuel(fuel) => write!(f, "no normal form within {} steps", fuel),
+        }
+    }
+}
+
+// normal order, each term with default_fuel of its termination hint
+pub fn normalize_all(terms: &[Term]) -> Vec<Result<Term, EvalError>> {
+    run(terms, |term| {
+        let fuel = reduce::default_fuel(reduce::termination(term));
+        normalize_one(term, fuel, Strategy::Normal)
+    })
+}
+
+// same with one fuel and strategy for every term
+pub fn normalize_all_with(
+    terms: &[Term],
+    fuel: usize,
+    strategy: Strategy,
+) -> Vec<Result<Term, EvalError>> {
+    run(terms, |term| normalize_one(term, fuel, strategy))
+}
+
+fn normalize_one(term: &Term, fuel: usize, strategy: Strategy) -> Result<Term, EvalError> {
+    reduce::normalize_with(term, fuel, strategy)
+        .map(|(normal, _)| normal)
+        .ok_or(EvalError::OutOfFuel(fuel))
+}
+
+// workers take the next unclaimed term until none are left, so one slow term
+// does not hold up the ones queued behind it
+fn run<F>(terms: &[Term], eval: F) -> Vec<Result<Term, EvalError>>
+where
+    F: Fn(&Term) -> Result<Term, EvalError> + Sync,
+{
+    let workers = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(terms.len());
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<Result<Term, EvalError>>>> =
+        Mutex::new((0..terms.len()).map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(term) = terms.get(i) else {
+                        break;
+                    };
+                    let result = eval(term);
+                    results.lock().unwrap()[i] = Some(result);
+                }
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every term evaluated"))
+        .collect()
+}
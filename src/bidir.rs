@@ -0,0 +1,334 @@
+// bidirectional type checking, an alternative to the unification-based Checker
+//
+// infer: Γ ⊢ t ⇒ A    the type is read off the term
+// check: Γ ⊢ t ⇐ A    the type is pushed into the term
+//
+// annotations flow inward through check, so only lambdas in inference position
+// (e.g. the function of an application) need binder annotations. there are no
+// unknowns: every error is reported at the node where the judgment fails.
+
+use std::mem;
+
+use crate::parser::{Constant, Term};
+use crate::symbol::Symbol;
+use crate::types::{self, Type, TypeError, TypeErrorKind};
+
+struct Bidir {
+    // types of the enclosing binders, innermost last
+    env: Vec<Type>,
+    // pre-order index of the next node to visit
+    node: usize,
+}
+
+pub fn infer(term: &Term) -> Result<Type, TypeError> {
+    Bidir::new().infer(term)
+}
+
+pub fn check(term: &Term, expected: &Type) -> Result<(), TypeError> {
+    Bidir::new().check(term, expected)
+}
+
+fn base(name: &str) -> Type {
+    Type::Base(Symbol::intern(name))
+}
+
+fn arrow(lhs: Type, rhs: Type) -> Type {
+    Type::Arrow(Box::new(lhs), Box::new(rhs))
+}
+
+// what is left to do, kept on an explicit stack so deep terms need no deep
+// stack: a judgment, or the rest of one once those it waits on are done. an
+// inference leaves its type on the stack of types, a check leaves nothing
+enum Frame<'a> {
+    Infer(&'a Term),
+    Check(&'a Term, Type),
+    // λx:A. t ⇒ A → B once t ⇒ B
+    Lambda(Type),
+    // the function of an application inferred, from the node it starts at:
+    // check the argument
+    Apply(&'a Term, usize),
+    // the bound term inferred: its body in scope of it, inferred or checked
+    Let(&'a Term, Option<Type>),
+    // a binder goes out of scope
+    Leave,
+    Forall(Symbol),
+    // the function of a type application inferred, from the node it starts at
+    Instantiate(&'a Type, usize),
+    // checking by inference: the inferred type against the expected one
+    Compare(Type, usize),
+    // ite with its condition checked: infer the then branch, then check the
+    // else branch against it
+    Then(&'a Term, &'a Term),
+    Else(&'a Term),
+}
+
+impl Bidir {
+    fn new() -> Self {
+        Self {
+            env: Vec::new(),
+            node: 0,
+        }
+    }
+
+    // claim the pre-order index of the node about to be visited
+    fn enter(&mut self) -> usize {
+        self.node += 1;
+        self.node - 1
+    }
+
+    fn infer(&mut self, term: &Term) -> Result<Type, TypeError> {
+        let mut types = self.run(Frame::Infer(term))?;
+        Ok(types.pop().expect("inferred type"))
+    }
+
+    fn check(&mut self, term: &Term, expected: &Type) -> Result<(), TypeError> {
+        self.run(Frame::Check(term, expected.clone())).map(|_| ())
+    }
+
+    // the frames from `start` until none are left, the types inferred
+    fn run<'a>(&mut self, start: Frame<'a>) -> Result<Vec<Type>, TypeError> {
+        let mut work = vec![start];
+        let mut types: Vec<Type> = Vec::new();
+        while let Some(frame) = work.pop() {
+            match frame {
+                Frame::Infer(term) => self.infer_node(term, &mut work, &mut types)?,
+                Frame::Check(term, expected) => self.check_node(term, expected, &mut work)?,
+                Frame::Lambda(param) => {
+                    self.env.pop();
+                    let body = types.pop().expect("inferred body");
+                    types.push(arrow(param, body));
+                }
+                Frame::Apply(rhs, fun_node) => {
+                    let mut fun = types.pop().expect("inferred function");
+                    let Type::Arrow(param, ret) = &mut fun else {
+                        return Err(TypeError {
+                            kind: TypeErrorKind::NotAFunction(fun),
+                            node: fun_node,
+                        });
+                    };
+                    types.push(mem::replace(ret, Type::Var(0)));
+                    work.push(Frame::Check(rhs, mem::replace(param, Type::Var(0))));
+                }
+                Frame::Let(body, expected) => {
+                    let bound = types.pop().expect("inferred bound term");
+                    self.env.push(bound);
+                    work.push(Frame::Leave);
+                    work.push(match expected {
+                        Some(expected) => Frame::Check(body, expected),
+                        None => Frame::Infer(body),
+                    });
+                }
+                Frame::Leave => {
+                    self.env.pop();
+                }
+                Frame::Forall(param) => {
+                    let body = types.pop().expect("inferred body");
+                    types.push(Type::Forall(param, Box::new(body)));
+                }
+                Frame::Instantiate(arg, fun_node) => {
+                    let fun = types.pop().expect("inferred function");
+                    let Type::Forall(param, body) = &fun else {
+                        return Err(TypeError {
+                            kind: TypeErrorKind::NotPolymorphic(fun),
+                            node: fun_node,
+                        });
+                    };
+                    types.push(types::subst_type(body, *param, arg));
+                }
+                Frame::Compare(expected, node) => {
+                    let actual = types.pop().expect("inferred type");
+                    if !types::same_type(&actual, &expected) {
+                        return Err(TypeError {
+                            kind: TypeErrorKind::Mismatch { expected, actual },
+                            node,
+                        });
+                    }
+                }
+                Frame::Then(then, other) => {
+                    work.push(Frame::Else(other));
+                    work.push(Frame::Infer(then));
+                }
+                // the then branch's type stays, it is the type of the ite
+                Frame::Else(other) => {
+                    let ty = types.last().expect("inferred branch").clone();
+                    work.push(Frame::Check(other, ty));
+                }
+            }
+        }
+        Ok(types)
+    }
+
+    fn infer_node<'a>(
+        &mut self,
+        term: &'a Term,
+        work: &mut Vec<Frame<'a>>,
+        types: &mut Vec<Type>,
+    ) -> Result<(), TypeError> {
+        let node = self.enter();
+        let fail = |kind| Err(TypeError { kind, node });
+        match term {
+            Term::Variable(index) if *index < 0 => return fail(TypeErrorKind::NeedsAnnotation),
+            Term::Variable(index) => {
+                types.push(self.env[self.env.len() - (*index as usize)].clone())
+            }
+            Term::Constant(constant) => types.push(match constant {
+                Constant::Int(_) => base("Int"),
+                Constant::Bool(_) => base("Bool"),
+                Constant::Add | Constant::Mul => {
+                    arrow(base("Int"), arrow(base("Int"), base("Int")))
+                }
+                // polymorphic, only typed when fully applied (see ite)
+                Constant::Ite => return fail(TypeErrorKind::NeedsAnnotation),
+            }),
+            Term::Lambda(_, None, _) => return fail(TypeErrorKind::NeedsAnnotation),
+            Term::Lambda(_, Some(param), body) => {
+                self.env.push(param.clone());
+                work.push(Frame::Lambda(param.clone()));
+                work.push(Frame::Infer(body));
+            }
+            Term::Application(lhs, rhs) => match ite(term) {
+                Some((cond, then, other)) => {
+                    // the three inner applications and the head have no judgment of their own
+                    self.node += 3;
+                    work.push(Frame::Then(then, other));
+                    work.push(Frame::Check(cond, base("Bool")));
+                }
+                None => {
+                    work.push(Frame::Apply(rhs, self.node));
+                    work.push(Frame::Infer(lhs));
+                }
+            },
+            Term::Let(_, bound, body) => {
+                work.push(Frame::Let(body, None));
+                work.push(Frame::Infer(bound));
+            }
+            Term::TypeLambda(param, body) => {
+                if self.in_scope(*param) {
+                    return fail(TypeErrorKind::Captured(*param));
+                }
+                work.push(Frame::Forall(*param));
+                work.push(Frame::Infer(body));
+            }
+            Term::TypeApplication(fun, arg) => {
+                work.push(Frame::Instantiate(arg, self.node));
+                work.push(Frame::Infer(fun));
+            }
+        }
+        Ok(())
+    }
+
+    // whether a binder in scope has a type mentioning `name`, which a \/
+    // binding the same name would capture
+    fn in_scope(&self, name: Symbol) -> bool {
+        self.env
+            .iter()
+            .any(|ty| types::free_type_names(ty).contains(&name))
+    }
+
+    fn check_node<'a>(
+        &mut self,
+        term: &'a Term,
+        mut expected: Type,
+        work: &mut Vec<Frame<'a>>,
+    ) -> Result<(), TypeError> {
+        match (term, &mut expected) {
+            (Term::Lambda(_, annot, body), Type::Arrow(param, ret)) => {
+                let node = self.enter();
+                if let Some(annot) = annot
+                    && !types::same_type(annot, param)
+                {
+                    return Err(TypeError {
+                        kind: TypeErrorKind::Mismatch {
+                            expected: *param.clone(),
+                            actual: annot.clone(),
+                        },
+                        node,
+                    });
+                }
+                // taken out of the expected type rather than copied
+                self.env.push(mem::replace(param, Type::Var(0)));
+                work.push(Frame::Leave);
+                work.push(Frame::Check(body, mem::replace(ret, Type::Var(0))));
+            }
+            (Term::Lambda(..), _) => {
+                return Err(TypeError {
+                    kind: TypeErrorKind::UnexpectedLambda(expected),
+                    node: self.enter(),
+                });
+            }
+            (Term::TypeLambda(param, body), Type::Forall(name, inner)) => {
+                let node = self.enter();
+                let outside = param != name && types::free_type_names(inner).contains(param);
+                if outside || self.in_scope(*param) {
+                    return Err(TypeError {
+                        kind: TypeErrorKind::Captured(*param),
+                        node,
+                    });
+                }
+                let inner = types::subst_type(inner, *name, &Type::Base(*param));
+                work.push(Frame::Check(body, inner));
+            }
+            (Term::Let(_, bound, body), _) => {
+                self.enter();
+                work.push(Frame::Let(body, Some(expected)));
+                work.push(Frame::Infer(bound));
+            }
+            // switch to inference and compare
+            _ => {
+                work.push(Frame::Compare(expected, self.node));
+                work.push(Frame::Infer(term));
+            }
+        }
+        Ok(())
+    }
+}
+
+// <<<ite|c>|t>|e>: c ⇐ Bool, t ⇒ A, e ⇐ A gives A
+fn ite(term: &Term) -> Option<(&Term, &Term, &Term)> {
+    let Term::Application(lhs, other) = term else {
+        return None;
+    };
+    let Term::Application(cond_app, then) = &**lhs else {
+        return None;
+    };
+    let Term::Application(head, cond) = &**cond_app else {
+        return None;
+    };
+    matches!(**head, Term::Constant(Constant::Ite)).then_some((&**cond, &**then, &**other))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{self, ParserConfig};
+
+    fn infer_source(source: &str) -> Result<String, TypeErrorKind> {
+        let config = ParserConfig {
+            system_f: true,
+            ..Default::default()
+        };
+        let (term, _) = parser::parse_with_config(source, config).unwrap();
+        infer(&term)
+            .map(|ty| ty.to_string())
+            .map_err(|err| err.kind)
+    }
+
+    #[test]
+    fn a_type_abstraction_captures_nothing_from_outside() {
+        assert_eq!(infer_source(r"/\A.{\x:A.{x}}"), Ok("∀A. A → A".to_string()));
+        let captured = Err(TypeErrorKind::Captured(Symbol::intern("A")));
+        assert_eq!(infer_source(r"\x:A.{/\A.{x}}"), captured);
+        let checked = r"<\f:\/B.A->B.{f}|/\A.{\x:A.{x}}>";
+        assert_eq!(infer_source(checked), captured);
+    }
+
+    #[test]
+    fn deep_terms_need_no_deep_stack() {
+        let n = 20_000;
+        let source = format!("{}x{}", r"\x:A.{".repeat(n), "}".repeat(n));
+        let (term, _) = parser::parse_with_config(&source, ParserConfig::default()).unwrap();
+        let ty = infer(&term).unwrap();
+        assert!(ty.to_string().starts_with("A → A → "));
+        assert_eq!(check(&term, &ty), Ok(()));
+    }
+}
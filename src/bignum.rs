@@ -0,0 +1,144 @@
+// just enough of an arbitrary precision natural for numbering terms
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+// little-endian base 2^32 digits, no trailing zero digits so zero is empty
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BigUint {
+    digits: Vec<u32>,
+}
+
+impl BigUint {
+    pub fn zero() -> Self {
+        Self::default()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    fn trim(mut self) -> Self {
+        while self.digits.last() == Some(&0) {
+            self.digits.pop();
+        }
+        self
+    }
+
+    // self * factor + addend
+    pub fn mul_add(&self, factor: u32, addend: u32) -> Self {
+        let mut carry = addend as u64;
+        let mut digits = Vec::with_capacity(self.digits.len() + 1);
+        for &digit in &self.digits {
+            let value = digit as u64 * factor as u64 + carry;
+            digits.push(value as u32);
+            carry = value >> 32;
+        }
+        digits.push(carry as u32);
+        Self { digits }.trim()
+    }
+
+    // quotient and remainder by a nonzero divisor
+    pub fn div_rem(&self, divisor: u32) -> (Self, u32) {
+        assert!(divisor != 0, "division by zero");
+        let mut rem = 0u64;
+        let mut digits = vec![0; self.digits.len()];
+        for (i, &digit) in self.digits.iter().enumerate().rev() {
+            let value = (rem << 32) | digit as u64;
+            digits[i] = (value / divisor as u64) as u32;
+            rem = value % divisor as u64;
+        }
+        (Self { digits }.trim(), rem as u32)
+    }
+
+    // number of significant bits, 0 for zero
+    pub fn bits(&self) -> usize {
+        self.digits.last().map_or(0, |top| {
+            self.digits.len() * 32 - top.leading_zeros() as usize
+        })
+    }
+
+    pub fn bit(&self, i: usize) -> bool {
+        self.digits
+            .get(i / 32)
+            .is_some_and(|digit| digit >> (i % 32) & 1 == 1)
+    }
+
+    pub fn set_bit(&mut self, i: usize) {
+        if self.digits.len() <= i / 32 {
+            self.digits.resize(i / 32 + 1, 0);
+        }
+        self.digits[i / 32] |= 1 << (i % 32);
+    }
+
+    // the value if it fits
+    pub fn to_u64(&self) -> Option<u64> {
+        match self.digits.as_slice() {
+            [] => Some(0),
+            [low] => Some(*low as u64),
+            [low, high] => Some((*high as u64) << 32 | *low as u64),
+            _ => None,
+        }
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(value: u64) -> Self {
+        Self {
+            digits: vec![value as u32, (value >> 32) as u32],
+        }
+        .trim()
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.digits
+            .len()
+            .cmp(&other.digits.len())
+            .then_with(|| self.digits.iter().rev().cmp(other.digits.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // nine decimal digits at a time, most significant chunk last
+        let mut chunks = Vec::new();
+        let mut rest = self.clone();
+        while !rest.is_zero() {
+            let (quotient, chunk) = rest.div_rem(1_000_000_000);
+            chunks.push(chunk);
+            rest = quotient;
+        }
+        let mut chunks = chunks.iter().rev();
+        write!(f, "{}", chunks.next().unwrap())?;
+        for chunk in chunks {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for BigUint {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, String> {
+        if source.is_empty() {
+            return Err("empty number".to_string());
+        }
+        source.chars().try_fold(Self::zero(), |acc, chr| {
+            chr.to_digit(10)
+                .map(|digit| acc.mul_add(10, digit))
+                .ok_or_else(|| format!("invalid digit {:?}", chr))
+        })
+    }
+}
@@ -0,0 +1,179 @@
+// Böhm trees, the head normal forms of a term unfolded level by level: a
+// term with head normal form \x1.{...\xn.{<<h|M1>|...Mk>}} is a node with the
+// binders x1..xn, the head h and the trees of M1..Mk as children, a term
+// without one is ⊥. terms with the same tree are equal in every context, so
+// it tells apart divergent terms that normal forms cannot
+//
+// having a head normal form is undecidable, ⊥ here means head reduction was
+// caught looping (see reduce::is_solvable) or ran out of fuel, and then may
+// be a node the fuel was too small for. the tree is lazy, subterms below the
+// depth asked for stay pending until expanded. heads are de Bruijn indices
+// counting the binders of the node and the nodes above it, as in the term.
+// types are erased, type abstractions and arguments vanish
+use crate::parser::Term;
+use crate::reduce::{self, Solvability, Termination};
+use crate::symbol::Symbol;
+use crate::unparse::unparse;
+
+#[derive(Clone, Debug)]
+pub enum Tree {
+    Bottom,
+    Node {
+        binders: Vec<Symbol>,
+        head: Term, // a variable or a constant
+        children: Vec<Tree>,
+    },
+    Pending(Term), // not unfolded yet
+}
+
+// the tree of `term` to `depth` levels, head reduction of each subterm gets
+// the fuel of a term that may diverge
+pub fn boehm_tree(term: &Term, depth: usize) -> Tree {
+    boehm_tree_with_fuel(term, depth, reduce::default_fuel(Termination::MayDiverge))
+}
+
+pub fn boehm_tree_with_fuel(term: &Term, depth: usize, fuel: usize) -> Tree {
+    let mut tree = Tree::Pending(term.clone());
+    tree.expand(depth, fuel);
+    tree
+}
+
+// the binders, head and arguments of a head normal form
+fn node(mut term: &Term) -> (Vec<Symbol>, Term, Vec<Tree>) {
+    let mut binders = Vec::new();
+    loop {
+        match term {
+            Term::Lambda(param, _, body) => {
+                binders.push(*param);
+                term = body;
+            }
+            Term::TypeLambda(_, body) => term = body,
+            _ => break,
+        }
+    }
+    let mut children = Vec::new();
+    loop {
+        match term {
+            Term::Application(fun, arg) => {
+                children.push(Tree::Pending(*arg.clone()));
+                term = fun;
+            }
+            Term::TypeApplication(fun, _) => term = fun,
+            _ => break,
+        }
+    }
+    children.reverse();
+    (binders, term.clone(), children)
+}
+
+// the free variable named `name`, added to `free` when new
+fn symbol(free: &mut Vec<Symbol>, name: &str) -> Term {
+    let at = match free.iter().position(|known| *known == name) {
+        Some(at) => at,
+        None => {
+            free.push(Symbol::intern(name));
+            free.len() - 1
+        }
+    };
+    Term::Variable(-(at as i32 + 1))
+}
+
+impl Tree {
+    // unfold pending subterms down to `depth` more levels
+    pub fn expand(&mut self, depth: usize, fuel: usize) {
+        if depth == 0 {
+            return;
+        }
+        if let Tree::Pending(term) = self {
+            *self = match reduce::is_solvable(term, fuel) {
+                Solvability::HeadNormal(normal, _) => {
+                    let (binders, head, children) = node(&normal);
+                    Tree::Node {
+                        binders,
+                        head,
+                        children,
+                    }
+                }
+                Solvability::Unsolvable(_) | Solvability::Unknown => Tree::Bottom,
+            };
+        }
+        if let Tree::Node { children, .. } = self {
+            for child in children {
+                child.expand(depth - 1, fuel);
+            }
+        }
+    }
+
+    // nothing is left pending: the whole tree is finite and computed
+    pub fn is_complete(&self) -> bool {
+        match self {
+            Tree::Bottom => true,
+            Tree::Node { children, .. } => children.iter().all(Tree::is_complete),
+            Tree::Pending(_) => false,
+        }
+    }
+
+    // the tree as a term, ⊥ and pending subterms (...) becoming free names
+    // added to `free`
+    pub fn to_term(&self, free: &mut Vec<Symbol>) -> Term {
+        match self {
+            Tree::Bottom => symbol(free, "⊥"),
+            Tree::Pending(_) => symbol(free, "..."),
+            Tree::Node {
+                binders,
+                head,
+                children,
+            } => {
+                let spine = children.iter().fold(head.clone(), |fun, child| {
+                    Term::Application(Box::new(fun), Box::new(child.to_term(free)))
+                });
+                binders.iter().rev().fold(spine, |body, param| {
+                    Term::Lambda(*param, None, Box::new(body))
+                })
+            }
+        }
+    }
+
+    // in the input syntax, with the free names of the term it came from
+    pub fn render(&self, free: &[Symbol]) -> String {
+        let mut free = free.to_vec();
+        let term = self.to_term(&mut free);
+        unparse(&term, &free)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{self, ParserConfig};
+
+    fn tree(source: &str, depth: usize) -> (Tree, String) {
+        let (term, free) = parser::parse_with_config(source, ParserConfig::default()).unwrap();
+        let tree = boehm_tree_with_fuel(&term, depth, 1000);
+        let rendered = tree.render(&free);
+        (tree, rendered)
+    }
+
+    #[test]
+    fn unsolvable_subterms_are_bottom() {
+        let omega = r"<\z.{<z|z>}|\z.{<z|z>}>";
+        let (whole, rendered) = tree(omega, 5);
+        assert!(matches!(whole, Tree::Bottom));
+        assert_eq!(rendered, "⊥");
+        let source = format!(r"<\a.{{\x.{{<<x|{}>|a>}}}}|\y.{{y}}>", omega);
+        let (whole, rendered) = tree(&source, 5);
+        assert!(whole.is_complete());
+        assert_eq!(rendered, r"\x.{<<x|⊥>|\y.{y}>}");
+    }
+
+    #[test]
+    fn infinite_trees_are_unfolded_to_the_depth_asked() {
+        let y = r"\f.{<\x.{<f|<x|x>>}|\x.{<f|<x|x>>}>}";
+        let (mut whole, rendered) = tree(y, 3);
+        assert!(!whole.is_complete());
+        assert_eq!(rendered, r"\f.{<f|<f|<f|...>>>}");
+        // depth counts from the root, the nodes already there are kept
+        whole.expand(5, 1000);
+        assert_eq!(whole.render(&[]), r"\f.{<f|<f|<f|<f|<f|...>>>>>}");
+    }
+}
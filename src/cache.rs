@@ -0,0 +1,119 @@
+// on-disk cache of normal forms, one file per term named by a hash of its source
+//
+// a file holds the term and its normal form, both unparsed on one line each; the
+// term is compared on lookup so a hash collision is only a miss
+use std::fs;
+use std::path::PathBuf;
+
+use crate::parser::{self, ParserConfig, Term};
+use crate::symbol::Symbol;
+use crate::unparse::unparse;
+
+// bump when the file layout or the meaning of a normal form changes
+const VERSION: &str = "1";
+
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    // $XDG_CACHE_HOME/lambda_rs, else ~/.cache/lambda_rs
+    pub fn default_dir() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+        Some(base.join("lambda_rs"))
+    }
+
+    // the normal form of `term` under normal order, with its free names
+    pub fn get(&self, term: &Term, free: &[Symbol]) -> Option<(Term, Vec<Symbol>)> {
+        let source = unparse(term, free);
+        let contents = fs::read_to_string(self.path(&source)).ok()?;
+        let (key, normal) = contents.split_once('\n')?;
+        if key != source {
+            return None;
+        }
+        // a damaged file is a miss, not an error
+        let config = ParserConfig {
+            system_f: true,
+            ..Default::default()
+        };
+        parser::parse_with_config(normal.trim_end(), config).ok()
+    }
+
+    // failing to write only loses the entry
+    pub fn put(&self, term: &Term, free: &[Symbol], normal: &Term) {
+        let source = unparse(term, free);
+        let contents = format!("{}\n{}\n", source, unparse(normal, free));
+        let _ = fs::create_dir_all(&self.dir).and_then(|_| fs::write(self.path(&source), contents));
+    }
+
+    fn path(&self, source: &str) -> PathBuf {
+        let hash = content_hash(&format!("{}\n{}", VERSION, source));
+        self.dir.join(format!("{:016x}.lam", hash))
+    }
+}
+
+// 64-bit FNV-1a, stable across runs and compiler versions unlike DefaultHasher
+pub fn content_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reduce;
+
+    fn parse(source: &str) -> (Term, Vec<Symbol>) {
+        parser::parse_with_config(source, ParserConfig::default()).unwrap()
+    }
+
+    fn cache(name: &str) -> Cache {
+        let dir = std::env::temp_dir().join(format!("lambda_rs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        Cache::new(dir)
+    }
+
+    #[test]
+    fn stored_normal_forms_are_found_again() {
+        let cache = cache("hit");
+        let (term, free) = parse(r"<\x.{<x|y>}|z>");
+        assert!(cache.get(&term, &free).is_none());
+        let (normal, _) = reduce::normalize(&term, 10).unwrap();
+        cache.put(&term, &free, &normal);
+        let (found, found_free) = cache.get(&term, &free).unwrap();
+        assert_eq!(unparse(&found, &found_free), "<z|y>");
+        // a different term is a miss, even one differing only in a free name
+        let (other, other_free) = parse(r"<\x.{<x|w>}|z>");
+        assert!(cache.get(&other, &other_free).is_none());
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[test]
+    fn a_colliding_or_damaged_file_is_a_miss() {
+        let cache = cache("collision");
+        let (term, free) = parse("<a|b>");
+        let (normal, _) = parse("<a|b>");
+        cache.put(&term, &free, &normal);
+        // another term's entry where this term's hash points
+        let path = cache.path(&unparse(&term, &free));
+        fs::write(&path, "<b|a>\n<b|a>\n").unwrap();
+        assert!(cache.get(&term, &free).is_none());
+        fs::write(&path, "<a|b>\n<a|\n").unwrap();
+        assert!(cache.get(&term, &free).is_none());
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[test]
+    fn the_hash_is_fnv_1a() {
+        assert_eq!(content_hash(""), 0xcbf29ce484222325);
+        assert_eq!(content_hash("a"), 0xaf63dc4c8601ec8c);
+        assert_ne!(content_hash("<a|b>"), content_hash("<b|a>"));
+    }
+}
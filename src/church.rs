@@ -0,0 +1,453 @@
+// Church booleans, numerals, lists and tuples, with the usual operations on them
+use std::sync::OnceLock;
+
+use crate::combinators::{app, apps, lam, var};
+use crate::parser::Term;
+use crate::pattern::{self, Pattern};
+use crate::reduce;
+use crate::symbol::Symbol;
+
+// λt. λf. t
+pub fn tru() -> Term {
+    lam("t", lam("f", var(2)))
+}
+
+// λt. λf. f
+pub fn fls() -> Term {
+    lam("t", lam("f", var(1)))
+}
+
+// the largest numeral written as a literal, c12 in the prelude or an integer
+// constant lowered by primitives; the term has n + 3 nodes, so a short name
+// must not stand for a huge one
+pub const MAX_NUMERAL: u64 = 10_000;
+
+// λf. λx. f (f (... x)), n times
+pub fn numeral(n: u64) -> Term {
+    let body = (0..n).fold(var(1), |acc, _| app(var(2), acc));
+    lam("f", lam("x", body))
+}
+
+// read a numeral back from its normal form, λf. λx. x is both 0 and false
+pub fn to_number(term: &Term) -> Option<u64> {
+    let Term::Lambda(_, _, inner) = term else {
+        return None;
+    };
+    let Term::Lambda(_, _, body) = &**inner else {
+        return None;
+    };
+    let mut body: &Term = body;
+    let mut n = 0;
+    loop {
+        match body {
+            Term::Variable(1) => return Some(n),
+            Term::Application(lhs, rhs) if **lhs == Term::Variable(2) => {
+                n += 1;
+                body = rhs;
+            }
+            _ => return None,
+        }
+    }
+}
+
+// read a boolean back from its normal form
+pub fn to_bool(term: &Term) -> Option<bool> {
+    match term {
+        Term::Lambda(_, _, inner) => match &**inner {
+            Term::Lambda(_, _, body) => match **body {
+                Term::Variable(2) => Some(true),
+                Term::Variable(1) => Some(false),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// λn. λf. λx. f (n f x)
+pub fn succ() -> Term {
+    lam(
+        "n",
+        lam("f", lam("x", app(var(2), apps(var(3), [var(2), var(1)])))),
+    )
+}
+
+// λm. λn. λf. λx. m f (n f x)
+pub fn add() -> Term {
+    let body = apps(var(4), [var(2), apps(var(3), [var(2), var(1)])]);
+    lam("m", lam("n", lam("f", lam("x", body))))
+}
+
+// λm. λn. λf. m (n f)
+pub fn mul() -> Term {
+    lam("m", lam("n", lam("f", app(var(3), app(var(2), var(1))))))
+}
+
+// λm. λn. n m, m to the power n; m^0 normalizes to λx. x, the η-contracted 1
+pub fn pow() -> Term {
+    lam("m", lam("n", app(var(1), var(2))))
+}
+
+// λn. λf. λx. n (λg. λh. h (g f)) (λu. x) (λu. u), pred 0 is 0
+pub fn pred() -> Term {
+    let step = lam("g", lam("h", app(var(1), app(var(2), var(4)))));
+    let body = apps(var(3), [step, lam("u", var(2)), lam("u", var(1))]);
+    lam("n", lam("f", lam("x", body)))
+}
+
+// λm. λn. n pred m, truncated at 0
+pub fn sub() -> Term {
+    lam("m", lam("n", apps(var(1), [pred(), var(2)])))
+}
+
+// λn. n (λx. false) true
+pub fn is_zero() -> Term {
+    lam("n", apps(var(1), [lam("x", fls()), tru()]))
+}
+
+// λm. λn. is_zero (sub m n)
+pub fn leq() -> Term {
+    lam("m", lam("n", app(is_zero(), apps(sub(), [var(2), var(1)]))))
+}
+
+// numerals computed by arithmetic stay below this, bigger ones are left to β-reduction
+const ARITHMETIC_LIMIT: u64 = 1 << 20;
+
+#[derive(Clone, Copy)]
+enum Operation {
+    Succ,
+    Pred,
+    Add,
+    Mul,
+    Sub,
+    Pow,
+}
+
+// the applications arithmetic recognizes, with the numerals as meta-variables
+// M and N, the first and second free variable
+fn operations() -> &'static [(Operation, Pattern)] {
+    static OPERATIONS: OnceLock<Vec<(Operation, Pattern)>> = OnceLock::new();
+    OPERATIONS.get_or_init(|| {
+        let names = || vec![Symbol::intern("M"), Symbol::intern("N")];
+        let unary = |fun| Pattern::new(app(fun, var(-2)), names(), |_| true);
+        let binary = |op| Pattern::new(apps(op, [var(-1), var(-2)]), names(), |_| true);
+        vec![
+            (Operation::Succ, unary(succ())),
+            (Operation::Pred, unary(pred())),
+            (Operation::Add, binary(add())),
+            (Operation::Mul, binary(mul())),
+            (Operation::Sub, binary(sub())),
+            (Operation::Pow, binary(pow())),
+        ]
+    })
+}
+
+// succ, pred, add, mul, pow or sub applied to numerals, as the normal form it
+// reduces to; None for anything else
+pub fn arithmetic(term: &Term) -> Option<Term> {
+    // every operation ends in a numeral, the cheap test first
+    let Term::Application(_, arg) = term else {
+        return None;
+    };
+    let n = to_number(arg)?;
+    let (operation, substitution) = operations().iter().find_map(|(operation, pattern)| {
+        pattern::match_term(pattern, term, &[]).map(|substitution| (*operation, substitution))
+    })?;
+    let m = || substitution.get("M").and_then(to_number);
+    let result = match operation {
+        Operation::Succ => n.checked_add(1)?,
+        Operation::Pred => n.saturating_sub(1),
+        Operation::Add => m()?.checked_add(n)?,
+        Operation::Mul => m()?.checked_mul(n)?,
+        Operation::Sub => m()?.saturating_sub(n),
+        Operation::Pow => {
+            let m = m()?;
+            if n == 0 {
+                return Some(lam("x", var(1)));
+            }
+            m.checked_pow(u32::try_from(n).ok()?)?
+        }
+    };
+    (result <= ARITHMETIC_LIMIT).then(|| numeral(result))
+}
+
+// λc. λn. n
+pub fn nil() -> Term {
+    lam("c", lam("n", var(1)))
+}
+
+// λh. λt. λc. λn. c h (t c n)
+pub fn cons() -> Term {
+    let body = apps(var(2), [var(4), apps(var(3), [var(2), var(1)])]);
+    lam("h", lam("t", lam("c", lam("n", body))))
+}
+
+// the list of the items, what the literal [a, b, ...] parses to
+pub fn list(items: impl IntoIterator<Item = Term>) -> Term {
+    let items: Vec<Term> = items.into_iter().collect();
+    let body = items.into_iter().rev().fold(var(1), |rest, item| {
+        apps(var(2), [reduce::shift(&item, 2, 0), rest])
+    });
+    lam("c", lam("n", body))
+}
+
+// read the items back from a list in normal form, shifted out of its binders
+pub fn to_list(term: &Term) -> Option<Vec<Term>> {
+    let Term::Lambda(_, _, inner) = term else {
+        return None;
+    };
+    let Term::Lambda(_, _, body) = &**inner else {
+        return None;
+    };
+    let mut body: &Term = body;
+    let mut items = Vec::new();
+    loop {
+        match body {
+            Term::Variable(1) => return Some(items),
+            Term::Application(lhs, rest) => match &**lhs {
+                Term::Application(head, item) if **head == Term::Variable(2) => {
+                    // items may not mention c or n
+                    if mentions(item, 0) {
+                        return None;
+                    }
+                    items.push(reduce::shift(item, -2, 0));
+                    body = rest;
+                }
+                _ => return None,
+            },
+            _ => return None,
+        }
+    }
+}
+
+// does the term refer to either of the two binders just outside it
+pub(crate) fn mentions(term: &Term, depth: i32) -> bool {
+    match term {
+        Term::Variable(index) => *index > depth && *index <= depth + 2,
+        Term::Constant(_) => false,
+        Term::Lambda(_, _, body) => mentions(body, depth + 1),
+        Term::Let(_, bound, body) => mentions(bound, depth) || mentions(body, depth + 1),
+        Term::Application(lhs, rhs) => mentions(lhs, depth) || mentions(rhs, depth),
+        Term::TypeLambda(_, body) | Term::TypeApplication(body, _) => mentions(body, depth),
+    }
+}
+
+// λl. l (λh. λt. false) true
+pub fn is_nil() -> Term {
+    lam("l", apps(var(1), [lam("h", lam("t", fls())), tru()]))
+}
+
+// λl. l (λh. λt. h) nil, the head of nil is nil
+pub fn head() -> Term {
+    lam("l", apps(var(1), [lam("h", lam("t", var(2))), nil()]))
+}
+
+// λl. λc. λn. l (λh. λt. λg. g h (t c)) (λt. n) (λh. λt. t), the tail of nil is nil
+pub fn tail() -> Term {
+    let step = lam(
+        "h",
+        lam("t", lam("g", apps(var(1), [var(3), app(var(2), var(5))]))),
+    );
+    let body = apps(var(3), [step, lam("t", var(2)), lam("h", lam("t", var(1)))]);
+    lam("l", lam("c", lam("n", body)))
+}
+
+// λf. λl. λc. λn. l (λh. λt. c (f h) t) n
+pub fn map() -> Term {
+    let step = lam("h", lam("t", apps(var(4), [app(var(6), var(2)), var(1)])));
+    lam(
+        "f",
+        lam("l", lam("c", lam("n", apps(var(3), [step, var(1)])))),
+    )
+}
+
+// λf. λz. λl. l f z
+pub fn foldr() -> Term {
+    lam("f", lam("z", lam("l", apps(var(1), [var(3), var(2)]))))
+}
+
+// λp. p a b, what (a, b) parses to
+pub fn pair(a: Term, b: Term) -> Term {
+    tuple([a, b])
+}
+
+// λp. p (λx. λy. x)
+pub fn fst() -> Term {
+    proj(0, 2)
+}
+
+// λp. p (λx. λy. y)
+pub fn snd() -> Term {
+    proj(1, 2)
+}
+
+// λp. p a b c ..., what (a, b, c, ...) parses to
+pub fn tuple(items: impl IntoIterator<Item = Term>) -> Term {
+    let body = items
+        .into_iter()
+        .fold(var(1), |acc, item| app(acc, reduce::shift(&item, 1, 0)));
+    lam("p", body)
+}
+
+// λt. t (λx0. ... λx{n-1}. x{i}), the i-th of n components counting from 0
+pub fn proj(i: usize, n: usize) -> Term {
+    assert!(i < n, "projection {} out of a {}-tuple", i, n);
+    let select = (0..n)
+        .rev()
+        .fold(var((n - i) as i32), |body, k| lam(&format!("x{}", k), body));
+    lam("t", app(var(1), select))
+}
+
+// do f and g agree on the numerals 0 to up_to_n, normal forms compared up to binder
+// names; a side that does not normalize within the default fuel counts as a mismatch
+pub fn ext_eq_on_nats(f: &Term, g: &Term, up_to_n: u64) -> bool {
+    let fuel = reduce::default_fuel(reduce::Termination::MayDiverge);
+    (0..=up_to_n).all(|n| {
+        let apply = |fun: &Term| reduce::normalize(&app(fun.clone(), numeral(n)), fuel);
+        match (apply(f), apply(g)) {
+            (Some((lhs, _)), Some((rhs, _))) => reduce::alpha_eq(&lhs, &rhs),
+            _ => false,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reduce::{self, Strategy};
+
+    // the normal form by β-reduction, and with arithmetic as :arith on does
+    fn normal_forms(fun: Term, args: &[u64]) -> [Term; 2] {
+        let term = apps(fun, args.iter().map(|&n| numeral(n)));
+        let fuel = 100_000;
+        let beta = reduce::normalize(&term, fuel).expect("normalizes").0;
+        let arith = reduce::normalize_arithmetic(&term, fuel, Strategy::Normal)
+            .expect("normalizes")
+            .0;
+        [beta, arith]
+    }
+
+    fn numbers(fun: fn() -> Term, args: &[u64]) -> [Option<u64>; 2] {
+        normal_forms(fun(), args).map(|normal| to_number(&normal))
+    }
+
+    fn bools(fun: fn() -> Term, args: &[u64]) -> [Option<bool>; 2] {
+        normal_forms(fun(), args).map(|normal| to_bool(&normal))
+    }
+
+    #[test]
+    fn unary_operations() {
+        for n in 0..5 {
+            assert_eq!(numbers(succ, &[n]), [Some(n + 1); 2]);
+            assert_eq!(numbers(pred, &[n]), [Some(n.saturating_sub(1)); 2]);
+            assert_eq!(bools(is_zero, &[n]), [Some(n == 0); 2]);
+        }
+    }
+
+    #[test]
+    fn binary_operations() {
+        for m in 0..4 {
+            for n in 0..4 {
+                assert_eq!(numbers(add, &[m, n]), [Some(m + n); 2]);
+                assert_eq!(numbers(mul, &[m, n]), [Some(m * n); 2]);
+                assert_eq!(numbers(sub, &[m, n]), [Some(m.saturating_sub(n)); 2]);
+                assert_eq!(bools(leq, &[m, n]), [Some(m <= n); 2]);
+            }
+        }
+    }
+
+    #[test]
+    fn powers() {
+        for m in 0..4 {
+            for n in 1..4 {
+                assert_eq!(numbers(pow, &[m, n]), [Some(m.pow(n as u32)); 2]);
+            }
+            // m^0 is λx. x either way, 1 η-contracted
+            for normal in normal_forms(pow(), &[m, 0]) {
+                assert!(reduce::alpha_eq(&normal, &lam("x", var(1))));
+            }
+        }
+    }
+
+    #[test]
+    fn extensional_equality_on_numerals() {
+        // λn. n + 1 and succ differ as terms but agree on every numeral
+        let plus_one = lam("n", apps(add(), [var(1), numeral(1)]));
+        assert!(!reduce::alpha_eq(&plus_one, &succ()));
+        assert!(ext_eq_on_nats(&plus_one, &succ(), 5));
+        let double = lam("n", apps(add(), [var(1), var(1)]));
+        let square = lam("n", apps(mul(), [var(1), var(1)]));
+        // 0 + 0 = 0 * 0 and 2 + 2 = 2 * 2, but 1 + 1 is not 1 * 1
+        assert!(ext_eq_on_nats(&double, &square, 0));
+        assert!(!ext_eq_on_nats(&double, &square, 2));
+        // a side without a normal form is a mismatch
+        let diverging = lam("n", crate::combinators::big_omega());
+        assert!(!ext_eq_on_nats(&diverging, &diverging, 0));
+    }
+
+    fn normal(term: Term) -> Term {
+        reduce::normalize(&term, 10_000).expect("normalizes").0
+    }
+
+    #[test]
+    fn lists_round_trip() {
+        let built = normal(apps(
+            cons(),
+            [
+                numeral(0),
+                apps(cons(), [numeral(1), app(app(cons(), numeral(2)), nil())]),
+            ],
+        ));
+        assert_eq!(built, list((0..3).map(numeral)));
+        let read: Vec<u64> = to_list(&built)
+            .unwrap()
+            .iter()
+            .map(|item| to_number(item).unwrap())
+            .collect();
+        assert_eq!(read, [0, 1, 2]);
+        assert_eq!(to_list(&nil()), Some(Vec::new()));
+        // an item mentioning the list's own binders is not an item
+        assert_eq!(
+            to_list(&lam("c", lam("n", apps(var(2), [var(1), var(1)])))),
+            None
+        );
+        // free variables in items are shifted out
+        assert_eq!(to_list(&list([var(-1)])), Some(vec![var(-1)]));
+    }
+
+    #[test]
+    fn list_operations() {
+        let abc = || list((0..3).map(numeral));
+        assert_eq!(to_number(&normal(app(head(), abc()))), Some(0));
+        assert_eq!(normal(app(tail(), abc())), list((1..3).map(numeral)));
+        assert_eq!(normal(app(tail(), nil())), nil());
+        assert_eq!(to_bool(&normal(app(is_nil(), abc()))), Some(false));
+        assert_eq!(to_bool(&normal(app(is_nil(), nil()))), Some(true));
+        let doubled = normal(apps(map(), [app(mul(), numeral(2)), abc()]));
+        let read: Vec<Option<u64>> = to_list(&doubled).unwrap().iter().map(to_number).collect();
+        assert_eq!(read, [Some(0), Some(2), Some(4)]);
+        let sum = normal(apps(foldr(), [add(), numeral(0), abc()]));
+        assert_eq!(to_number(&sum), Some(3));
+    }
+
+    #[test]
+    fn tuples_project_their_components() {
+        let ab = || pair(var(-1), var(-2));
+        assert_eq!(normal(app(fst(), ab())), var(-1));
+        assert_eq!(normal(app(snd(), ab())), var(-2));
+        let triple = || tuple((0..3).map(numeral));
+        for i in 0..3 {
+            let component = normal(app(proj(i, 3), triple()));
+            assert_eq!(to_number(&component), Some(i as u64));
+        }
+        // what the literal parses to
+        let (parsed, _) = crate::parser::parse_with_config("(a, b)", Default::default()).unwrap();
+        assert_eq!(parsed, ab());
+        // components are shifted under the binder
+        assert_eq!(
+            tuple([lam("x", var(2))]),
+            lam("p", app(var(1), lam("x", var(3))))
+        );
+    }
+}
@@ -0,0 +1,603 @@
+// closed terms as JavaScript arrow functions or Python lambdas, one parameter
+// each so currying is kept: \x.{\y.{<x|y>}} is (x => (y => x(y))) or
+// (lambda x: (lambda y: x(y))). for Rust, a standalone file with a function
+// returning the term as nested closures over one reference-counted Value type
+//
+// all three evaluate arguments before the call, so the program diverges
+// wherever applicative order does. lets become an immediately applied
+// function (a block in Rust), types are erased
+use std::collections::HashMap;
+
+use crate::parser::{Constant, Term};
+use crate::symbol::Symbol;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    JavaScript,
+    Python,
+    Rust,
+}
+
+impl Target {
+    pub fn from_name(name: &str) -> Option<Target> {
+        match name.to_ascii_lowercase().as_str() {
+            "js" | "javascript" => Some(Target::JavaScript),
+            "py" | "python" => Some(Target::Python),
+            "rs" | "rust" => Some(Target::Rust),
+            _ => None,
+        }
+    }
+
+    fn reserved(self) -> &'static [&'static str] {
+        match self {
+            Target::JavaScript => &[
+                "break",
+                "case",
+                "catch",
+                "class",
+                "const",
+                "continue",
+                "debugger",
+                "default",
+                "delete",
+                "do",
+                "else",
+                "enum",
+                "export",
+                "extends",
+                "false",
+                "finally",
+                "for",
+                "function",
+                "if",
+                "import",
+                "in",
+                "instanceof",
+                "new",
+                "null",
+                "return",
+                "super",
+                "switch",
+                "this",
+                "throw",
+                "true",
+                "try",
+                "typeof",
+                "var",
+                "void",
+                "while",
+                "with",
+                "yield",
+                "let",
+                "static",
+                "implements",
+                "interface",
+                "package",
+                "private",
+                "protected",
+                "public",
+                "await",
+                "arguments",
+                "eval",
+                "undefined",
+            ],
+            Target::Python => &[
+                "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class",
+                "continue", "def", "del", "elif", "else", "except", "finally", "for", "from",
+                "global", "if", "import", "in", "is", "lambda", "nonlocal", "not", "or", "pass",
+                "raise", "return", "try", "while", "with", "yield",
+            ],
+            Target::Rust => &[
+                "as", "async", "await", "box", "break", "const", "continue", "crate", "do", "dyn",
+                "else", "enum", "extern", "false", "fn", "for", "gen", "if", "impl", "in", "let",
+                "loop", "macro", "match", "mod", "move", "mut", "priv", "pub", "ref", "return",
+                "self", "Self", "static", "struct", "super", "trait", "true", "try", "type",
+                "typeof", "unsafe", "use", "virtual", "where", "while", "yield", "_", "Value",
+            ],
+        }
+    }
+
+    // a lambda up to its body, a closing parenthesis ends it
+    fn lambda(self, param: &str) -> String {
+        match self {
+            Target::JavaScript => format!("({} => ", param),
+            Target::Python => format!("(lambda {}: ", param),
+            Target::Rust => unreachable!("Rust has its own emitter"),
+        }
+    }
+}
+
+// the expression, or the first free variable that keeps the term from being
+// closed
+pub fn generate(term: &Term, free: &[Symbol], target: Target) -> Result<String, String> {
+    let mut env = Scope::default();
+    match target {
+        Target::Rust => Ok(format!(
+            "{}\npub fn term() -> Value {{\n    {}\n}}\n",
+            RUST_PRELUDE,
+            RustEmitter {
+                free,
+                env,
+                outer: free_above(term),
+                ends: subtree_ends(term),
+                node: 0,
+            }
+            .emit(term, 1)?
+        )),
+        _ => emit(term, free, target, &mut env),
+    }
+}
+
+const RUST_PRELUDE: &str = "\
+// generated by lambda_rs, the term is built by term()
+use std::rc::Rc;
+
+// every value of the untyped lambda calculus is a function of one value
+#[derive(Clone)]
+pub struct Value(Rc<dyn Fn(Value) -> Value>);
+
+impl Value {
+    pub fn new(f: impl Fn(Value) -> Value + 'static) -> Value {
+        Value(Rc::new(f))
+    }
+
+    pub fn apply(&self, arg: Value) -> Value {
+        (self.0)(arg)
+    }
+}
+";
+
+fn free_variable(free: &[Symbol], index: i32) -> String {
+    format!(
+        "free variable {}, only closed terms can be generated",
+        free[(-index - 1) as usize]
+    )
+}
+
+// what the emitters do next: write a term or some text, or enter or leave
+// the scope of a binder
+enum Piece<'a> {
+    Term(&'a Term, usize), // at that depth of blocks, Rust only
+    Text(String),
+    Bind(Symbol),
+    Leave,
+}
+
+// the expression written left to right with an explicit stack. a let's bound
+// term comes after its body, which names it the same: leaving the body
+// restores the scope
+fn emit(term: &Term, free: &[Symbol], target: Target, env: &mut Scope) -> Result<String, String> {
+    let mut out = String::new();
+    let mut work = vec![Piece::Term(term, 0)];
+    while let Some(piece) = work.pop() {
+        let term = match piece {
+            Piece::Text(text) => {
+                out.push_str(&text);
+                continue;
+            }
+            Piece::Bind(param) => {
+                env.bind(param, target);
+                continue;
+            }
+            Piece::Leave => {
+                env.pop();
+                continue;
+            }
+            Piece::Term(term, _) => term,
+        };
+        match term {
+            Term::Variable(index) if *index < 0 => return Err(free_variable(free, *index)),
+            Term::Variable(index) => out.push_str(env.get(*index)),
+            Term::Constant(constant) => out.push_str(&self::constant(*constant, target)),
+            Term::Lambda(param, _, body) => {
+                let name = env.bind(*param, target);
+                out.push_str(&target.lambda(&name));
+                work.extend([
+                    Piece::Text(")".to_string()),
+                    Piece::Leave,
+                    Piece::Term(body, 0),
+                ]);
+            }
+            Term::Application(lhs, rhs) => work.extend([
+                Piece::Text(")".to_string()),
+                Piece::Term(rhs, 0),
+                Piece::Text("(".to_string()),
+                Piece::Term(lhs, 0),
+            ]),
+            Term::TypeLambda(_, body) | Term::TypeApplication(body, _) => {
+                work.push(Piece::Term(body, 0))
+            }
+            Term::Let(name, bound, body) => {
+                let name = env.bind(*name, target);
+                out.push_str(&target.lambda(&name));
+                work.extend([
+                    Piece::Text(")".to_string()),
+                    Piece::Term(bound, 0),
+                    Piece::Text(")(".to_string()),
+                    Piece::Leave,
+                    Piece::Term(body, 0),
+                ]);
+            }
+        }
+    }
+    Ok(out)
+}
+
+// closures only borrow what they capture, so each clones the variables its
+// body uses from outside before moving them in
+struct RustEmitter<'a> {
+    free: &'a [Symbol],
+    env: Scope,
+    // for every node in pre-order, the variables it uses from outside, see free_above
+    outer: Vec<Vec<i32>>,
+    // for every node in pre-order, the index just past its subterm
+    ends: Vec<usize>,
+    node: usize, // pre-order index of the next node emitted
+}
+
+impl RustEmitter<'_> {
+    // written left to right with an explicit stack as `emit` does, `indent` is
+    // the depth of blocks
+    fn emit(&mut self, term: &Term, indent: usize) -> Result<String, String> {
+        let pad = |depth: usize| "    ".repeat(depth);
+        let mut out = String::new();
+        let mut work = vec![Piece::Term(term, indent)];
+        while let Some(piece) = work.pop() {
+            let (term, indent) = match piece {
+                Piece::Text(text) => {
+                    out.push_str(&text);
+                    continue;
+                }
+                Piece::Bind(param) => {
+                    self.env.bind(param, Target::Rust);
+                    continue;
+                }
+                Piece::Leave => {
+                    self.env.pop();
+                    continue;
+                }
+                Piece::Term(term, indent) => (term, indent),
+            };
+            let node = self.node;
+            self.node += 1;
+            match term {
+                Term::Variable(index) if *index < 0 => {
+                    return Err(free_variable(self.free, *index));
+                }
+                Term::Variable(index) => {
+                    out.push_str(self.env.get(*index));
+                    out.push_str(".clone()");
+                }
+                Term::Constant(_) => {
+                    return Err("primitive constants have no Rust counterpart".to_string());
+                }
+                Term::Lambda(param, _, body) => {
+                    let outer = &self.outer[node + 1];
+                    // index 1 is the parameter itself
+                    let used = outer.contains(&1);
+                    let captured: Vec<String> = outer
+                        .iter()
+                        .filter(|&&index| index > 1)
+                        .map(|&index| self.env.get(index - 1).to_string())
+                        .collect();
+                    let name = self.env.bind(*param, Target::Rust);
+                    // rustc warns about a parameter the body never uses
+                    let name = if used { name } else { "_".to_string() };
+                    let end = if captured.is_empty() {
+                        out.push_str(&format!("Value::new(move |{}| ", name));
+                        ")".to_string()
+                    } else {
+                        out.push_str("Value::new({\n");
+                        for name in &captured {
+                            out.push_str(&format!(
+                                "{}let {} = {}.clone();\n",
+                                pad(indent + 1),
+                                name,
+                                name
+                            ));
+                        }
+                        out.push_str(&format!("{}move |{}| ", pad(indent + 1), name));
+                        format!("\n{}}})", pad(indent))
+                    };
+                    work.extend([
+                        Piece::Text(end),
+                        Piece::Leave,
+                        Piece::Term(body, indent + 1),
+                    ]);
+                }
+                Term::Application(lhs, rhs) => {
+                    work.extend([
+                        Piece::Text(")".to_string()),
+                        Piece::Term(rhs, indent),
+                        Piece::Text(".apply(".to_string()),
+                    ]);
+                    match &**lhs {
+                        Term::Variable(index) if *index > 0 => {
+                            self.node += 1;
+                            out.push_str(self.env.get(*index));
+                        }
+                        _ => work.push(Piece::Term(lhs, indent)),
+                    }
+                }
+                Term::TypeLambda(_, body) | Term::TypeApplication(body, _) => {
+                    work.push(Piece::Term(body, indent))
+                }
+                // the name is written before the bound term, which is named
+                // without it in scope: choose it now, bind it after
+                Term::Let(name, bound, body) => {
+                    let used = self.outer[self.ends[node + 1]].contains(&1);
+                    let shown = match used {
+                        true => self.env.choose(*name, Target::Rust).0,
+                        false => "_".to_string(),
+                    };
+                    out.push_str(&format!("{{\n{}let {} = ", pad(indent + 1), shown));
+                    work.extend([
+                        Piece::Text(format!("\n{}}}", pad(indent))),
+                        Piece::Leave,
+                        Piece::Term(body, indent + 1),
+                        Piece::Bind(*name),
+                        Piece::Text(format!(";\n{}", pad(indent + 1))),
+                        Piece::Term(bound, indent + 1),
+                    ]);
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+// the nodes of `term` in pre-order
+fn pre_order(term: &Term) -> Vec<&Term> {
+    let mut nodes = Vec::new();
+    let mut work = vec![term];
+    while let Some(node) = work.pop() {
+        nodes.push(node);
+        match node {
+            Term::Variable(_) | Term::Constant(_) => {}
+            Term::Lambda(_, _, body)
+            | Term::TypeLambda(_, body)
+            | Term::TypeApplication(body, _) => work.push(body),
+            Term::Application(lhs, rhs) | Term::Let(_, lhs, rhs) => {
+                work.push(rhs);
+                work.push(lhs);
+            }
+        }
+    }
+    nodes
+}
+
+// for every node of `term` in pre-order, the index just past its subterm. a
+// node's first child follows it, the second child the end of the first
+fn subtree_ends(term: &Term) -> Vec<usize> {
+    let nodes = pre_order(term);
+    let mut ends = vec![0; nodes.len()];
+    for at in (0..nodes.len()).rev() {
+        ends[at] = match nodes[at] {
+            Term::Variable(_) | Term::Constant(_) => at + 1,
+            Term::Lambda(..) | Term::TypeLambda(..) | Term::TypeApplication(..) => ends[at + 1],
+            Term::Application(..) | Term::Let(..) => ends[ends[at + 1]],
+        };
+    }
+    ends
+}
+
+// for every node of `term` in pre-order, the variables of its subterm pointing
+// above it, counted from there, without duplicates and in the order they occur.
+// one bottom-up pass, the sets of the children merged into their parent's
+fn free_above(term: &Term) -> Vec<Vec<i32>> {
+    fn merge(mut first: Vec<i32>, second: &[i32]) -> Vec<i32> {
+        for index in second {
+            if !first.contains(index) {
+                first.push(*index);
+            }
+        }
+        first
+    }
+    // the variables of child `child` pointing above `binders` more binders
+    let above = |sets: &[Vec<i32>], child: usize, binders: i32| {
+        sets[child]
+            .iter()
+            .filter(|&&index| index > binders)
+            .map(|&index| index - binders)
+            .collect::<Vec<i32>>()
+    };
+    let nodes = pre_order(term);
+    let ends = subtree_ends(term);
+    let mut sets = vec![Vec::new(); nodes.len()];
+    // children follow their parent, so going backwards they are done first
+    for at in (0..nodes.len()).rev() {
+        let first = at + 1;
+        sets[at] = match nodes[at] {
+            Term::Variable(index) if *index > 0 => vec![*index],
+            Term::Variable(_) | Term::Constant(_) => Vec::new(),
+            Term::Lambda(..) => above(&sets, first, 1),
+            Term::TypeLambda(..) | Term::TypeApplication(..) => sets[first].clone(),
+            Term::Application(..) => merge(above(&sets, first, 0), &above(&sets, ends[first], 0)),
+            Term::Let(..) => merge(above(&sets, first, 0), &above(&sets, ends[first], 1)),
+        };
+    }
+    sets
+}
+
+// the primitives as curried functions of the target
+fn constant(constant: Constant, target: Target) -> String {
+    match (constant, target) {
+        (Constant::Int(value), _) => value.to_string(),
+        (Constant::Bool(value), Target::JavaScript) => value.to_string(),
+        (Constant::Bool(true), Target::Python) => "True".to_string(),
+        (Constant::Bool(false), Target::Python) => "False".to_string(),
+        (Constant::Add, Target::JavaScript) => "(a => (b => a + b))".to_string(),
+        (Constant::Add, Target::Python) => "(lambda a: (lambda b: a + b))".to_string(),
+        (Constant::Mul, Target::JavaScript) => "(a => (b => a * b))".to_string(),
+        (Constant::Mul, Target::Python) => "(lambda a: (lambda b: a * b))".to_string(),
+        (Constant::Ite, Target::JavaScript) => "(c => (t => (e => c ? t : e)))".to_string(),
+        (Constant::Ite, Target::Python) => {
+            "(lambda c: (lambda t: (lambda e: t if c else e)))".to_string()
+        }
+        (_, Target::Rust) => unreachable!("Rust has its own emitter"),
+    }
+}
+
+// the names given to the enclosing binders, innermost last. `live` counts the
+// binders holding each name, `next` is the first suffix to try for a base name
+#[derive(Default)]
+struct Scope {
+    names: Vec<Bound>,
+    live: HashMap<String, usize>,
+    next: HashMap<Symbol, usize>,
+}
+
+struct Bound {
+    name: String,
+    param: Symbol,
+    next: Option<usize>, // what `next` of param was before this binder
+}
+
+impl Scope {
+    // the name of the variable with de Bruijn index `index`
+    fn get(&self, index: i32) -> &str {
+        &self.names[self.names.len() - index as usize].name
+    }
+
+    // a name for the binder that is a valid identifier, no reserved word, and
+    // does not shadow an enclosing binder. suffixes go on from the last one
+    // given to the same name, so a chain of shadowing binders is linear
+    fn bind(&mut self, param: Symbol, target: Target) -> String {
+        let previous = self.next.get(&param).copied();
+        let (name, next) = self.choose(param, target);
+        if let Some(next) = next {
+            self.next.insert(param, next);
+        }
+        *self.live.entry(name.clone()).or_default() += 1;
+        self.names.push(Bound {
+            name: name.clone(),
+            param,
+            next: previous,
+        });
+        name
+    }
+
+    // the name `bind` would give the binder now, and the suffix to try after
+    // it if that is a suffixed one
+    fn choose(&self, param: Symbol, target: Target) -> (String, Option<usize>) {
+        let taken = |candidate: &str| {
+            target.reserved().contains(&candidate)
+                || self.live.get(candidate).is_some_and(|&n| n > 0)
+        };
+        let base = param.as_str();
+        if !taken(base) {
+            return (base.to_string(), None);
+        }
+        let mut n = self.next.get(&param).copied().unwrap_or(1);
+        while taken(&format!("{}_{}", base, n)) {
+            n += 1;
+        }
+        (format!("{}_{}", base, n), Some(n + 1))
+    }
+
+    // leave the innermost binder
+    fn pop(&mut self) {
+        let bound = self.names.pop().expect("a binder to leave");
+        *self.live.get_mut(&bound.name).expect("live name") -= 1;
+        match bound.next {
+            Some(next) => self.next.insert(bound.param, next),
+            None => self.next.remove(&bound.param),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::{app, k, lam, var};
+
+    // the Rust output without the prelude
+    fn rust(term: &Term) -> String {
+        let out = generate(term, &[], Target::Rust).unwrap();
+        out.strip_prefix(RUST_PRELUDE).unwrap().to_string()
+    }
+
+    #[test]
+    fn scripts_are_curried_lambdas() {
+        assert_eq!(
+            generate(&k(), &[], Target::JavaScript).unwrap(),
+            "(x => (y => x))"
+        );
+        let twice = Term::Let(
+            Symbol::intern("u"),
+            Box::new(lam("x", var(1))),
+            Box::new(app(var(1), var(1))),
+        );
+        assert_eq!(
+            generate(&twice, &[], Target::Python).unwrap(),
+            "(lambda u: u(u))((lambda x: x))"
+        );
+        assert!(generate(&var(-1), &[Symbol::intern("f")], Target::JavaScript).is_err());
+    }
+
+    #[test]
+    fn unused_rust_binders_are_underscores() {
+        assert_eq!(
+            rust(&k()),
+            "\npub fn term() -> Value {
+    Value::new(move |x| Value::new({
+            let x = x.clone();
+            move |_| x.clone()
+        }))
+}
+"
+        );
+        let unused = Term::Let(
+            Symbol::intern("u"),
+            Box::new(lam("x", var(1))),
+            Box::new(lam("z", var(1))),
+        );
+        assert_eq!(
+            rust(&unused),
+            "\npub fn term() -> Value {
+    {
+        let _ = Value::new(move |x| x.clone());
+        Value::new(move |z| z.clone())
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn shadowing_binders_are_renamed_and_deep_terms_generated() {
+        let shadowed = lam("x", app(lam("x", lam("x", var(3))), lam("x", var(1))));
+        assert_eq!(
+            generate(&shadowed, &[], Target::JavaScript).unwrap(),
+            "(x => (x_1 => (x_2 => x))((x_1 => x_1)))"
+        );
+        let reserved = lam("in", lam("in", var(2)));
+        assert_eq!(
+            generate(&reserved, &[], Target::Python).unwrap(),
+            "(lambda in_1: (lambda in_2: in_1))"
+        );
+        let deep = (0..20_000).fold(var(1), |body, _| lam("x", body));
+        let js = generate(&deep, &[], Target::JavaScript).unwrap();
+        assert!(
+            js.starts_with("(x => (x_1 => ")
+                && js.ends_with(&format!("x_19999{}", ")".repeat(20_000)))
+        );
+        let deep = (0..20_000).fold(lam("y", var(1)), |body, _| app(lam("x", var(1)), body));
+        assert!(rust(&deep).contains(&"Value::new(move |x| x.clone()).apply(".repeat(2)));
+    }
+
+    #[test]
+    fn rust_closures_clone_what_they_capture_in_order() {
+        // \f.{\x.{\y.{<<f|y>|x>}}}: the innermost closure captures f then x
+        let flip = lam("f", lam("x", lam("y", app(app(var(3), var(1)), var(2)))));
+        let out = rust(&flip);
+        assert!(out.contains("let f = f.clone();\n                let x = x.clone();\n"));
+        assert!(out.contains("f.apply(y.clone()).apply(x.clone())"));
+        let sets = free_above(&flip);
+        assert_eq!(sets.len(), flip.size());
+        assert_eq!(
+            (sets[0].len(), &sets[2], &sets[3]),
+            (0, &vec![2, 1], &vec![3, 1, 2])
+        );
+    }
+}
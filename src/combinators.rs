@@ -0,0 +1,108 @@
+// the standard combinators as closed terms, built directly in de Bruijn form
+use crate::parser::Term;
+use crate::symbol::Symbol;
+
+// bound variable, 1 is the innermost binder
+pub fn var(index: i32) -> Term {
+    Term::Variable(index)
+}
+
+// unannotated lambda
+pub fn lam(param: &str, body: Term) -> Term {
+    Term::Lambda(Symbol::intern(param), None, Box::new(body))
+}
+
+pub fn app(lhs: Term, rhs: Term) -> Term {
+    Term::Application(Box::new(lhs), Box::new(rhs))
+}
+
+// fold a spine: apps(f, [a, b]) is <<f|a>|b>
+pub fn apps(head: Term, args: impl IntoIterator<Item = Term>) -> Term {
+    args.into_iter().fold(head, app)
+}
+
+// λx. x
+pub fn i() -> Term {
+    lam("x", var(1))
+}
+
+// λx. λy. x
+pub fn k() -> Term {
+    lam("x", lam("y", var(2)))
+}
+
+// λx. λy. λz. x z (y z)
+pub fn s() -> Term {
+    lam(
+        "x",
+        lam("y", lam("z", app(app(var(3), var(1)), app(var(2), var(1))))),
+    )
+}
+
+// λx. λy. λz. x (y z), composition
+pub fn b() -> Term {
+    lam("x", lam("y", lam("z", app(var(3), app(var(2), var(1))))))
+}
+
+// λx. λy. λz. x z y, flip
+pub fn c() -> Term {
+    lam("x", lam("y", lam("z", app(app(var(3), var(1)), var(2)))))
+}
+
+// λf. (λx. f (x x)) (λx. f (x x)), the fixed point combinator for normal order
+pub fn y() -> Term {
+    let half = lam("x", app(var(2), app(var(1), var(1))));
+    lam("f", app(half.clone(), half))
+}
+
+// λf. (λx. f (λv. x x v)) (λx. f (λv. x x v)), the fixed point combinator for
+// applicative order, the η-expansion stops x x from unfolding before it is applied
+pub fn z() -> Term {
+    let half = lam("x", app(var(2), lam("v", apps(var(2), [var(2), var(1)]))));
+    lam("f", app(half.clone(), half))
+}
+
+// λx. x x
+pub fn omega() -> Term {
+    lam("x", app(var(1), var(1)))
+}
+
+// ω ω, reduces to itself
+pub fn big_omega() -> Term {
+    app(omega(), omega())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::church;
+    use crate::parser::{self, ParserConfig};
+    use crate::prelude;
+    use crate::reduce::{self, Strategy};
+
+    // 3! with letrec, through Y or Z as the strategy asks. the branches are
+    // thunks so applicative order only takes the one the test picks. B multiplies
+    // numerals, unlike mul it is not a primitive
+    fn factorial(strategy: Strategy) -> Term {
+        let source = r"letrec fact = \n.{
+            <<<<is_zero|n>|\d.{c1}>|\d.{<<B|n>|<fact|<pred|n>>>}>|I>
+        } in <fact|c3>";
+        let config = ParserConfig {
+            strategy,
+            ..Default::default()
+        };
+        let (term, free) = parser::parse_with_config(source, config).unwrap();
+        prelude::resolve(&term, &free)
+    }
+
+    #[test]
+    fn letrec_factorial_normalizes() {
+        let (normal, _) = reduce::normalize(&factorial(Strategy::Normal), 10_000).unwrap();
+        assert_eq!(church::to_number(&normal), Some(6));
+        // Z stops at a value under applicative order, the rest is under binders
+        let term = factorial(Strategy::Applicative);
+        let (value, _) = reduce::normalize_with(&term, 10_000, Strategy::Applicative).unwrap();
+        let (normal, _) = reduce::normalize(&value, 10_000).unwrap();
+        assert_eq!(church::to_number(&normal), Some(6));
+    }
+}
@@ -0,0 +1,503 @@
+// index-based term layout: nodes live in one Vec and refer to their children by
+// position, 16 bytes each, with annotations, types and constants in side tables
+//
+// Term stays the interface, convert with Compact::from_term and to_term. nodes are
+// never changed once pushed, so reduction shares every subterm it does not touch
+// and leaves the rest behind until collect_garbage.
+use std::mem;
+
+use crate::parser::{Constant, Term};
+use crate::profile;
+use crate::reduce;
+use crate::symbol::Symbol;
+use crate::types::{self, Type};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeId(u32);
+
+// index into Compact::types, NO_TYPE for an unannotated lambda
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TypeId(u32);
+
+const NO_TYPE: TypeId = TypeId(u32::MAX);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Node {
+    Variable(i32),
+    Constant(u32), // index into Compact::constants
+    Lambda(Symbol, TypeId, NodeId),
+    Application(NodeId, NodeId),
+    TypeLambda(Symbol, NodeId),
+    TypeApplication(NodeId, TypeId),
+    Let(Symbol, NodeId, NodeId),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Compact {
+    nodes: Vec<Node>,
+    types: Vec<Type>,
+    constants: Vec<Constant>,
+    root: Option<NodeId>,
+}
+
+// what the arena holds, and how much of it the root still reaches
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub nodes: usize,
+    pub live: usize, // reachable from the root, the rest goes at collect_garbage
+    pub types: usize,
+    pub constants: usize,
+    // heap held by the tables, shared subterms count once
+    pub bytes: usize,
+}
+
+enum Frame<T> {
+    Visit(T, i32),
+    Build(T),
+}
+
+impl Compact {
+    pub fn from_term(term: &Term) -> Self {
+        let mut compact = Self::default();
+        let root = compact.push_term(term);
+        compact.root = Some(root);
+        compact
+    }
+
+    pub fn to_term(&self) -> Term {
+        self.subterm(self.root())
+    }
+
+    fn subterm(&self, id: NodeId) -> Term {
+        let mut work = vec![Frame::Visit(id, 0)];
+        let mut done: Vec<Term> = Vec::new();
+        while let Some(frame) = work.pop() {
+            match frame {
+                Frame::Visit(id, _) => match self.node(id) {
+                    Node::Variable(index) => done.push(Term::Variable(index)),
+                    Node::Constant(k) => done.push(Term::Constant(self.constants[k as usize])),
+                    node => {
+                        work.push(Frame::Build(id));
+                        for child in children(node).into_iter().rev().flatten() {
+                            work.push(Frame::Visit(child, 0));
+                        }
+                    }
+                },
+                Frame::Build(id) => {
+                    let mut pop = || Box::new(done.pop().expect("child built"));
+                    let term = match self.node(id) {
+                        Node::Lambda(param, ty, _) => Term::Lambda(param, self.annot(ty), pop()),
+                        Node::TypeLambda(param, _) => Term::TypeLambda(param, pop()),
+                        Node::TypeApplication(_, ty) => {
+                            Term::TypeApplication(pop(), self.types[ty.0 as usize].clone())
+                        }
+                        Node::Application(..) => {
+                            let rhs = pop();
+                            Term::Application(pop(), rhs)
+                        }
+                        Node::Let(name, ..) => {
+                            let body = pop();
+                            Term::Let(name, pop(), body)
+                        }
+                        Node::Variable(_) | Node::Constant(_) => {
+                            unreachable!("leaves are not built")
+                        }
+                    };
+                    done.push(term);
+                }
+            }
+        }
+        done.pop().expect("root built")
+    }
+
+    pub fn root(&self) -> NodeId {
+        self.root.expect("empty Compact")
+    }
+
+    pub fn node(&self, id: NodeId) -> Node {
+        self.nodes[id.0 as usize]
+    }
+
+    // nodes stored, reachable or not
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    fn annot(&self, ty: TypeId) -> Option<Type> {
+        (ty != NO_TYPE).then(|| self.types[ty.0 as usize].clone())
+    }
+
+    fn push(&mut self, node: Node) -> NodeId {
+        self.nodes.push(node);
+        NodeId(self.nodes.len() as u32 - 1)
+    }
+
+    fn push_type(&mut self, ty: &Type) -> TypeId {
+        self.types.push(ty.clone());
+        TypeId(self.types.len() as u32 - 1)
+    }
+
+    // append the nodes of `term`, children before parents
+    fn push_term(&mut self, term: &Term) -> NodeId {
+        let mut work = vec![Frame::Visit(term, 0)];
+        let mut done: Vec<NodeId> = Vec::new();
+        while let Some(frame) = work.pop() {
+            match frame {
+                Frame::Visit(node, _) => match node {
+                    Term::Variable(index) => done.push(self.push(Node::Variable(*index))),
+                    Term::Constant(constant) => {
+                        self.constants.push(*constant);
+                        let k = self.constants.len() as u32 - 1;
+                        done.push(self.push(Node::Constant(k)));
+                    }
+                    Term::Lambda(_, _, body)
+                    | Term::TypeLambda(_, body)
+                    | Term::TypeApplication(body, _) => {
+                        work.push(Frame::Build(node));
+                        work.push(Frame::Visit(body, 0));
+                    }
+                    Term::Application(lhs, rhs) | Term::Let(_, lhs, rhs) => {
+                        work.push(Frame::Build(node));
+                        work.push(Frame::Visit(rhs, 0));
+                        work.push(Frame::Visit(lhs, 0));
+                    }
+                },
+                Frame::Build(node) => {
+                    let mut pop = || done.pop().expect("child built");
+                    let built = match node {
+                        Term::Lambda(param, annot, _) => {
+                            let body = pop();
+                            let ty = annot.as_ref().map_or(NO_TYPE, |ty| self.push_type(ty));
+                            Node::Lambda(*param, ty, body)
+                        }
+                        Term::TypeLambda(param, _) => Node::TypeLambda(*param, pop()),
+                        Term::TypeApplication(_, ty) => {
+                            let fun = pop();
+                            Node::TypeApplication(fun, self.push_type(ty))
+                        }
+                        Term::Application(..) => {
+                            let rhs = pop();
+                            Node::Application(pop(), rhs)
+                        }
+                        Term::Let(name, ..) => {
+                            let body = pop();
+                            Node::Let(*name, pop(), body)
+                        }
+                        Term::Variable(_) | Term::Constant(_) => {
+                            unreachable!("leaves are not built")
+                        }
+                    };
+                    let id = self.push(built);
+                    done.push(id);
+                }
+            }
+        }
+        done.pop().expect("root built")
+    }
+
+    // rebuild the subterm at `root` bottom-up, `leaf` maps variables and constants
+    // given the number of term binders above them inside the subterm
+    fn map_leaves(
+        &mut self,
+        root: NodeId,
+        mut leaf: impl FnMut(&mut Self, NodeId, i32) -> NodeId,
+    ) -> NodeId {
+        let mut work = vec![Frame::Visit(root, 0)];
+        let mut done: Vec<NodeId> = Vec::new();
+        while let Some(frame) = work.pop() {
+            match frame {
+                Frame::Visit(id, depth) => match self.node(id) {
+                    Node::Variable(_) | Node::Constant(_) => {
+                        let mapped = leaf(self, id, depth);
+                        done.push(mapped);
+                    }
+                    node => {
+                        work.push(Frame::Build(id));
+                        let inner = match node {
+                            Node::Lambda(..) => [depth + 1, depth + 1],
+                            Node::Let(..) => [depth + 1, depth],
+                            _ => [depth, depth],
+                        };
+                        // inner[0] for the last child, inner[1] for the first
+                        for (child, depth) in children(node).into_iter().rev().flatten().zip(inner)
+                        {
+                            work.push(Frame::Visit(child, depth));
+                        }
+                    }
+                },
+                Frame::Build(id) => {
+                    let mut pop = || done.pop().expect("child built");
+                    let built = match self.node(id) {
+                        Node::Lambda(param, ty, _) => Node::Lambda(param, ty, pop()),
+                        Node::TypeLambda(param, _) => Node::TypeLambda(param, pop()),
+                        Node::TypeApplication(_, ty) => Node::TypeApplication(pop(), ty),
+                        Node::Application(..) => {
+                            let rhs = pop();
+                            Node::Application(pop(), rhs)
+                        }
+                        Node::Let(name, ..) => {
+                            let body = pop();
+                            Node::Let(name, pop(), body)
+                        }
+                        Node::Variable(_) | Node::Constant(_) => {
+                            unreachable!("leaves are not built")
+                        }
+                    };
+                    let id = self.push(built);
+                    done.push(id);
+                }
+            }
+        }
+        done.pop().expect("root built")
+    }
+
+    // as reduce::shift
+    fn shift(&mut self, id: NodeId, d: i32, cutoff: i32) -> NodeId {
+        if d == 0 {
+            return id;
+        }
+        self.map_leaves(id, |this, leaf, depth| match this.node(leaf) {
+            Node::Variable(index) if index > cutoff + depth => this.push(Node::Variable(index + d)),
+            _ => leaf,
+        })
+    }
+
+    // as reduce::beta, contract <\x.{body}|arg>
+    fn beta(&mut self, body: NodeId, arg: NodeId) -> NodeId {
+        self.map_leaves(body, |this, leaf, depth| match this.node(leaf) {
+            Node::Variable(index) if index == depth + 1 => this.shift(arg, depth, 0),
+            Node::Variable(index) if index > depth + 1 => this.push(Node::Variable(index - 1)),
+            _ => leaf,
+        })
+    }
+
+    // leftmost-outermost redex, as the chain of nodes from the root down to it
+    fn find_redex(&self) -> Option<Vec<NodeId>> {
+        let mut work = vec![(self.root(), 0usize)];
+        let mut path: Vec<NodeId> = Vec::new();
+        while let Some((id, len)) = work.pop() {
+            path.truncate(len);
+            path.push(id);
+            if self.is_redex(id) {
+                return Some(path);
+            }
+            for child in children(self.node(id)).into_iter().rev().flatten() {
+                work.push((child, len + 1));
+            }
+        }
+        None
+    }
+
+    fn is_redex(&self, id: NodeId) -> bool {
+        match self.node(id) {
+            Node::Let(..) => true,
+            Node::Application(fun, _) => match self.node(fun) {
+                Node::Lambda(..) => true,
+                // δ-redexes are left to reduce, only spines headed by a constant qualify
+                _ => self.constant_headed(id) && reduce::is_redex(&self.subterm(id)),
+            },
+            Node::TypeApplication(fun, _) => matches!(self.node(fun), Node::TypeLambda(..)),
+            _ => false,
+        }
+    }
+
+    fn constant_headed(&self, mut id: NodeId) -> bool {
+        while let Node::Application(fun, _) = self.node(id) {
+            id = fun;
+        }
+        matches!(self.node(id), Node::Constant(_))
+    }
+
+    fn contract(&mut self, id: NodeId) -> NodeId {
+        match self.node(id) {
+            Node::Let(_, bound, body) => self.beta(body, bound),
+            Node::Application(fun, arg) => match self.node(fun) {
+                Node::Lambda(_, _, body) => self.beta(body, arg),
+                _ => {
+                    let contracted = reduce::contract(&self.subterm(id), &[]);
+                    self.push_term(&contracted)
+                }
+            },
+            Node::TypeApplication(fun, ty) => match self.node(fun) {
+                Node::TypeLambda(param, body) => {
+                    let body = self.subterm(body);
+                    let ty = self.types[ty.0 as usize].clone();
+                    let substituted = types::subst_type_in_term(&body, param, &ty);
+                    self.push_term(&substituted)
+                }
+                _ => panic!("Not a redex"),
+            },
+            _ => panic!("Not a redex"),
+        }
+    }
+
+    // one normal-order step, false if the term is in normal form
+    pub fn step(&mut self) -> bool {
+        let Some(path) = self.find_redex() else {
+            return false;
+        };
+        let mut replaced = self.contract(*path.last().unwrap());
+        // copy the spine above the redex, sharing the siblings
+        for window in path.windows(2).rev() {
+            let (parent, old) = (window[0], window[1]);
+            let node = match self.node(parent) {
+                Node::Lambda(param, ty, _) => Node::Lambda(param, ty, replaced),
+                Node::TypeLambda(param, _) => Node::TypeLambda(param, replaced),
+                Node::TypeApplication(_, ty) => Node::TypeApplication(replaced, ty),
+                Node::Application(lhs, rhs) if lhs == old => Node::Application(replaced, rhs),
+                Node::Application(lhs, _) => Node::Application(lhs, replaced),
+                Node::Let(name, bound, body) if bound == old => Node::Let(name, replaced, body),
+                Node::Let(name, bound, _) => Node::Let(name, bound, replaced),
+                leaf => unreachable!("{:?} has no children", leaf),
+            };
+            replaced = self.push(node);
+        }
+        self.root = Some(replaced);
+        true
+    }
+
+    // as reduce::normalize, returns the number of steps taken
+    pub fn normalize(&mut self, fuel: usize) -> Option<usize> {
+        for steps in 0..=fuel {
+            if !self.step() {
+                return Some(steps);
+            }
+            // drop what the reduction left behind once it dominates
+            if self.nodes.len() > 1024 && steps % 64 == 63 {
+                self.collect_garbage();
+            }
+        }
+        None
+    }
+
+    pub fn stats(&self) -> Stats {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut live = 0;
+        let mut work: Vec<NodeId> = self.root.into_iter().collect();
+        while let Some(id) = work.pop() {
+            if std::mem::replace(&mut seen[id.0 as usize], true) {
+                continue;
+            }
+            live += 1;
+            work.extend(children(self.node(id)).into_iter().flatten());
+        }
+        Stats {
+            nodes: self.nodes.len(),
+            live,
+            types: self.types.len(),
+            constants: self.constants.len(),
+            bytes: self.nodes.capacity() * mem::size_of::<Node>()
+                + self.types.capacity() * mem::size_of::<Type>()
+                + self
+                    .types
+                    .iter()
+                    .map(profile::type_heap_bytes)
+                    .sum::<usize>()
+                + self.constants.capacity() * mem::size_of::<Constant>(),
+        }
+    }
+
+    // keep only the nodes reachable from the root, preserving sharing
+    pub fn collect_garbage(&mut self) {
+        let mut moved: Vec<Option<NodeId>> = vec![None; self.nodes.len()];
+        let mut kept = Compact::default();
+        let mut work = vec![Frame::Visit(self.root(), 0)];
+        while let Some(frame) = work.pop() {
+            match frame {
+                Frame::Visit(id, _) => {
+                    if moved[id.0 as usize].is_some() {
+                        continue;
+                    }
+                    work.push(Frame::Build(id));
+                    for child in children(self.node(id)).into_iter().flatten() {
+                        work.push(Frame::Visit(child, 0));
+                    }
+                }
+                Frame::Build(id) => {
+                    if moved[id.0 as usize].is_some() {
+                        continue;
+                    }
+                    let new = |child: NodeId| moved[child.0 as usize].expect("child moved");
+                    let mut keep_type = |ty: TypeId| {
+                        if ty == NO_TYPE {
+                            ty
+                        } else {
+                            kept.push_type(&self.types[ty.0 as usize])
+                        }
+                    };
+                    let node = match self.node(id) {
+                        Node::Constant(k) => {
+                            kept.constants.push(self.constants[k as usize]);
+                            Node::Constant(kept.constants.len() as u32 - 1)
+                        }
+                        Node::Variable(index) => Node::Variable(index),
+                        Node::Lambda(param, ty, body) => {
+                            Node::Lambda(param, keep_type(ty), new(body))
+                        }
+                        Node::TypeLambda(param, body) => Node::TypeLambda(param, new(body)),
+                        Node::TypeApplication(fun, ty) => {
+                            Node::TypeApplication(new(fun), keep_type(ty))
+                        }
+                        Node::Application(lhs, rhs) => Node::Application(new(lhs), new(rhs)),
+                        Node::Let(name, bound, body) => Node::Let(name, new(bound), new(body)),
+                    };
+                    moved[id.0 as usize] = Some(kept.push(node));
+                }
+            }
+        }
+        kept.root = moved[self.root().0 as usize];
+        *self = kept;
+    }
+}
+
+// children in order, lhs before rhs and bound before body
+fn children(node: Node) -> [Option<NodeId>; 2] {
+    match node {
+        Node::Variable(_) | Node::Constant(_) => [None, None],
+        Node::Lambda(_, _, body) | Node::TypeLambda(_, body) | Node::TypeApplication(body, _) => {
+            [Some(body), None]
+        }
+        Node::Application(lhs, rhs) | Node::Let(_, lhs, rhs) => [Some(lhs), Some(rhs)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::church;
+    use crate::combinators::{app, apps, lam, omega, var};
+    use crate::reduce;
+
+    #[test]
+    fn reduction_agrees_with_reduce() {
+        let sum = apps(church::add(), [church::numeral(2), church::numeral(3)]);
+        let mut compact = Compact::from_term(&sum);
+        let (normal, steps) = reduce::normalize(&sum, 1000).unwrap();
+        assert_eq!(compact.normalize(1000), Some(steps));
+        assert_eq!(compact.to_term(), normal);
+        assert_eq!(
+            Compact::from_term(&app(omega(), omega())).normalize(100),
+            None
+        );
+    }
+
+    #[test]
+    fn garbage_is_collected_and_sharing_kept() {
+        let twice = lam("x", app(var(1), var(1)));
+        let term = app(twice, lam("y", app(var(1), var(-1))));
+        let mut compact = Compact::from_term(&term);
+        assert!(compact.step());
+        let before = compact.stats();
+        assert!(before.live < before.nodes);
+        compact.collect_garbage();
+        let after = compact.stats();
+        assert_eq!((after.nodes, after.live), (before.live, before.live));
+        // λy.<y|c> is one subterm in both places
+        assert_eq!(after.live, 5);
+        assert_eq!(compact.to_term(), reduce::step(&term).unwrap());
+    }
+}
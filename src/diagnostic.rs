@@ -0,0 +1,117 @@
+// byte range [start, end) into the source text
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    // smallest span covering both
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+// 1-based line and column (in chars) of a byte offset
+pub fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let col = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    (line, col)
+}
+
+// render an error the way rustc does:
+//
+// error: mismatched types
+//  --> 1:11
+//   |
+// 1 | <\x:A.{x}|\y.{y}>
+//   |           ^^^^^^ expected `A`, found `α → α`
+//   |
+//   = note: ...
+pub fn render(source: &str, span: Span, title: &str, label: &str, note: Option<&str>) -> String {
+    let (line, col) = line_col(source, span.start);
+    let text = source.lines().nth(line - 1).unwrap_or("");
+    let gutter = " ".repeat(line.to_string().len());
+    // spans running past the end of the line are cut there
+    let width = source[span.start.min(source.len())..span.end.min(source.len())]
+        .lines()
+        .next()
+        .map_or(1, |part| part.chars().count().max(1));
+    let mut out = format!("error: {}\n", title);
+    out.push_str(&format!("{}--> {}:{}\n", gutter, line, col));
+    out.push_str(&format!("{} |\n", gutter));
+    out.push_str(&format!("{} | {}\n", line, text));
+    out.push_str(&format!(
+        "{} | {}{} {}\n",
+        gutter,
+        " ".repeat(col - 1),
+        "^".repeat(width),
+        label
+    ));
+    if let Some(note) = note {
+        out.push_str(&format!("{} |\n", gutter));
+        let mut lines = note.lines();
+        if let Some(first) = lines.next() {
+            out.push_str(&format!("{} = note: {}\n", gutter, first));
+        }
+        for rest in lines {
+            out.push_str(&format!("{}         {}\n", gutter, rest));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_become_lines_and_columns_of_chars() {
+        let source = "λx.\n  <x|y>";
+        assert_eq!(line_col(source, 0), (1, 1));
+        assert_eq!(line_col(source, 2), (1, 2));
+        assert_eq!(line_col(source, 7), (2, 3));
+        assert_eq!(line_col(source, 100), (2, 8));
+    }
+
+    #[test]
+    fn errors_render_like_rustc() {
+        let source = r"<\x:A.{x}|\y.{y}>";
+        let rendered = render(
+            source,
+            Span::new(10, 16),
+            "mismatched types",
+            "expected `A`, found `α → α`",
+            Some("while checking\nthe argument"),
+        );
+        assert_eq!(
+            rendered,
+            "error: mismatched types\n \
+             --> 1:11\n  \
+             |\n\
+             1 | <\\x:A.{x}|\\y.{y}>\n  \
+             |           ^^^^^^ expected `A`, found `α → α`\n  \
+             |\n  \
+             = note: while checking\n          \
+             the argument\n"
+        );
+    }
+
+    #[test]
+    fn spans_are_cut_at_the_end_of_their_line() {
+        let source = "\n\n\n\n\n\n\n\n\nfirst line\nsecond";
+        let rendered = render(source, Span::new(15, 22), "t", "here", None);
+        assert_eq!(
+            rendered,
+            "error: t\n  --> 10:7\n   |\n10 | first line\n   |       ^^^^ here\n"
+        );
+        // an empty span at the end still gets a caret
+        let rendered = render("<x|", Span::new(3, 3), "t", "end", None);
+        assert!(rendered.ends_with("  |    ^ end\n"), "{}", rendered);
+    }
+}
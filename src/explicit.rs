@@ -0,0 +1,462 @@
+// normalization with explicit substitutions: a term is never rewritten, it is
+// paired with a substitution σ (the environment) that is only consulted when a
+// variable is reached
+//
+// σ is a shared list of thunks, so extending it for a β step is O(1) and every
+// closure built under it shares it instead of copying the body. thunks are
+// updated in place once evaluated (call by need). normal forms are reached the
+// same way as by reduce::normalize: weak head reduction, then reading back
+// under binders, applied to fresh variables.
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::parser::{Constant, Term};
+use crate::symbol::Symbol;
+use crate::types::{self, Type};
+
+type Env<'a> = Option<Rc<EnvNode<'a>>>;
+
+struct EnvNode<'a> {
+    thunk: Rc<Thunk<'a>>,
+    next: Env<'a>,
+}
+
+// environments can be as long as the term is deep, drop them a node at a time
+impl Drop for EnvNode<'_> {
+    fn drop(&mut self) {
+        let mut next = self.next.take();
+        while let Some(node) = next {
+            next = match Rc::try_unwrap(node) {
+                Ok(mut node) => node.next.take(),
+                Err(_) => None,
+            };
+        }
+    }
+}
+
+// substitutions for type variables, innermost first
+type TypeEnv = Option<Rc<TypeEnvNode>>;
+
+struct TypeEnvNode {
+    name: Symbol,
+    ty: Type,
+    next: TypeEnv,
+}
+
+type Thunk<'a> = RefCell<State<'a>>;
+
+enum State<'a> {
+    Delayed(&'a Term, Env<'a>, TypeEnv),
+    Evaluating, // forced again before it has a value, it needs itself
+    Forced(Value<'a>),
+}
+
+// weak head normal forms
+#[derive(Clone)]
+enum Value<'a> {
+    Lambda(&'a Term, Env<'a>, TypeEnv),
+    TypeLambda(&'a Term, Env<'a>, TypeEnv),
+    Constant(Constant),
+    // a constant that still takes arguments, or will never δ-reduce
+    Primitive(Constant, Vec<Rc<Thunk<'a>>>),
+    Neutral(Head<'a>, Vec<Item<'a>>),
+}
+
+#[derive(Clone)]
+enum Head<'a> {
+    Level(usize), // bound by the readback, counted from the outside
+    Free(i32),
+    Stuck(Box<Value<'a>>), // cannot take what follows, e.g. a lambda given a type
+}
+
+// what a head is applied to
+#[derive(Clone)]
+enum Item<'a> {
+    Argument(Rc<Thunk<'a>>),
+    Type(Type),
+}
+
+// the machine stack during weak head reduction
+enum Frame<'a> {
+    Item(Item<'a>),
+    Update(Rc<Thunk<'a>>), // store the value reached here
+    // δ-reduce once the value reached here is a literal, holding the first
+    // operand of add and mul while the second is evaluated
+    Delta(Constant, Vec<Rc<Thunk<'a>>>, Option<i64>),
+}
+
+enum Focus<'a> {
+    Code(&'a Term, Env<'a>, TypeEnv),
+    Value(Value<'a>),
+}
+
+// pending work of the readback
+enum Readback<'a> {
+    Value(Value<'a>, usize),
+    Thunk(Rc<Thunk<'a>>, usize),
+    Lambda(Symbol, Option<Type>),
+    TypeLambda(Symbol),
+    Application,
+    TypeApplication(Type),
+}
+
+struct OutOfFuel;
+
+struct Machine {
+    steps: usize,
+    fuel: usize,
+}
+
+// reduce to normal form in at most `fuel` contractions, returning it with the
+// contractions done; a shared argument is only reduced once
+pub fn normalize(term: &Term, fuel: usize) -> Option<(Term, usize)> {
+    let mut machine = Machine { steps: 0, fuel };
+    let normal = machine.normalize(term).ok()?;
+    Some((normal, machine.steps))
+}
+
+fn delayed<'a>(term: &'a Term, env: &Env<'a>, tenv: &TypeEnv) -> Rc<Thunk<'a>> {
+    Rc::new(RefCell::new(State::Delayed(
+        term,
+        env.clone(),
+        tenv.clone(),
+    )))
+}
+
+fn forced(value: Value) -> Rc<Thunk> {
+    Rc::new(RefCell::new(State::Forced(value)))
+}
+
+fn extend<'a>(thunk: Rc<Thunk<'a>>, env: &Env<'a>) -> Env<'a> {
+    Some(Rc::new(EnvNode {
+        thunk,
+        next: env.clone(),
+    }))
+}
+
+fn extend_type(name: Symbol, ty: Type, tenv: &TypeEnv) -> TypeEnv {
+    Some(Rc::new(TypeEnvNode {
+        name,
+        ty,
+        next: tenv.clone(),
+    }))
+}
+
+fn lookup<'a>(env: &Env<'a>, index: i32) -> Rc<Thunk<'a>> {
+    let mut node = env.as_ref().expect("Unbound variable");
+    for _ in 1..index {
+        node = node.next.as_ref().expect("Unbound variable");
+    }
+    node.thunk.clone()
+}
+
+// apply the substitutions of `tenv` to `ty`, inner ones shadow outer ones
+fn resolve(ty: &Type, tenv: &TypeEnv) -> Type {
+    let mut ty = ty.clone();
+    let mut seen: Vec<Symbol> = Vec::new();
+    let mut node = tenv;
+    while let Some(entry) = node {
+        if !seen.contains(&entry.name) {
+            seen.push(entry.name);
+            ty = types::subst_type(&ty, entry.name, &entry.ty);
+        }
+        node = &entry.next;
+    }
+    ty
+}
+
+// the value of a thunk, or its code, leaving it marked as being evaluated
+fn take<'a>(thunk: &Thunk<'a>) -> Result<Focus<'a>, OutOfFuel> {
+    let state = std::mem::replace(&mut *thunk.borrow_mut(), State::Evaluating);
+    match state {
+        State::Forced(value) => {
+            *thunk.borrow_mut() = State::Forced(value.clone());
+            Ok(Focus::Value(value))
+        }
+        State::Delayed(term, env, tenv) => Ok(Focus::Code(term, env, tenv)),
+        State::Evaluating => Err(OutOfFuel),
+    }
+}
+
+// continue with the value of `thunk`, evaluating it first if needed
+fn enter<'a>(thunk: &Rc<Thunk<'a>>, stack: &mut Vec<Frame<'a>>) -> Result<Focus<'a>, OutOfFuel> {
+    match take(thunk)? {
+        Focus::Value(value) => Ok(Focus::Value(value)),
+        code => {
+            stack.push(Frame::Update(thunk.clone()));
+            Ok(code)
+        }
+    }
+}
+
+// a primitive given `args`: wait for more, or evaluate the first one to see
+// whether it δ-reduces, see primitives::delta
+fn saturate<'a>(
+    op: Constant,
+    args: Vec<Rc<Thunk<'a>>>,
+    stack: &mut Vec<Frame<'a>>,
+) -> Result<Focus<'a>, OutOfFuel> {
+    if args.len() < arity(op) {
+        return Ok(Focus::Value(Value::Primitive(op, args)));
+    }
+    let first = args[0].clone();
+    stack.push(Frame::Delta(op, args, None));
+    enter(&first, stack)
+}
+
+fn arity(op: Constant) -> usize {
+    match op {
+        Constant::Int(_) | Constant::Bool(_) => 0,
+        Constant::Add | Constant::Mul => 2,
+        Constant::Ite => 3,
+    }
+}
+
+impl Machine {
+    fn tick(&mut self) -> Result<(), OutOfFuel> {
+        if self.steps == self.fuel {
+            return Err(OutOfFuel);
+        }
+        self.steps += 1;
+        Ok(())
+    }
+
+    fn force<'a>(&mut self, thunk: &Rc<Thunk<'a>>) -> Result<Value<'a>, OutOfFuel> {
+        let mut stack = Vec::new();
+        let focus = enter(thunk, &mut stack)?;
+        self.whnf(focus, stack)
+    }
+
+    // weak head reduction of `focus` applied to `stack`, a loop over the
+    // frames of the stack that never recurses
+    fn whnf<'a>(
+        &mut self,
+        mut focus: Focus<'a>,
+        mut stack: Vec<Frame<'a>>,
+    ) -> Result<Value<'a>, OutOfFuel> {
+        loop {
+            let value = match focus {
+                Focus::Code(term, env, tenv) => match term {
+                    Term::Application(fun, arg) => {
+                        stack.push(Frame::Item(Item::Argument(delayed(arg, &env, &tenv))));
+                        focus = Focus::Code(fun, env, tenv);
+                        continue;
+                    }
+                    Term::TypeApplication(fun, ty) => {
+                        stack.push(Frame::Item(Item::Type(resolve(ty, &tenv))));
+                        focus = Focus::Code(fun, env, tenv);
+                        continue;
+                    }
+                    Term::Let(_, bound, body) => {
+                        self.tick()?;
+                        let env = extend(delayed(bound, &env, &tenv), &env);
+                        focus = Focus::Code(body, env, tenv);
+                        continue;
+                    }
+                    Term::Variable(index) if *index < 0 => {
+                        Value::Neutral(Head::Free(*index), Vec::new())
+                    }
+                    Term::Variable(index) => {
+                        let thunk = lookup(&env, *index);
+                        match take(&thunk)? {
+                            Focus::Value(value) => value,
+                            code => {
+                                stack.push(Frame::Update(thunk));
+                                focus = code;
+                                continue;
+                            }
+                        }
+                    }
+                    Term::Constant(constant) => Value::Constant(*constant),
+                    Term::Lambda(..) => Value::Lambda(term, env, tenv),
+                    Term::TypeLambda(..) => Value::TypeLambda(term, env, tenv),
+                },
+                Focus::Value(value) => value,
+            };
+            // `value` is in weak head normal form, feed it what is on the stack
+            match (value, stack.pop()) {
+                (value, None) => return Ok(value),
+                (value, Some(Frame::Update(thunk))) => {
+                    *thunk.borrow_mut() = State::Forced(value.clone());
+                    focus = Focus::Value(value);
+                }
+                (Value::Lambda(lambda, env, tenv), Some(Frame::Item(Item::Argument(arg)))) => {
+                    self.tick()?;
+                    let Term::Lambda(_, _, body) = lambda else {
+                        unreachable!("closures of lambdas hold lambdas")
+                    };
+                    focus = Focus::Code(body, extend(arg, &env), tenv);
+                }
+                (Value::TypeLambda(lambda, env, tenv), Some(Frame::Item(Item::Type(ty)))) => {
+                    self.tick()?;
+                    let Term::TypeLambda(param, body) = lambda else {
+                        unreachable!("closures of type lambdas hold type lambdas")
+                    };
+                    focus = Focus::Code(body, env, extend_type(*param, ty, &tenv));
+                }
+                (value, Some(Frame::Delta(op, args, first))) => {
+                    focus = match (op, first, value) {
+                        (Constant::Ite, _, Value::Constant(Constant::Bool(cond))) => {
+                            self.tick()?;
+                            enter(&args[if cond { 1 } else { 2 }], &mut stack)?
+                        }
+                        (
+                            Constant::Add | Constant::Mul,
+                            None,
+                            Value::Constant(Constant::Int(m)),
+                        ) => {
+                            let second = args[1].clone();
+                            stack.push(Frame::Delta(op, args, Some(m)));
+                            enter(&second, &mut stack)?
+                        }
+                        (Constant::Add, Some(m), Value::Constant(Constant::Int(n))) => {
+                            self.tick()?;
+                            Focus::Value(Value::Constant(Constant::Int(m.wrapping_add(n))))
+                        }
+                        (Constant::Mul, Some(m), Value::Constant(Constant::Int(n))) => {
+                            self.tick()?;
+                            Focus::Value(Value::Constant(Constant::Int(m.wrapping_mul(n))))
+                        }
+                        // an operand that is not a literal, this never δ-reduces
+                        _ => Focus::Value(Value::Primitive(op, args)),
+                    };
+                }
+                (Value::Constant(op), Some(Frame::Item(Item::Argument(arg)))) if arity(op) > 0 => {
+                    focus = saturate(op, vec![arg], &mut stack)?;
+                }
+                (Value::Primitive(op, mut args), Some(Frame::Item(Item::Argument(arg))))
+                    if args.len() < arity(op) =>
+                {
+                    args.push(arg);
+                    focus = saturate(op, args, &mut stack)?;
+                }
+                (Value::Neutral(head, mut spine), Some(Frame::Item(item))) => {
+                    spine.push(item);
+                    focus = Focus::Value(Value::Neutral(head, spine));
+                }
+                (value, Some(Frame::Item(item))) => {
+                    focus = Focus::Value(Value::Neutral(Head::Stuck(Box::new(value)), vec![item]));
+                }
+            }
+        }
+    }
+
+    // read the normal form back, reducing under binders
+    fn normalize(&mut self, term: &Term) -> Result<Term, OutOfFuel> {
+        let root = self.whnf(Focus::Code(term, None, None), Vec::new())?;
+        let mut work = vec![Readback::Value(root, 0)];
+        let mut done: Vec<Term> = Vec::new();
+        while let Some(frame) = work.pop() {
+            match frame {
+                Readback::Thunk(thunk, depth) => {
+                    let value = self.force(&thunk)?;
+                    work.push(Readback::Value(value, depth));
+                }
+                Readback::Value(value, depth) => match value {
+                    Value::Lambda(lambda, env, tenv) => {
+                        let Term::Lambda(param, annot, body) = lambda else {
+                            unreachable!("closures of lambdas hold lambdas")
+                        };
+                        let bound = forced(Value::Neutral(Head::Level(depth), Vec::new()));
+                        let annot = annot.as_ref().map(|ty| resolve(ty, &tenv));
+                        let body =
+                            self.whnf(Focus::Code(body, extend(bound, &env), tenv), Vec::new())?;
+                        work.push(Readback::Lambda(*param, annot));
+                        work.push(Readback::Value(body, depth + 1));
+                    }
+                    Value::TypeLambda(lambda, env, tenv) => {
+                        let Term::TypeLambda(param, body) = lambda else {
+                            unreachable!("closures of type lambdas hold type lambdas")
+                        };
+                        // the parameter stands for itself inside the body
+                        let tenv = extend_type(*param, Type::Base(*param), &tenv);
+                        let body = self.whnf(Focus::Code(body, env, tenv), Vec::new())?;
+                        work.push(Readback::TypeLambda(*param));
+                        work.push(Readback::Value(body, depth));
+                    }
+                    Value::Constant(constant) => done.push(Term::Constant(constant)),
+                    Value::Primitive(op, args) => {
+                        for arg in args.into_iter().rev() {
+                            work.push(Readback::Application);
+                            work.push(Readback::Thunk(arg, depth));
+                        }
+                        done.push(Term::Constant(op));
+                    }
+                    Value::Neutral(head, spine) => {
+                        for item in spine.into_iter().rev() {
+                            match item {
+                                Item::Argument(arg) => {
+                                    work.push(Readback::Application);
+                                    work.push(Readback::Thunk(arg, depth));
+                                }
+                                Item::Type(ty) => work.push(Readback::TypeApplication(ty)),
+                            }
+                        }
+                        match head {
+                            Head::Level(level) => done.push(Term::Variable((depth - level) as i32)),
+                            Head::Free(index) => done.push(Term::Variable(index)),
+                            Head::Stuck(value) => work.push(Readback::Value(*value, depth)),
+                        }
+                    }
+                },
+                Readback::Lambda(param, annot) => {
+                    let body = done.pop().expect("body read back");
+                    done.push(Term::Lambda(param, annot, Box::new(body)));
+                }
+                Readback::TypeLambda(param) => {
+                    let body = done.pop().expect("body read back");
+                    done.push(Term::TypeLambda(param, Box::new(body)));
+                }
+                Readback::Application => {
+                    let rhs = done.pop().expect("argument read back");
+                    let lhs = done.pop().expect("function read back");
+                    done.push(Term::Application(Box::new(lhs), Box::new(rhs)));
+                }
+                Readback::TypeApplication(ty) => {
+                    let fun = done.pop().expect("function read back");
+                    done.push(Term::TypeApplication(Box::new(fun), ty));
+                }
+            }
+        }
+        Ok(done.pop().expect("root read back"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::{app, apps, i, lam, omega, var};
+    use crate::{church, reduce};
+
+    #[test]
+    fn normal_forms_agree_with_reduce() {
+        let sum = apps(church::add(), [church::numeral(2), church::numeral(3)]);
+        let (normal, _) = normalize(&sum, 1000).unwrap();
+        assert_eq!(church::to_number(&normal), Some(5));
+        assert_eq!(normalize(&app(omega(), omega()), 1000), None);
+        let eta = lam("x", app(lam("y", app(var(-1), var(1))), var(1)));
+        assert_eq!(normalize(&eta, 10), reduce::normalize(&eta, 10));
+    }
+
+    #[test]
+    fn deep_terms_are_read_back_without_recursion() {
+        let depth = 100_000;
+        let mut body = var(1);
+        for _ in 0..depth {
+            body = app(body, app(i(), var(-1)));
+        }
+        let mut term = body;
+        for _ in 0..depth {
+            term = lam("x", term);
+        }
+        let (normal, steps) = normalize(&term, 2 * depth).unwrap();
+        assert_eq!(steps, depth);
+        assert_eq!(normal.size(), 3 * depth + 1);
+        // each argument waits on the next one
+        let mut chain = var(-1);
+        for _ in 0..depth {
+            chain = app(i(), chain);
+        }
+        assert_eq!(normalize(&chain, depth), Some((var(-1), depth)));
+    }
+}
@@ -0,0 +1,599 @@
+// terms printed for proof assistants, to be pasted into a Coq, Agda or Lean
+// file: a prologue declaring what the term needs, then a definition `term`
+//
+// a term that has a simple or System F type (lets expanded first, they do not
+// generalize there) is printed with its binder types, its type unknowns and
+// free variables postulated. any other term is printed in a postulated domain
+// D with app : D -> D -> D and lam : (D -> D) -> D, so it still checks
+use std::collections::HashMap;
+
+use crate::parser::Term;
+use crate::reduce;
+use crate::symbol::Symbol;
+use crate::types::{self, Checker, Type, free_type_names};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Assistant {
+    Coq,
+    Agda,
+    Lean,
+}
+
+impl Assistant {
+    pub fn from_name(name: &str) -> Option<Assistant> {
+        match name.to_ascii_lowercase().as_str() {
+            "coq" | "rocq" => Some(Assistant::Coq),
+            "agda" => Some(Assistant::Agda),
+            "lean" => Some(Assistant::Lean),
+            _ => None,
+        }
+    }
+
+    fn universe(self) -> &'static str {
+        match self {
+            Assistant::Agda => "Set",
+            Assistant::Coq | Assistant::Lean => "Type",
+        }
+    }
+
+    fn arrow(self) -> &'static str {
+        match self {
+            Assistant::Coq => "->",
+            Assistant::Agda | Assistant::Lean => "→",
+        }
+    }
+
+    // what separates the binders of a lambda from its body
+    fn maps_to(self) -> &'static str {
+        match self {
+            Assistant::Coq | Assistant::Lean => "=>",
+            Assistant::Agda => "→",
+        }
+    }
+
+    fn lambda(self) -> &'static str {
+        match self {
+            Assistant::Coq | Assistant::Lean => "fun",
+            Assistant::Agda => "λ",
+        }
+    }
+}
+
+// reserved words of any of the three, binders are renamed away from them
+const RESERVED: [&str; 36] = [
+    "fun",
+    "forall",
+    "let",
+    "in",
+    "match",
+    "with",
+    "end",
+    "fix",
+    "as",
+    "return",
+    "if",
+    "then",
+    "else",
+    "Type",
+    "Set",
+    "Prop",
+    "Sort",
+    "def",
+    "axiom",
+    "theorem",
+    "have",
+    "show",
+    "by",
+    "do",
+    "at",
+    "from",
+    "where",
+    "module",
+    "data",
+    "postulate",
+    "open",
+    "import",
+    "record",
+    "field",
+    "Definition",
+    "_",
+];
+
+struct Printer {
+    assistant: Assistant,
+    // type of every node in pre-order, None in the untyped domain
+    types: Option<Vec<Type>>,
+    node: usize,
+    type_names: HashMap<usize, String>, // for the unknowns
+    free: Vec<String>,
+    env: Vec<String>,
+    taken: Vec<String>, // names of the prologue
+    // app and lam of the untyped domain
+    domain: Option<(String, String)>,
+}
+
+// the declarations and the definition, or why the term cannot be exported
+pub fn export(term: &Term, free: &[Symbol], assistant: Assistant) -> Result<String, String> {
+    if constant(term) {
+        return Err("primitive constants have no counterpart there".to_string());
+    }
+    let expanded = expand_lets(term);
+    let mut checker = Checker::new().with_node_types();
+    let typed = checker.infer(&expanded).ok();
+    let mut printer = Printer {
+        assistant,
+        types: typed.as_ref().map(|_| checker.node_types().to_vec()),
+        node: 0,
+        type_names: HashMap::new(),
+        free: free.iter().map(|name| name.to_string()).collect(),
+        env: Vec::new(),
+        taken: free.iter().map(|name| name.to_string()).collect(),
+        domain: None,
+    };
+    let mut declarations: Vec<(String, String)> = Vec::new();
+    let (term, ty) = match typed {
+        Some(ty) => {
+            let (names, free_types) = printer.collect(&expanded);
+            for name in names {
+                declarations.push((name, assistant.universe().to_string()));
+            }
+            for (index, name) in free.iter().enumerate() {
+                // names resolved away, e.g. by the prelude, are not used
+                if let Some(ty) = free_types.get(&index) {
+                    declarations.push((name.to_string(), printer.print_type(ty)));
+                }
+            }
+            (expanded, printer.print_type(&ty))
+        }
+        None => {
+            let domain = printer.reserve("D");
+            let app = printer.reserve("app");
+            let lam = printer.reserve("lam");
+            let arrow = assistant.arrow();
+            declarations.push((domain.clone(), assistant.universe().to_string()));
+            declarations.push((app.clone(), format!("{0} {1} {0} {1} {0}", domain, arrow)));
+            declarations.push((lam.clone(), format!("({0} {1} {0}) {1} {0}", domain, arrow)));
+            let mut used = Vec::new();
+            free_indices(term, &mut used);
+            for (index, name) in free.iter().enumerate() {
+                if used.contains(&index) {
+                    declarations.push((name.to_string(), domain.clone()));
+                }
+            }
+            printer.domain = Some((app, lam));
+            (term.clone(), domain)
+        }
+    };
+    let name = printer.reserve("term");
+    let body = printer.print(&term);
+    let mut out = String::new();
+    match assistant {
+        Assistant::Coq => {
+            for (name, ty) in &declarations {
+                out.push_str(&format!("Parameter {} : {}.\n", name, ty));
+            }
+            out.push_str(&format!("Definition {} : {} := {}.\n", name, ty, body));
+        }
+        Assistant::Agda => {
+            if !declarations.is_empty() {
+                out.push_str("postulate\n");
+                for (name, ty) in &declarations {
+                    out.push_str(&format!("  {} : {}\n", name, ty));
+                }
+                out.push('\n');
+            }
+            out.push_str(&format!("{} : {}\n{} = {}\n", name, ty, name, body));
+        }
+        Assistant::Lean => {
+            for (name, ty) in &declarations {
+                out.push_str(&format!("axiom {} : {}\n", name, ty));
+            }
+            // definitions built from axioms do not compile to code
+            out.push_str(&format!(
+                "noncomputable def {} : {} := {}\n",
+                name, ty, body
+            ));
+        }
+    }
+    Ok(out)
+}
+
+// the free variables that occur, as indices into the free names
+fn free_indices(term: &Term, found: &mut Vec<usize>) {
+    reduce::walk_paths(term, |node, _| {
+        if let Term::Variable(index) = node
+            && *index < 0
+        {
+            found.push((-index - 1) as usize);
+        }
+        false
+    });
+}
+
+fn constant(term: &Term) -> bool {
+    let mut found = false;
+    reduce::walk_paths(term, |node, _| {
+        found = matches!(node, Term::Constant(_));
+        found
+    });
+    found
+}
+
+// every let replaced by its body with the bound term substituted
+fn expand_lets(term: &Term) -> Term {
+    let mut term = term.clone();
+    loop {
+        let path = reduce::redexes(&term)
+            .into_iter()
+            .find(|path| matches!(reduce::subterm(&term, path), Term::Let(..)));
+        match path {
+            Some(path) => term = reduce::contract(&term, &path),
+            None => return term,
+        }
+    }
+}
+
+impl Printer {
+    // a name like `base` that clashes with no free variable, prologue name or
+    // reserved word
+    fn reserve(&mut self, base: &str) -> String {
+        let name = self.fresh(base);
+        self.taken.push(name.clone());
+        name
+    }
+
+    fn fresh(&self, base: &str) -> String {
+        let taken = |candidate: &str| {
+            RESERVED.contains(&candidate)
+                || self.taken.iter().any(|name| name == candidate)
+                || self.env.iter().any(|name| name == candidate)
+        };
+        if !taken(base) {
+            return base.to_string();
+        }
+        (1..)
+            .map(|n| format!("{}_{}", base, n))
+            .find(|candidate| !taken(candidate))
+            .unwrap()
+    }
+
+    // the type names to postulate, unknowns named on the way, and the type of
+    // each free variable used
+    fn collect(&mut self, term: &Term) -> (Vec<String>, HashMap<usize, Type>) {
+        let types = self.types.clone().unwrap_or_default();
+        let mut names: Vec<String> = Vec::new();
+        let mut unknowns: Vec<usize> = Vec::new();
+        let mut free_types = HashMap::new();
+        let mut params = Vec::new();
+        let mut node = 0;
+        // each subterm with the type names bound around it
+        let mut work: Vec<(&Term, Vec<Symbol>)> = vec![(term, Vec::new())];
+        while let Some((subterm, bound)) = work.pop() {
+            let ty = &types[node];
+            node += 1;
+            for name in free_type_names(ty) {
+                if !bound.contains(&name) && !names.iter().any(|taken| name == taken.as_str()) {
+                    names.push(name.to_string());
+                }
+            }
+            vars(ty, &mut unknowns);
+            match subterm {
+                Term::Variable(index) if *index < 0 => {
+                    free_types.insert((-index - 1) as usize, ty.clone());
+                }
+                Term::Variable(_) | Term::Constant(_) => {}
+                Term::Lambda(_, _, body) | Term::TypeApplication(body, _) => {
+                    work.push((body, bound))
+                }
+                Term::TypeLambda(param, body) => {
+                    params.push(param.to_string());
+                    let mut bound = bound;
+                    bound.push(*param);
+                    work.push((body, bound));
+                }
+                Term::Application(lhs, rhs) | Term::Let(_, lhs, rhs) => {
+                    work.push((rhs, bound.clone()));
+                    work.push((lhs, bound));
+                }
+            }
+        }
+        self.taken.extend(names.iter().cloned());
+        // unknowns are not named after a type abstraction either
+        self.taken.extend(params);
+        let letters = ('A'..='Z').map(|c| c.to_string());
+        let mut candidates = letters.chain((1..).map(|n| format!("T{}", n)));
+        for var in unknowns {
+            let name = candidates
+                .find(|candidate| {
+                    !self.taken.contains(candidate) && !RESERVED.contains(&candidate.as_str())
+                })
+                .unwrap();
+            self.taken.push(name.clone());
+            self.type_names.insert(var, name.clone());
+            names.push(name);
+        }
+        (names, free_types)
+    }
+
+    fn print_type(&self, ty: &Type) -> String {
+        enum Piece<'a> {
+            Type(&'a Type),
+            Text(&'static str),
+        }
+        let mut out = String::new();
+        let mut work = vec![Piece::Type(ty)];
+        while let Some(piece) = work.pop() {
+            match piece {
+                Piece::Text(text) => out.push_str(text),
+                Piece::Type(Type::Base(name)) => out.push_str(name.as_str()),
+                Piece::Type(Type::Var(var)) => out.push_str(&self.type_names[var]),
+                Piece::Type(Type::Arrow(lhs, rhs)) => {
+                    work.push(Piece::Type(rhs));
+                    work.push(Piece::Text(" "));
+                    work.push(Piece::Text(self.assistant.arrow()));
+                    work.push(Piece::Text(" "));
+                    if matches!(**lhs, Type::Arrow(..) | Type::Forall(..)) {
+                        work.extend([Piece::Text(")"), Piece::Type(lhs), Piece::Text("(")]);
+                    } else {
+                        work.push(Piece::Type(lhs));
+                    }
+                }
+                Piece::Type(Type::Forall(param, body)) => {
+                    match self.assistant {
+                        Assistant::Coq => out.push_str(&format!("forall ({} : Type), ", param)),
+                        _ => out.push_str(&format!(
+                            "({} : {}) {} ",
+                            param,
+                            self.assistant.universe(),
+                            self.assistant.arrow()
+                        )),
+                    }
+                    work.push(Piece::Type(body));
+                }
+            }
+        }
+        out
+    }
+
+    // the type of the node being printed, and move past it
+    fn next_type(&mut self) -> Option<Type> {
+        let ty = self.types.as_ref().map(|types| types[self.node].clone());
+        self.node += 1;
+        ty
+    }
+
+    // print over an explicit stack: subterms in pre-order, so each takes its
+    // type in turn, their text on `done` until the node around them is built
+    fn print(&mut self, term: &Term) -> String {
+        enum Frame<'a> {
+            Visit(&'a Term),
+            Lambda(String),
+            Binders(Vec<String>, usize),
+            Application(&'a Term, &'a Term),
+            TypeApplication(&'a Term, &'a Type),
+            Let(Symbol, &'a Term),
+            LetBody(String),
+        }
+        let mut done: Vec<String> = Vec::new();
+        let mut work = vec![Frame::Visit(term)];
+        while let Some(frame) = work.pop() {
+            match frame {
+                Frame::Visit(term) => {
+                    let ty = self.next_type();
+                    match term {
+                        Term::Variable(index) if *index < 0 => {
+                            done.push(self.free[(-index - 1) as usize].clone())
+                        }
+                        Term::Variable(index) => {
+                            done.push(self.env[self.env.len() - *index as usize].clone())
+                        }
+                        Term::Constant(constant) => done.push(constant.to_string()),
+                        Term::Lambda(param, _, body) if self.domain.is_some() => {
+                            let name = self.bind(*param);
+                            work.push(Frame::Lambda(name));
+                            work.push(Frame::Visit(body));
+                        }
+                        // types are erased in the untyped domain
+                        Term::TypeLambda(_, body) if self.domain.is_some() => {
+                            work.push(Frame::Visit(body))
+                        }
+                        Term::Lambda(..) | Term::TypeLambda(..) => {
+                            // consecutive binders share one fun
+                            let mut binders = Vec::new();
+                            let mut node = term;
+                            let mut ty = ty;
+                            let mut bound = 0;
+                            loop {
+                                match node {
+                                    Term::Lambda(param, _, body) => {
+                                        let param_type = match &ty {
+                                            Some(Type::Arrow(arg, _)) => self.print_type(arg),
+                                            _ => unreachable!("a lambda has an arrow type"),
+                                        };
+                                        let name = self.bind(*param);
+                                        binders.push(format!("({} : {})", name, param_type));
+                                        bound += 1;
+                                        node = body;
+                                    }
+                                    Term::TypeLambda(param, body) => {
+                                        binders.push(format!(
+                                            "({} : {})",
+                                            param,
+                                            self.assistant.universe()
+                                        ));
+                                        node = body;
+                                    }
+                                    _ => break,
+                                }
+                                if !matches!(node, Term::Lambda(..) | Term::TypeLambda(..)) {
+                                    break;
+                                }
+                                ty = self.next_type();
+                            }
+                            work.push(Frame::Binders(binders, bound));
+                            work.push(Frame::Visit(node));
+                        }
+                        Term::Application(lhs, rhs) => {
+                            work.push(Frame::Application(lhs, rhs));
+                            work.push(Frame::Visit(rhs));
+                            work.push(Frame::Visit(lhs));
+                        }
+                        Term::TypeApplication(fun, arg) => {
+                            work.push(Frame::TypeApplication(fun, arg));
+                            work.push(Frame::Visit(fun));
+                        }
+                        Term::Let(name, bound, body) => {
+                            work.push(Frame::Let(*name, body));
+                            work.push(Frame::Visit(bound));
+                        }
+                    }
+                }
+                Frame::Lambda(name) => {
+                    let body = done.pop().expect("printed body");
+                    self.env.pop();
+                    let (_, lam) = self.domain.as_ref().expect("untyped domain");
+                    let lambda = format!(
+                        "{} {} {} {}",
+                        self.assistant.lambda(),
+                        name,
+                        self.assistant.maps_to(),
+                        body
+                    );
+                    done.push(format!("{} ({})", lam, lambda));
+                }
+                Frame::Binders(binders, bound) => {
+                    let body = done.pop().expect("printed body");
+                    self.env.truncate(self.env.len() - bound);
+                    done.push(format!(
+                        "{} {} {} {}",
+                        self.assistant.lambda(),
+                        binders.join(" "),
+                        self.assistant.maps_to(),
+                        body
+                    ));
+                }
+                Frame::Application(lhs, rhs) => {
+                    let arg = done.pop().expect("printed argument");
+                    let fun = done.pop().expect("printed function");
+                    let fun = match *lhs {
+                        Term::Variable(_) => fun,
+                        Term::Lambda(..) | Term::TypeLambda(..) | Term::Let(..) => {
+                            format!("({})", fun)
+                        }
+                        // app takes the function as an argument in the untyped domain
+                        _ if self.domain.is_some() => format!("({})", fun),
+                        _ => fun,
+                    };
+                    let arg = match *rhs {
+                        Term::Variable(_) | Term::Constant(_) => arg,
+                        _ => format!("({})", arg),
+                    };
+                    done.push(match &self.domain {
+                        Some((app, _)) => format!("{} {} {}", app, fun, arg),
+                        None => format!("{} {}", fun, arg),
+                    });
+                }
+                Frame::TypeApplication(fun, arg) => {
+                    let printed = done.pop().expect("printed function");
+                    done.push(match self.types {
+                        Some(_) => {
+                            let printed = match *fun {
+                                Term::Lambda(..) | Term::TypeLambda(..) => {
+                                    format!("({})", printed)
+                                }
+                                _ => printed,
+                            };
+                            let arg = match arg {
+                                Type::Base(_) | Type::Var(_) => self.print_type(arg),
+                                _ => format!("({})", self.print_type(arg)),
+                            };
+                            format!("{} {}", printed, arg)
+                        }
+                        None => printed,
+                    });
+                }
+                Frame::Let(name, body) => {
+                    let name = self.bind(name);
+                    work.push(Frame::LetBody(name));
+                    work.push(Frame::Visit(body));
+                }
+                Frame::LetBody(name) => {
+                    let body = done.pop().expect("printed body");
+                    let bound = done.pop().expect("printed bound term");
+                    self.env.pop();
+                    done.push(match self.assistant {
+                        Assistant::Coq => format!("let {} := {} in {}", name, bound, body),
+                        Assistant::Agda => format!("let {} = {} in {}", name, bound, body),
+                        Assistant::Lean => format!("let {} := {}; {}", name, bound, body),
+                    });
+                }
+            }
+        }
+        done.pop().expect("printed term")
+    }
+
+    // a fresh name for a binder, pushed onto the environment
+    fn bind(&mut self, param: Symbol) -> String {
+        let name = self.fresh(param.as_str());
+        self.env.push(name.clone());
+        name
+    }
+}
+
+// unknowns of a type in order of first appearance, appended to `vars`
+fn vars(ty: &Type, found: &mut Vec<usize>) {
+    for node in types::walk(ty) {
+        if let Type::Var(var) = node
+            && !found.contains(var)
+        {
+            found.push(*var);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::{app, i, lam, var};
+
+    #[test]
+    fn typed_terms_keep_their_types() {
+        assert_eq!(
+            export(&i(), &[], Assistant::Coq).unwrap(),
+            "Parameter A : Type.\nDefinition term : A -> A := fun (x : A) => x.\n"
+        );
+        let call = lam("x", app(var(-1), var(1)));
+        assert_eq!(
+            export(&call, &[Symbol::intern("f")], Assistant::Agda).unwrap(),
+            "postulate\n  A : Set\n  B : Set\n  f : A → B\n\nterm : A → B\nterm = λ (x : A) → f x\n"
+        );
+    }
+
+    #[test]
+    fn untyped_terms_go_through_a_domain() {
+        let omega = lam("x", app(var(1), var(1)));
+        assert_eq!(
+            export(&omega, &[], Assistant::Lean).unwrap(),
+            "axiom D : Type\naxiom app : D → D → D\naxiom lam : (D → D) → D\n\
+             noncomputable def term : D := lam (fun x => app x x)\n"
+        );
+    }
+
+    #[test]
+    fn deep_terms_are_exported() {
+        let mut typed = var(1);
+        for _ in 0..100_000 {
+            typed = app(var(2), typed);
+        }
+        let typed = lam("f", lam("x", typed));
+        let out = export(&typed, &[], Assistant::Coq).unwrap();
+        assert!(out.ends_with(&format!("f x{}.\n", ")".repeat(99_999))));
+        // binders are named apart, so distinct names keep this linear
+        let mut untyped = app(var(1), var(1));
+        for n in (0..10_000).rev() {
+            untyped = lam(&format!("x{}", n), untyped);
+        }
+        let out = export(&untyped, &[], Assistant::Lean).unwrap();
+        assert!(out.ends_with(&format!("app x9999 x9999{}\n", ")".repeat(10_000))));
+    }
+}
@@ -0,0 +1,155 @@
+// C ABI for embedding, declared in include/lambda_rs.h
+//
+// terms are opaque handles owned by the caller and released with lambda_free,
+// strings handed out are released with lambda_string_free. functions that can
+// fail return NULL and, if `error` is not NULL, store a message there. parse
+// errors come back from parser::try_parse as messages, so bad input never
+// panics and nothing is caught here. a panic left is a bug: an extern "C"
+// function does not unwind, the process aborts
+//
+// the contract of each function is in its SAFETY comment and in the header
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::{CStr, CString, c_char};
+use std::ptr;
+
+use crate::api;
+use crate::parser::Term;
+use crate::pretty_printer::PrettyPrinter;
+use crate::reduce;
+use crate::symbol::Symbol;
+
+pub struct LambdaTerm {
+    term: Term,
+    free: Vec<Symbol>,
+}
+
+// interior NUL bytes cannot cross the boundary, they are dropped
+fn c_string(text: String) -> *mut c_char {
+    let text = CString::new(text.replace('\0', "")).expect("no NUL left");
+    text.into_raw()
+}
+
+// SAFETY: `error` is NULL or valid for a write
+unsafe fn fail<T>(error: *mut *mut c_char, message: String) -> *mut T {
+    if !error.is_null() {
+        unsafe { *error = c_string(message) };
+    }
+    ptr::null_mut()
+}
+
+// SAFETY: `source` is a NUL-terminated string, `error` NULL or writable
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lambda_parse(
+    source: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut LambdaTerm {
+    if source.is_null() {
+        return unsafe { fail(error, "source is NULL".to_string()) };
+    }
+    let Ok(source) = unsafe { CStr::from_ptr(source) }.to_str() else {
+        return unsafe { fail(error, "source is not UTF-8".to_string()) };
+    };
+    match api::parse_source(source) {
+        Ok((term, free)) => Box::into_raw(Box::new(LambdaTerm { term, free })),
+        Err(message) => unsafe { fail(error, message) },
+    }
+}
+
+// a new term: the normal form under normal order within `fuel` steps, with the
+// steps taken stored in `steps` if it is not NULL
+//
+// SAFETY: `term` comes from lambda_parse or lambda_normalize, `steps` and
+// `error` are NULL or writable
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lambda_normalize(
+    term: *const LambdaTerm,
+    fuel: usize,
+    steps: *mut usize,
+    error: *mut *mut c_char,
+) -> *mut LambdaTerm {
+    let Some(term) = (unsafe { term.as_ref() }) else {
+        return unsafe { fail(error, "term is NULL".to_string()) };
+    };
+    match reduce::normalize(&term.term, fuel) {
+        Some((normal, taken)) => {
+            if !steps.is_null() {
+                unsafe { *steps = taken };
+            }
+            Box::into_raw(Box::new(LambdaTerm {
+                term: normal,
+                free: term.free.clone(),
+            }))
+        }
+        None => unsafe { fail(error, format!("no normal form within {} steps", fuel)) },
+    }
+}
+
+// the term pretty-printed, NULL for a NULL term
+//
+// SAFETY: `term` is NULL or a live handle
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lambda_print(term: *const LambdaTerm) -> *mut c_char {
+    match unsafe { term.as_ref() } {
+        Some(term) => c_string(PrettyPrinter::new().format(&term.term, &term.free)),
+        None => ptr::null_mut(),
+    }
+}
+
+// SAFETY: `term` is NULL or a live handle, not used afterwards
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lambda_free(term: *mut LambdaTerm) {
+    if !term.is_null() {
+        drop(unsafe { Box::from_raw(term) });
+    }
+}
+
+// SAFETY: `text` is NULL or was returned by this library, not used afterwards
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lambda_string_free(text: *mut c_char) {
+    if !text.is_null() {
+        drop(unsafe { CString::from_raw(text) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn taken(text: *mut c_char) -> String {
+        let owned = unsafe { CStr::from_ptr(text) }
+            .to_str()
+            .unwrap()
+            .to_string();
+        unsafe { lambda_string_free(text) };
+        owned
+    }
+
+    #[test]
+    fn terms_round_trip_through_handles() {
+        let source = CString::new("<\\x.{x}|y>").unwrap();
+        let mut error = ptr::null_mut();
+        let mut steps = 0;
+        unsafe {
+            let term = lambda_parse(source.as_ptr(), &mut error);
+            assert!(!term.is_null() && error.is_null());
+            let normal = lambda_normalize(term, 10, &mut steps, &mut error);
+            assert_eq!(steps, 1);
+            assert_eq!(taken(lambda_print(normal)), "$y");
+            lambda_free(normal);
+            lambda_free(term);
+        }
+    }
+
+    #[test]
+    fn failures_are_messages() {
+        let source = CString::new("\\x.{").unwrap();
+        let mut error = ptr::null_mut();
+        unsafe {
+            assert!(lambda_parse(source.as_ptr(), &mut error).is_null());
+            assert!(!taken(error).is_empty());
+            assert!(lambda_parse(ptr::null(), ptr::null_mut()).is_null());
+            assert!(lambda_print(ptr::null()).is_null());
+        }
+    }
+}
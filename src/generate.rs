@@ -0,0 +1,405 @@
+// random closed terms, for testing passes over terms against many inputs
+//
+// uniform draws every closed term with the given number of nodes (as counted
+// by Term::size) with the same probability, from a table of how many there
+// are. boltzmann draws from the free sampler for de Bruijn terms in natural
+// size, where a λ or an application counts 1 and a variable one more than the
+// binders it skips, until a closed term within 20% of the target comes out
+//
+// the generator is seeded, so a failure found with a seed can be replayed.
+// enumerate_closed_terms lists every closed term instead, for exhaustive checks
+//
+// check runs a property over sampled terms and shrinks a failure to a smaller
+// closed term that still fails, the part of proptest a pass over terms needs.
+//
+// with the arbitrary feature Term is Arbitrary, read node by node from the
+// input so a fuzzer's mutations change the term locally: one choice per node
+// and one more per variable. input that runs out closes the term off with
+// the innermost variable, so every input makes a closed term. there is no
+// proptest Strategy; a seed is all it needs,
+//     any::<u64>().prop_map(|seed| boltzmann(30, &mut Rng::new(seed)))
+// is a strategy for terms of about 30 nodes, shrinking by seed only
+use crate::parser::Term;
+use crate::reduce;
+use crate::symbol::Symbol;
+
+// SplitMix64, small and good enough for sampling
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // uniform in 0..n, n > 0
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_f64() * n as f64) as usize
+    }
+}
+
+// past this the counts no longer fit an f64
+pub const MAX_UNIFORM_SIZE: usize = 300;
+
+// binders are named after their depth: a, b, ..., z, a1, ...
+fn binder(depth: usize) -> Symbol {
+    let letter = (b'a' + (depth % 26) as u8) as char;
+    match depth / 26 {
+        0 => Symbol::intern(&letter.to_string()),
+        round => Symbol::intern(&format!("{}{}", letter, round)),
+    }
+}
+
+enum Build {
+    Lambda(Symbol),
+    Application,
+}
+
+// put the terms together bottom-up: the plan lists nodes in pre-order
+fn assemble(plan: Vec<Result<Build, i32>>) -> Term {
+    let mut done: Vec<Term> = Vec::new();
+    for step in plan.into_iter().rev() {
+        let term = match step {
+            Err(index) => Term::Variable(index),
+            Ok(Build::Lambda(name)) => Term::Lambda(name, None, Box::new(done.pop().unwrap())),
+            Ok(Build::Application) => {
+                let lhs = done.pop().unwrap();
+                let rhs = done.pop().unwrap();
+                Term::Application(Box::new(lhs), Box::new(rhs))
+            }
+        };
+        done.push(term);
+    }
+    done.pop().expect("a plan builds one term")
+}
+
+// counts[n][m]: terms of n nodes whose variables point at most m binders up
+fn counts(size: usize) -> Vec<Vec<f64>> {
+    let mut counts = vec![vec![0.0; size + 2]; size + 1];
+    for n in 1..=size {
+        for m in 0..=size {
+            let mut count = if n == 1 { m as f64 } else { 0.0 };
+            if n >= 2 {
+                count += counts[n - 1][m + 1];
+            }
+            for k in 1..n.saturating_sub(1) {
+                count += counts[k][m] * counts[n - 1 - k][m];
+            }
+            counts[n][m] = count;
+        }
+    }
+    counts
+}
+
+// None if no closed term has `size` nodes (size 1) or the size is above
+// MAX_UNIFORM_SIZE
+pub fn uniform(size: usize, rng: &mut Rng) -> Option<Term> {
+    if size > MAX_UNIFORM_SIZE {
+        return None;
+    }
+    let counts = counts(size);
+    if counts[size][0] == 0.0 {
+        return None;
+    }
+    let mut plan = Vec::with_capacity(size);
+    // nodes still to draw, with their size and binders available
+    let mut work = vec![(size, 0)];
+    while let Some((n, m)) = work.pop() {
+        let mut pick = rng.next_f64() * counts[n][m];
+        if n == 1 {
+            plan.push(Err((pick as usize).min(m - 1) as i32 + 1));
+            continue;
+        }
+        pick -= counts[n - 1][m + 1];
+        if pick < 0.0 {
+            plan.push(Ok(Build::Lambda(binder(m))));
+            work.push((n - 1, m + 1));
+            continue;
+        }
+        // an application, its function takes k nodes; rounding leaves the last
+        let mut k = 1;
+        while k < n - 2 {
+            pick -= counts[k][m] * counts[n - 1 - k][m];
+            if pick < 0.0 && counts[k][m] * counts[n - 1 - k][m] > 0.0 {
+                break;
+            }
+            k += 1;
+        }
+        while counts[k][m] * counts[n - 1 - k][m] == 0.0 {
+            k -= 1;
+        }
+        plan.push(Ok(Build::Application));
+        work.push((n - 1 - k, m));
+        work.push((k, m));
+    }
+    Some(assemble(plan))
+}
+
+// generating function of de Bruijn terms in natural size, below its
+// singularity: L = z/(1 - z) + z L + z L²
+fn plain(z: f64) -> f64 {
+    let indices = z / (1.0 - z);
+    ((1.0 - z) - ((1.0 - z) * (1.0 - z) - 4.0 * z * indices).sqrt()) / (2.0 * z)
+}
+
+// the z at which the expected natural size is `target`
+fn tune(target: usize) -> f64 {
+    // (1 - z)³ = 4z² at the singularity
+    let (mut low, mut high) = (0.0, 0.5);
+    for _ in 0..100 {
+        let mid: f64 = (low + high) / 2.0;
+        if (1.0 - mid).powi(3) > 4.0 * mid * mid {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    let singularity = low;
+    let expected = |z: f64| {
+        let h = 1e-9;
+        z * (plain(z + h) - plain(z - h)) / (2.0 * h) / plain(z)
+    };
+    let (mut low, mut high) = (0.0, singularity - 1e-12);
+    for _ in 0..100 {
+        let mid = (low + high) / 2.0;
+        if expected(mid) < target as f64 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+// a closed term of natural size within 20% of `target`, which is at least 2
+pub fn boltzmann(target: usize, rng: &mut Rng) -> Term {
+    assert!(target >= 2, "no closed term is smaller than 2");
+    let z = tune(target);
+    let (lowest, highest) = ((target * 4).div_ceil(5), target * 6 / 5);
+    let (total, indices) = (plain(z), z / (1.0 - z));
+    'attempt: loop {
+        let mut plan = Vec::new();
+        let mut size = 0;
+        let mut work = vec![0];
+        while let Some(depth) = work.pop() {
+            let pick = rng.next_f64() * total;
+            size += 1;
+            if pick < indices {
+                // index k with probability proportional to z^k
+                let mut index = 1;
+                while rng.next_f64() < z {
+                    index += 1;
+                    size += 1;
+                }
+                if index > depth || size > highest {
+                    continue 'attempt;
+                }
+                plan.push(Err(index as i32));
+            } else if pick < indices + z * total {
+                plan.push(Ok(Build::Lambda(binder(depth))));
+                work.push(depth + 1);
+            } else {
+                plan.push(Ok(Build::Application));
+                work.push(depth);
+                work.push(depth);
+            }
+            if size > highest {
+                continue 'attempt;
+            }
+        }
+        if size >= lowest {
+            return assemble(plan);
+        }
+    }
+}
+
+// a term `property` does not hold for: `drawn` by boltzmann from `seed`, and
+// the smallest term shrinking found from it
+#[derive(Debug)]
+pub struct Counterexample {
+    pub seed: u64,
+    pub drawn: Term,
+    pub shrunk: Term,
+}
+
+// `property` over `cases` terms of about `size` nodes, the seed of each drawn
+// from `seed`
+pub fn check(
+    cases: usize,
+    size: usize,
+    seed: u64,
+    mut property: impl FnMut(&Term) -> bool,
+) -> Result<(), Counterexample> {
+    let mut seeds = Rng::new(seed);
+    for _ in 0..cases {
+        let seed = seeds.next_u64();
+        let drawn = boltzmann(size, &mut Rng::new(seed));
+        if !property(&drawn) {
+            let shrunk = shrink(&drawn, &mut property);
+            return Err(Counterexample {
+                seed,
+                drawn,
+                shrunk,
+            });
+        }
+    }
+    Ok(())
+}
+
+// move to a smaller failing candidate, smallest first, while there is one
+fn shrink(term: &Term, property: &mut impl FnMut(&Term) -> bool) -> Term {
+    let mut current = term.clone();
+    loop {
+        let mut candidates = smaller(&current);
+        candidates.sort_by_key(Term::size);
+        match candidates
+            .into_iter()
+            .find(|candidate| !property(candidate))
+        {
+            Some(candidate) => current = candidate,
+            None => return current,
+        }
+    }
+}
+
+// closed terms smaller than `term`: its closed proper subterms, and `term`
+// with an application swapped for one of its sides, which binds the same
+fn smaller(term: &Term) -> Vec<Term> {
+    let mut found = Vec::new();
+    reduce::walk_paths(term, |node, path| {
+        if !path.is_empty() && reduce::is_closed(node) {
+            found.push(node.clone());
+        }
+        if let Term::Application(lhs, rhs) = node {
+            found.push(reduce::replace(term, path, (**lhs).clone()));
+            found.push(reduce::replace(term, path, (**rhs).clone()));
+        }
+        false
+    });
+    found
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Term {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut plan = Vec::new();
+        // binders available to each node still to read
+        let mut work = vec![0];
+        while let Some(m) = work.pop() {
+            // empty input reads 0 and the lowest index
+            match u.int_in_range(0..=2u8)? {
+                0 if m > 0 => plan.push(Err(u.int_in_range(1..=m)? as i32)),
+                0 | 1 => {
+                    plan.push(Ok(Build::Lambda(binder(m))));
+                    work.push(m + 1);
+                }
+                _ => {
+                    plan.push(Ok(Build::Application));
+                    work.push(m);
+                    work.push(m);
+                }
+            }
+        }
+        Ok(assemble(plan))
+    }
+}
+
+// every closed term of at most `max_size` nodes, each once, smaller terms
+// first. terms of one size come variables first (by index), then λs, then
+// applications by the size of their function, each part in the same order
+pub fn enumerate_closed_terms(max_size: usize) -> impl Iterator<Item = Term> {
+    (1..=max_size).flat_map(|size| terms(size, 0))
+}
+
+// the terms of `size` nodes whose variables point at most `depth` binders up,
+// built lazily, the counts grow too fast to keep them
+fn terms(size: usize, depth: usize) -> Box<dyn Iterator<Item = Term>> {
+    if size == 1 {
+        return Box::new((1..=depth as i32).map(Term::Variable));
+    }
+    let lambdas = terms(size - 1, depth + 1)
+        .map(move |body| Term::Lambda(binder(depth), None, Box::new(body)));
+    let applications = (1..size - 1).flat_map(move |left| {
+        terms(left, depth).flat_map(move |fun| {
+            terms(size - 1 - left, depth)
+                .map(move |arg| Term::Application(Box::new(fun.clone()), Box::new(arg)))
+        })
+    });
+    Box::new(lambdas.chain(applications))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampled_terms_are_closed_and_replayable() {
+        for size in [2, 10, 30] {
+            let term = uniform(size, &mut Rng::new(7)).unwrap();
+            assert_eq!(term.size(), size);
+            assert!(reduce::is_closed(&term));
+        }
+        let drawn = boltzmann(40, &mut Rng::new(3));
+        assert!(reduce::is_closed(&drawn));
+        assert_eq!(drawn, boltzmann(40, &mut Rng::new(3)));
+    }
+
+    #[test]
+    fn a_property_that_holds_passes() {
+        let idempotent = |term: &Term| match reduce::normalize(term, 200) {
+            Some((normal, _)) => reduce::normalize(&normal, 200) == Some((normal, 0)),
+            None => true,
+        };
+        assert!(check(200, 20, 1, idempotent).is_ok());
+    }
+
+    #[test]
+    fn a_failure_shrinks_to_a_smallest_term() {
+        let failure = check(100, 30, 1, reduce::is_normal_form).unwrap_err();
+        assert!(!reduce::is_normal_form(&failure.drawn));
+        assert_eq!(failure.drawn, boltzmann(30, &mut Rng::new(failure.seed)));
+        // <\a.{a}|\a.{a}> or \a.{<\b.{b}|a>}
+        assert!(!reduce::is_normal_form(&failure.shrunk));
+        assert!(reduce::is_closed(&failure.shrunk));
+        assert_eq!(failure.shrunk.size(), 5);
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn arbitrary_input_makes_closed_terms() {
+        use arbitrary::{Arbitrary, Unstructured};
+        let mut rng = Rng::new(5);
+        for len in [0, 1, 10, 100, 1000] {
+            let data: Vec<u8> = (0..len).map(|_| rng.next_u64() as u8).collect();
+            let term = Term::arbitrary(&mut Unstructured::new(&data)).unwrap();
+            assert!(reduce::is_closed(&term));
+            assert_eq!(
+                term,
+                Term::arbitrary(&mut Unstructured::new(&data)).unwrap()
+            );
+        }
+        // nothing to read is the identity
+        let identity = Term::arbitrary(&mut Unstructured::new(&[])).unwrap();
+        assert_eq!(
+            identity,
+            Term::Lambda(binder(0), None, Box::new(Term::Variable(1)))
+        );
+        // a λ, an application, two variables
+        let term = Term::arbitrary(&mut Unstructured::new(&[1, 2, 0, 0, 0, 0])).unwrap();
+        assert_eq!(term.size(), 4);
+    }
+}
@@ -0,0 +1,166 @@
+// Gödel numbering, a bijection between the naturals and pure terms in de Bruijn form
+//
+// n = 3k      the variable k + 1
+// n = 3k + 1  λ. term(k)
+// n = 3k + 2  term(a) term(b), where k interleaves the bits of a (even positions)
+//             and b (odd positions)
+//
+// a free variable -(j+1) under d binders is numbered as the index d + j + 1, one
+// past the root binders; binder names and annotations are not part of the number
+use crate::bignum::BigUint;
+use crate::parser::Term;
+use crate::symbol::Symbol;
+
+// the pending work of both directions, kept on the heap so deep terms and
+// large numbers do not overflow the stack
+enum Frame<T> {
+    Visit(T, i32), // with the binders around it
+    Lambda(i32),   // the body is done
+    Application,   // both sides are done
+}
+
+// panics on lets, constants and System F nodes, which have no number
+pub fn term_to_nat(term: &Term) -> BigUint {
+    let mut work = vec![Frame::Visit(term, 0)];
+    let mut done: Vec<BigUint> = Vec::new();
+    while let Some(frame) = work.pop() {
+        match frame {
+            Frame::Visit(node, depth) => match node {
+                Term::Variable(index) => {
+                    let index = if *index < 0 { depth - index } else { *index };
+                    done.push(BigUint::from(index as u64 - 1).mul_add(3, 0));
+                }
+                Term::Lambda(_, _, body) => {
+                    work.push(Frame::Lambda(depth));
+                    work.push(Frame::Visit(body, depth + 1));
+                }
+                Term::Application(lhs, rhs) => {
+                    work.push(Frame::Application);
+                    work.push(Frame::Visit(rhs, depth));
+                    work.push(Frame::Visit(lhs, depth));
+                }
+                _ => panic!("Only pure terms have a Gödel number"),
+            },
+            Frame::Lambda(_) => {
+                let body = done.pop().expect("body numbered");
+                done.push(body.mul_add(3, 1));
+            }
+            Frame::Application => {
+                let rhs = done.pop().expect("argument numbered");
+                let lhs = done.pop().expect("function numbered");
+                done.push(interleave(&lhs, &rhs).mul_add(3, 2));
+            }
+        }
+    }
+    done.pop().expect("root numbered")
+}
+
+// the inverse, binders are named x1, x2, ... by depth; panics on numbers whose
+// variables do not fit an i32
+pub fn nat_to_term(n: &BigUint) -> Term {
+    let mut work = vec![Frame::Visit(n.clone(), 0)];
+    let mut done: Vec<Term> = Vec::new();
+    while let Some(frame) = work.pop() {
+        match frame {
+            Frame::Visit(n, depth) => {
+                let (k, kind) = n.div_rem(3);
+                match kind {
+                    0 => {
+                        let index = k
+                            .to_u64()
+                            .and_then(|k| i32::try_from(k + 1).ok())
+                            .expect("Variable index too large for a term");
+                        done.push(if index > depth {
+                            Term::Variable(depth - index)
+                        } else {
+                            Term::Variable(index)
+                        });
+                    }
+                    1 => {
+                        work.push(Frame::Lambda(depth));
+                        work.push(Frame::Visit(k, depth + 1));
+                    }
+                    _ => {
+                        let (a, b) = deinterleave(&k);
+                        work.push(Frame::Application);
+                        work.push(Frame::Visit(b, depth));
+                        work.push(Frame::Visit(a, depth));
+                    }
+                }
+            }
+            Frame::Lambda(depth) => {
+                let body = done.pop().expect("body built");
+                done.push(Term::Lambda(
+                    Symbol::intern(&format!("x{}", depth + 1)),
+                    None,
+                    Box::new(body),
+                ));
+            }
+            Frame::Application => {
+                let arg = done.pop().expect("argument built");
+                let fun = done.pop().expect("function built");
+                done.push(Term::Application(Box::new(fun), Box::new(arg)));
+            }
+        }
+    }
+    done.pop().expect("root built")
+}
+
+fn interleave(a: &BigUint, b: &BigUint) -> BigUint {
+    let mut out = BigUint::zero();
+    for i in 0..a.bits().max(b.bits()) {
+        if a.bit(i) {
+            out.set_bit(2 * i);
+        }
+        if b.bit(i) {
+            out.set_bit(2 * i + 1);
+        }
+    }
+    out
+}
+
+fn deinterleave(n: &BigUint) -> (BigUint, BigUint) {
+    let (mut a, mut b) = (BigUint::zero(), BigUint::zero());
+    for i in 0..n.bits() {
+        if n.bit(i) {
+            if i % 2 == 0 {
+                a.set_bit(i / 2);
+            } else {
+                b.set_bit(i / 2);
+            }
+        }
+    }
+    (a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::{app, lam, var};
+    use crate::{generate, reduce};
+
+    #[test]
+    fn numbers_and_terms_are_inverse() {
+        for term in generate::enumerate_closed_terms(8) {
+            assert!(reduce::alpha_eq(&nat_to_term(&term_to_nat(&term)), &term));
+        }
+        for n in 0..500u64 {
+            let n = BigUint::from(n);
+            assert_eq!(term_to_nat(&nat_to_term(&n)), n);
+        }
+        // a free variable is one past the root binders
+        assert_eq!(term_to_nat(&lam("x", var(-1))), BigUint::from(3 * 3 + 1));
+    }
+
+    #[test]
+    fn deep_terms_are_numbered_without_recursion() {
+        // every binder adds log2 3 bits to the number, building it is quadratic
+        let depth = 30_000;
+        let mut term = var(1);
+        for _ in 0..depth {
+            term = lam("x", term);
+        }
+        let term = app(term, var(1 - depth));
+        assert!(reduce::alpha_eq(&nat_to_term(&term_to_nat(&term)), &term));
+    }
+}
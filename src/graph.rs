@@ -0,0 +1,208 @@
+// reduction graphs of a term: every term reachable by contracting any redex,
+// explored breadth first, with an edge for each contraction. terms equal up to
+// renaming of binders are one node, so the diamonds of confluence close up
+//
+// exploration stops after `depth` levels and takes at most `width` new terms
+// on each, the nodes left unexplored are marked as the frontier. sequence
+// gives the single path one strategy takes instead
+use std::collections::HashMap;
+
+use crate::parser::Term;
+use crate::pretty_printer::PrettyPrinter;
+use crate::reduce::{self, Path, Strategy};
+use crate::symbol::Symbol;
+
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub path: Path, // of the contracted redex in the `from` term
+}
+
+pub struct Graph {
+    pub nodes: Vec<Term>,
+    pub edges: Vec<Edge>,
+    pub frontier: Vec<bool>, // per node, its redexes were not followed
+}
+
+pub fn explore(term: &Term, depth: usize, width: usize) -> Graph {
+    let mut graph = Graph {
+        nodes: vec![term.clone()],
+        edges: Vec::new(),
+        frontier: vec![false],
+    };
+    // node indices by size, alpha_eq is only tried within a bucket
+    let mut by_size: HashMap<usize, Vec<usize>> = HashMap::new();
+    by_size.entry(term.size()).or_default().push(0);
+    let mut level = vec![0];
+    for _ in 0..depth {
+        let mut next = Vec::new();
+        for &from in &level {
+            for path in reduce::redexes(&graph.nodes[from]) {
+                let reduct = reduce::contract(&graph.nodes[from], &path);
+                let bucket = by_size.entry(reduct.size()).or_default();
+                let known = bucket
+                    .iter()
+                    .copied()
+                    .find(|&node| reduce::alpha_eq(&graph.nodes[node], &reduct));
+                let to = match known {
+                    Some(node) => node,
+                    None if next.len() < width => {
+                        graph.nodes.push(reduct);
+                        graph.frontier.push(false);
+                        let node = graph.nodes.len() - 1;
+                        bucket.push(node);
+                        next.push(node);
+                        node
+                    }
+                    // one too many on this level, its source is not complete
+                    None => {
+                        graph.frontier[from] = true;
+                        continue;
+                    }
+                };
+                graph.edges.push(Edge { from, to, path });
+            }
+        }
+        level = next;
+    }
+    for node in level {
+        graph.frontier[node] = !reduce::is_normal_form(&graph.nodes[node]);
+    }
+    graph
+}
+
+// the path a strategy takes from `term`, at most `steps` contractions long
+pub fn sequence(term: &Term, strategy: Strategy, steps: usize) -> Graph {
+    let mut graph = Graph {
+        nodes: vec![term.clone()],
+        edges: Vec::new(),
+        frontier: vec![false],
+    };
+    for from in 0..steps {
+        let Some(path) = reduce::next_redex(&graph.nodes[from], strategy) else {
+            return graph;
+        };
+        let reduct = reduce::contract(&graph.nodes[from], &path);
+        graph.nodes.push(reduct);
+        graph.frontier.push(false);
+        graph.edges.push(Edge {
+            from,
+            to: from + 1,
+            path,
+        });
+    }
+    let last = graph.nodes.len() - 1;
+    graph.frontier[last] = reduce::next_redex(&graph.nodes[last], strategy).is_some();
+    graph
+}
+
+// labels longer than this many characters are cut
+const LABEL_LIMIT: usize = 60;
+
+pub(crate) fn label(text: String) -> String {
+    match text.char_indices().nth(LABEL_LIMIT) {
+        Some((at, _)) => format!("{}…", &text[..at]),
+        None => text,
+    }
+}
+
+impl Graph {
+    // the redex an edge contracts, printed under the binders above it
+    pub fn redex_label(&self, edge: &Edge, free: &[Symbol]) -> String {
+        let term = &self.nodes[edge.from];
+        let binders = reduce::binders_along(term, &edge.path);
+        let redex = reduce::subterm(term, &edge.path);
+        label(PrettyPrinter::new().format_under(redex, &binders, free))
+    }
+
+    // Graphviz: the start term boxed, normal forms double-circled and the
+    // frontier dashed
+    pub fn to_dot(&self, free: &[Symbol]) -> String {
+        let mut out = String::from("digraph reduction {\n    node [shape=ellipse];\n");
+        for (index, term) in self.nodes.iter().enumerate() {
+            let text = label(PrettyPrinter::new().format(term, free));
+            let mut attributes = format!("label={}", dot_string(&text));
+            if index == 0 {
+                attributes.push_str(", shape=box");
+            } else if reduce::is_normal_form(term) {
+                attributes.push_str(", shape=doublecircle");
+            }
+            if self.frontier[index] {
+                attributes.push_str(", style=dashed");
+            }
+            out.push_str(&format!("    n{} [{}];\n", index, attributes));
+        }
+        for edge in &self.edges {
+            out.push_str(&format!(
+                "    n{} -> n{} [label={}];\n",
+                edge.from,
+                edge.to,
+                dot_string(&self.redex_label(edge, free))
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+fn dot_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::{app, apps, big_omega, i, k};
+
+    #[test]
+    fn the_diamond_of_confluence_closes() {
+        let graph = explore(&app(app(k(), i()), app(i(), i())), 5, 10);
+        assert_eq!(graph.nodes.len(), 5);
+        assert_eq!(graph.edges.len(), 6);
+        assert!(graph.frontier.iter().all(|frontier| !frontier));
+        let normal: Vec<&Term> = graph
+            .nodes
+            .iter()
+            .filter(|node| reduce::is_normal_form(node))
+            .collect();
+        assert_eq!(normal.len(), 1);
+        assert!(reduce::alpha_eq(normal[0], &i()));
+    }
+
+    #[test]
+    fn exploration_stops_at_its_width_and_depth() {
+        let term = app(app(k(), i()), app(i(), i()));
+        let narrow = explore(&term, 5, 1);
+        assert!(narrow.frontier[0]);
+        let shallow = explore(&term, 1, 10);
+        assert_eq!(shallow.nodes.len(), 3);
+        assert_eq!(shallow.frontier, [false, true, true]);
+        // Ω only ever reaches itself
+        let omega = explore(&big_omega(), 10, 10);
+        assert_eq!((omega.nodes.len(), omega.edges.len()), (1, 1));
+    }
+
+    #[test]
+    fn sequences_follow_one_strategy() {
+        let term = apps(k(), [i(), big_omega()]);
+        let normal = sequence(&term, Strategy::Normal, 10);
+        assert_eq!(normal.nodes.len(), 3);
+        assert!(!normal.frontier[2]);
+        let applicative = sequence(&term, Strategy::Applicative, 3);
+        assert_eq!(applicative.nodes.len(), 4);
+        assert!(applicative.frontier[3]);
+    }
+
+    #[test]
+    fn dot_output_marks_the_kinds_of_node() {
+        let graph = explore(&app(i(), app(i(), k())), 5, 10);
+        let dot = graph.to_dot(&[]);
+        assert!(dot.starts_with("digraph reduction {\n"));
+        assert_eq!(dot.matches("shape=box").count(), 1);
+        assert_eq!(dot.matches("shape=doublecircle").count(), 1);
+        assert_eq!(dot.matches(" -> ").count(), graph.edges.len());
+        assert!(!dot.contains("dashed"));
+        assert_eq!(dot_string(r#"a"\b"#), r#""a\"\\b""#);
+        assert_eq!(label("x".repeat(70)), format!("{}…", "x".repeat(60)));
+    }
+}
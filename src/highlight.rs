@@ -0,0 +1,187 @@
+// what each piece of the source is, for syntax highlighting: binders, the
+// variables they bind, free names, keywords, punctuation and `--` comments
+// (as in script files)
+//
+// names are told apart by scope::analyze. if the source does not parse, the
+// tokens are still classified, with every identifier as a plain Name, and
+// characters that start no token are stepped over
+use crate::diagnostic::Span;
+use crate::parser::ParserConfig;
+use crate::scope::{self, Occurrence};
+use crate::tokenizer::{self, Token};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Class {
+    Binder(usize), // with its index into Scopes::binders
+    Bound(usize),  // the index of its binder
+    Free,
+    Keyword, // let, letrec and in
+    Name,    // type names, or any identifier when the source does not parse
+    Number,
+    Punctuation,
+    Comment,
+}
+
+// `--` to the end of the line replaced by spaces, byte for byte so offsets
+// stay put, and the spans of the comments
+pub fn blank_comments(text: &str) -> (String, Vec<Span>) {
+    let mut code = String::with_capacity(text.len());
+    let mut comments = Vec::new();
+    let mut at = 0;
+    for line in text.split_inclusive('\n') {
+        let kept = line.find("--").unwrap_or(line.len());
+        code.push_str(&line[..kept]);
+        code.extend(
+            line[kept..]
+                .bytes()
+                .map(|byte| if byte == b'\n' { '\n' } else { ' ' }),
+        );
+        let comment = line[kept..].trim_end_matches(['\n', '\r']);
+        if !comment.is_empty() {
+            comments.push(Span::new(at + kept, at + kept + comment.len()));
+        }
+        at += line.len();
+    }
+    (code, comments)
+}
+
+// the tokens of `code` and their spans, the text the tokenizer stops at left
+// out and tokenizing going on after it
+fn lex(code: &str) -> (Vec<Token>, Vec<Span>) {
+    let mut tokens = Vec::new();
+    let mut spans = Vec::new();
+    let mut from = 0;
+    while from < code.len() {
+        let (stop, next) = match tokenizer::try_tokenize_spanned(&code[from..]) {
+            Ok(_) => (code.len(), code.len()),
+            Err((_, bad)) => {
+                let skip = code[from + bad.start..]
+                    .chars()
+                    .next()
+                    .map_or(1, char::len_utf8);
+                (from + bad.start, from + bad.end.max(bad.start + skip))
+            }
+        };
+        // up to where it stopped the text tokenizes
+        if let Ok((found, found_spans)) = tokenizer::try_tokenize_spanned(&code[from..stop]) {
+            tokens.extend(found);
+            spans.extend(
+                found_spans
+                    .into_iter()
+                    .map(|span| Span::new(span.start + from, span.end + from)),
+            );
+        }
+        from = next;
+    }
+    (tokens, spans)
+}
+
+// every token and comment of `source` with its class, in source order
+pub fn classify(source: &str, config: ParserConfig) -> Vec<(Span, Class)> {
+    let (code, comments) = blank_comments(source);
+    let mut classified: Vec<(Span, Class)> = comments
+        .into_iter()
+        .map(|span| (span, Class::Comment))
+        .collect();
+    let (tokens, token_spans, scopes) = match scope::analyze(&code, config) {
+        Ok(analysis) => (analysis.tokens, analysis.token_spans, Some(analysis.scopes)),
+        Err(_) => {
+            let (tokens, spans) = lex(&code);
+            (tokens, spans, None)
+        }
+    };
+    let mut names: Vec<(Span, Class)> = Vec::new();
+    if let Some(scopes) = &scopes {
+        for (id, binder) in scopes.binders.iter().enumerate() {
+            names.push((binder.span, Class::Binder(id)));
+        }
+        for occurrence in &scopes.occurrences {
+            names.push(match occurrence {
+                Occurrence::Bound { span, binder, .. } => (*span, Class::Bound(*binder)),
+                Occurrence::Free { span, .. } => (*span, Class::Free),
+            });
+        }
+        names.sort_by_key(|(span, _)| span.start);
+    }
+    for (token, span) in tokens.iter().zip(token_spans) {
+        let class = match token {
+            Token::Var(name) if matches!(name.as_str(), "let" | "letrec" | "in") => Class::Keyword,
+            Token::Var(_) => {
+                match names.binary_search_by_key(&span.start, |(span, _)| span.start) {
+                    Ok(at) => names[at].1,
+                    Err(_) => Class::Name,
+                }
+            }
+            Token::Int(_) => Class::Number,
+            _ => Class::Punctuation,
+        };
+        classified.push((span, class));
+    }
+    classified.sort_by_key(|(span, _)| span.start);
+    classified
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the text of each piece that is not punctuation, with its class
+    fn pieces(source: &str) -> Vec<(&str, Class)> {
+        classify(source, ParserConfig::default())
+            .into_iter()
+            .filter(|(_, class)| *class != Class::Punctuation)
+            .map(|(span, class)| (&source[span.start..span.end], class))
+            .collect()
+    }
+
+    #[test]
+    fn names_are_classified_by_scope() {
+        assert_eq!(
+            pieces(r"\x.{<<x|y>|\x.{x}>} -- shadowed"),
+            [
+                ("x", Class::Binder(0)),
+                ("x", Class::Bound(0)),
+                ("y", Class::Free),
+                ("x", Class::Binder(1)),
+                ("x", Class::Bound(1)),
+                ("-- shadowed", Class::Comment),
+            ]
+        );
+        assert_eq!(
+            pieces("let a = y in a"),
+            [
+                ("let", Class::Keyword),
+                ("a", Class::Binder(0)),
+                ("y", Class::Free),
+                ("in", Class::Keyword),
+                ("a", Class::Bound(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn broken_sources_are_still_highlighted() {
+        assert_eq!(
+            pieces(r"\x.{<x|y}"),
+            [("x", Class::Name), ("x", Class::Name), ("y", Class::Name)]
+        );
+        // the tokens around a character that starts none are kept
+        assert_eq!(
+            pieces("<x|#y> -- still a comment"),
+            [
+                ("x", Class::Name),
+                ("y", Class::Name),
+                ("-- still a comment", Class::Comment)
+            ]
+        );
+    }
+
+    #[test]
+    fn comments_are_blanked_byte_for_byte() {
+        let source = "x -- λ\n-- y\nz";
+        let (code, comments) = blank_comments(source);
+        assert_eq!(code.len(), source.len());
+        assert_eq!(code, "x      \n    \nz");
+        assert_eq!(comments, [Span::new(2, 7), Span::new(8, 12)]);
+    }
+}
@@ -0,0 +1,387 @@
+// files in the textbook or Haskell-style syntax: `\x -> x y` or `λx. x y`,
+// application by juxtaposition, `let x = t in u`, `--` comments, and lines
+// `name = term` or `name x y = term` defining names for the lines below.
+// a line starting with whitespace continues the one before, as in scripts
+//
+// names are made valid identifiers of this crate: primes and other characters
+// become `_`, and a free or defined name that would then clash with another
+// gets a number. to_script prints the result as a script file
+use std::collections::{HashMap, HashSet};
+
+use crate::parser::Term;
+use crate::symbol::Symbol;
+use crate::unparse::unparse;
+
+// a line of the file, its term converted
+pub struct Imported {
+    pub line: usize,
+    pub name: Option<String>, // for a definition
+    pub term: Term,
+    pub free: Vec<Symbol>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Lambda, // '\' or 'λ'
+    Arrow,  // '->' or '.' after the parameters
+    LParen,
+    RParen,
+    Equals,
+}
+
+fn ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn ident_body(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '\''
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            c if c.is_whitespace() => continue,
+            '\\' | 'λ' => Token::Lambda,
+            '.' => Token::Arrow,
+            '-' if chars.next_if_eq(&'>').is_some() => Token::Arrow,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '=' => Token::Equals,
+            c if ident_start(c) => {
+                let mut name = c.to_string();
+                while let Some(c) = chars.next_if(|&c| ident_body(c)) {
+                    name.push(c);
+                }
+                Token::Ident(name)
+            }
+            c => return Err(format!("unexpected character '{}'", c)),
+        });
+    }
+    Ok(tokens)
+}
+
+fn is_keyword(token: Option<&Token>, keyword: &str) -> bool {
+    matches!(token, Some(Token::Ident(name)) if name == keyword)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    at: usize,
+    env: Vec<String>,
+    free: Vec<String>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.at)
+    }
+
+    fn expect(&mut self, expected: Token, what: &str) -> Result<(), String> {
+        if self.peek() == Some(&expected) {
+            self.at += 1;
+            Ok(())
+        } else {
+            Err(format!("expected {}", what))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Ident(name)) if name != "let" && name != "in" => {
+                self.at += 1;
+                Ok(name.clone())
+            }
+            _ => Err("expected a name".to_string()),
+        }
+    }
+
+    // a lambda or let reaches as far right as it can
+    fn term(&mut self) -> Result<Term, String> {
+        match self.peek() {
+            Some(Token::Lambda) => {
+                self.at += 1;
+                let mut params = vec![self.ident()?];
+                while matches!(self.peek(), Some(Token::Ident(_))) {
+                    params.push(self.ident()?);
+                }
+                self.expect(Token::Arrow, "'->' or '.' after the parameters")?;
+                self.abstraction(params)
+            }
+            token if is_keyword(token, "let") => {
+                self.at += 1;
+                let name = self.ident()?;
+                self.expect(Token::Equals, "'=' after the name in let")?;
+                let bound = self.term()?;
+                if !is_keyword(self.peek(), "in") {
+                    return Err("expected 'in' after let".to_string());
+                }
+                self.at += 1;
+                self.env.push(name.clone());
+                let body = self.term();
+                self.env.pop();
+                Ok(Term::Let(
+                    Symbol::intern(&sanitize(&name)),
+                    Box::new(bound),
+                    Box::new(body?),
+                ))
+            }
+            _ => {
+                let mut term = self.atom()?;
+                loop {
+                    match self.peek() {
+                        Some(Token::Ident(name)) if name == "in" => break,
+                        Some(Token::Ident(_) | Token::LParen) => {
+                            term = Term::Application(Box::new(term), Box::new(self.atom()?));
+                        }
+                        // `f \x -> x` passes the lambda as the last argument
+                        Some(Token::Lambda) => {
+                            term = Term::Application(Box::new(term), Box::new(self.term()?));
+                        }
+                        _ => break,
+                    }
+                }
+                Ok(term)
+            }
+        }
+    }
+
+    // the body of `\params -> body`, with the parameters bound
+    fn abstraction(&mut self, params: Vec<String>) -> Result<Term, String> {
+        self.env.extend(params.iter().cloned());
+        let body = self.term();
+        self.env.truncate(self.env.len() - params.len());
+        let mut term = body?;
+        for param in params.iter().rev() {
+            term = Term::Lambda(Symbol::intern(&sanitize(param)), None, Box::new(term));
+        }
+        Ok(term)
+    }
+
+    fn atom(&mut self) -> Result<Term, String> {
+        match self.peek() {
+            Some(Token::LParen) => {
+                self.at += 1;
+                let term = self.term()?;
+                self.expect(Token::RParen, "')'")?;
+                Ok(term)
+            }
+            Some(Token::Ident(_)) => {
+                let name = self.ident()?;
+                Ok(match self.env.iter().rposition(|bound| *bound == name) {
+                    Some(at) => Term::Variable((self.env.len() - at) as i32),
+                    None => {
+                        let at = match self.free.iter().position(|free| *free == name) {
+                            Some(at) => at,
+                            None => {
+                                self.free.push(name);
+                                self.free.len() - 1
+                            }
+                        };
+                        Term::Variable(-(at as i32 + 1))
+                    }
+                })
+            }
+            Some(_) => Err("unexpected token".to_string()),
+            None => Err("unexpected end of term".to_string()),
+        }
+    }
+}
+
+// a valid identifier of this crate, not necessarily unique
+fn sanitize(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    match name.as_str() {
+        "let" | "letrec" | "in" => name + "_",
+        _ => name,
+    }
+}
+
+// the same sanitized name for the same name everywhere in the file
+#[derive(Default)]
+struct Names {
+    renamed: HashMap<String, String>,
+    taken: HashSet<String>,
+}
+
+impl Names {
+    fn get(&mut self, name: &str) -> String {
+        if let Some(renamed) = self.renamed.get(name) {
+            return renamed.clone();
+        }
+        let base = sanitize(name);
+        let mut candidate = base.clone();
+        let mut counter = 1;
+        while self.taken.contains(&candidate) {
+            candidate = format!("{}{}", base, counter);
+            counter += 1;
+        }
+        self.taken.insert(candidate.clone());
+        self.renamed.insert(name.to_string(), candidate.clone());
+        candidate
+    }
+}
+
+// logical lines with the number of the line they start on
+fn lines(source: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (number, raw) in source.lines().enumerate() {
+        let text = raw.split_once("--").map_or(raw, |(code, _)| code);
+        if text.trim().is_empty() {
+            continue;
+        }
+        match lines.last_mut() {
+            Some((_, last)) if text.starts_with(char::is_whitespace) => {
+                last.push(' ');
+                last.push_str(text.trim());
+            }
+            _ => lines.push((number + 1, text.trim().to_string())),
+        }
+    }
+    lines
+}
+
+// every definition and term of the file, or the first error with its line
+pub fn import(source: &str) -> Result<Vec<Imported>, String> {
+    let mut names = Names::default();
+    let mut imported = Vec::new();
+    for (line, text) in lines(source) {
+        let fail = |msg: String| format!("line {}: {}", line, msg);
+        let tokens = tokenize(&text).map_err(fail)?;
+        // `name params = term`, but not a term starting with let
+        let equals = tokens.iter().position(|token| *token == Token::Equals);
+        let (name, params, body) = match equals {
+            Some(at)
+                if at > 0
+                    && !is_keyword(tokens.first(), "let")
+                    && tokens[..at]
+                        .iter()
+                        .all(|token| matches!(token, Token::Ident(_))) =>
+            {
+                let mut heads = tokens[..at].iter().map(|token| match token {
+                    Token::Ident(name) => name.clone(),
+                    _ => unreachable!("checked above"),
+                });
+                let name = heads.next();
+                (name, heads.collect(), &tokens[at + 1..])
+            }
+            _ => (None, Vec::new(), &tokens[..]),
+        };
+        let mut parser = Parser {
+            tokens: body,
+            at: 0,
+            env: Vec::new(),
+            free: Vec::new(),
+        };
+        let term = parser.abstraction(params).map_err(fail)?;
+        if parser.at < body.len() {
+            return Err(fail("unexpected token after the term".to_string()));
+        }
+        imported.push(Imported {
+            line,
+            name: name.map(|name| names.get(&name)),
+            term,
+            free: parser
+                .free
+                .iter()
+                .map(|free| Symbol::intern(&names.get(free)))
+                .collect(),
+        });
+    }
+    Ok(imported)
+}
+
+// a single term on its own, newlines are spaces and there are no definitions
+pub fn term(text: &str) -> Result<(Term, Vec<Symbol>), String> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        at: 0,
+        env: Vec::new(),
+        free: Vec::new(),
+    };
+    let term = parser.term()?;
+    if parser.at < tokens.len() {
+        return Err("unexpected token after the term".to_string());
+    }
+    let mut names = Names::default();
+    let free = parser
+        .free
+        .iter()
+        .map(|free| Symbol::intern(&names.get(free)))
+        .collect();
+    Ok((term, free))
+}
+
+// a script file with the same definitions and terms, see script
+pub fn to_script(imported: &[Imported]) -> String {
+    let mut out = String::new();
+    for item in imported {
+        if let Some(name) = &item.name {
+            out.push_str(name);
+            out.push_str(" = ");
+        }
+        out.push_str(&unparse(&item.term, &item.free));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn converted(text: &str) -> String {
+        let (term, free) = term(text).unwrap();
+        unparse(&term, &free)
+    }
+
+    #[test]
+    fn both_syntaxes_convert() {
+        assert_eq!(converted(r"\x y -> x y z"), r"\x.{\y.{<<x|y>|z>}}");
+        assert_eq!(converted("λx. λy. x (y x)"), r"\x.{\y.{<x|<y|x>>}}");
+        assert_eq!(converted(r"f a \x -> x b"), r"<<f|a>|\x.{<x|b>}>");
+        assert_eq!(
+            converted(r"let id = \x -> x in id id"),
+            r"let id = \x.{x} in <id|id>"
+        );
+    }
+
+    #[test]
+    fn files_convert_to_scripts() {
+        let source = "-- the usual\n\
+                      twice f x = f (f x)\n\
+                      x' = twice\n\
+                      \x20 x_\n\
+                      x' y\n";
+        let imported = import(source).unwrap();
+        assert_eq!(
+            imported.iter().map(|item| item.line).collect::<Vec<_>>(),
+            [2, 3, 5]
+        );
+        assert_eq!(
+            to_script(&imported),
+            "twice = \\f.{\\x.{<f|<f|x>>}}\n\
+             x_ = <twice|x_1>\n\
+             <x_|y>\n"
+        );
+    }
+
+    #[test]
+    fn errors_name_their_line() {
+        assert_eq!(
+            import("a = b\n\nc = (d").err().as_deref(),
+            Some("line 3: expected ')'")
+        );
+        assert_eq!(
+            import("a = b c )").err().as_deref(),
+            Some("line 1: unexpected token after the term")
+        );
+        assert!(term("x # y").is_err());
+    }
+}
@@ -0,0 +1,67 @@
+// Iota and Jot, languages with a single combinator, read and written through Ski
+//
+// Iota:  P = 'i' | '*' P P       i is ι = λf. f S K, * is prefix application
+// Jot:   [ε] = I   [w0] = [w] S K   [w1] = S (K [w])   every bit string is a program
+use crate::ski::Ski;
+
+fn ski_app(lhs: Ski, rhs: Ski) -> Ski {
+    Ski::App(Box::new(lhs), Box::new(rhs))
+}
+
+// λf. f S K as the SKI term S (S I (K S)) (K K)
+pub fn iota() -> Ski {
+    let si_ks = ski_app(ski_app(Ski::S, Ski::I), ski_app(Ski::K, Ski::S));
+    ski_app(ski_app(Ski::S, si_ks), ski_app(Ski::K, Ski::K))
+}
+
+// I = ιι, K = ι(ι(ιι)), S = ι(ι(ι(ιι))); None if a variable is left
+pub fn iota_encode(ski: &Ski) -> Option<String> {
+    Some(match ski {
+        Ski::I => "*ii".to_string(),
+        Ski::K => "*i*i*ii".to_string(),
+        Ski::S => "*i*i*i*ii".to_string(),
+        Ski::Var(_) => return None,
+        Ski::App(fun, arg) => format!("*{}{}", iota_encode(fun)?, iota_encode(arg)?),
+    })
+}
+
+// None on characters other than * and i, or a program that is cut short or too long
+pub fn iota_decode(source: &str) -> Option<Ski> {
+    let mut chars = source.chars().filter(|c| !c.is_whitespace());
+    let ski = iota_program(&mut chars)?;
+    chars.next().is_none().then_some(ski)
+}
+
+fn iota_program(chars: &mut impl Iterator<Item = char>) -> Option<Ski> {
+    match chars.next()? {
+        'i' => Some(iota()),
+        '*' => {
+            let fun = iota_program(chars)?;
+            Some(ski_app(fun, iota_program(chars)?))
+        }
+        _ => None,
+    }
+}
+
+// K = 11100, S = 11111000 and F G = 1 F G; I is written as S K K
+pub fn jot_encode(ski: &Ski) -> Option<String> {
+    Some(match ski {
+        Ski::K => "11100".to_string(),
+        Ski::S => "11111000".to_string(),
+        Ski::I => jot_encode(&ski_app(ski_app(Ski::S, Ski::K), Ski::K))?,
+        Ski::Var(_) => return None,
+        Ski::App(fun, arg) => format!("1{}{}", jot_encode(fun)?, jot_encode(arg)?),
+    })
+}
+
+// None on characters other than 0 and 1
+pub fn jot_decode(source: &str) -> Option<Ski> {
+    source
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .try_fold(Ski::I, |acc, bit| match bit {
+            '0' => Some(ski_app(ski_app(acc, Ski::S), Ski::K)),
+            '1' => Some(ski_app(Ski::S, ski_app(Ski::K, acc))),
+            _ => None,
+        })
+}
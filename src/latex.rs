@@ -0,0 +1,212 @@
+// terms in the LaTeX notation of papers, `\lambda x.\, x\,y`, read by turning
+// them into the `\x -> x y` syntax of import. math delimiters and spacing
+// macros are dropped, `\to` and `\mapsto` are arrows, `\cdot` is the dot
+// after the parameters, and braces group like parentheses
+//
+// font macros such as \mathsf{succ} keep only their argument, subscripts
+// join the name (x_{12} is x_12), \_ is an underscore and \prime a prime.
+// any other macro made of letters is read as a name, so \omega stays omega
+use crate::import;
+use crate::parser::Term;
+use crate::symbol::Symbol;
+
+const SPACING: &[&str] = &[
+    "quad",
+    "qquad",
+    "thinspace",
+    "medspace",
+    "thickspace",
+    "enspace",
+    "negthinspace",
+    "left",
+    "right",
+    "big",
+    "Big",
+    "bigg",
+    "Bigg",
+    "bigl",
+    "bigr",
+    "Bigl",
+    "Bigr",
+    "displaystyle",
+    "textstyle",
+];
+
+const FONTS: &[&str] = &[
+    "mathit",
+    "mathrm",
+    "mathsf",
+    "mathtt",
+    "mathbf",
+    "mathsl",
+    "mathcal",
+    "text",
+    "textit",
+    "textrm",
+    "textsf",
+    "texttt",
+    "textbf",
+    "operatorname",
+    "mbox",
+];
+
+// whether `source` uses LaTeX rather than this crate's syntax, whose lambda
+// is a backslash too
+pub fn is_latex(source: &str) -> bool {
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '$' => return true,
+            '\\' => match chars.peek() {
+                Some(',' | ';' | ':' | '!' | ' ' | '(' | ')' | '[' | ']') => return true,
+                Some(c) if c.is_ascii_alphabetic() => {
+                    let mut name = String::new();
+                    while let Some(c) = chars.next_if(char::is_ascii_alphabetic) {
+                        name.push(c);
+                    }
+                    // `\x.{` and `\x:A.{` are lambdas of this crate, whatever
+                    // the name, \to.{to} included
+                    if matches!(chars.peek(), Some('.' | ':')) {
+                        continue;
+                    }
+                    if name == "lambda"
+                        || SPACING.contains(&name.as_str())
+                        || FONTS.contains(&name.as_str())
+                        || matches!(name.as_str(), "to" | "rightarrow" | "mapsto" | "cdot")
+                    {
+                        return true;
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    false
+}
+
+// the same term in the syntax of import
+pub fn translate(source: &str) -> Result<String, String> {
+    let mut out = String::new();
+    // per open brace, whether it was a group, closed by ')', or the argument
+    // of a font macro or subscript, closed by nothing
+    let mut braces: Vec<bool> = Vec::new();
+    let mut argument = false; // the next brace opens an argument
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '$' | '~' | '&' => out.push(' '),
+            '{' => {
+                braces.push(!argument);
+                if !argument {
+                    out.push('(');
+                }
+                argument = false;
+                continue;
+            }
+            '}' => match braces.pop() {
+                Some(true) => out.push(')'),
+                Some(false) => {}
+                None => return Err("unbalanced '}'".to_string()),
+            },
+            '_' => {
+                out.push('_');
+                argument = true;
+                continue;
+            }
+            '^' => {
+                argument = true;
+                continue;
+            }
+            '\\' => match chars.peek().copied() {
+                Some(c) if c.is_ascii_alphabetic() => {
+                    let mut name = String::new();
+                    while let Some(c) = chars.next_if(char::is_ascii_alphabetic) {
+                        name.push(c);
+                    }
+                    match name.as_str() {
+                        "lambda" => out.push_str(" \\"),
+                        "to" | "rightarrow" | "mapsto" => out.push_str(" -> "),
+                        "cdot" => out.push('.'),
+                        "prime" => out.push('\''),
+                        name if SPACING.contains(&name) => out.push(' '),
+                        name if FONTS.contains(&name) => {
+                            argument = true;
+                            continue;
+                        }
+                        name => out.push_str(name),
+                    }
+                }
+                Some('_') => {
+                    chars.next();
+                    out.push('_');
+                }
+                // \( \) \[ \] delimit math
+                Some('(' | ')' | '[' | ']' | ',' | ';' | ':' | '!' | ' ' | '\\') => {
+                    chars.next();
+                    out.push(' ');
+                }
+                Some(c) => return Err(format!("unexpected macro \\{}", c)),
+                None => return Err("unexpected '\\' at the end".to_string()),
+            },
+            '\n' | '\r' => out.push(' '),
+            c => out.push(c),
+        }
+        argument = false;
+    }
+    if !braces.is_empty() {
+        return Err("unbalanced '{'".to_string());
+    }
+    Ok(out)
+}
+
+pub fn parse(source: &str) -> Result<(Term, Vec<Symbol>), String> {
+    import::term(&translate(source)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unparse::unparse;
+
+    fn converted(source: &str) -> String {
+        let (term, free) = parse(source).unwrap();
+        unparse(&term, &free)
+    }
+
+    #[test]
+    fn latex_is_told_apart_from_the_crate_syntax() {
+        assert!(is_latex(r"$\lambda x.\, x$"));
+        assert!(is_latex(r"\mathsf{succ}\ n"));
+        assert!(is_latex(r"x \to y"));
+        assert!(!is_latex(r"\x.{<x|x>}"));
+        assert!(!is_latex(r"\to.{to}"));
+        assert!(!is_latex(r"\lambda:A.{lambda}"));
+    }
+
+    #[test]
+    fn papers_notation_converts() {
+        assert_eq!(converted(r"$\lambda x.\, x\,y$"), r"\x.{<x|y>}");
+        assert_eq!(
+            converted(r"\lambda f\, x \mapsto f\,(f\,x)"),
+            r"\f.{\x.{<f|<f|x>>}}"
+        );
+        assert_eq!(converted(r"\mathsf{succ}\;\omega"), "<succ|omega>");
+        assert_eq!(
+            converted(r"\lambda x_{12} \cdot x_{12}\, x^{\prime}"),
+            r"\x_12.{<x_12|x_>}"
+        );
+        assert_eq!(
+            converted(r"\left( \lambda x. x \right) {y\,z}"),
+            r"<\x.{x}|<y|z>>"
+        );
+    }
+
+    #[test]
+    fn unbalanced_braces_and_stray_macros_are_errors() {
+        assert_eq!(translate("{x").unwrap_err(), "unbalanced '{'");
+        assert_eq!(translate("x}").unwrap_err(), "unbalanced '}'");
+        assert_eq!(translate(r"\#x").unwrap_err(), r"unexpected macro \#");
+        assert_eq!(translate("x\\").unwrap_err(), "unexpected '\\' at the end");
+    }
+}
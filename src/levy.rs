@@ -0,0 +1,444 @@
+// Lévy's labelled λ-calculus: every subterm carries a label, a word over
+// atoms a, b, ... and the overlined ⌈α⌉ and underlined ⌊α⌋ forms of labels.
+// the initial term gets a distinct atom on each node, and a β-step
+//
+//     <(\x.{M})^α|N>^β  →  β⌈α⌉·M[x := ⌊α⌋·N]
+//
+// where γ·P puts γ in front of the label of P, and x^γ becomes γ⌊α⌋·N. the
+// label α of the function is the degree of the redex: two redexes are in the
+// same family, the redexes an optimal evaluator shares, exactly when they
+// have the same degree
+//
+// redexes can be marked, the mark stays on every copy of the application
+// through later steps, so the residuals of a marked redex are the marked
+// applications left. only the pure calculus is labelled
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::parser::Term;
+use crate::reduce::{self, Dir, Path, Strategy};
+use crate::symbol::Symbol;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Letter {
+    Atom(usize),
+    Over(Label),
+    Under(Label),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Label(Rc<Vec<Letter>>);
+
+impl Label {
+    fn atom(n: usize) -> Label {
+        Label(Rc::new(vec![Letter::Atom(n)]))
+    }
+
+    fn over(&self) -> Label {
+        Label(Rc::new(vec![Letter::Over(self.clone())]))
+    }
+
+    fn under(&self) -> Label {
+        Label(Rc::new(vec![Letter::Under(self.clone())]))
+    }
+
+    fn then(&self, other: &Label) -> Label {
+        let mut letters = Vec::with_capacity(self.0.len() + other.0.len());
+        letters.extend(self.0.iter().cloned());
+        letters.extend(other.0.iter().cloned());
+        Label(Rc::new(letters))
+    }
+
+    // the number of atoms, which grows with the redexes a label went through
+    pub fn len(&self) -> usize {
+        self.0
+            .iter()
+            .map(|letter| match letter {
+                Letter::Atom(_) => 1,
+                Letter::Over(label) | Letter::Under(label) => label.len(),
+            })
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+// atoms are a..z, then a1..z1 and so on
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for letter in self.0.iter() {
+            match letter {
+                Letter::Atom(n) => {
+                    write!(f, "{}", (b'a' + (n % 26) as u8) as char)?;
+                    if n / 26 > 0 {
+                        write!(f, "{}", n / 26)?;
+                    }
+                }
+                Letter::Over(label) => write!(f, "⌈{}⌉", label)?,
+                Letter::Under(label) => write!(f, "⌊{}⌋", label)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Node {
+    Variable(i32),
+    Lambda(Symbol, Box<Labelled>),
+    Application(Box<Labelled>, Box<Labelled>, Vec<usize>), // with the marks on it
+}
+
+#[derive(Debug)]
+pub struct Labelled {
+    pub label: Label,
+    pub node: Node,
+}
+
+// like Term, the traversals below keep their work on the heap: clone, drop,
+// conversions and substitution survive terms of any depth
+impl Clone for Labelled {
+    fn clone(&self) -> Self {
+        map_variables(self, |variable, index, _| Labelled {
+            label: variable.label.clone(),
+            node: Node::Variable(index),
+        })
+    }
+}
+
+impl Drop for Labelled {
+    fn drop(&mut self) {
+        let mut stack: Vec<Labelled> = Vec::new();
+        take_children(&mut self.node, &mut stack);
+        // every popped node is dropped childless
+        while let Some(mut node) = stack.pop() {
+            take_children(&mut node.node, &mut stack);
+        }
+    }
+}
+
+fn take_children(node: &mut Node, stack: &mut Vec<Labelled>) {
+    match std::mem::replace(node, Node::Variable(0)) {
+        Node::Variable(_) => {}
+        Node::Lambda(_, body) => stack.push(*body),
+        Node::Application(fun, arg, _) => stack.extend([*fun, *arg]),
+    }
+}
+
+impl Labelled {
+    // `term` with a distinct atom on each node, in pre-order
+    pub fn new(term: &Term) -> Result<Labelled, String> {
+        enum Frame<'a> {
+            Visit(&'a Term),
+            Build(&'a Term, Label),
+        }
+        let mut next = 0;
+        let mut work = vec![Frame::Visit(term)];
+        let mut done: Vec<Labelled> = Vec::new();
+        while let Some(frame) = work.pop() {
+            match frame {
+                Frame::Visit(node) => {
+                    let atom = Label::atom(next);
+                    next += 1;
+                    match node {
+                        Term::Variable(index) => done.push(Labelled {
+                            label: atom,
+                            node: Node::Variable(*index),
+                        }),
+                        Term::Lambda(_, _, body) => {
+                            work.push(Frame::Build(node, atom));
+                            work.push(Frame::Visit(body));
+                        }
+                        Term::Application(fun, arg) => {
+                            work.push(Frame::Build(node, atom));
+                            work.push(Frame::Visit(arg));
+                            work.push(Frame::Visit(fun));
+                        }
+                        _ => return Err("only the untyped calculus is labelled".to_string()),
+                    }
+                }
+                Frame::Build(node, label) => {
+                    let mut pop = || Box::new(done.pop().expect("child built"));
+                    let node = match node {
+                        Term::Lambda(param, ..) => Node::Lambda(*param, pop()),
+                        _ => {
+                            let arg = pop();
+                            Node::Application(pop(), arg, Vec::new())
+                        }
+                    };
+                    done.push(Labelled { label, node });
+                }
+            }
+        }
+        Ok(done.pop().expect("root built"))
+    }
+
+    pub fn to_term(&self) -> Term {
+        enum Frame<'a> {
+            Visit(&'a Labelled),
+            Build(&'a Labelled),
+        }
+        let mut work = vec![Frame::Visit(self)];
+        let mut done: Vec<Term> = Vec::new();
+        while let Some(frame) = work.pop() {
+            match frame {
+                Frame::Visit(node) => match &node.node {
+                    Node::Variable(index) => done.push(Term::Variable(*index)),
+                    Node::Lambda(_, body) => {
+                        work.push(Frame::Build(node));
+                        work.push(Frame::Visit(body));
+                    }
+                    Node::Application(fun, arg, _) => {
+                        work.push(Frame::Build(node));
+                        work.push(Frame::Visit(arg));
+                        work.push(Frame::Visit(fun));
+                    }
+                },
+                Frame::Build(node) => {
+                    let mut pop = || Box::new(done.pop().expect("child built"));
+                    let built = match &node.node {
+                        Node::Lambda(param, _) => Term::Lambda(*param, None, pop()),
+                        _ => {
+                            let arg = pop();
+                            Term::Application(pop(), arg)
+                        }
+                    };
+                    done.push(built);
+                }
+            }
+        }
+        done.pop().expect("root built")
+    }
+
+    pub fn subterm(&self, path: &[Dir]) -> &Labelled {
+        path.iter().fold(self, |node, dir| match (&node.node, dir) {
+            (Node::Lambda(_, body), Dir::Body) => body,
+            (Node::Application(fun, _, _), Dir::Fun) => fun,
+            (Node::Application(_, arg, _), Dir::Arg) => arg,
+            _ => panic!("Path does not match term structure"),
+        })
+    }
+
+    fn subterm_mut(&mut self, path: &[Dir]) -> &mut Labelled {
+        path.iter()
+            .fold(self, |node, dir| match (&mut node.node, dir) {
+                (Node::Lambda(_, body), Dir::Body) => body,
+                (Node::Application(fun, _, _), Dir::Fun) => fun,
+                (Node::Application(_, arg, _), Dir::Arg) => arg,
+                _ => panic!("Path does not match term structure"),
+            })
+    }
+
+    // the degree of the redex at `path`, None if there is no redex there
+    pub fn degree(&self, path: &[Dir]) -> Option<&Label> {
+        match &self.subterm(path).node {
+            Node::Application(fun, _, _) if matches!(fun.node, Node::Lambda(..)) => {
+                Some(&fun.label)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn redexes(&self) -> Vec<Path> {
+        reduce::redexes(&self.to_term())
+    }
+
+    // the redexes grouped by family, families in the order of their first redex
+    pub fn families(&self) -> Vec<(Label, Vec<Path>)> {
+        let mut families: Vec<(Label, Vec<Path>)> = Vec::new();
+        let mut index: HashMap<Label, usize> = HashMap::new();
+        for path in self.redexes() {
+            let degree = self.degree(&path).unwrap().clone();
+            match index.get(&degree) {
+                Some(&at) => families[at].1.push(path),
+                None => {
+                    index.insert(degree.clone(), families.len());
+                    families.push((degree, vec![path]));
+                }
+            }
+        }
+        families
+    }
+
+    pub fn same_family(&self, a: &[Dir], b: &[Dir]) -> bool {
+        matches!((self.degree(a), self.degree(b)), (Some(a), Some(b)) if a == b)
+    }
+
+    // put `mark` on the application at `path`, false if it is not a redex
+    pub fn mark(&mut self, path: &[Dir], mark: usize) -> bool {
+        if self.degree(path).is_none() {
+            return false;
+        }
+        if let Node::Application(_, _, marks) = &mut self.subterm_mut(path).node {
+            marks.push(mark);
+        }
+        true
+    }
+
+    // the redexes carrying `mark`
+    pub fn residuals(&self, mark: usize) -> Vec<Path> {
+        self.redexes()
+            .into_iter()
+            .filter(|path| match &self.subterm(path).node {
+                Node::Application(_, _, marks) => marks.contains(&mark),
+                _ => false,
+            })
+            .collect()
+    }
+
+    // contract the redex at `path`, returning its degree
+    pub fn contract(&mut self, path: &[Dir]) -> Label {
+        let redex = self.subterm_mut(path);
+        let placeholder = Node::Variable(0);
+        let Node::Application(mut fun, arg, _) = std::mem::replace(&mut redex.node, placeholder)
+        else {
+            panic!("Not a redex")
+        };
+        let Node::Lambda(_, body) = std::mem::replace(&mut fun.node, Node::Variable(0)) else {
+            panic!("Not a redex")
+        };
+        let degree = fun.label.clone();
+        let arg = prefix(&degree.under(), *arg);
+        let result = subst(&body, &arg, 0);
+        redex.label = redex.label.then(&degree.over());
+        *redex = prefix(&redex.label, result);
+        degree
+    }
+
+    // one step under `strategy`, with the path and degree of the redex
+    pub fn step(&mut self, strategy: Strategy) -> Option<(Path, Label)> {
+        let path = reduce::next_redex(&self.to_term(), strategy)?;
+        let degree = self.contract(&path);
+        Some((path, degree))
+    }
+}
+
+fn prefix(label: &Label, mut term: Labelled) -> Labelled {
+    term.label = label.then(&term.label);
+    term
+}
+
+// rebuild `term` bottom-up with a work stack, as traverse::map_leaves does:
+// `variable` maps each variable given its index and the binders above it,
+// the other nodes keep their labels and marks
+fn map_variables(
+    term: &Labelled,
+    mut variable: impl FnMut(&Labelled, i32, i32) -> Labelled,
+) -> Labelled {
+    enum Frame<'a> {
+        Visit(&'a Labelled, i32),
+        Build(&'a Labelled),
+    }
+    let mut work = vec![Frame::Visit(term, 0)];
+    let mut done: Vec<Labelled> = Vec::new();
+    while let Some(frame) = work.pop() {
+        match frame {
+            Frame::Visit(node, depth) => match &node.node {
+                Node::Variable(index) => done.push(variable(node, *index, depth)),
+                Node::Lambda(_, body) => {
+                    work.push(Frame::Build(node));
+                    work.push(Frame::Visit(body, depth + 1));
+                }
+                Node::Application(fun, arg, _) => {
+                    work.push(Frame::Build(node));
+                    work.push(Frame::Visit(arg, depth));
+                    work.push(Frame::Visit(fun, depth));
+                }
+            },
+            Frame::Build(node) => {
+                let mut pop = || Box::new(done.pop().expect("child built"));
+                let built = match &node.node {
+                    Node::Lambda(param, _) => Node::Lambda(*param, pop()),
+                    Node::Application(_, _, marks) => {
+                        let arg = pop();
+                        Node::Application(pop(), arg, marks.clone())
+                    }
+                    Node::Variable(_) => unreachable!("variables are not built"),
+                };
+                done.push(Labelled {
+                    label: node.label.clone(),
+                    node: built,
+                });
+            }
+        }
+    }
+    done.pop().expect("root built")
+}
+
+fn shift(term: &Labelled, d: i32, cutoff: i32) -> Labelled {
+    map_variables(term, |variable, index, depth| Labelled {
+        label: variable.label.clone(),
+        node: Node::Variable(if index > cutoff + depth {
+            index + d
+        } else {
+            index
+        }),
+    })
+}
+
+// as in reduce, `arg` for the variable bound just outside `body`, `depth`
+// binders down, each occurrence keeping its label in front
+fn subst(body: &Labelled, arg: &Labelled, depth: i32) -> Labelled {
+    map_variables(body, |variable, index, inner| {
+        let depth = depth + inner;
+        if index == depth + 1 {
+            return prefix(&variable.label, shift(arg, depth, 0));
+        }
+        Labelled {
+            label: variable.label.clone(),
+            node: Node::Variable(if index > depth + 1 { index - 1 } else { index }),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::{app, i, lam, var};
+    use crate::reduce::Dir;
+
+    #[test]
+    fn copies_of_a_redex_share_a_family() {
+        // \x.{<x|x>} copies the redex <I|c>
+        let term = app(lam("x", app(var(1), var(1))), app(i(), var(-1)));
+        let mut labelled = Labelled::new(&term).unwrap();
+        let (_, first) = labelled.step(Strategy::Normal).unwrap();
+        let families = labelled.families();
+        assert_eq!(families.len(), 1);
+        assert_eq!(families[0].1, vec![vec![Dir::Fun], vec![Dir::Arg]]);
+        assert_ne!(families[0].0, first);
+        assert_eq!(
+            labelled.to_term(),
+            app(app(i(), var(-1)), app(i(), var(-1)))
+        );
+    }
+
+    #[test]
+    fn marks_follow_the_residuals() {
+        let term = app(lam("x", app(var(1), var(1))), app(i(), var(-1)));
+        let mut labelled = Labelled::new(&term).unwrap();
+        assert!(labelled.mark(&[Dir::Arg], 7));
+        assert!(!labelled.mark(&[Dir::Fun], 8));
+        labelled.step(Strategy::Normal);
+        assert_eq!(labelled.residuals(7).len(), 2);
+        assert!(labelled.same_family(&[Dir::Fun], &[Dir::Arg]));
+    }
+
+    #[test]
+    fn deep_terms_are_labelled_without_recursion() {
+        let depth = 100_000;
+        let mut term = var(1);
+        for _ in 0..depth {
+            term = lam("x", term);
+        }
+        let term = app(lam("y", var(1)), term);
+        let mut labelled = Labelled::new(&term).unwrap();
+        let copy = labelled.clone();
+        labelled.step(Strategy::Normal).unwrap();
+        assert_eq!(copy.to_term(), term);
+        assert_eq!(labelled.to_term().depth(), depth + 1);
+    }
+}
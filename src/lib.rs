@@ -0,0 +1,77 @@
+/*
+Syntax:
+TERM = VAR | LAMBDA | APPLICATION | LET | LIST | TUPLE | TYPE_LAMBDA | TERM '[' TYPE ']'
+VAR = [a-zA-Z_][a-zA-Z0-9_]* -- normal identifier rules
+LAMBDA = '\\' VAR [':' TYPE] '.' '{' TERM '}' -- \x.{x+1} or \x:A->A.{x} for example
+APPLICATION = '<' TERM '|' TERM '>' -- something like Dirac, <\x.{x+1}|y>
+LET = 'let' VAR '=' TERM 'in' TERM -- not recursive, VAR is only bound in the second TERM
+    | 'letrec' VAR '=' TERM 'in' TERM -- VAR is bound in both, through Y or Z (ParserConfig::strategy)
+LIST = '[' [TERM {',' TERM}] ']' -- Church list, [a, b] is \c.{\n.{<<c|a>|<<c|b>|n>>}}
+TUPLE = '(' TERM {',' TERM} ')' -- (a, b) is \p.{<<p|a>|b>}, a single TERM is only grouped
+TYPE = ATOM ['->' TYPE] | '\\/' VAR '.' TYPE -- arrows associate to the right
+ATOM = VAR | '(' TYPE ')'
+TYPE_LAMBDA = '/\\' VAR '.' '{' TERM '}' -- /\A.{\x:A.{x}}
+-- type abstraction, type application and \/ are System F only (ParserConfig::system_f)
+-- with the primitives feature: integer literals, true, false, add, mul and ite
+*/
+
+pub mod animate;
+pub mod api;
+pub mod batch;
+pub mod bidir;
+pub mod bignum;
+pub mod boehm;
+pub mod cache;
+pub mod church;
+pub mod codegen;
+pub mod combinators;
+pub mod compact;
+pub mod diagnostic;
+pub mod explicit;
+pub mod export;
+pub mod ffi;
+pub mod generate;
+pub mod godel;
+pub mod graph;
+pub mod highlight;
+pub mod import;
+pub mod iota;
+pub mod json;
+pub mod latex;
+pub mod levy;
+pub mod lsp;
+pub mod mermaid;
+#[cfg(feature = "notebook")]
+pub mod notebook;
+#[cfg(feature = "optimal")]
+pub mod optimal;
+pub mod parser;
+pub mod pattern;
+pub mod playground;
+pub mod prelude;
+pub mod pretty_printer;
+#[cfg(feature = "primitives")]
+pub mod primitives;
+pub mod profile;
+pub mod quote;
+pub mod reduce;
+pub mod repl;
+pub mod rewrite;
+pub mod rpc;
+pub mod scope;
+pub mod scott;
+pub mod script;
+pub mod serve;
+pub mod share;
+pub mod ski;
+pub mod snapshot;
+pub mod symbol;
+pub mod synth;
+pub mod tokenizer;
+pub mod traverse;
+pub mod tui;
+pub mod typed;
+pub mod types;
+pub mod unify;
+pub mod unparse;
+pub mod vm;
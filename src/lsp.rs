@@ -0,0 +1,617 @@
+// language server for script files (.lam, see script) over stdin and stdout:
+// diagnostics for lexing, parsing and unbound names, hover on names and
+// go-to-binder
+//
+// documents are synced whole, positions are converted between byte offsets
+// and the protocol's lines and UTF-16 columns
+use std::collections::HashMap;
+use std::io::{self, BufRead, Read, Write};
+
+use crate::diagnostic::Span;
+use crate::highlight;
+use crate::json::Json;
+use crate::parser::ParserConfig;
+use crate::prelude;
+use crate::scope::{self, Analysis, Occurrence, SyntaxError};
+use crate::script;
+
+// bytes of a message body, as for the HTTP server; a longer one is skipped
+const MAX_BODY: usize = 1 << 20;
+
+// a definition or a term of a script, spans are into the whole document
+struct Statement {
+    name: Option<(String, Span)>,
+    analysis: Result<Analysis, SyntaxError>,
+    offset: usize, // of the term text, add to the spans of the analysis
+}
+
+struct Document {
+    text: String,
+    statements: Vec<Statement>,
+}
+
+// what is under the cursor
+enum Target<'a> {
+    Binder(&'a Statement, usize),
+    Occurrence(&'a Statement, &'a Occurrence),
+    Definition(&'a str),
+}
+
+pub fn run() {
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    let mut documents: HashMap<String, Document> = HashMap::new();
+    let mut shut_down = false;
+    while let Some(message) = read_message(&mut input) {
+        let Ok(message) = Json::parse(&message) else {
+            continue;
+        };
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        let id = message.get("id").cloned();
+        let params = message.get("params").unwrap_or(&Json::Null);
+        let uri = params
+            .get("textDocument")
+            .and_then(|document| document.get("uri"))
+            .and_then(Json::as_str)
+            .unwrap_or("")
+            .to_string();
+        let result = match method {
+            "initialize" => Json::object([
+                (
+                    "capabilities",
+                    Json::object([
+                        ("textDocumentSync", 1.into()),
+                        ("hoverProvider", true.into()),
+                        ("definitionProvider", true.into()),
+                    ]),
+                ),
+                ("serverInfo", Json::object([("name", "lambda_rs".into())])),
+            ]),
+            "shutdown" => {
+                shut_down = true;
+                Json::Null
+            }
+            "exit" => std::process::exit(if shut_down { 0 } else { 1 }),
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                let text = match method {
+                    "textDocument/didOpen" => params
+                        .get("textDocument")
+                        .and_then(|document| document.get("text")),
+                    // full sync: the last change is the whole text
+                    _ => params
+                        .get("contentChanges")
+                        .and_then(Json::as_array)
+                        .and_then(|changes| changes.last())
+                        .and_then(|change| change.get("text")),
+                };
+                let document = analyze(text.and_then(Json::as_str).unwrap_or(""));
+                send(
+                    &mut output,
+                    &notification(
+                        "textDocument/publishDiagnostics",
+                        diagnostics(&uri, &document),
+                    ),
+                );
+                documents.insert(uri, document);
+                continue;
+            }
+            "textDocument/didClose" => {
+                documents.remove(&uri);
+                let cleared = Json::object([
+                    ("uri", uri.into()),
+                    ("diagnostics", Json::Array(Vec::new())),
+                ]);
+                send(
+                    &mut output,
+                    &notification("textDocument/publishDiagnostics", cleared),
+                );
+                continue;
+            }
+            "textDocument/hover" | "textDocument/definition" => {
+                let answer = documents.get(&uri).and_then(|document| {
+                    let offset = offset_of(&document.text, params.get("position")?)?;
+                    let target = target(document, offset)?;
+                    Some(match method {
+                        "textDocument/hover" => hover(document, &target),
+                        _ => definition(&uri, document, &target)?,
+                    })
+                });
+                answer.unwrap_or(Json::Null)
+            }
+            _ if id.is_some() => {
+                let error = Json::object([
+                    ("code", Json::Number(-32601.0)),
+                    ("message", format!("unsupported method {}", method).into()),
+                ]);
+                let response = Json::object([
+                    ("jsonrpc", "2.0".into()),
+                    ("id", id.unwrap()),
+                    ("error", error),
+                ]);
+                send(&mut output, &response);
+                continue;
+            }
+            // other notifications need no answer
+            _ => continue,
+        };
+        if let Some(id) = id {
+            send(
+                &mut output,
+                &Json::object([("jsonrpc", "2.0".into()), ("id", id), ("result", result)]),
+            );
+        }
+    }
+}
+
+// the body of the next message, None once the input ends. a body past
+// MAX_BODY is read past without keeping it and comes back empty, which is no
+// message
+fn read_message(input: &mut impl BufRead) -> Option<String> {
+    let mut length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            length = value.trim().parse().ok();
+        }
+    }
+    let length = length?;
+    if length > MAX_BODY {
+        let skipped = io::copy(&mut input.take(length as u64), &mut io::sink()).ok()?;
+        return (skipped == length as u64).then(String::new);
+    }
+    let mut body = vec![0; length];
+    input.read_exact(&mut body).ok()?;
+    String::from_utf8(body).ok()
+}
+
+fn send(output: &mut impl Write, message: &Json) {
+    let body = message.to_string();
+    let _ = write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+    let _ = output.flush();
+}
+
+fn notification(method: &str, params: Json) -> Json {
+    Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("method", method.into()),
+        ("params", params),
+    ])
+}
+
+// split into statements the way script::run does, keeping offsets: comments
+// are blanked out so a statement is one range of the text
+fn analyze(text: &str) -> Document {
+    let (code, _) = highlight::blank_comments(text);
+    let mut ranges: Vec<Span> = Vec::new();
+    let mut at = 0;
+    for line in code.split_inclusive('\n') {
+        let content = line.trim_end_matches('\n');
+        if !content.trim().is_empty() {
+            let end = at + content.trim_end().len();
+            match ranges.last_mut() {
+                Some(range) if content.starts_with(char::is_whitespace) => range.end = end,
+                _ => ranges.push(Span::new(
+                    at + (content.len() - content.trim_start().len()),
+                    end,
+                )),
+            }
+        }
+        at += line.len();
+    }
+    let config = ParserConfig {
+        system_f: true,
+        ..Default::default()
+    };
+    let statements = ranges
+        .into_iter()
+        .map(|range| {
+            let source = &code[range.start..range.end];
+            let (name, offset) = match source.split_once('=') {
+                Some((name, _)) if script::is_name(name.trim()) => {
+                    let start = range.start + (name.len() - name.trim_start().len());
+                    let name = name.trim().to_string();
+                    let span = Span::new(start, start + name.len());
+                    let offset = range.start + source.find('=').unwrap() + 1;
+                    (Some((name, span)), offset)
+                }
+                _ => (None, range.start),
+            };
+            let analysis = scope::analyze(&code[offset..range.end], config.clone());
+            Statement {
+                name,
+                analysis,
+                offset,
+            }
+        })
+        .collect();
+    Document {
+        text: text.to_string(),
+        statements,
+    }
+}
+
+fn shifted(span: Span, offset: usize) -> Span {
+    Span::new(span.start + offset, span.end + offset)
+}
+
+fn occurrence_span(occurrence: &Occurrence) -> Span {
+    match occurrence {
+        Occurrence::Bound { span, .. } | Occurrence::Free { span, .. } => *span,
+    }
+}
+
+// the last definition of `name` before statement `before`
+fn defined<'a>(document: &'a Document, name: &str, before: usize) -> Option<&'a Statement> {
+    document.statements[..before]
+        .iter()
+        .rev()
+        .find(|statement| matches!(&statement.name, Some((defined, _)) if defined == name))
+}
+
+fn diagnostics(uri: &str, document: &Document) -> Json {
+    let mut found = Vec::new();
+    for (number, statement) in document.statements.iter().enumerate() {
+        match &statement.analysis {
+            Err(error) => {
+                found.push((
+                    shifted(error.span, statement.offset),
+                    1,
+                    error.message.clone(),
+                ));
+            }
+            Ok(analysis) => {
+                for occurrence in &analysis.scopes.occurrences {
+                    if let Occurrence::Free { span, name } = occurrence
+                        && defined(document, name.as_str(), number).is_none()
+                        && !prelude::defines(name.as_str())
+                    {
+                        let message =
+                            format!("`{}` is not defined, it stays a free variable", name);
+                        found.push((shifted(*span, statement.offset), 2, message));
+                    }
+                }
+            }
+        }
+    }
+    let diagnostics = found
+        .into_iter()
+        .map(|(span, severity, message)| {
+            Json::object([
+                ("range", range(&document.text, span)),
+                ("severity", severity.into()),
+                ("source", "lambda_rs".into()),
+                ("message", message.into()),
+            ])
+        })
+        .collect();
+    Json::object([
+        ("uri", uri.into()),
+        ("diagnostics", Json::Array(diagnostics)),
+    ])
+}
+
+fn target(document: &Document, offset: usize) -> Option<Target<'_>> {
+    let contains = |span: Span| span.start <= offset && offset <= span.end;
+    for statement in &document.statements {
+        if let Some((name, span)) = &statement.name
+            && contains(*span)
+        {
+            return Some(Target::Definition(name));
+        }
+        let Ok(analysis) = &statement.analysis else {
+            continue;
+        };
+        let scopes = &analysis.scopes;
+        if let Some(occurrence) = scopes
+            .occurrences
+            .iter()
+            .find(|occurrence| contains(shifted(occurrence_span(occurrence), statement.offset)))
+        {
+            return Some(Target::Occurrence(statement, occurrence));
+        }
+        if let Some(binder) = (0..scopes.binders.len())
+            .find(|&binder| contains(shifted(scopes.binders[binder].span, statement.offset)))
+        {
+            return Some(Target::Binder(statement, binder));
+        }
+    }
+    None
+}
+
+fn hover(document: &Document, target: &Target) -> Json {
+    let text = match target {
+        Target::Definition(name) => format!("`{}`: definition", name),
+        Target::Binder(statement, binder) => {
+            let Ok(analysis) = &statement.analysis else {
+                unreachable!("binders come from an analysis")
+            };
+            let uses = analysis
+                .scopes
+                .occurrences
+                .iter()
+                .filter(|occurrence| matches!(occurrence, Occurrence::Bound { binder: b, .. } if b == binder))
+                .count();
+            let name = analysis.scopes.binders[*binder].name;
+            match uses {
+                1 => format!("`{}`: binder, used once", name),
+                _ => format!("`{}`: binder, used {} times", name, uses),
+            }
+        }
+        Target::Occurrence(statement, Occurrence::Bound { binder, index, .. }) => {
+            let Ok(analysis) = &statement.analysis else {
+                unreachable!("occurrences come from an analysis")
+            };
+            let binder = &analysis.scopes.binders[*binder];
+            let (line, column) = position(&document.text, binder.span.start + statement.offset);
+            format!(
+                "`{}`: bound at {}:{}, de Bruijn index {}",
+                binder.name,
+                line + 1,
+                column + 1,
+                index
+            )
+        }
+        Target::Occurrence(statement, Occurrence::Free { name, .. }) => {
+            let number = statement_number(document, statement);
+            if let Some(definition) = defined(document, name.as_str(), number) {
+                let (_, span) = definition.name.as_ref().expect("definitions are named");
+                let (line, _) = position(&document.text, span.start);
+                format!(
+                    "`{}`: free, stands for the definition on line {}",
+                    name,
+                    line + 1
+                )
+            } else if prelude::defines(name.as_str()) {
+                format!("`{}`: free, stands for the prelude definition", name)
+            } else {
+                format!("`{}`: free variable", name)
+            }
+        }
+    };
+    Json::object([(
+        "contents",
+        Json::object([("kind", "markdown".into()), ("value", text.into())]),
+    )])
+}
+
+fn definition(uri: &str, document: &Document, target: &Target) -> Option<Json> {
+    let span = match target {
+        Target::Occurrence(statement, Occurrence::Bound { binder, .. }) => {
+            let Ok(analysis) = &statement.analysis else {
+                return None;
+            };
+            shifted(analysis.scopes.binders[*binder].span, statement.offset)
+        }
+        Target::Occurrence(statement, Occurrence::Free { name, .. }) => {
+            let number = statement_number(document, statement);
+            defined(document, name.as_str(), number)?.name.as_ref()?.1
+        }
+        Target::Binder(..) | Target::Definition(_) => return None,
+    };
+    Some(Json::object([
+        ("uri", uri.into()),
+        ("range", range(&document.text, span)),
+    ]))
+}
+
+fn statement_number(document: &Document, statement: &Statement) -> usize {
+    document
+        .statements
+        .iter()
+        .position(|other| std::ptr::eq(other, statement))
+        .expect("statement of the document")
+}
+
+// 0-based line and UTF-16 column of a byte offset
+fn position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |at| at + 1);
+    let line = before.matches('\n').count();
+    (line, before[line_start..].encode_utf16().count())
+}
+
+fn range(text: &str, span: Span) -> Json {
+    let point = |offset| {
+        let (line, character) = position(text, offset);
+        Json::object([("line", line.into()), ("character", character.into())])
+    };
+    Json::object([("start", point(span.start)), ("end", point(span.end))])
+}
+
+// byte offset of a protocol position, clamped to its line
+fn offset_of(text: &str, position: &Json) -> Option<usize> {
+    let line = position.get("line")?.as_usize()?;
+    let character = position.get("character")?.as_usize()?;
+    let mut start = 0;
+    for _ in 0..line {
+        start += text[start..].find('\n')? + 1;
+    }
+    let line_text = text[start..].split('\n').next().unwrap_or("");
+    let mut units = 0;
+    for (at, c) in line_text.char_indices() {
+        if units >= character {
+            return Some(start + at);
+        }
+        units += c.len_utf16();
+    }
+    Some(start + line_text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the message and the (line, character) range of every diagnostic of `text`
+    fn published(text: &str) -> Vec<(String, [usize; 4])> {
+        let found = diagnostics("file:///test.lam", &analyze(text));
+        let point = |range: &Json, end: &str| {
+            let point = range.get(end).unwrap();
+            let coordinate = |key| point.get(key).and_then(Json::as_usize).unwrap();
+            (coordinate("line"), coordinate("character"))
+        };
+        found
+            .get("diagnostics")
+            .and_then(Json::as_array)
+            .unwrap()
+            .iter()
+            .map(|diagnostic| {
+                let range = diagnostic.get("range").unwrap();
+                let ((l0, c0), (l1, c1)) = (point(range, "start"), point(range, "end"));
+                let message = diagnostic.get("message").and_then(Json::as_str).unwrap();
+                (message.to_string(), [l0, c0, l1, c1])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn trailing_tokens_are_diagnosed_at_the_first_one() {
+        let found = published("id = \\x.{x}\n\\x.{x} )\n");
+        assert_eq!(
+            found,
+            vec![("unexpected token after the term".to_string(), [1, 7, 1, 8])]
+        );
+        // in a definition the span is shifted past the name
+        let found = published("id = \\x.{x} y z");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].1, [0, 12, 0, 13]);
+    }
+
+    #[test]
+    fn undefined_names_are_warnings() {
+        let found = published("id = \\x.{x}\n<id|y>\n");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].1, [1, 4, 1, 5]);
+        assert!(found[0].0.contains("`y` is not defined"));
+    }
+
+    #[test]
+    fn deep_statements_are_analyzed() {
+        let n = 20_000;
+        let text = format!("{}x{}\n<x|y>", "\\x.{".repeat(n), "}".repeat(n));
+        let found = published(&text);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].1, [1, 1, 1, 2]);
+        assert_eq!(hovered(&text, 0, 4 * n - 3), "`x`: binder, used once");
+    }
+
+    // the answer to a hover or definition request at `line` and `character`
+    fn ask(text: &str, method: &str, line: usize, character: usize) -> Option<Json> {
+        let document = analyze(text);
+        let position = Json::object([("line", line.into()), ("character", character.into())]);
+        let target = target(&document, offset_of(text, &position)?)?;
+        match method {
+            "hover" => Some(hover(&document, &target)),
+            _ => definition("file:///test.lam", &document, &target),
+        }
+    }
+
+    fn hovered(text: &str, line: usize, character: usize) -> String {
+        let answer = ask(text, "hover", line, character).unwrap();
+        let contents = answer.get("contents").unwrap();
+        contents
+            .get("value")
+            .and_then(Json::as_str)
+            .unwrap()
+            .to_string()
+    }
+
+    fn defined_at(text: &str, line: usize, character: usize) -> Option<[usize; 4]> {
+        let answer = ask(text, "definition", line, character)?;
+        let range = answer.get("range").unwrap();
+        let point = |end: &str, key: &str| {
+            let point = range.get(end).unwrap();
+            point.get(key).and_then(Json::as_usize).unwrap()
+        };
+        Some([
+            point("start", "line"),
+            point("start", "character"),
+            point("end", "line"),
+            point("end", "character"),
+        ])
+    }
+
+    const SCRIPT: &str = "id = \\x.{x}\n<<id|y>|I>\n";
+
+    #[test]
+    fn hover_tells_what_a_name_stands_for() {
+        assert_eq!(hovered(SCRIPT, 0, 0), "`id`: definition");
+        assert_eq!(hovered(SCRIPT, 0, 6), "`x`: binder, used once");
+        assert_eq!(
+            hovered(SCRIPT, 0, 9),
+            "`x`: bound at 1:7, de Bruijn index 1"
+        );
+        assert_eq!(
+            hovered(SCRIPT, 1, 2),
+            "`id`: free, stands for the definition on line 1"
+        );
+        assert_eq!(hovered(SCRIPT, 1, 5), "`y`: free variable");
+        assert_eq!(
+            hovered(SCRIPT, 1, 8),
+            "`I`: free, stands for the prelude definition"
+        );
+        assert!(ask(SCRIPT, "hover", 1, 0).is_none());
+    }
+
+    #[test]
+    fn definitions_are_the_binder_or_the_named_statement() {
+        assert_eq!(defined_at(SCRIPT, 0, 9), Some([0, 6, 0, 7]));
+        assert_eq!(defined_at(SCRIPT, 1, 2), Some([0, 0, 0, 2]));
+        // neither free names nor binders lead anywhere
+        assert_eq!(defined_at(SCRIPT, 1, 5), None);
+        assert_eq!(defined_at(SCRIPT, 0, 6), None);
+    }
+
+    #[test]
+    fn columns_count_utf16_units() {
+        // λ is two bytes and one unit, 𝕩 four bytes and two units
+        let text = "-- λ𝕩 z\nid";
+        let at = |line: usize, character: usize| {
+            offset_of(
+                text,
+                &Json::object([("line", line.into()), ("character", character.into())]),
+            )
+        };
+        assert_eq!(at(0, 3), Some(3));
+        assert_eq!(at(0, 4), Some(5));
+        assert_eq!(at(0, 6), Some(9));
+        // past the end of the line is its end
+        assert_eq!(at(0, 100), Some(11));
+        assert_eq!(at(1, 1), Some(13));
+        assert_eq!(at(2, 0), None);
+        assert_eq!(position(text, 9), (0, 6));
+        assert_eq!(position(text, 13), (1, 1));
+    }
+
+    #[test]
+    fn messages_are_framed_by_their_byte_length() {
+        let oversized = "x".repeat(MAX_BODY + 1);
+        let stream = format!(
+            "Content-Length: 2\r\n\r\n{{}}content-length: 4\r\nContent-Type: x\r\n\r\nnull\
+             Content-Length: {}\r\n\r\n{}Content-Length: 1\r\n\r\n1",
+            oversized.len(),
+            oversized
+        );
+        let mut input = stream.as_bytes();
+        assert_eq!(read_message(&mut input).as_deref(), Some("{}"));
+        assert_eq!(read_message(&mut input).as_deref(), Some("null"));
+        assert_eq!(read_message(&mut input).as_deref(), Some(""));
+        assert_eq!(read_message(&mut input).as_deref(), Some("1"));
+        assert_eq!(read_message(&mut input), None);
+        // a body cut short ends the input
+        let mut input = "Content-Length: 5\r\n\r\nnul".as_bytes();
+        assert_eq!(read_message(&mut input), None);
+        let mut output = Vec::new();
+        send(&mut output, &"λ".into());
+        assert_eq!(output, "Content-Length: 4\r\n\r\n\"λ\"".as_bytes());
+    }
+}
@@ -1,18 +1,588 @@
-/*
-Syntax:
-TERM = VAR | LAMBDA | APPLICATION
-VAR = [a-zA-Z_][a-zA-Z0-9_]* -- normal identifier rules
-LAMBDA = '\\' VAR '.' '{' TERM '}' -- \x.{x+1} for example
-APPLICATION = '<' TERM '|' TERM '>' -- something like Dirac, <\x.{x+1}|y>
-*/
+use std::path::Path;
+use std::str::FromStr;
 
-mod tokenizer;
-mod parser;
-mod pretty_printer;
-
-use crate::pretty_printer::PrettyPrinter;
+use lambda_rs::cache::Cache;
+use lambda_rs::codegen::{self, Target};
+use lambda_rs::export::{self, Assistant};
+use lambda_rs::generate::{self, Rng};
+#[cfg(feature = "optimal")]
+use lambda_rs::optimal;
+use lambda_rs::parser::{ParserConfig, Term};
+use lambda_rs::pretty_printer::PrettyPrinter;
+use lambda_rs::profile::{self, Profiler};
+use lambda_rs::reduce::{self, Solvability, Strategy};
+use lambda_rs::rewrite::Rules;
+use lambda_rs::snapshot::Snapshot;
+use lambda_rs::symbol::Symbol;
+use lambda_rs::unparse::unparse;
+use lambda_rs::{
+    animate, boehm, graph, import, latex, levy, lsp, mermaid, parser, playground, prelude, repl,
+    rpc, script, serve, synth, tokenizer, tui,
+};
 
 fn main() {
+    // `--tui TERM` opens the interactive stepper on TERM, `--repl` starts the REPL,
+    // `--run FILE [--no-cache]` runs a script, caching the normal forms of its definitions,
+    // `--import FILE` prints a file in the `\x -> x y` syntax as a script,
+    // `--latex TERM` prints TERM in LaTeX notation, `\lambda x.\, x\,y`, in this syntax,
+    // `--export coq|agda|lean TERM` prints TERM for a proof assistant,
+    // `--dot [--depth N] [--width N] TERM` prints the reduction graph of TERM for Graphviz,
+    // `--mermaid ast|steps|graph TERM` prints the syntax tree, the normal order
+    // reduction or the reduction graph of TERM as a Mermaid flowchart,
+    // `--playground [--wasm PATH] [-o FILE]` writes a static HTML page reducing terms in
+    // the browser, with the wasm32 build of the library at PATH embedded,
+    // `--svg [--steps N] [--delay SECONDS] TERM` prints the normal order reduction of TERM,
+    // at most N steps (default 20), as an animated SVG of its trees,
+    // `--boehm [--depth N] TERM` prints the Böhm tree of TERM to depth N (default 5),
+    // ⊥ for subterms without a head normal form within the fuel and ... below,
+    // `--optimal TERM` (with the optimal feature) normalizes TERM by sharing graph
+    // reduction and compares its β-interactions, one per family of redexes, with the
+    // β-steps of normal order,
+    // `--levy [--steps N] TERM` reduces TERM in normal order, at most N steps (default 100),
+    // naming the family of each redex contracted by its degree in Lévy's labelling,
+    // `--share [--min N] TERM` prints the normal form of TERM with its repeated subterms of
+    // at least N nodes (default 10) let-bound, `let t0 = ... in ...`,
+    // `--checkpoint FILE [--every N] TERM` normalizes TERM, saving the state to FILE every
+    // N steps (default 10000), and `--resume FILE [--every N]` goes on from such a file,
+    // `--solvable TERM` reports whether TERM has a head normal form,
+    // `--gen [--boltzmann] [--seed N] [--count N] SIZE` prints random closed terms,
+    // `--gen --all SIZE` every closed term of at most SIZE nodes, smallest first,
+    // `--synth [--size N] [--count N] EXAMPLE...` prints the first N (default 1) closed
+    // normal terms of at most SIZE nodes (default 8) taking each `IN; IN => OUT` to OUT,
+    // `--codegen js|python|rust TERM` (or `--emit`) prints closed TERM as a JavaScript or
+    // Python expression, or a Rust source file,
+    // `--rules FILE TERM` normalizes TERM with the rewrite rules of FILE, one per line,
+    // `--stats TERM` normalizes TERM and reports its size and the memory it took,
+    // `--serve [--port N]` answers POST /normalize over HTTP on localhost,
+    // `--rpc` answers JSON-RPC requests, one per line, on stdin/stdout,
+    // `--lsp` serves the language server protocol for script files on stdin/stdout
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let rest = args.get(1..).unwrap_or_default();
+    match args.first().map(String::as_str) {
+        Some("--tui") => run_tui(rest),
+        Some("--lsp") => lsp::run(),
+        Some("--serve") => run_serve(rest),
+        Some("--rpc") => rpc::run(),
+        Some("--repl") => repl::run(),
+        Some("--run") => run_script(rest),
+        Some("--import") => run_import(rest),
+        Some("--latex") => run_latex(rest),
+        Some("--export") => run_export(rest),
+        Some("--dot") => run_dot(rest),
+        Some("--mermaid") => run_mermaid(rest),
+        Some("--playground") => run_playground(rest),
+        Some("--svg") => run_svg(rest),
+        Some("--boehm") => run_boehm(rest),
+        #[cfg(feature = "optimal")]
+        Some("--optimal") => run_optimal(rest),
+        Some("--levy") => run_levy(rest),
+        Some("--share") => run_share(rest),
+        Some("--checkpoint") => run_checkpoint(rest),
+        Some("--resume") => run_resume(rest),
+        Some("--solvable") => run_solvable(rest),
+        Some("--gen") => run_gen(rest),
+        Some("--synth") => run_synth(rest),
+        Some("--codegen" | "--emit") => run_codegen(rest),
+        Some("--rules") => run_rules(rest),
+        Some("--stats") => run_stats(rest),
+        _ => demo(),
+    }
+}
+
+fn usage(text: &str) -> ! {
+    eprintln!("usage: {}", text);
+    std::process::exit(2);
+}
+
+fn fail(msg: impl std::fmt::Display) -> ! {
+    eprintln!("error: {}", msg);
+    std::process::exit(1);
+}
+
+// the words of the command line after the options as one term, every
+// language level accepted; a parse error ends the program
+fn parse(words: &[String]) -> (Term, Vec<Symbol>) {
+    let config = ParserConfig {
+        system_f: true,
+        ..Default::default()
+    };
+    parser::parse_with_config(&words.join(" "), config).unwrap_or_else(|msg| fail(msg))
+}
+
+// same, prelude names resolved
+fn parse_resolved(words: &[String]) -> (Term, Vec<Symbol>) {
+    let (term, free) = parse(words);
+    (prelude::resolve(&term, &free), free)
+}
+
+// the leading `--name VALUE` options with a name in `valued` and `--name`
+// switches in `switches`, in any order, and the arguments after them
+fn options<'a>(
+    args: &'a [String],
+    valued: &[&str],
+    switches: &[&str],
+) -> (Vec<(&'a str, &'a str)>, &'a [String]) {
+    let mut found = Vec::new();
+    let mut rest = args;
+    loop {
+        match rest {
+            [flag, tail @ ..] if switches.contains(&flag.as_str()) => {
+                found.push((flag.as_str(), ""));
+                rest = tail;
+            }
+            [flag, value, tail @ ..] if valued.contains(&flag.as_str()) => {
+                found.push((flag.as_str(), value.as_str()));
+                rest = tail;
+            }
+            _ => return (found, rest),
+        }
+    }
+}
+
+// the last value given to `name`, the usage if it does not parse
+fn option<T: FromStr>(found: &[(&str, &str)], name: &str, text: &str) -> Option<T> {
+    let (_, value) = found.iter().rev().find(|(flag, _)| *flag == name)?;
+    Some(value.parse().unwrap_or_else(|_| usage(text)))
+}
+
+fn read(path: &str) -> String {
+    std::fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("{}: {}", path, err);
+        std::process::exit(1);
+    })
+}
+
+fn run_tui(args: &[String]) {
+    let (term, free) = parse(args);
+    tui::run(term, free);
+}
+
+fn run_serve(args: &[String]) {
+    const USAGE: &str = "--serve [--port N]";
+    let (found, rest) = options(args, &["--port"], &[]);
+    if !rest.is_empty() {
+        usage(USAGE);
+    }
+    if let Err(err) = serve::run(option(&found, "--port", USAGE).unwrap_or(8080)) {
+        fail(err);
+    }
+}
+
+fn run_script(args: &[String]) {
+    let Some(path) = args.first() else {
+        usage("--run FILE [--no-cache]");
+    };
+    let source = read(path);
+    let cache = if args[1..].iter().any(|arg| arg == "--no-cache") {
+        None
+    } else {
+        Cache::default_dir().map(Cache::new)
+    };
+    match script::run(&source, cache.as_ref()) {
+        Ok(output) => output.iter().for_each(|line| println!("{}", line)),
+        Err(msg) => {
+            eprintln!("{}: {}", path, msg);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_import(args: &[String]) {
+    let Some(path) = args.first() else {
+        usage("--import FILE");
+    };
+    match import::import(&read(path)) {
+        Ok(imported) => print!("{}", import::to_script(&imported)),
+        Err(msg) => {
+            eprintln!("{}: {}", path, msg);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_latex(args: &[String]) {
+    match latex::parse(&args.join(" ")) {
+        Ok((term, free)) => println!("{}", unparse(&term, &free)),
+        Err(msg) => fail(msg),
+    }
+}
+
+fn run_export(args: &[String]) {
+    let Some(assistant) = args.first().and_then(|name| Assistant::from_name(name)) else {
+        usage("--export coq|agda|lean TERM");
+    };
+    let (term, free) = parse_resolved(&args[1..]);
+    match export::export(&term, &free, assistant) {
+        Ok(text) => print!("{}", text),
+        Err(msg) => fail(msg),
+    }
+}
+
+fn run_dot(args: &[String]) {
+    const USAGE: &str = "--dot [--depth N] [--width N] TERM";
+    let (found, rest) = options(args, &["--depth", "--width"], &[]);
+    let depth = option(&found, "--depth", USAGE).unwrap_or(6);
+    let width = option(&found, "--width", USAGE).unwrap_or(16);
+    let (term, free) = parse_resolved(rest);
+    print!("{}", graph::explore(&term, depth, width).to_dot(&free));
+}
+
+fn run_mermaid(args: &[String]) {
+    let Some(kind) = args.first() else {
+        usage("--mermaid ast|steps|graph TERM");
+    };
+    let (term, free) = parse_resolved(&args[1..]);
+    match kind.as_str() {
+        "ast" => print!("{}", mermaid::ast(&term, &free)),
+        "steps" => {
+            let steps = graph::sequence(&term, Strategy::Normal, 20);
+            print!("{}", mermaid::graph(&steps, &free));
+        }
+        "graph" => print!("{}", mermaid::graph(&graph::explore(&term, 6, 16), &free)),
+        _ => usage("--mermaid ast|steps|graph TERM"),
+    }
+}
+
+fn run_playground(args: &[String]) {
+    const USAGE: &str = "--playground [--wasm PATH] [-o FILE]";
+    let (found, rest) = options(args, &["--wasm", "-o"], &[]);
+    if !rest.is_empty() {
+        usage(USAGE);
+    }
+    let wasm: String =
+        option(&found, "--wasm", USAGE).unwrap_or_else(|| playground::DEFAULT_WASM.to_string());
+    let output: Option<String> = option(&found, "-o", USAGE);
+    let bytes = std::fs::read(&wasm).unwrap_or_else(|err| {
+        eprintln!("{}: {}", wasm, err);
+        eprintln!("build it with: cargo build --lib --release --target wasm32-unknown-unknown");
+        std::process::exit(1);
+    });
+    let html = playground::html(&bytes).unwrap_or_else(|msg| {
+        eprintln!("{}: {}", wasm, msg);
+        std::process::exit(1);
+    });
+    match output {
+        Some(path) => {
+            if let Err(err) = std::fs::write(&path, html) {
+                eprintln!("{}: {}", path, err);
+                std::process::exit(1);
+            }
+        }
+        None => print!("{}", html),
+    }
+}
+
+fn run_svg(args: &[String]) {
+    const USAGE: &str = "--svg [--steps N] [--delay SECONDS] TERM";
+    let (found, rest) = options(args, &["--steps", "--delay"], &[]);
+    let steps = option(&found, "--steps", USAGE).unwrap_or(20);
+    let delay: f64 = option(&found, "--delay", USAGE).unwrap_or(1.5);
+    if rest.is_empty() || delay.is_nan() || delay <= 0.0 {
+        usage(USAGE);
+    }
+    let (term, free) = parse_resolved(rest);
+    let sequence = graph::sequence(&term, Strategy::Normal, steps);
+    match animate::svg(&sequence, &free, delay) {
+        Ok(svg) => print!("{}", svg),
+        Err(msg) => fail(msg),
+    }
+}
+
+fn run_boehm(args: &[String]) {
+    const USAGE: &str = "--boehm [--depth N] TERM";
+    let (found, rest) = options(args, &["--depth"], &[]);
+    if rest.is_empty() {
+        usage(USAGE);
+    }
+    let depth = option(&found, "--depth", USAGE).unwrap_or(5);
+    let (term, free) = parse_resolved(rest);
+    println!("{}", boehm::boehm_tree(&term, depth).render(&free));
+}
+
+#[cfg(feature = "optimal")]
+fn run_optimal(args: &[String]) {
+    let (term, free) = parse_resolved(args);
+    let fuel = reduce::default_fuel(reduce::termination(&term));
+    match optimal::reduce(&term, fuel) {
+        Ok(reduction) => {
+            println!("normal form:  {}", unparse(&reduction.normal, &free));
+            println!("families:     {} β-interactions", reduction.families);
+            println!("interactions: {}", reduction.interactions);
+        }
+        Err(failure) => println!("optimal reduction failed: {}", failure),
+    }
+    match reduce::normalize(&term, fuel) {
+        Some((_, steps)) => println!("normal order: {} β-steps", steps),
+        None => println!("normal order: no normal form within {} steps", fuel),
+    }
+}
+
+fn run_levy(args: &[String]) {
+    const USAGE: &str = "--levy [--steps N] TERM";
+    let (found, rest) = options(args, &["--steps"], &[]);
+    if rest.is_empty() {
+        usage(USAGE);
+    }
+    let steps = option(&found, "--steps", USAGE).unwrap_or(100);
+    let (term, free) = parse_resolved(rest);
+    let mut labelled = levy::Labelled::new(&term).unwrap_or_else(|msg| fail(msg));
+    println!("{}", unparse(&term, &free));
+    let mut families: Vec<levy::Label> = Vec::new();
+    let mut taken = 0;
+    while taken < steps {
+        let Some((_, degree)) = labelled.step(Strategy::Normal) else {
+            break;
+        };
+        taken += 1;
+        let family = match families.iter().position(|known| *known == degree) {
+            Some(at) => at,
+            None => {
+                families.push(degree);
+                families.len() - 1
+            }
+        };
+        let term = unparse(&labelled.to_term(), &free);
+        println!(
+            "{}. family {} (degree {}): {}",
+            taken,
+            family + 1,
+            families[family],
+            term
+        );
+    }
+    println!("{} steps, {} families", taken, families.len());
+}
+
+fn run_share(args: &[String]) {
+    const USAGE: &str = "--share [--min N] TERM";
+    let (found, rest) = options(args, &["--min"], &[]);
+    if rest.is_empty() {
+        usage(USAGE);
+    }
+    let min_size = option(&found, "--min", USAGE).unwrap_or(10);
+    let (term, free) = parse_resolved(rest);
+    let fuel = reduce::default_fuel(reduce::termination(&term));
+    match reduce::normalize(&term, fuel) {
+        Some((normal, _)) => {
+            let plain = PrettyPrinter::new().format(&normal, &free);
+            let shared = PrettyPrinter::new()
+                .with_sharing(min_size)
+                .format(&normal, &free);
+            println!("{}", shared);
+            println!(
+                "({} characters, {} unshared)",
+                shared.chars().count(),
+                plain.chars().count()
+            );
+        }
+        None => {
+            eprintln!("no normal form within {} steps", fuel);
+            std::process::exit(1);
+        }
+    }
+}
+
+const CHECKPOINT_USAGE: &str = "--checkpoint FILE [--every N] TERM | --resume FILE [--every N]";
+
+fn run_checkpoint(args: &[String]) {
+    let [file, args @ ..] = args else {
+        usage(CHECKPOINT_USAGE);
+    };
+    let (found, rest) = options(args, &["--every"], &[]);
+    if rest.is_empty() {
+        usage(CHECKPOINT_USAGE);
+    }
+    let (term, free) = parse_resolved(rest);
+    let state = Snapshot::new(term, free, Strategy::Normal);
+    checkpoint(
+        state,
+        Path::new(file),
+        option(&found, "--every", CHECKPOINT_USAGE).unwrap_or(10_000),
+    );
+}
+
+fn run_resume(args: &[String]) {
+    let [file, args @ ..] = args else {
+        usage(CHECKPOINT_USAGE);
+    };
+    let (found, rest) = options(args, &["--every"], &[]);
+    if !rest.is_empty() {
+        usage(CHECKPOINT_USAGE);
+    }
+    let state = Snapshot::load(Path::new(file)).unwrap_or_else(|msg| fail(msg));
+    checkpoint(
+        state,
+        Path::new(file),
+        option(&found, "--every", CHECKPOINT_USAGE).unwrap_or(10_000),
+    );
+}
+
+// interrupted, the run goes on from the last snapshot saved
+fn checkpoint(mut state: Snapshot, file: &Path, every: usize) {
+    loop {
+        let done = state.run(every.max(1));
+        if let Err(msg) = state.save(file) {
+            fail(msg);
+        }
+        if done {
+            break;
+        }
+        eprintln!("{} steps, saved to {}", state.steps, file.display());
+    }
+    let normal = PrettyPrinter::new().format(&state.term, &state.free);
+    println!("{}   ({} steps)", normal, state.steps);
+}
+
+fn run_solvable(args: &[String]) {
+    if args.is_empty() {
+        usage("--solvable TERM");
+    }
+    let (term, free) = parse_resolved(args);
+    let fuel = reduce::default_fuel(reduce::Termination::MayDiverge);
+    match reduce::is_solvable(&term, fuel) {
+        Solvability::HeadNormal(normal, steps) => println!(
+            "solvable, head normal form after {} steps: {}",
+            steps,
+            unparse(&normal, &free)
+        ),
+        Solvability::Unsolvable(steps) => {
+            println!(
+                "unsolvable, head reduction repeats itself after {} steps",
+                steps
+            )
+        }
+        Solvability::Unknown => println!("no head normal form within {} steps", fuel),
+    }
+}
+
+fn run_gen(args: &[String]) {
+    const USAGE: &str = "--gen [--boltzmann] [--seed N] [--count N] SIZE | --gen --all SIZE";
+    if let [flag, size] = args
+        && flag == "--all"
+    {
+        let size: usize = size.parse().unwrap_or_else(|_| usage(USAGE));
+        for term in generate::enumerate_closed_terms(size) {
+            println!("{}", unparse(&term, &[]));
+        }
+        return;
+    }
+    let (found, rest) = options(args, &["--seed", "--count"], &["--boltzmann"]);
+    let [size] = rest else { usage(USAGE) };
+    let size: usize = size.parse().unwrap_or_else(|_| usage(USAGE));
+    let boltzmann = found.iter().any(|(flag, _)| *flag == "--boltzmann");
+    let count = option(&found, "--count", USAGE).unwrap_or(1);
+    // without a seed every run differs
+    let seed = option(&found, "--seed", USAGE).unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+    });
+    let mut rng = Rng::new(seed);
+    for _ in 0..count {
+        let term = if boltzmann {
+            (size >= 2).then(|| generate::boltzmann(size, &mut rng))
+        } else {
+            generate::uniform(size, &mut rng)
+        };
+        match term {
+            Some(term) => println!("{}", unparse(&term, &[])),
+            None => fail(format!("no closed term of size {} can be drawn", size)),
+        }
+    }
+}
+
+fn run_synth(args: &[String]) {
+    const USAGE: &str = "--synth [--size N] [--count N] 'IN; IN => OUT'...";
+    let (found, rest) = options(args, &["--size", "--count"], &[]);
+    if rest.is_empty() {
+        usage(USAGE);
+    }
+    let size = option(&found, "--size", USAGE).unwrap_or(8);
+    let count = option(&found, "--count", USAGE).unwrap_or(1);
+    let mut free = Vec::new();
+    let examples: Vec<synth::Example> = rest
+        .iter()
+        .map(|source| synth::parse_example(source, &mut free))
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|msg| {
+            eprintln!("error: {}", msg);
+            std::process::exit(2);
+        });
+    let fuel = reduce::default_fuel(reduce::Termination::MayDiverge);
+    let mut printed = 0;
+    for term in synth::synthesize(&examples, size, fuel).take(count) {
+        println!("{}", unparse(&term, &free));
+        printed += 1;
+    }
+    if printed == 0 {
+        eprintln!("no term of at most {} nodes fits the examples", size);
+        std::process::exit(1);
+    }
+}
+
+fn run_codegen(args: &[String]) {
+    let Some(target) = args.first().and_then(|name| Target::from_name(name)) else {
+        usage("--codegen js|python|rust TERM");
+    };
+    let (term, free) = parse_resolved(&args[1..]);
+    match codegen::generate(&term, &free, target) {
+        Ok(code) => println!("{}", code),
+        Err(msg) => fail(msg),
+    }
+}
+
+fn run_rules(args: &[String]) {
+    let Some(path) = args.first() else {
+        usage("--rules FILE TERM");
+    };
+    let rules = Rules::parse(&read(path)).unwrap_or_else(|msg| {
+        eprintln!("{}: {}", path, msg);
+        std::process::exit(1);
+    });
+    let (term, mut free) = parse_resolved(&args[1..]);
+    let fuel = reduce::default_fuel(reduce::Termination::MayDiverge);
+    match rules.normalize(&term, &mut free, fuel, Strategy::Normal, false) {
+        Ok((normal, steps)) => {
+            println!(
+                "{}   ({} steps)",
+                PrettyPrinter::new().format(&normal, &free),
+                steps
+            )
+        }
+        Err(stopped) => {
+            eprintln!("{}", stopped);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_stats(args: &[String]) {
+    let (term, _) = parse_resolved(args);
+    let fuel = reduce::default_fuel(reduce::termination(&term));
+    let mut profiler = Profiler::new();
+    let normal = profiler.normalize(&term, fuel, Strategy::Normal);
+    let metrics = profiler.metrics();
+    println!(
+        "term:        {} nodes, {} bytes",
+        term.size(),
+        profile::heap_bytes(&term)
+    );
+    match normal {
+        Some((normal, _)) => {
+            println!(
+                "normal form: {} nodes, {} bytes",
+                normal.size(),
+                profile::heap_bytes(&normal)
+            )
+        }
+        None => println!("normal form: none within {} steps", fuel),
+    }
+    println!("steps:       {} ({} β)", metrics.steps, metrics.beta_steps);
+    println!("largest:     {} nodes", metrics.max_size);
+    println!("peak:        {} bytes", metrics.peak_bytes);
+}
+
+fn demo() {
     // S-combinator
     let input = r"<\t.{<\x.{\y.{\z.{<<x|z>|<y|z>>}}}|t>}|SOME_FUCKING_FREE>";
     let tokens = tokenizer::tokenize(input);
@@ -0,0 +1,161 @@
+// Mermaid flowcharts, for Markdown documents and issues that render them:
+// the syntax tree of a term and its reduction graphs (see graph)
+use crate::graph::{self, Graph};
+use crate::parser::Term;
+use crate::pretty_printer::PrettyPrinter;
+use crate::reduce;
+use crate::symbol::Symbol;
+use crate::types::Type;
+use crate::unparse::unparse_type;
+
+// quoted node text, with what Mermaid would read as markup as entities
+fn text(label: &str) -> String {
+    let mut out = String::from("\"");
+    for c in label.chars() {
+        match c {
+            '"' => out.push_str("#quot;"),
+            '<' => out.push_str("#lt;"),
+            '>' => out.push_str("#gt;"),
+            '#' => out.push_str("#35;"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn annotated(name: &str, annot: Option<&Type>) -> String {
+    match annot {
+        Some(ty) => format!("{} : {}", name, unparse_type(ty)),
+        None => name.to_string(),
+    }
+}
+
+// one node per subterm, children left to right: function before argument
+// and bound term before body
+pub fn ast(term: &Term, free: &[Symbol]) -> String {
+    let mut out = String::from("flowchart TD\n");
+    let mut env: Vec<Symbol> = Vec::new();
+    let mut next = 0;
+    // each subterm with its parent, the number of binders in scope above it
+    // and the one it is under itself
+    let mut work: Vec<(&Term, Option<usize>, usize, Option<Symbol>)> = vec![(term, None, 0, None)];
+    while let Some((node, parent, depth, binder)) = work.pop() {
+        env.truncate(depth);
+        env.extend(binder);
+        let depth = env.len();
+        let id = next;
+        next += 1;
+        let label = match node {
+            Term::Variable(index) if *index < 0 => free[(-index - 1) as usize].to_string(),
+            Term::Variable(index) => env[env.len() - *index as usize].to_string(),
+            Term::Constant(constant) => constant.to_string(),
+            Term::Lambda(param, annot, _) => {
+                format!("λ{}", annotated(param.as_str(), annot.as_ref()))
+            }
+            Term::Application(..) => "@".to_string(),
+            Term::TypeLambda(param, _) => format!("Λ{}", param),
+            Term::TypeApplication(_, ty) => format!("@ [{}]", unparse_type(ty)),
+            Term::Let(name, ..) => format!("let {}", name),
+        };
+        out.push_str(&format!("    n{}[{}]\n", id, text(&label)));
+        if let Some(parent) = parent {
+            out.push_str(&format!("    n{} --> n{}\n", parent, id));
+        }
+        match node {
+            Term::Variable(_) | Term::Constant(_) => {}
+            Term::Lambda(param, _, body) => work.push((body, Some(id), depth, Some(*param))),
+            Term::TypeLambda(_, body) | Term::TypeApplication(body, _) => {
+                work.push((body, Some(id), depth, None))
+            }
+            Term::Application(lhs, rhs) => {
+                work.push((rhs, Some(id), depth, None));
+                work.push((lhs, Some(id), depth, None));
+            }
+            Term::Let(name, bound, body) => {
+                work.push((body, Some(id), depth, Some(*name)));
+                work.push((bound, Some(id), depth, None));
+            }
+        }
+    }
+    out
+}
+
+// the terms of a reduction graph or sequence, edges labeled with the redex
+// contracted: the start term has rounded corners, normal forms are circled and
+// the frontier is drawn with a dashed border
+pub fn graph(graph: &Graph, free: &[Symbol]) -> String {
+    let mut out = String::from("flowchart TD\n");
+    for (index, term) in graph.nodes.iter().enumerate() {
+        let label = text(&graph::label(PrettyPrinter::new().format(term, free)));
+        let node = if index == 0 {
+            format!("({})", label)
+        } else if reduce::is_normal_form(term) {
+            format!("(({}))", label)
+        } else {
+            format!("[{}]", label)
+        };
+        out.push_str(&format!("    n{}{}\n", index, node));
+    }
+    for edge in &graph.edges {
+        out.push_str(&format!(
+            "    n{} -->|{}| n{}\n",
+            edge.from,
+            text(&graph.redex_label(edge, free)),
+            edge.to
+        ));
+    }
+    let frontier: Vec<String> = (0..graph.nodes.len())
+        .filter(|&node| graph.frontier[node])
+        .map(|node| format!("n{}", node))
+        .collect();
+    if !frontier.is_empty() {
+        out.push_str("    classDef frontier stroke-dasharray: 5 5\n");
+        out.push_str(&format!("    class {} frontier\n", frontier.join(",")));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::{app, i, k};
+    use crate::parser::{self, ParserConfig};
+
+    #[test]
+    fn the_syntax_tree_has_a_node_per_subterm() {
+        let (term, free) =
+            parser::parse_with_config(r"<\x.{<x|y>}|let a = z in a>", ParserConfig::default())
+                .unwrap();
+        assert_eq!(
+            ast(&term, &free),
+            "flowchart TD\n    \
+             n0[\"@\"]\n    \
+             n1[\"λx\"]\n    n0 --> n1\n    \
+             n2[\"@\"]\n    n1 --> n2\n    \
+             n3[\"x\"]\n    n2 --> n3\n    \
+             n4[\"y\"]\n    n2 --> n4\n    \
+             n5[\"let a\"]\n    n0 --> n5\n    \
+             n6[\"z\"]\n    n5 --> n6\n    \
+             n7[\"a\"]\n    n5 --> n7\n"
+        );
+    }
+
+    #[test]
+    fn markup_in_labels_is_escaped() {
+        assert_eq!(text(r#"<a|"b"> #1"#), "\"#lt;a|#quot;b#quot;#gt; #35;1\"");
+    }
+
+    #[test]
+    fn graphs_mark_the_start_normal_forms_and_frontier() {
+        let term = app(app(k(), i()), app(i(), i()));
+        let whole = graph(&graph::explore(&term, 5, 10), &[]);
+        let nodes: Vec<&str> = whole.lines().filter(|line| !line.contains("-->")).collect();
+        assert!(nodes[1].starts_with("    n0(\""));
+        assert_eq!(nodes.iter().filter(|line| line.contains("((\"")).count(), 1);
+        assert_eq!(whole.matches("-->|").count(), 6);
+        assert!(!whole.contains("classDef"));
+        let cut = graph(&graph::explore(&term, 1, 10), &[]);
+        assert!(cut.ends_with("    class n1,n2 frontier\n"), "{}", cut);
+    }
+}
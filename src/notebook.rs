@@ -0,0 +1,338 @@
+// rich display in Jupyter notebooks through the evcxr kernel, which shows a
+// value whose type has an evcxr_display method by what that prints between
+// its content markers, so no dependency is needed:
+//
+//     :dep lambda_rs = { package = "LambdaRS", path = "...", features = ["notebook"] }
+//     use lambda_rs::notebook::lambda;
+//     lambda("<<add|c2>|c3>")
+//
+// lambda stands in for a %%lambda cell: it takes a term in this syntax or in
+// LaTeX (see latex) and shows it with its type and normal form. to_latex and
+// to_html render any term, Math shows LaTeX typeset by the notebook
+use std::fmt::Write;
+
+use crate::api;
+use crate::highlight::{self, Class};
+use crate::latex;
+use crate::parser::{Constant, ParserConfig, Term};
+use crate::prelude;
+use crate::reduce::{self, Strategy};
+use crate::symbol::Symbol;
+use crate::types::{self, Type};
+use crate::unparse::unparse;
+
+fn display(mime: &str, content: &str) {
+    println!(
+        "EVCXR_BEGIN_CONTENT {}\n{}\nEVCXR_END_CONTENT",
+        mime, content
+    );
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// single letters as they are, x_12 with a subscript, longer names in italic
+// as one word. primes stay outside
+fn name(name: &str) -> String {
+    let primes = &name[name.trim_end_matches('\'').len()..];
+    let name = name.trim_end_matches('\'');
+    let (base, subscript) = match name.split_once('_') {
+        Some((base, digits))
+            if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            (base, Some(digits))
+        }
+        _ => (name, None),
+    };
+    let mut out = if base.chars().count() == 1 {
+        base.to_string()
+    } else {
+        format!("\\mathit{{{}}}", base.replace('_', "\\_"))
+    };
+    if let Some(digits) = subscript {
+        out.push_str(&format!("_{{{}}}", digits));
+    }
+    out.push_str(primes);
+    out
+}
+
+const GREEK: [&str; 12] = [
+    "alpha",
+    "beta",
+    "gamma",
+    "delta",
+    "varepsilon",
+    "zeta",
+    "eta",
+    "theta",
+    "iota",
+    "kappa",
+    "mu",
+    "nu",
+];
+
+fn type_latex(ty: &Type) -> String {
+    match ty {
+        Type::Base(base) => name(base.as_str()),
+        Type::Var(var) => match var / GREEK.len() {
+            0 => format!("\\{}", GREEK[var % GREEK.len()]),
+            n => format!("\\{}_{{{}}}", GREEK[var % GREEK.len()], n),
+        },
+        Type::Arrow(lhs, rhs) if matches!(**lhs, Type::Arrow(..) | Type::Forall(..)) => {
+            format!("({}) \\to {}", type_latex(lhs), type_latex(rhs))
+        }
+        Type::Arrow(lhs, rhs) => format!("{} \\to {}", type_latex(lhs), type_latex(rhs)),
+        Type::Forall(param, body) => {
+            format!("\\forall {}.\\, {}", name(param.as_str()), type_latex(body))
+        }
+    }
+}
+
+// the term in the notation of papers, `\lambda x.\, x\,y`, which latex reads
+// back: binder annotations are left out, as latex has no types. System F terms
+// keep their \Lambda and type arguments and do not read back. a binder
+// shadowing another name is primed so every name means one thing
+pub fn to_latex(term: &Term, free: &[Symbol]) -> String {
+    let mut out = String::new();
+    write_latex(&mut out, term, free, &mut Vec::new());
+    out
+}
+
+// whether the term has to be parenthesized as a function or as an argument
+fn extends_right(term: &Term) -> bool {
+    matches!(
+        term,
+        Term::Lambda(..) | Term::TypeLambda(..) | Term::Let(..)
+    )
+}
+
+fn fresh(param: &str, free: &[Symbol], env: &[String]) -> String {
+    let mut fresh = param.to_string();
+    while free.iter().any(|name| *name == fresh.as_str()) || env.contains(&fresh) {
+        fresh.push('\'');
+    }
+    fresh
+}
+
+fn write_latex(out: &mut String, term: &Term, free: &[Symbol], env: &mut Vec<String>) {
+    match term {
+        Term::Variable(index) if *index < 0 => {
+            out.push_str(&name(free[(-index - 1) as usize].as_str()))
+        }
+        Term::Variable(index) => out.push_str(&name(&env[env.len() - *index as usize])),
+        Term::Constant(Constant::Int(value)) => out.push_str(&value.to_string()),
+        Term::Constant(constant) => out.push_str(&format!("\\mathsf{{{}}}", constant)),
+        Term::Lambda(param, _, body) => {
+            let param = fresh(param.as_str(), free, env);
+            out.push_str(&format!("\\lambda {}.\\, ", name(&param)));
+            env.push(param);
+            write_latex(out, body, free, env);
+            env.pop();
+        }
+        Term::TypeLambda(param, body) => {
+            out.push_str(&format!("\\Lambda {}.\\, ", name(param.as_str())));
+            write_latex(out, body, free, env);
+        }
+        Term::Application(lhs, rhs) => {
+            match &**lhs {
+                lhs if extends_right(lhs) => {
+                    out.push('(');
+                    write_latex(out, lhs, free, env);
+                    out.push(')');
+                }
+                lhs => write_latex(out, lhs, free, env),
+            }
+            out.push_str("\\,");
+            if extends_right(rhs)
+                || matches!(**rhs, Term::Application(..) | Term::TypeApplication(..))
+            {
+                out.push('(');
+                write_latex(out, rhs, free, env);
+                out.push(')');
+            } else {
+                write_latex(out, rhs, free, env);
+            }
+        }
+        Term::TypeApplication(fun, ty) => {
+            if extends_right(fun) {
+                out.push('(');
+                write_latex(out, fun, free, env);
+                out.push(')');
+            } else {
+                write_latex(out, fun, free, env);
+            }
+            out.push_str(&format!("\\,[{}]", type_latex(ty)));
+        }
+        Term::Let(binder, bound, body) => {
+            let binder = fresh(binder.as_str(), free, env);
+            out.push_str(&format!("\\mathsf{{let}}\\ {} = ", name(&binder)));
+            write_latex(out, bound, free, env);
+            out.push_str("\\ \\mathsf{in}\\ ");
+            env.push(binder);
+            write_latex(out, body, free, env);
+            env.pop();
+        }
+    }
+}
+
+// colours of binders, one after the other, and of free names
+const PALETTE: [&str; 6] = [
+    "#1f77b4", "#2ca02c", "#9467bd", "#8c564b", "#e377c2", "#17becf",
+];
+const FREE: &str = "#d62728";
+
+fn style(class: Class) -> Option<String> {
+    match class {
+        Class::Binder(id) => Some(format!(
+            "font-weight:bold;color:{}",
+            PALETTE[id % PALETTE.len()]
+        )),
+        Class::Bound(id) => Some(format!("color:{}", PALETTE[id % PALETTE.len()])),
+        Class::Free => Some(format!("color:{}", FREE)),
+        Class::Keyword => Some("font-weight:bold".to_string()),
+        Class::Number => Some("color:#098658".to_string()),
+        Class::Name | Class::Punctuation | Class::Comment => None,
+    }
+}
+
+// the term in source syntax as inline code, highlighted as highlight::classify
+// tells: a bound name takes the colour of its binder
+pub fn to_html(term: &Term, free: &[Symbol]) -> String {
+    let source = unparse(term, free);
+    let config = ParserConfig {
+        system_f: true,
+        ..Default::default()
+    };
+    let mut out = String::from("<code>");
+    let mut at = 0;
+    for (span, class) in highlight::classify(&source, config) {
+        out.push_str(&escape(&source[at..span.start]));
+        let text = escape(&source[span.start..span.end]);
+        match style(class) {
+            Some(style) => {
+                let _ = write!(out, "<span style=\"{}\">{}</span>", style, text);
+            }
+            None => out.push_str(&text),
+        }
+        at = span.end;
+    }
+    out.push_str(&escape(&source[at..]));
+    out.push_str("</code>");
+    out
+}
+
+// LaTeX the notebook typesets as a display formula
+pub struct Math(pub String);
+
+impl Math {
+    pub fn evcxr_display(&self) {
+        display("text/latex", &format!("$$ {} $$", self.0));
+    }
+}
+
+pub struct Cell {
+    source: String,
+    parsed: Result<(Term, Vec<Symbol>), String>,
+}
+
+pub fn lambda(source: &str) -> Cell {
+    let parsed = if latex::is_latex(source) {
+        latex::parse(source).map(|(term, free)| (prelude::resolve(&term, &free), free))
+    } else {
+        api::parse_source(source)
+    };
+    Cell {
+        source: source.to_string(),
+        parsed,
+    }
+}
+
+impl Cell {
+    pub fn term(&self) -> Option<&Term> {
+        self.parsed.as_ref().ok().map(|(term, _)| term)
+    }
+
+    // in as many steps as the REPL gives it
+    pub fn normal_form(&self) -> Option<(Term, usize)> {
+        let term = self.term()?;
+        let fuel = reduce::default_fuel(reduce::termination(term));
+        reduce::normalize_with(term, fuel, Strategy::Normal)
+    }
+
+    // the term reducing to its normal form, as a formula
+    pub fn latex(&self) -> Math {
+        match &self.parsed {
+            Ok((term, free)) => match self.normal_form() {
+                Some((normal, _)) => Math(format!(
+                    "{} \\twoheadrightarrow_\\beta {}",
+                    to_latex(term, free),
+                    to_latex(&normal, free)
+                )),
+                None => Math(to_latex(term, free)),
+            },
+            Err(_) => Math("\\text{parse error}".to_string()),
+        }
+    }
+
+    // a table of the term, its type and normal form, or the parse error
+    pub fn to_html(&self) -> String {
+        let (term, free) = match &self.parsed {
+            Ok(parsed) => parsed,
+            Err(error) => {
+                return format!(
+                    "<pre>{}</pre><p style=\"color:#b00\">error: {}</p>",
+                    escape(&self.source),
+                    escape(error)
+                );
+            }
+        };
+        let mut rows = vec![("term", to_html(term, free))];
+        rows.push((
+            "type",
+            match types::type_of(term) {
+                Ok(ty) => format!("<code>{}</code>", escape(&ty.to_string())),
+                Err(err) => format!("not typable: {}", escape(&err.kind.to_string())),
+            },
+        ));
+        rows.push((
+            "normal form",
+            match self.normal_form() {
+                Some((normal, steps)) => format!("{} in {} steps", to_html(&normal, free), steps),
+                None => "none within the step limit".to_string(),
+            },
+        ));
+        let mut out = String::from("<table>");
+        for (header, value) in rows {
+            let _ = write!(
+                out,
+                "<tr><th style=\"text-align:left\">{}</th><td style=\"text-align:left\">{}</td></tr>",
+                header, value
+            );
+        }
+        out.push_str("</table>");
+        out
+    }
+
+    pub fn evcxr_display(&self) {
+        display("text/html", &self.to_html());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::{app, lam, var};
+
+    #[test]
+    fn html_is_highlighted_source() {
+        let term = lam("x", app(var(1), var(-1)));
+        assert_eq!(
+            to_html(&term, &[Symbol::intern("y")]),
+            "<code>\\<span style=\"font-weight:bold;color:#1f77b4\">x</span>.{&lt;\
+             <span style=\"color:#1f77b4\">x</span>|<span style=\"color:#d62728\">y</span>&gt;}</code>"
+        );
+    }
+}
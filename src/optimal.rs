@@ -0,0 +1,684 @@
+// optimal reduction (Lamping, in the formulation of Gonthier, Abadi and
+// Lévy): the term becomes a sharing graph, an interaction net of λ and
+// application nodes, fans sharing subgraphs, croissants and brackets keeping
+// track of the levels the fans apply at, and erasers for unused arguments.
+// every node has a level, and rewriting only ever happens between two nodes
+// connected by their principal ports:
+//
+//  - a λ and an application at the same level: β-interaction
+//  - two fans, croissants or brackets of the same level: they annihilate
+//  - any other pair: the node of the higher level is copied past the other
+//    one, its level decremented by a croissant and incremented by a bracket
+//
+// β-interactions contract whole families of redexes in the sense of Lévy at
+// once, never copying a redex, so their number is the least number of
+// β-steps of any reduction to normal form. interactions are looked for along
+// what the read back visits, garbage behind erasers is left alone
+//
+// only the pure calculus is handled: lets become redexes, types are erased,
+// constants are refused. the read back follows the contexts of the
+// semantics of Gonthier, Abadi and Lévy through the control nodes
+use std::fmt;
+
+use crate::parser::Term;
+use crate::symbol::Symbol;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Root,
+    Lambda(Symbol), // principal, body, binder
+    App,            // principal (the function), result, argument
+    Fan,            // principal, two sharers
+    Croissant,      // principal towards the binder, the occurrence
+    Bracket,        // principal out of the argument box, inside it
+    Eraser,
+    Free(i32), // a free variable, as in the term
+}
+
+impl Kind {
+    fn arity(self) -> usize {
+        match self {
+            Kind::Lambda(_) | Kind::App | Kind::Fan => 3,
+            Kind::Croissant | Kind::Bracket => 2,
+            Kind::Root | Kind::Eraser | Kind::Free(_) => 1,
+        }
+    }
+
+    fn is_control(self) -> bool {
+        matches!(self, Kind::Fan | Kind::Croissant | Kind::Bracket)
+    }
+
+    // what moving past the node does to the level of another one
+    fn offset(self) -> i32 {
+        match self {
+            Kind::Croissant => -1,
+            Kind::Bracket => 1,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Port {
+    node: usize,
+    slot: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    kind: Kind,
+    level: i32,
+    ports: [Port; 3], // what each slot is wired to, dead nodes stay behind
+}
+
+// why reduce gave up
+#[derive(Clone, Debug, PartialEq)]
+pub enum Failure {
+    Unsupported(&'static str),
+    Fuel(usize),     // the interactions taken
+    ReadBack(usize), // the graph is normal but has no term reading, a bug
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::Unsupported(what) => write!(f, "{} are not supported", what),
+            Failure::Fuel(interactions) => {
+                write!(f, "no normal form within {} interactions", interactions)
+            }
+            Failure::ReadBack(_) => write!(f, "the normal graph does not read back as a term"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Reduction {
+    pub normal: Term,
+    pub families: usize,     // β-interactions
+    pub interactions: usize, // all rewrites, β included
+}
+
+// the net of a term, node 0 is the root
+pub struct Net {
+    nodes: Vec<Node>,
+    pub families: usize,
+    pub interactions: usize,
+}
+
+const ROOT: Port = Port { node: 0, slot: 0 };
+
+fn port(node: usize, slot: usize) -> Port {
+    Port { node, slot }
+}
+
+impl Net {
+    pub fn new(term: &Term) -> Result<Net, Failure> {
+        let mut net = Net {
+            nodes: Vec::new(),
+            families: 0,
+            interactions: 0,
+        };
+        net.node(Kind::Root, 0);
+        let mut binders = Vec::new();
+        let mut free = Vec::new();
+        let root = net.build(term, 0, &mut binders, &mut free)?;
+        net.link(ROOT, root);
+        for (index, wire) in free {
+            let node = net.node(Kind::Free(index), 0);
+            net.link(port(node, 0), wire);
+        }
+        Ok(net)
+    }
+
+    fn node(&mut self, kind: Kind, level: i32) -> usize {
+        self.nodes.push(Node {
+            kind,
+            level,
+            ports: [ROOT; 3],
+        });
+        self.nodes.len() - 1
+    }
+
+    fn link(&mut self, a: Port, b: Port) {
+        self.nodes[a.node].ports[a.slot] = b;
+        self.nodes[b.node].ports[b.slot] = a;
+    }
+
+    fn target(&self, port: Port) -> Port {
+        self.nodes[port.node].ports[port.slot]
+    }
+
+    // the net of `term` at `level`, returning the port its root is to be
+    // wired to. wires from variable occurrences are collected in `binders`,
+    // one list per enclosing λ, and in `free` for free variables. the term is
+    // walked with a work stack, so its depth is only bounded by memory
+    fn build(
+        &mut self,
+        term: &Term,
+        level: i32,
+        binders: &mut Vec<Vec<Port>>,
+        free: &mut Vec<(i32, Port)>,
+    ) -> Result<Port, Failure> {
+        let mut work = vec![Build::Visit(term, level)];
+        // the ports of the finished subterms
+        let mut done: Vec<Port> = Vec::new();
+        while let Some(frame) = work.pop() {
+            match frame {
+                Build::Visit(term, level) => match term {
+                    Term::Variable(index) => {
+                        let croissant = self.node(Kind::Croissant, level);
+                        let wire = port(croissant, 0);
+                        if *index > 0 {
+                            let at = binders.len() - *index as usize;
+                            binders[at].push(wire);
+                        } else {
+                            free.push((*index, wire));
+                        }
+                        done.push(port(croissant, 1));
+                    }
+                    Term::Lambda(param, _, body) => {
+                        let lambda = self.node(Kind::Lambda(*param), level);
+                        binders.push(Vec::new());
+                        work.push(Build::Lambda(lambda, level));
+                        work.push(Build::Visit(body, level));
+                    }
+                    Term::Application(fun, arg) => {
+                        let app = self.node(Kind::App, level);
+                        work.push(Build::Arg(app, arg, level));
+                        work.push(Build::Visit(fun, level));
+                    }
+                    // the redex <\name.{body}|bound>
+                    Term::Let(name, bound, body) => {
+                        let app = self.node(Kind::App, level);
+                        let lambda = self.node(Kind::Lambda(*name), level);
+                        binders.push(Vec::new());
+                        work.push(Build::Arg(app, bound, level));
+                        work.push(Build::Lambda(lambda, level));
+                        work.push(Build::Visit(body, level));
+                    }
+                    Term::TypeLambda(_, body) => work.push(Build::Visit(body, level)),
+                    Term::TypeApplication(fun, _) => work.push(Build::Visit(fun, level)),
+                    Term::Constant(_) => return Err(Failure::Unsupported("constants")),
+                },
+                Build::Lambda(lambda, level) => {
+                    let body = done.pop().expect("body built");
+                    self.link(port(lambda, 1), body);
+                    let occurrences = binders.pop().unwrap();
+                    let shared = self.share(&occurrences, level);
+                    self.link(port(lambda, 2), shared);
+                    done.push(port(lambda, 0));
+                }
+                Build::Arg(app, arg, level) => {
+                    let fun = done.pop().expect("function built");
+                    self.link(port(app, 0), fun);
+                    // the argument is a box one level up, the wires of its free
+                    // variables leave it through brackets
+                    work.push(Build::App {
+                        app,
+                        level,
+                        before: binders.iter().map(Vec::len).collect(),
+                        free_before: free.len(),
+                    });
+                    work.push(Build::Visit(arg, level + 1));
+                }
+                Build::App {
+                    app,
+                    level,
+                    before,
+                    free_before,
+                } => {
+                    let arg = done.pop().expect("argument built");
+                    self.link(port(app, 2), arg);
+                    for (occurrences, from) in binders.iter_mut().zip(before) {
+                        for wire in &mut occurrences[from..] {
+                            *wire = self.bracket(*wire, level);
+                        }
+                    }
+                    for (_, wire) in &mut free[free_before..] {
+                        *wire = self.bracket(*wire, level);
+                    }
+                    done.push(port(app, 1));
+                }
+            }
+        }
+        Ok(done.pop().expect("root built"))
+    }
+
+    fn bracket(&mut self, wire: Port, level: i32) -> Port {
+        let bracket = self.node(Kind::Bracket, level);
+        self.link(port(bracket, 1), wire);
+        port(bracket, 0)
+    }
+
+    // one wire for the occurrences of a variable: a chain of fans, an eraser
+    // for none
+    fn share(&mut self, occurrences: &[Port], level: i32) -> Port {
+        let Some((last, rest)) = occurrences.split_last() else {
+            return port(self.node(Kind::Eraser, level), 0);
+        };
+        let mut shared = *last;
+        for wire in rest.iter().rev() {
+            let fan = self.node(Kind::Fan, level);
+            self.link(port(fan, 1), *wire);
+            self.link(port(fan, 2), shared);
+            shared = port(fan, 0);
+        }
+        shared
+    }
+
+    fn active(&self, a: usize, b: usize) -> bool {
+        use Kind::*;
+        match (self.nodes[a].kind, self.nodes[b].kind) {
+            (Root, _) | (_, Root) => false,
+            (Lambda(_), App) | (App, Lambda(_)) => true,
+            (Eraser, _) | (_, Eraser) => true,
+            (x, y) => x.is_control() || y.is_control(),
+        }
+    }
+
+    fn rewrite(&mut self, a: usize, b: usize) {
+        let (x, y) = (self.nodes[a].clone(), self.nodes[b].clone());
+        self.interactions += 1;
+        if matches!(
+            (x.kind, y.kind),
+            (Kind::Lambda(_), Kind::App) | (Kind::App, Kind::Lambda(_))
+        ) {
+            self.families += 1;
+            self.annihilate(a, b);
+        } else if x.kind == y.kind && x.level == y.level {
+            self.annihilate(a, b);
+        } else {
+            // the one copied past the other is the one of the higher level,
+            // an eraser or a free variable copies itself past anything
+            let a_first = match (x.kind, y.kind) {
+                (Kind::Eraser | Kind::Free(_), _) => true,
+                (_, Kind::Eraser | Kind::Free(_)) => false,
+                _ if x.level != y.level => x.level < y.level,
+                _ => x.kind.is_control(),
+            };
+            match a_first {
+                true => self.commute(a, b),
+                false => self.commute(b, a),
+            }
+        }
+    }
+
+    // wire the auxiliary ports of `a` to those of `b`, slot by slot
+    fn annihilate(&mut self, a: usize, b: usize) {
+        let arity = self.nodes[a].kind.arity();
+        for slot in 1..arity {
+            let mut x = self.target(port(a, slot));
+            let mut y = self.target(port(b, slot));
+            // a wire between the two nodes continues on the other side
+            if x.node == b {
+                x = self.target(port(a, x.slot));
+            }
+            if y.node == a {
+                y = self.target(port(b, y.slot));
+            }
+            if x.node != a && x.node != b && y.node != a && y.node != b {
+                self.link(x, y);
+            }
+        }
+    }
+
+    // copies of `b` for each auxiliary port of `a` and the other way round,
+    // `b` raised or lowered by moving past `a`
+    fn commute(&mut self, a: usize, b: usize) {
+        let (kind_a, level_a) = (self.nodes[a].kind, self.nodes[a].level);
+        let (kind_b, level_b) = (self.nodes[b].kind, self.nodes[b].level + kind_a.offset());
+        let (m, n) = (kind_a.arity() - 1, kind_b.arity() - 1);
+        let copies_b: Vec<usize> = (0..m).map(|_| self.node(kind_b, level_b)).collect();
+        let copies_a: Vec<usize> = (0..n).map(|_| self.node(kind_a, level_a)).collect();
+        // where the copies go, a wire between `a` and `b` joining two copies
+        let outside = |net: &Net, aux: Port| {
+            let target = net.target(aux);
+            if target.node == a {
+                port(copies_b[target.slot - 1], 0)
+            } else if target.node == b {
+                port(copies_a[target.slot - 1], 0)
+            } else {
+                target
+            }
+        };
+        let mut wires = Vec::new();
+        for (k, copy) in copies_b.iter().enumerate() {
+            wires.push((port(*copy, 0), outside(self, port(a, k + 1))));
+        }
+        for (l, copy) in copies_a.iter().enumerate() {
+            wires.push((port(*copy, 0), outside(self, port(b, l + 1))));
+        }
+        for (from, to) in wires {
+            self.link(from, to);
+        }
+        for (k, copy_b) in copies_b.iter().enumerate() {
+            for (l, copy_a) in copies_a.iter().enumerate() {
+                self.link(port(*copy_b, l + 1), port(*copy_a, k + 1));
+            }
+        }
+    }
+
+    // rewrite until what the read back visits is normal, at most `fuel`
+    // interactions. a pass walks down from the root, following each head to
+    // where it is stuck before turning to the arguments, until a pass finds
+    // nothing to do
+    pub fn normalize(&mut self, fuel: usize) -> Result<(), Failure> {
+        loop {
+            let before = self.interactions;
+            self.pass(fuel)?;
+            if self.interactions == before {
+                return Ok(());
+            }
+        }
+    }
+
+    fn pass(&mut self, fuel: usize) -> Result<(), Failure> {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut todo = vec![ROOT];
+        while let Some(start) = todo.pop() {
+            // ports looked out of on the way up, with the arguments to walk
+            // once the head is stuck
+            let mut back: Vec<(Port, Option<Port>)> = Vec::new();
+            let mut from = start;
+            loop {
+                let there = self.target(from);
+                let node = there.node;
+                if there.slot == 0 && from.slot == 0 && self.active(from.node, node) {
+                    if self.interactions >= fuel {
+                        return Err(Failure::Fuel(self.interactions));
+                    }
+                    self.rewrite(from.node, node);
+                    visited.resize(self.nodes.len(), false);
+                    match back.pop() {
+                        Some((port, _)) => from = port,
+                        None => unreachable!("the root is not active"),
+                    }
+                    continue;
+                }
+                if visited[node] {
+                    break;
+                }
+                visited[node] = true;
+                match (self.nodes[node].kind, there.slot) {
+                    // down: into the body, or through a control node facing up,
+                    // both sharers of a fan
+                    (Kind::Lambda(_), 0) | (Kind::Croissant | Kind::Bracket, 0) => {
+                        from = port(node, 1);
+                    }
+                    (Kind::Fan, 0) => {
+                        todo.push(port(node, 2));
+                        from = port(node, 1);
+                    }
+                    // up: to the head of an application, through a control
+                    // node towards a binder
+                    (Kind::App, 1) => {
+                        back.push((from, Some(port(node, 2))));
+                        from = port(node, 0);
+                    }
+                    (Kind::Fan | Kind::Croissant | Kind::Bracket, _) => {
+                        back.push((from, None));
+                        from = port(node, 0);
+                    }
+                    // a variable, a free variable, or nothing to read
+                    _ => break,
+                }
+            }
+            todo.extend(back.into_iter().filter_map(|(_, arg)| arg));
+        }
+        Ok(())
+    }
+
+    // the term the normal graph stands for
+    pub fn read_back(&self) -> Option<Term> {
+        let mut binders = Vec::new();
+        // sharing can make the term much larger than the graph
+        let mut budget = 10 * self.nodes.len() + 1_000_000;
+        self.read(ROOT, Vec::new(), &mut binders, &mut budget)
+    }
+
+    // follows wires from `from` through the control nodes, keeping the
+    // subterms it is inside of on a stack of its own
+    fn read(
+        &self,
+        mut from: Port,
+        mut context: Vec<Level>,
+        binders: &mut Vec<(usize, Vec<Level>)>,
+        budget: &mut usize,
+    ) -> Option<Term> {
+        let mut stack: Vec<Read> = Vec::new();
+        loop {
+            *budget = budget.checked_sub(1)?;
+            let there = self.target(from);
+            let node = &self.nodes[there.node];
+            let level = node.level as usize;
+            let mut term = match (node.kind, there.slot) {
+                (Kind::Lambda(param), 0) => {
+                    binders.push((there.node, below(&context, level)));
+                    stack.push(Read::Lambda(param));
+                    from = port(there.node, 1);
+                    continue;
+                }
+                // one λ of the graph can be several of the term, told
+                // apart by the levels below its own
+                (Kind::Lambda(_), 2) => {
+                    let here = below(&context, level);
+                    let at = binders
+                        .iter()
+                        .rposition(|(binder, context)| *binder == there.node && *context == here)
+                        .or_else(|| {
+                            binders
+                                .iter()
+                                .rposition(|(binder, _)| *binder == there.node)
+                        })?;
+                    Term::Variable((binders.len() - at) as i32)
+                }
+                (Kind::Free(index), 0) => Term::Variable(index),
+                (Kind::App, 1) => {
+                    stack.push(Read::Arg(port(there.node, 2), context.clone()));
+                    from = port(there.node, 0);
+                    continue;
+                }
+                (Kind::Fan, 0) => {
+                    let Level::Choice(slot, rest) = take(&mut context, level) else {
+                        return None;
+                    };
+                    put(&mut context, level, *rest);
+                    from = port(there.node, slot);
+                    continue;
+                }
+                (Kind::Fan, slot) => {
+                    let rest = take(&mut context, level);
+                    put(&mut context, level, Level::Choice(slot, Box::new(rest)));
+                    from = port(there.node, 0);
+                    continue;
+                }
+                (Kind::Croissant, 0) => {
+                    if level < context.len() {
+                        context.remove(level);
+                    }
+                    from = port(there.node, 1);
+                    continue;
+                }
+                (Kind::Croissant, _) => {
+                    pad(&mut context, level);
+                    context.insert(level, Level::Mark);
+                    from = port(there.node, 0);
+                    continue;
+                }
+                (Kind::Bracket, 0) => {
+                    let (inner, outer) = match take(&mut context, level) {
+                        Level::Pair(inner, outer) => (*inner, *outer),
+                        Level::Any => (Level::Any, Level::Any),
+                        _ => return None,
+                    };
+                    put(&mut context, level, outer);
+                    pad(&mut context, level + 1);
+                    context.insert(level + 1, inner);
+                    from = port(there.node, 1);
+                    continue;
+                }
+                (Kind::Bracket, _) => {
+                    let outer = take(&mut context, level);
+                    let inner = match level + 1 < context.len() {
+                        true => context.remove(level + 1),
+                        false => Level::Any,
+                    };
+                    put(
+                        &mut context,
+                        level,
+                        Level::Pair(Box::new(inner), Box::new(outer)),
+                    );
+                    from = port(there.node, 0);
+                    continue;
+                }
+                _ => return None,
+            };
+            // hand the finished term to the one waiting for it
+            loop {
+                match stack.pop() {
+                    None => return Some(term),
+                    Some(Read::Lambda(param)) => {
+                        binders.pop();
+                        term = Term::Lambda(param, None, Box::new(term));
+                    }
+                    Some(Read::Arg(arg, arg_context)) => {
+                        stack.push(Read::App(term));
+                        from = arg;
+                        context = arg_context;
+                        break;
+                    }
+                    Some(Read::App(fun)) => {
+                        term = Term::Application(Box::new(fun), Box::new(term));
+                    }
+                }
+            }
+        }
+    }
+}
+
+// what Net::build waits for: a node to build, or the wiring of a λ or an
+// application once the subterms it is waiting on are on top of the ports built
+enum Build<'a> {
+    Visit(&'a Term, i32),
+    Lambda(usize, i32),        // its body is built
+    Arg(usize, &'a Term, i32), // the function is built, the argument is next
+    // the argument is built, `before` holds how many occurrences each binder had
+    // and `free_before` the free ones before it
+    App {
+        app: usize,
+        level: i32,
+        before: Vec<usize>,
+        free_before: usize,
+    },
+}
+
+// what Net::read waits for: the body of a λ, the argument of an application
+// to read from its port in its context, or the application of a function read
+enum Read {
+    Lambda(Symbol),
+    Arg(Port, Vec<Level>),
+    App(Term),
+}
+
+// one level of a context: the choices of the fans passed at that level,
+// bracket pairs and croissant marks. levels past the end are Any
+#[derive(Clone, Debug, PartialEq)]
+enum Level {
+    Any,
+    Mark,
+    Choice(usize, Box<Level>),
+    Pair(Box<Level>, Box<Level>),
+}
+
+// the levels under `level`, without the trailing ones that say nothing
+fn below(context: &[Level], level: usize) -> Vec<Level> {
+    let mut levels = context[..level.min(context.len())].to_vec();
+    while levels.last() == Some(&Level::Any) {
+        levels.pop();
+    }
+    levels
+}
+
+fn pad(context: &mut Vec<Level>, len: usize) {
+    while context.len() < len {
+        context.push(Level::Any);
+    }
+}
+
+fn take(context: &mut [Level], level: usize) -> Level {
+    match context.get_mut(level) {
+        Some(at) => std::mem::replace(at, Level::Any),
+        None => Level::Any,
+    }
+}
+
+fn put(context: &mut Vec<Level>, level: usize, value: Level) {
+    pad(context, level + 1);
+    context[level] = value;
+}
+
+// the normal form of `term` by optimal reduction, with its counts
+pub fn reduce(term: &Term, fuel: usize) -> Result<Reduction, Failure> {
+    let mut net = Net::new(term)?;
+    net.normalize(fuel)?;
+    let normal = net.read_back().ok_or(Failure::ReadBack(net.interactions))?;
+    Ok(Reduction {
+        normal,
+        families: net.families,
+        interactions: net.interactions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::{app, i, lam, omega, var};
+    use crate::generate;
+    use crate::reduce as normal_order;
+
+    #[test]
+    fn normal_forms_agree_with_normal_order() {
+        for term in generate::enumerate_closed_terms(8) {
+            let Some((normal, steps)) = normal_order::normalize(&term, 100) else {
+                continue;
+            };
+            let reduction = reduce(&term, 10_000).unwrap();
+            assert!(normal_order::alpha_eq(&reduction.normal, &normal));
+            assert!(reduction.families <= steps);
+        }
+    }
+
+    #[test]
+    fn a_shared_redex_is_one_family() {
+        // normal order contracts <I|c> once for each x
+        let term = app(lam("x", app(var(1), var(1))), app(i(), var(-1)));
+        let reduction = reduce(&term, 1000).unwrap();
+        assert_eq!(reduction.normal, app(var(-1), var(-1)));
+        assert_eq!(reduction.families, 2);
+        assert_eq!(normal_order::normalize(&term, 1000).unwrap().1, 3);
+    }
+
+    #[test]
+    fn failures_are_reported() {
+        assert_eq!(
+            reduce(&app(omega(), omega()), 100).unwrap_err(),
+            Failure::Fuel(100)
+        );
+        assert!(matches!(
+            reduce(&Term::Constant(crate::parser::Constant::Int(1)), 100),
+            Err(Failure::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn deep_terms_build_and_read_back_without_recursion() {
+        let n = 100_000;
+        let lambdas = (0..n).fold(var(1), |body, _| lam("x", body));
+        let reduction = reduce(&lambdas, 10).unwrap();
+        assert_eq!(reduction.normal, lambdas);
+        let applications = (0..n).fold(var(-1), |fun, _| app(fun, app(i(), var(-2))));
+        let reduction = reduce(&applications, 10 * n).unwrap();
+        assert_eq!(reduction.families, n);
+        assert_eq!(reduction.normal.depth(), n + 1);
+    }
+}
@@ -1,91 +1,684 @@
+use std::fmt;
 use std::iter::Peekable;
 
-use crate::tokenizer::Token;
+use crate::combinators;
+use crate::diagnostic::Span;
+use crate::reduce::{Strategy, shift};
+use crate::symbol::Symbol;
+use crate::tokenizer::{self, Token};
+use crate::traverse;
+use crate::types::Type;
 
-#[derive(PartialEq)]
+#[derive(Debug)]
 pub enum Term {
-    Variable(i32), // negative for free variable
-    Lambda(String, Box<Term>),
+    Variable(i32),                           // negative for free variable
+    Lambda(Symbol, Option<Type>, Box<Term>), // optional binder annotation
     Application(Box<Term>, Box<Term>),
+    TypeLambda(Symbol, Box<Term>),     // System F only
+    TypeApplication(Box<Term>, Type),  // System F only
+    Constant(Constant),                // primitives feature only
+    Let(Symbol, Box<Term>, Box<Term>), // let x = bound in body, x is bound in body only
+}
+
+// clone, equality and drop walk the term with a work stack, see traverse
+impl Clone for Term {
+    fn clone(&self) -> Self {
+        traverse::map_leaves(self, |leaf, _| match leaf {
+            Term::Variable(index) => Term::Variable(*index),
+            Term::Constant(constant) => Term::Constant(*constant),
+            _ => unreachable!("not a leaf"),
+        })
+    }
+}
+
+impl PartialEq for Term {
+    fn eq(&self, other: &Self) -> bool {
+        traverse::zip_all(self, other, |a, b| match (a, b) {
+            (Term::Variable(x), Term::Variable(y)) => x == y,
+            (Term::Constant(x), Term::Constant(y)) => x == y,
+            (Term::Lambda(x, s, _), Term::Lambda(y, t, _)) => x == y && s == t,
+            (Term::TypeLambda(x, _), Term::TypeLambda(y, _)) => x == y,
+            (Term::TypeApplication(_, s), Term::TypeApplication(_, t)) => s == t,
+            (Term::Let(x, ..), Term::Let(y, ..)) => x == y,
+            (Term::Application(..), Term::Application(..)) => true,
+            _ => false,
+        })
+    }
+}
+
+impl Drop for Term {
+    fn drop(&mut self) {
+        let mut stack = Vec::new();
+        traverse::take_children(self, &mut stack);
+        // every popped term is dropped childless
+        while let Some(mut term) = stack.pop() {
+            traverse::take_children(&mut term, &mut stack);
+        }
+    }
+}
+
+impl Term {
+    // number of nodes
+    pub fn size(&self) -> usize {
+        let mut size = 0;
+        let mut work = vec![self];
+        while let Some(node) = work.pop() {
+            size += 1;
+            work.extend(node.children());
+        }
+        size
+    }
+
+    // nodes on the longest path from the root down to a leaf
+    pub fn depth(&self) -> usize {
+        let mut deepest = 0;
+        let mut work = vec![(self, 1)];
+        while let Some((node, depth)) = work.pop() {
+            deepest = deepest.max(depth);
+            work.extend(node.children().map(|child| (child, depth + 1)));
+        }
+        deepest
+    }
+
+    fn children(&self) -> impl Iterator<Item = &Term> {
+        let (first, second) = match self {
+            Term::Variable(_) | Term::Constant(_) => (None, None),
+            Term::Lambda(_, _, body)
+            | Term::TypeLambda(_, body)
+            | Term::TypeApplication(body, _) => (Some(&**body), None),
+            Term::Application(lhs, rhs) | Term::Let(_, lhs, rhs) => (Some(&**lhs), Some(&**rhs)),
+        };
+        first.into_iter().chain(second)
+    }
+}
+
+// built-in values and operations, reduced by the δ-rules in `primitives`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Constant {
+    Int(i64),
+    Bool(bool),
+    Add, // Int -> Int -> Int
+    Mul, // Int -> Int -> Int
+    Ite, // Bool -> a -> a -> a, if-then-else
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constant::Int(value) => write!(f, "{}", value),
+            Constant::Bool(value) => write!(f, "{}", value),
+            Constant::Add => write!(f, "add"),
+            Constant::Mul => write!(f, "mul"),
+            Constant::Ite => write!(f, "ite"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ParserConfig {
+    pub system_f: bool, // accept /\A.{...}, term [Type] and \/A.Type
+    pub style: Style,   // which binder annotations are accepted
+    // strategy the term is meant to be reduced with, letrec uses the fixed point
+    // combinator that works under it
+    pub strategy: Strategy,
+    // deeper terms are an error, for callers whose passes after parsing
+    // recurse on the term
+    pub max_depth: Option<usize>,
+}
+
+// presentation of typed terms, for the parser and the Checker
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Style {
+    #[default]
+    Mixed, // annotations are optional
+    Church, // every lambda is annotated, the types are checked
+    Curry,  // no annotations, the types are inferred
 }
 
 pub struct Parser<'a> {
     iter: Peekable<std::slice::Iter<'a, Token>>,
-    env: Vec<String>,
-    freevar: Vec<String>,
+    env: Vec<Symbol>,
+    freevar: Vec<Symbol>,
+    config: ParserConfig,
+    tokens: &'a [Token],
+    // optional spans of the tokens, enable node span tracking
+    token_spans: &'a [Span],
+    // span of every parsed node in pre-order
+    node_spans: Vec<Span>,
+    depth: usize, // terms and types being parsed
 }
 
 impl<'a> Parser<'a> {
     pub fn new(tokens: &'a [Token]) -> Self {
+        Self::with_config(tokens, ParserConfig::default())
+    }
+
+    pub fn with_config(tokens: &'a [Token], config: ParserConfig) -> Self {
         Self {
             iter: tokens.iter().peekable(),
             env: Vec::new(),
             freevar: Vec::new(),
+            config,
+            tokens,
+            token_spans: &[],
+            node_spans: Vec::new(),
+            depth: 0,
         }
     }
 
-    pub fn parse(&mut self) -> (Term, Vec<String>) {
-        (self.parse_term(), self.freevar.clone())
+    // record node spans, `spans` as returned by tokenizer::tokenize_spanned
+    pub fn with_token_spans(mut self, spans: &'a [Span]) -> Self {
+        self.token_spans = spans;
+        self
     }
 
-    fn expect_token(&mut self, expected: &Token, msg: &str) {
-        if self.iter.next() != Some(expected) {
-            panic!("{}", msg);
+    // source span of every node of the parsed term in pre-order (root first, lhs before rhs)
+    pub fn node_spans(&self) -> &[Span] {
+        &self.node_spans
+    }
+
+    // index of the next token to consume, after a parse error the one it is about
+    // (tokens.len() at the end of the input)
+    pub fn position(&self) -> usize {
+        self.tokens.len() - self.iter.len()
+    }
+
+    // span from token `start` up to the last consumed token
+    fn span_from(&self, start: usize) -> Span {
+        let first = self.token_spans[start];
+        let last = self.token_spans[self.position() - 1];
+        first.to(last)
+    }
+
+    // panics with the message of the first error, see try_parse
+    pub fn parse(&mut self) -> (Term, Vec<Symbol>) {
+        self.try_parse().unwrap_or_else(|msg| panic!("{}", msg))
+    }
+
+    // the term and its free names, or the first error with position() at the
+    // token it is about. the term has to take up all of the tokens
+    pub fn try_parse(&mut self) -> Result<(Term, Vec<Symbol>), String> {
+        let term = self.parse_term()?;
+        if self.position() < self.tokens.len() {
+            return Err("unexpected token after the term".to_string());
         }
+        Ok((term, self.freevar.clone()))
     }
 
-    fn expect_ident(&mut self) -> String {
-        if let Some(Token::Var(name)) = self.iter.next() {
-            name.clone()
-        } else {
-            panic!("Expected identifier");
+    // tokens are only consumed once they fit, so position() points at a bad one
+    fn expect_token(&mut self, expected: &Token, msg: &str) -> Result<(), String> {
+        if self.iter.next_if_eq(&expected).is_none() {
+            return Err(msg.to_string());
         }
+        Ok(())
     }
 
-    fn parse_term(&mut self) -> Term {
-        match self.iter.peek() {
-            Some(Token::Var(_)) => self.parse_var(),
-            Some(Token::Lambda) => self.parse_lambda(),
-            Some(Token::Bra) => self.parse_application(),
-            _ => panic!("Unexpected token"),
+    fn expect_ident(&mut self) -> Result<Symbol, String> {
+        match self.iter.next_if(|token| matches!(token, Token::Var(_))) {
+            Some(Token::Var(name)) => Ok(*name),
+            _ => Err("Expected identifier".to_string()),
         }
     }
 
-    fn parse_var(&mut self) -> Term {
-        let ident = self.expect_ident();
+    // the term starting at the next token. the parser keeps the terms it is
+    // inside of on a stack of its own, so the nesting of the source is only
+    // bounded by memory (and max_depth)
+    fn parse_term(&mut self) -> Result<Term, String> {
+        let tracking = !self.token_spans.is_empty();
+        let mut stack: Vec<Frame> = Vec::new();
+        'start: loop {
+            if self.config.max_depth.is_some_and(|max| self.depth >= max) {
+                return Err("Term nested too deeply".to_string());
+            }
+            self.depth += 1;
+            let node = self.node_spans.len();
+            if tracking {
+                self.node_spans.push(Span::default());
+            }
+            stack.push(Frame::Finish {
+                node,
+                start: self.position(),
+            });
+            let mut term = match self.iter.peek() {
+                Some(Token::Var(name)) if name == "let" || name == "letrec" => {
+                    let recursive = self.iter.next() == Some(&Token::Var(Symbol::intern("letrec")));
+                    let start = self.position();
+                    let name = self.expect_ident()?;
+                    self.expect_token(&Token::Equals, "Expected '=' after name in let")?;
+                    if recursive {
+                        self.env.push(name);
+                    }
+                    stack.push(Frame::LetBound {
+                        name,
+                        recursive,
+                        start,
+                        first: self.node_spans.len(),
+                    });
+                    continue 'start;
+                }
+                Some(Token::Var(_)) => self.parse_var()?,
+                Some(Token::Lambda) => {
+                    let (param, annot) = self.parse_lambda_head()?;
+                    self.env.push(param);
+                    stack.push(Frame::LambdaBody(param, annot));
+                    continue 'start;
+                }
+                Some(Token::Bra) => {
+                    self.iter.next();
+                    stack.push(Frame::ApplicationLhs);
+                    continue 'start;
+                }
+                Some(Token::TypeLambda) => {
+                    self.iter.next();
+                    self.require_system_f()?;
+                    let param = self.expect_ident()?;
+                    self.expect_token(&Token::Dot, "Expected '.' after type variable")?;
+                    self.expect_token(
+                        &Token::LBrace,
+                        "Expected '{' after '.' in type abstraction",
+                    )?;
+                    stack.push(Frame::TypeLambdaBody(param));
+                    continue 'start;
+                }
+                Some(Token::LBracket | Token::LParen) => {
+                    let tuple = self.iter.next() == Some(&Token::LParen);
+                    let start = self.position() - 1;
+                    let close = if tuple {
+                        Token::RParen
+                    } else {
+                        Token::RBracket
+                    };
+                    if self.iter.next_if_eq(&&close).is_some() {
+                        self.collection(tuple, Vec::new(), start)?
+                    } else {
+                        stack.push(Frame::Item {
+                            tuple,
+                            start,
+                            items: Vec::new(),
+                            first: self.node_spans.len(),
+                        });
+                        continue 'start;
+                    }
+                }
+                Some(Token::Int(value)) => {
+                    let value = *value;
+                    self.iter.next();
+                    Term::Constant(Constant::Int(value))
+                }
+                _ => return Err("Unexpected token".to_string()),
+            };
+            // hand the finished term to the one waiting for it
+            loop {
+                let Some(frame) = stack.pop() else {
+                    return Ok(term);
+                };
+                term = match frame {
+                    Frame::Finish { node, start } => {
+                        self.depth -= 1;
+                        self.finish(term, node, start)?
+                    }
+                    Frame::ApplicationLhs => {
+                        self.expect_token(&Token::Delim, "Expected delimiter '|' in application")?;
+                        stack.push(Frame::ApplicationRhs(term));
+                        continue 'start;
+                    }
+                    Frame::ApplicationRhs(lhs) => {
+                        self.expect_token(&Token::Ket, "Expected '>' after application")?;
+                        Term::Application(Box::new(lhs), Box::new(term))
+                    }
+                    Frame::LambdaBody(param, annot) => {
+                        self.expect_token(&Token::RBrace, "Expected '}' after lambda body")?;
+                        self.env.pop();
+                        Term::Lambda(param, annot, Box::new(term))
+                    }
+                    Frame::LetBound {
+                        name,
+                        recursive,
+                        start,
+                        first,
+                    } => {
+                        let bound = if recursive {
+                            self.env.pop();
+                            self.recursive(name, term, start, first)
+                        } else {
+                            term
+                        };
+                        self.expect_token(
+                            &Token::Var(Symbol::intern("in")),
+                            "Expected 'in' after let binding",
+                        )?;
+                        self.env.push(name);
+                        stack.push(Frame::LetBody(name, bound));
+                        continue 'start;
+                    }
+                    Frame::LetBody(name, bound) => {
+                        self.env.pop();
+                        Term::Let(name, Box::new(bound), Box::new(term))
+                    }
+                    Frame::TypeLambdaBody(param) => {
+                        self.expect_token(
+                            &Token::RBrace,
+                            "Expected '}' after type abstraction body",
+                        )?;
+                        Term::TypeLambda(param, Box::new(term))
+                    }
+                    Frame::Item {
+                        tuple,
+                        start,
+                        mut items,
+                        first,
+                    } => {
+                        items.push((term, self.node_spans.split_off(first)));
+                        if self.iter.next_if_eq(&&Token::Comma).is_some() {
+                            stack.push(Frame::Item {
+                                tuple,
+                                start,
+                                items,
+                                first: self.node_spans.len(),
+                            });
+                            continue 'start;
+                        }
+                        let (close, msg) = match tuple {
+                            true => (Token::RParen, "Expected ',' or ')' in tuple"),
+                            false => (Token::RBracket, "Expected ',' or ']' in list"),
+                        };
+                        self.expect_token(&close, msg)?;
+                        self.collection(tuple, items, start)?
+                    }
+                };
+            }
+        }
+    }
+
+    // the span of the term started at token `start`, and the type
+    // applications after it: term [A] [B]
+    fn finish(&mut self, mut term: Term, node: usize, start: usize) -> Result<Term, String> {
+        let tracking = !self.token_spans.is_empty();
+        if tracking {
+            self.node_spans[node] = self.span_from(start);
+        }
+        while self.iter.next_if_eq(&&Token::LBracket).is_some() {
+            self.require_system_f()?;
+            let arg = self.parse_type()?;
+            self.expect_token(&Token::RBracket, "Expected ']' after type argument")?;
+            term = Term::TypeApplication(Box::new(term), arg);
+            // the wrapper precedes what it wraps in pre-order
+            if tracking {
+                self.node_spans.insert(node, self.span_from(start));
+            }
+        }
+        Ok(term)
+    }
+
+    fn require_system_f(&self) -> Result<(), String> {
+        if !self.config.system_f {
+            return Err("System F syntax used but not enabled in ParserConfig".to_string());
+        }
+        Ok(())
+    }
+
+    fn parse_var(&mut self) -> Result<Term, String> {
+        let ident = self.expect_ident()?;
         if let Some(idx) = self.env.iter().rposition(|name| name == &ident) {
             let depth = self.env.len() - idx;
-            Term::Variable(depth as i32)
+            Ok(Term::Variable(depth as i32))
+        } else if let Some(constant) = Self::primitive(ident.as_str()) {
+            Ok(constant)
         } else {
-            self.freevar.push(ident.clone());
-            Term::Variable(-(self.freevar.len() as i32))
+            self.freevar.push(ident);
+            Ok(Term::Variable(-(self.freevar.len() as i32)))
         }
     }
 
-    fn parse_lambda(&mut self) -> Term {
-        self.iter.next();
-        let param = self.expect_ident();
-        self.expect_token(&Token::Dot, "Expected '.' after variable in lambda");
-        self.expect_token(&Token::LBrace, "Expected '{' after '.' in lambda");
-        self.env.push(param.clone());
-        let body = self.parse_term();
-        self.expect_token(&Token::RBrace, "Expected '}' after lambda body");
-        self.env.pop();
-        Term::Lambda(param, Box::new(body))
+    // unbound names of primitives are constants, binders may still shadow them
+    #[cfg(feature = "primitives")]
+    fn primitive(ident: &str) -> Option<Term> {
+        crate::primitives::from_name(ident).map(Term::Constant)
+    }
+
+    #[cfg(not(feature = "primitives"))]
+    fn primitive(_ident: &str) -> Option<Term> {
+        None
     }
 
-    fn parse_application(&mut self) -> Term {
+    // \x:A.{ up to the body
+    fn parse_lambda_head(&mut self) -> Result<(Symbol, Option<Type>), String> {
         self.iter.next();
-        let lhs = self.parse_term();
-        if let Some(Token::Delim) = self.iter.next() {
+        let param = self.expect_ident()?;
+        let annot = if self.iter.next_if_eq(&&Token::Colon).is_some() {
+            if self.config.style == Style::Curry {
+                return Err("Binder annotations are not allowed in Curry-style terms".to_string());
+            }
+            Some(self.parse_type()?)
         } else {
-            panic!("Expected delimiter '|' in application");
+            if self.config.style == Style::Church {
+                return Err(
+                    "Expected ':' and a type after lambda parameter in Church-style terms"
+                        .to_string(),
+                );
+            }
+            None
         };
-        let rhs = self.parse_term();
-        if let Some(Token::Ket) = self.iter.next() {
-        } else {
-            panic!("Expected '>' after application");
+        self.expect_token(&Token::Dot, "Expected '.' after variable in lambda")?;
+        self.expect_token(&Token::LBrace, "Expected '{' after '.' in lambda")?;
+        Ok((param, annot))
+    }
+
+    // letrec f = t in u is let f = FIX (λf. t) in u, FIX being Y or Z. `bound`
+    // is t, parsed with f in scope, its spans from `first` on
+    fn recursive(&mut self, name: Symbol, bound: Term, start: usize, first: usize) -> Term {
+        let fix = match self.config.strategy {
+            Strategy::Normal => combinators::y(),
+            Strategy::Applicative => combinators::z(),
+        };
+        // the application, the combinator and the λf come before the bound term
+        if !self.token_spans.is_empty() {
+            let added = vec![self.span_from(start); 2 + fix.size()];
+            self.node_spans.splice(first..first, added);
+        }
+        let lambda = Term::Lambda(name, None, Box::new(bound));
+        Term::Application(Box::new(fix), Box::new(lambda))
+    }
+
+    // the items of a list or tuple literal opened at token `start`
+    fn collection(
+        &mut self,
+        tuple: bool,
+        items: Vec<(Term, Vec<Span>)>,
+        start: usize,
+    ) -> Result<Term, String> {
+        match tuple {
+            true => self.tuple(items, start),
+            false => Ok(self.list(items, start)),
+        }
+    }
+
+    // [a, b] is the Church list λc. λn. c a (c b n), like cons a (cons b nil)
+    // the items are parsed outside the two binders and shifted under them
+    fn list(&mut self, items: Vec<(Term, Vec<Span>)>, start: usize) -> Term {
+        let body = items
+            .iter()
+            .rev()
+            .fold(Term::Variable(1), |rest, (item, _)| {
+                let head =
+                    Term::Application(Box::new(Term::Variable(2)), Box::new(shift(item, 2, 0)));
+                Term::Application(Box::new(head), Box::new(rest))
+            });
+        // the nodes added by the encoding span the whole literal, the λc is the caller's
+        if !self.token_spans.is_empty() {
+            let whole = self.span_from(start);
+            self.node_spans.push(whole);
+            for (_, spans) in &items {
+                self.node_spans.extend([whole; 3]);
+                self.node_spans.extend(spans);
+            }
+            self.node_spans.push(whole);
+        }
+        let nil = Term::Lambda(Symbol::intern("n"), None, Box::new(body));
+        Term::Lambda(Symbol::intern("c"), None, Box::new(nil))
+    }
+
+    // (a, b, c) is the tuple λp. p a b c, (a) is just a
+    fn tuple(&mut self, mut items: Vec<(Term, Vec<Span>)>, start: usize) -> Result<Term, String> {
+        match items.len() {
+            0 => return Err("Expected term in parentheses".to_string()),
+            1 => {
+                let (item, spans) = items.pop().unwrap();
+                // the caller's node is the item itself
+                self.node_spans.extend(spans.into_iter().skip(1));
+                return Ok(item);
+            }
+            _ => {}
+        }
+        let body = items.iter().fold(Term::Variable(1), |acc, (item, _)| {
+            Term::Application(Box::new(acc), Box::new(shift(item, 1, 0)))
+        });
+        // the spine of applications and p come first, then the items, the λp is the caller's
+        if !self.token_spans.is_empty() {
+            let whole = self.span_from(start);
+            self.node_spans.extend(vec![whole; items.len() + 1]);
+            for (_, spans) in &items {
+                self.node_spans.extend(spans);
+            }
+        }
+        Ok(Term::Lambda(Symbol::intern("p"), None, Box::new(body)))
+    }
+
+    // arrows are right associative: A->B->C is A->(B->C). a stack of the
+    // types being parsed like parse_term's
+    fn parse_type(&mut self) -> Result<Type, String> {
+        enum Open {
+            Paren,
+            Forall(Symbol),
+            Arrow(Type),
+        }
+        let mut stack: Vec<Open> = Vec::new();
+        'start: loop {
+            let depth = self.depth + stack.len();
+            if self.config.max_depth.is_some_and(|max| depth >= max) {
+                return Err("Term nested too deeply".to_string());
+            }
+            let mut ty = match self
+                .iter
+                .next_if(|token| matches!(token, Token::Var(_) | Token::LParen | Token::Forall))
+            {
+                Some(Token::Var(name)) => Type::Base(*name),
+                Some(Token::LParen) => {
+                    stack.push(Open::Paren);
+                    continue 'start;
+                }
+                Some(Token::Forall) => {
+                    self.require_system_f()?;
+                    let param = self.expect_ident()?;
+                    self.expect_token(&Token::Dot, "Expected '.' after quantified variable")?;
+                    // the body extends as far as possible: \/A.A->A is \/A.(A->A)
+                    stack.push(Open::Forall(param));
+                    continue 'start;
+                }
+                _ => return Err("Expected type".to_string()),
+            };
+            // `ty` can take an arrow after it until it is the body of one
+            let mut atom = true;
+            loop {
+                if atom && self.iter.next_if_eq(&&Token::Arrow).is_some() {
+                    stack.push(Open::Arrow(ty));
+                    continue 'start;
+                }
+                atom = false;
+                ty = match stack.pop() {
+                    None => return Ok(ty),
+                    Some(Open::Arrow(lhs)) => Type::Arrow(Box::new(lhs), Box::new(ty)),
+                    Some(Open::Forall(param)) => Type::Forall(param, Box::new(ty)),
+                    Some(Open::Paren) => {
+                        self.expect_token(&Token::RParen, "Expected ')' after type")?;
+                        atom = true;
+                        ty
+                    }
+                };
+            }
+        }
+    }
+}
+
+// what a term being parsed waits for, see Parser::parse_term
+enum Frame {
+    // the end of the term started at token `start`, its span at `node`
+    Finish {
+        node: usize,
+        start: usize,
+    },
+    ApplicationLhs,
+    ApplicationRhs(Term),
+    LambdaBody(Symbol, Option<Type>),
+    // `first` is where the bound term's spans start
+    LetBound {
+        name: Symbol,
+        recursive: bool,
+        start: usize,
+        first: usize,
+    },
+    LetBody(Symbol, Term),
+    TypeLambdaBody(Symbol),
+    // a list or tuple literal opened at token `start`, the items so far
+    Item {
+        tuple: bool,
+        start: usize,
+        items: Vec<(Term, Vec<Span>)>,
+        first: usize,
+    },
+}
+
+// tokenize and parse `source`, the first error of either as its message
+pub fn parse_with_config(
+    source: &str,
+    config: ParserConfig,
+) -> Result<(Term, Vec<Symbol>), String> {
+    let (tokens, _) = tokenizer::try_tokenize_spanned(source).map_err(|(msg, _)| msg)?;
+    Parser::with_config(&tokens, config).try_parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deep_terms_parse_without_recursion() {
+        let n = 100_000;
+        let lambdas = format!("{}a{}", r"\a.{".repeat(n), "}".repeat(n));
+        let (term, free) = parse_with_config(&lambdas, ParserConfig::default()).unwrap();
+        assert_eq!((term.depth(), free.len()), (n + 1, 0));
+        let applications = format!("{}f{}", "<".repeat(n), "|x>".repeat(n));
+        let (term, free) = parse_with_config(&applications, ParserConfig::default()).unwrap();
+        assert_eq!((term.depth(), free.len()), (n + 1, n + 1));
+        // types stay small, their drop recurses
+        let arrows = format!(r"\x:{}A.{{x}}", "A->".repeat(1000));
+        assert!(parse_with_config(&arrows, ParserConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn max_depth_counts_terms_and_types() {
+        let config = |max| ParserConfig {
+            max_depth: Some(max),
+            ..Default::default()
         };
-        Term::Application(Box::new(lhs), Box::new(rhs))
+        assert!(parse_with_config(r"\x.{\y.{x}}", config(3)).is_ok());
+        let deep = parse_with_config(r"\x.{\y.{x}}", config(2));
+        assert_eq!(deep.unwrap_err(), "Term nested too deeply");
+        assert!(parse_with_config(r"\x:A.{x}", config(2)).is_ok());
+        assert!(parse_with_config(r"\x:A->A.{x}", config(2)).is_err());
+    }
+
+    #[test]
+    fn tokens_after_the_term_are_an_error() {
+        let config = ParserConfig::default;
+        let trailing = parse_with_config(r"\x.{x} y", config());
+        assert_eq!(trailing.unwrap_err(), "unexpected token after the term");
+        assert!(parse_with_config("x y z", config()).is_err());
+        assert!(parse_with_config(r"<\x.{x}|y> )", config()).is_err());
+        let tokens = tokenizer::tokenize(r"\x.{x} garbage");
+        let mut parser = Parser::new(&tokens);
+        assert!(parser.try_parse().is_err());
+        assert_eq!(parser.position(), tokens.len() - 1);
+        assert!(parse_with_config(r"<\x.{x}|y>", config()).is_ok());
     }
 }
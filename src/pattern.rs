@@ -0,0 +1,303 @@
+// first-order patterns: terms whose free names are either meta-variables,
+// standing for any subterm, or symbols, matching only the free name they
+// spell. first-order because a meta-variable is never applied to the binders
+// around it: one under a binder of the pattern only matches a subterm that
+// does not use that binder, so \x.{<F|x>} matches η-redexes and nothing else
+//
+// a meta-variable occurring twice must match terms equal up to renaming of
+// binders. the bindings of a substitution borrow from the matched term where
+// they can, only ones under binders of the pattern are shifted copies
+use std::borrow::Cow;
+
+use crate::parser::{self, ParserConfig, Term};
+use crate::reduce::{self, Path};
+use crate::symbol::Symbol;
+use crate::traverse;
+use crate::types;
+
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    term: Term,
+    names: Vec<Symbol>, // the free names of `term`
+    metas: Vec<bool>,   // for each name, whether it is a meta-variable
+}
+
+// what the meta-variables of a pattern matched, in the order they were met
+#[derive(Clone, Debug, Default)]
+pub struct Substitution<'a> {
+    bindings: Vec<(Symbol, Cow<'a, Term>)>,
+}
+
+impl Substitution<'_> {
+    pub fn get(&self, meta: &str) -> Option<&Term> {
+        self.bindings
+            .iter()
+            .find(|(name, _)| *name == meta)
+            .map(|(_, term)| &**term)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Term)> {
+        self.bindings
+            .iter()
+            .map(|(name, term)| (name.as_str(), &**term))
+    }
+}
+
+// the convention of parse: meta-variables start with an uppercase letter
+pub fn is_meta_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase())
+}
+
+impl Pattern {
+    // `term` with free names `names`, the ones `is_meta` holds for being
+    // meta-variables
+    pub fn new(term: Term, names: Vec<Symbol>, is_meta: impl Fn(&str) -> bool) -> Pattern {
+        let metas = names.iter().map(|name| is_meta(name.as_str())).collect();
+        Pattern { term, names, metas }
+    }
+
+    // the source parsed as a term of any language level, without the prelude
+    pub fn parse(source: &str) -> Result<Pattern, String> {
+        let config = ParserConfig {
+            system_f: true,
+            ..Default::default()
+        };
+        let (term, names) = parser::parse_with_config(source, config)?;
+        Ok(Pattern::new(term, names, is_meta_name))
+    }
+
+    pub fn term(&self) -> &Term {
+        &self.term
+    }
+
+    pub fn metas(&self) -> impl Iterator<Item = &str> {
+        self.names
+            .iter()
+            .zip(&self.metas)
+            .filter(|(_, meta)| **meta)
+            .map(|(name, _)| name.as_str())
+    }
+
+    // the pattern is nothing but a meta-variable and matches every term
+    pub fn is_trivial(&self) -> bool {
+        matches!(self.term, Term::Variable(index) if index < 0 && self.metas[(-index - 1) as usize])
+    }
+
+    // the pattern with its meta-variables replaced, None if one is not bound.
+    // symbols are free names of the result, added to `free` when new
+    pub fn instantiate(&self, substitution: &Substitution, free: &mut Vec<Symbol>) -> Option<Term> {
+        let mut unbound = false;
+        let term = traverse::map_leaves(&self.term, |leaf, depth| match leaf {
+            Term::Variable(index) if *index < 0 => {
+                let at = (-index - 1) as usize;
+                let name = self.names[at];
+                if !self.metas[at] {
+                    return Term::Variable(-(symbol(free, name) as i32 + 1));
+                }
+                match substitution.get(name.as_str()) {
+                    Some(bound) => reduce::shift(bound, depth, 0),
+                    None => {
+                        unbound = true;
+                        leaf.clone()
+                    }
+                }
+            }
+            _ => leaf.clone(),
+        });
+        (!unbound).then_some(term)
+    }
+}
+
+// the index of a free name, added if it is new
+fn symbol(free: &mut Vec<Symbol>, name: Symbol) -> usize {
+    match free.iter().position(|known| *known == name) {
+        Some(at) => at,
+        None => {
+            free.push(name);
+            free.len() - 1
+        }
+    }
+}
+
+// the bindings that make `pattern` equal to `term`, whose free names are `free`
+pub fn match_term<'a>(
+    pattern: &Pattern,
+    term: &'a Term,
+    free: &[Symbol],
+) -> Option<Substitution<'a>> {
+    let mut substitution = Substitution::default();
+    matches(pattern, &pattern.term, term, free, 0, &mut substitution).then_some(substitution)
+}
+
+// every subterm `pattern` matches, in pre-order with its path. variables the
+// bindings share with binders above the subterm keep their indices
+pub fn find<'a>(
+    pattern: &Pattern,
+    term: &'a Term,
+    free: &[Symbol],
+) -> Vec<(Path, Substitution<'a>)> {
+    let mut found = Vec::new();
+    reduce::walk_paths(term, |node, path| {
+        found.extend(
+            match_term(pattern, node, free).map(|substitution| (path.to_vec(), substitution)),
+        );
+        false
+    });
+    found
+}
+
+// `depth` binders of the pattern are above `node`
+fn matches<'a>(
+    pattern: &Pattern,
+    node: &Term,
+    term: &'a Term,
+    free: &[Symbol],
+    depth: i32,
+    substitution: &mut Substitution<'a>,
+) -> bool {
+    match (node, term) {
+        (Term::Variable(index), _) if *index < 0 => {
+            let at = (-index - 1) as usize;
+            let name = pattern.names[at];
+            if !pattern.metas[at] {
+                return matches!(term, Term::Variable(other)
+                    if *other < 0 && free[(-other - 1) as usize] == name);
+            }
+            let Some(outside) = lower(term, depth) else {
+                return false;
+            };
+            match substitution.get(name.as_str()) {
+                Some(bound) => reduce::alpha_eq(bound, &outside),
+                None => {
+                    substitution.bindings.push((name, outside));
+                    true
+                }
+            }
+        }
+        (Term::Variable(x), Term::Variable(y)) => x == y,
+        (Term::Constant(x), Term::Constant(y)) => x == y,
+        (Term::Lambda(_, _, node), Term::Lambda(_, _, term)) => {
+            matches(pattern, node, term, free, depth + 1, substitution)
+        }
+        (Term::TypeLambda(_, node), Term::TypeLambda(_, term)) => {
+            matches(pattern, node, term, free, depth, substitution)
+        }
+        (Term::Application(nf, na), Term::Application(tf, ta)) => {
+            matches(pattern, nf, tf, free, depth, substitution)
+                && matches(pattern, na, ta, free, depth, substitution)
+        }
+        (Term::TypeApplication(node, s), Term::TypeApplication(term, t)) => {
+            types::same_type(s, t) && matches(pattern, node, term, free, depth, substitution)
+        }
+        (Term::Let(_, nb, node), Term::Let(_, tb, term)) => {
+            matches(pattern, nb, tb, free, depth, substitution)
+                && matches(pattern, node, term, free, depth + 1, substitution)
+        }
+        _ => false,
+    }
+}
+
+// `term` seen from `depth` binders further out, None if it uses one of them
+fn lower(term: &Term, depth: i32) -> Option<Cow<'_, Term>> {
+    if depth == 0 {
+        return Some(Cow::Borrowed(term));
+    }
+    let mut escapes = false;
+    let lowered = traverse::map_leaves(term, |leaf, inner| match leaf {
+        Term::Variable(index) if *index > inner + depth => Term::Variable(index - depth),
+        Term::Variable(index) if *index > inner => {
+            escapes = true;
+            leaf.clone()
+        }
+        _ => leaf.clone(),
+    });
+    (!escapes).then_some(Cow::Owned(lowered))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reduce::Dir;
+    use crate::unparse::unparse;
+
+    fn parse(source: &str) -> (Term, Vec<Symbol>) {
+        parser::parse_with_config(source, ParserConfig::default()).unwrap()
+    }
+
+    // what each meta-variable matched, printed, or None for a miss
+    fn bindings(pattern: &str, source: &str) -> Option<Vec<(String, String)>> {
+        let pattern = Pattern::parse(pattern).unwrap();
+        let (term, free) = parse(source);
+        let substitution = match_term(&pattern, &term, &free)?;
+        Some(
+            substitution
+                .iter()
+                .map(|(meta, bound)| (meta.to_string(), unparse(bound, &free)))
+                .collect(),
+        )
+    }
+
+    fn bound(pairs: &[(&str, &str)]) -> Option<Vec<(String, String)>> {
+        Some(
+            pairs
+                .iter()
+                .map(|(meta, term)| (meta.to_string(), term.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn meta_variables_match_subterms_and_symbols_themselves() {
+        assert_eq!(
+            bindings("<<plus|X>|Y>", r"<<plus|<s|z>>|\x.{x}>"),
+            bound(&[("X", "<s|z>"), ("Y", r"\x.{x}")])
+        );
+        assert_eq!(bindings("<<plus|X>|z>", "<<minus|a>|z>"), None);
+        assert_eq!(bindings("<<plus|X>|z>", "<plus|z>"), None);
+    }
+
+    #[test]
+    fn a_repeated_meta_variable_matches_alpha_equal_terms() {
+        assert_eq!(
+            bindings("<<pair|X>|X>", r"<<pair|\x.{x}>|\y.{y}>"),
+            bound(&[("X", r"\x.{x}")])
+        );
+        assert_eq!(bindings("<<pair|X>|X>", "<<pair|a>|b>"), None);
+    }
+
+    #[test]
+    fn meta_variables_under_binders_do_not_capture_them() {
+        assert_eq!(
+            bindings(r"\x.{<F|x>}", r"\y.{<<f|a>|y>}"),
+            bound(&[("F", "<f|a>")])
+        );
+        assert_eq!(bindings(r"\x.{<F|x>}", r"\y.{<y|y>}"), None);
+    }
+
+    #[test]
+    fn find_reports_every_match_with_its_path() {
+        let pattern = Pattern::parse("<f|X>").unwrap();
+        let (term, free) = parse("<g|<f|<f|z>>>");
+        let paths: Vec<Path> = find(&pattern, &term, &free)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(paths, vec![vec![Dir::Arg], vec![Dir::Arg, Dir::Arg]]);
+    }
+
+    #[test]
+    fn instantiate_fills_in_the_bindings() {
+        let pattern = Pattern::parse("<<plus|X>|z>").unwrap();
+        let (term, mut free) = parse("<<plus|<s|a>>|z>");
+        let substitution = match_term(&pattern, &term, &free).unwrap();
+        let replacement = Pattern::parse(r"\n.{<<succ|X>|n>}").unwrap();
+        let result = replacement.instantiate(&substitution, &mut free).unwrap();
+        assert_eq!(unparse(&result, &free), r"\n.{<<succ|<s|a>>|n>}");
+        assert!(
+            Pattern::parse("<f|Y>")
+                .unwrap()
+                .instantiate(&substitution, &mut free)
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,37 @@
+// a single static HTML page running the engine in the browser, to hand out
+// without a server: the wasm32 build of this crate (see api) is embedded as
+// base64 in playground.html, which reduces terms step by step through it
+//
+// the module is built separately with
+//     cargo build --lib --release --target wasm32-unknown-unknown
+const TEMPLATE: &str = include_str!("playground.html");
+
+// where the build above puts the module
+pub const DEFAULT_WASM: &str = "target/wasm32-unknown-unknown/release/lambda_rs.wasm";
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let word = chunk.iter().enumerate().fold(0u32, |word, (at, &byte)| {
+            word | (byte as u32) << (16 - 8 * at)
+        });
+        for at in 0..4 {
+            if at <= chunk.len() {
+                out.push(BASE64[(word >> (18 - 6 * at) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// the page with `wasm` embedded, or why it is not a wasm module
+pub fn html(wasm: &[u8]) -> Result<String, String> {
+    if !wasm.starts_with(b"\0asm") {
+        return Err("not a WebAssembly module".to_string());
+    }
+    Ok(TEMPLATE.replace("{{WASM}}", &base64(wasm)))
+}
@@ -0,0 +1,148 @@
+// named definitions available to free variables, see `resolve`
+use crate::church;
+use crate::combinators::{self, lam, var};
+use crate::diagnostic::Span;
+use crate::parser::Term;
+use crate::scott;
+use crate::symbol::Symbol;
+use crate::traverse;
+
+// builds the term of one definition
+pub type Definition = fn() -> Term;
+
+// every definition of the prelude, numerals are written c0, c1, ... (Church)
+// and s0, s1, ... (Scott) instead
+pub fn definitions() -> Vec<(&'static str, Definition)> {
+    vec![
+        ("I", combinators::i),
+        ("K", combinators::k),
+        ("S", combinators::s),
+        ("B", combinators::b),
+        ("C", combinators::c),
+        ("Y", combinators::y),
+        ("omega", combinators::omega),
+        ("true", church::tru),
+        ("false", church::fls),
+        ("succ", church::succ),
+        ("add", church::add),
+        ("mul", church::mul),
+        ("pow", church::pow),
+        ("pred", church::pred),
+        ("sub", church::sub),
+        ("is_zero", church::is_zero),
+        ("leq", church::leq),
+        ("nil", church::nil),
+        ("cons", church::cons),
+        ("head", church::head),
+        ("tail", church::tail),
+        ("is_nil", church::is_nil),
+        ("map", church::map),
+        ("foldr", church::foldr),
+        ("pair", || lam("a", lam("b", church::pair(var(2), var(1))))),
+        ("fst", church::fst),
+        ("snd", church::snd),
+        ("scott_zero", scott::zero),
+        ("scott_succ", scott::succ),
+        ("scott_pred", scott::pred),
+        ("scott_is_zero", scott::is_zero),
+        ("scott_nil", scott::nil),
+        ("scott_cons", scott::cons),
+        ("scott_head", scott::head),
+        ("scott_tail", scott::tail),
+        ("scott_is_nil", scott::is_nil),
+    ]
+}
+
+pub fn lookup(name: &str) -> Option<Term> {
+    if let Some(n) = numeral_suffix(name, 'c') {
+        return Some(church::numeral(n));
+    }
+    if let Some(n) = numeral_suffix(name, 's') {
+        return Some(scott::numeral(n));
+    }
+    definitions()
+        .into_iter()
+        .find(|(defined, _)| *defined == name)
+        .map(|(_, build)| build())
+}
+
+// whether `lookup` finds the name, without building its term
+pub fn defines(name: &str) -> bool {
+    numeral_suffix(name, 'c').is_some()
+        || numeral_suffix(name, 's').is_some()
+        || definitions().iter().any(|(defined, _)| *defined == name)
+}
+
+// `c12` with prefix 'c' is 12, names past church::MAX_NUMERAL stay free
+fn numeral_suffix(name: &str, prefix: char) -> Option<u64> {
+    let digits = name.strip_prefix(prefix)?;
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok().filter(|&n| n <= church::MAX_NUMERAL)
+}
+
+// replace the free variables naming prelude definitions by their terms
+// those are closed, so nothing needs shifting; other free variables are kept
+// with the primitives feature the built-in add, mul, true and false take precedence
+pub fn resolve(term: &Term, free: &[Symbol]) -> Term {
+    resolve_spanned(term, free, &[]).0
+}
+
+// same, and keep node spans in step: every node of an inserted definition gets
+// the span of the name it replaces
+pub fn resolve_spanned(term: &Term, free: &[Symbol], spans: &[Span]) -> (Term, Vec<Span>) {
+    let definition = |index: i32| match index {
+        index if index < 0 => lookup(free[(-index - 1) as usize].as_str()),
+        _ => None,
+    };
+    let resolved = traverse::map_leaves(term, |leaf, _| match leaf {
+        Term::Variable(index) => definition(*index).unwrap_or_else(|| leaf.clone()),
+        _ => leaf.clone(),
+    });
+    // the spans are in pre-order, walk the term the same way
+    let mut out = Vec::new();
+    let mut spans = spans.iter().copied();
+    let mut work = vec![term];
+    while let Some(node) = work.pop() {
+        let Some(span) = spans.next() else {
+            break;
+        };
+        match node {
+            Term::Variable(index) => match definition(*index) {
+                Some(definition) => out.extend(vec![span; definition.size()]),
+                None => out.push(span),
+            },
+            Term::Constant(_) => out.push(span),
+            Term::Lambda(_, _, body)
+            | Term::TypeLambda(_, body)
+            | Term::TypeApplication(body, _) => {
+                out.push(span);
+                work.push(body);
+            }
+            Term::Application(lhs, rhs) | Term::Let(_, lhs, rhs) => {
+                out.push(span);
+                work.push(rhs);
+                work.push(lhs);
+            }
+        }
+    }
+    (resolved, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numeral_literals_stop_at_the_cap() {
+        assert_eq!(lookup("c3"), Some(church::numeral(3)));
+        let largest = format!("s{}", church::MAX_NUMERAL);
+        assert!(defines(&largest) && lookup(&largest).is_some());
+        for name in ["c10001", "s300000000", "c18446744073709551616"] {
+            assert!(!defines(name), "{}", name);
+            assert_eq!(lookup(name), None);
+        }
+        assert!(defines("succ") && !defines("succ2"));
+    }
+}
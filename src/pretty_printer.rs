@@ -1,68 +1,409 @@
+use std::borrow::Cow;
+use std::fmt::{self, Write};
+
 use crate::parser::Term;
+use crate::share;
+use crate::symbol::Symbol;
+use crate::types::Type;
 
 const MAXLEN: usize = 10;
 
+// which binders get a type annotation printed
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Annotate {
+    #[default]
+    Off,
+    TopLevel, // only the leading lambdas of the term: λ(x : α). λ(y : β). ...
+    Full,     // every lambda
+}
+
+// the parentheses put around a subterm
+#[derive(Clone, Copy)]
+enum Wrap {
+    Never,
+    Long,   // once it is longer than MAXLEN
+    Always, // unless it already starts with '(' and ends with ')'
+}
+
+// what the parentheses around a printed subterm depend on
+#[derive(Clone, Copy, Default)]
+struct Shape {
+    len: usize,  // in bytes
+    open: bool,  // starts with '('
+    close: bool, // ends with ')'
+}
+
+impl Wrap {
+    fn applies(self, shape: Shape) -> bool {
+        let wrapped = shape.open && shape.close;
+        match self {
+            Wrap::Never => false,
+            Wrap::Long => shape.len > MAXLEN && !wrapped,
+            Wrap::Always => !wrapped,
+        }
+    }
+}
+
+impl Shape {
+    fn of(text: &str) -> Self {
+        Self {
+            len: text.len(),
+            open: text.starts_with('('),
+            close: text.ends_with(')'),
+        }
+    }
+
+    fn wrap(self, wrap: Wrap) -> Self {
+        if wrap.applies(self) {
+            Self {
+                len: self.len + 2,
+                open: true,
+                close: true,
+            }
+        } else {
+            self
+        }
+    }
+}
+
+// result of the first pass, all in pre-order: shapes of the nodes, printed
+// binders of the lambdas and printed arguments of the type applications
+#[derive(Default)]
+struct Layout {
+    shapes: Vec<Shape>,
+    binders: Vec<Cow<'static, str>>,
+    types: Vec<String>,
+}
+
+// pending work of measure
+enum Measure<'a> {
+    Enter(&'a Term),
+    Bind(Symbol), // the bound term of a let is done, its name scopes over the body
+    // pre-order index of the node, and of its binder or type argument in Layout
+    Exit(&'a Term, usize, usize),
+}
+
+// pending work of emit
+enum Emit<'a> {
+    Enter(&'a Term, Wrap),
+    Text(&'static str),
+    TypeArgument(usize), // index into Layout::types
+    Close(bool),         // whether Enter opened a parenthesis
+    Bind(Symbol),
+    Unbind,
+}
+
 pub struct PrettyPrinter {
-    env: Vec<String>,
+    env: Vec<Symbol>,
+    annotate: Annotate,
+    // types of the lambdas in pre-order, falls back to the declared annotations if empty
+    binder_types: Vec<Type>,
+    // index into binder_types of the next lambda to print
+    next_binder: usize,
+    // still on the chain of leading lambdas
+    top_level: bool,
+    // let-bind repeated subterms of at least this many nodes, see share
+    sharing: Option<usize>,
+}
+
+impl Default for PrettyPrinter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl PrettyPrinter {
     pub fn new() -> Self {
-        Self { env: Vec::new() }
+        Self {
+            env: Vec::new(),
+            annotate: Annotate::Off,
+            binder_types: Vec::new(),
+            next_binder: 0,
+            top_level: true,
+            sharing: None,
+        }
     }
 
-    pub fn format(&mut self, term: &Term, free: &[String]) -> String {
-        self.env.clear();
-        self.print_term(term, free)
+    // print binder types, `binder_types` as given by Checker::binder_types
+    pub fn with_types(mut self, annotate: Annotate, binder_types: Vec<Type>) -> Self {
+        self.annotate = annotate;
+        self.binder_types = binder_types;
+        self
     }
 
-    fn print_term(&mut self, term: &Term, free: &[String]) -> String {
-        match term {
-            Term::Variable(index) => self.print_var(*index, free),
-            Term::Lambda(param, body) => self.print_lambda(param, body, free),
-            Term::Application(lhs, rhs) => self.print_application(lhs, rhs, free),
-        }
+    // print repeated subterms of at least `min_size` nodes once, let-bound
+    pub fn with_sharing(mut self, min_size: usize) -> Self {
+        self.sharing = Some(min_size);
+        self
     }
 
-    fn print_var(&self, index: i32, free: &[String]) -> String {
-        if index < 0 {
-            let freepos = -(index + 1) as usize;
-            format!("${}", free[freepos])
-        } else {
-            let bindpos = self.env.len() - (index as usize);
-            self.env[bindpos].clone()
+    pub fn format(&mut self, term: &Term, free: &[Symbol]) -> String {
+        self.format_under(term, &[], free)
+    }
+
+    // format a subterm whose outer binders are `binders` (outermost first)
+    pub fn format_under(&mut self, term: &Term, binders: &[Symbol], free: &[Symbol]) -> String {
+        let shared = self
+            .sharing
+            .map(|min_size| share::share(term, free, min_size));
+        let term = shared.as_ref().unwrap_or(term);
+        let layout = self.measure(term, binders, free);
+        let mut out = String::with_capacity(layout.shapes[0].len);
+        self.emit(&mut out, term, binders, free, &layout)
+            .expect("writing to a String cannot fail");
+        out
+    }
+
+    // same as format, into any sink
+    pub fn write(&mut self, out: &mut impl Write, term: &Term, free: &[Symbol]) -> fmt::Result {
+        self.write_under(out, term, &[], free)
+    }
+
+    pub fn write_under(
+        &mut self,
+        out: &mut impl Write,
+        term: &Term,
+        binders: &[Symbol],
+        free: &[Symbol],
+    ) -> fmt::Result {
+        let shared = self
+            .sharing
+            .map(|min_size| share::share(term, free, min_size));
+        let term = shared.as_ref().unwrap_or(term);
+        let layout = self.measure(term, binders, free);
+        self.emit(out, term, binders, free, &layout)
+    }
+
+    // parentheses depend on the printed length of subterms, so lengths are
+    // worked out bottom-up first and the text is written top-down after. both
+    // passes use an explicit stack, binder bookkeeping happens in pre-order
+    fn measure(&mut self, term: &Term, binders: &[Symbol], free: &[Symbol]) -> Layout {
+        self.env = binders.to_vec();
+        self.next_binder = 0;
+        self.top_level = true;
+        let mut layout = Layout::default();
+        let mut work = vec![Measure::Enter(term)];
+        let mut done: Vec<Shape> = Vec::new();
+        while let Some(frame) = work.pop() {
+            let (node, index, extra) = match frame {
+                Measure::Enter(node) => {
+                    let index = layout.shapes.len();
+                    layout.shapes.push(Shape::default());
+                    match node {
+                        Term::Variable(var) => {
+                            let shape = match self.var_name(*var, free) {
+                                Ok(bound) => Shape::of(bound),
+                                Err(free) => Shape {
+                                    len: 1 + free.len(),
+                                    open: false,
+                                    close: free.ends_with(')'),
+                                },
+                            };
+                            layout.shapes[index] = shape;
+                            done.push(shape);
+                        }
+                        Term::Constant(constant) => {
+                            let shape = Shape::of(&constant.to_string());
+                            layout.shapes[index] = shape;
+                            done.push(shape);
+                        }
+                        Term::Lambda(param, annot, body) => {
+                            let binder = self.print_binder(*param, annot);
+                            work.push(Measure::Exit(node, index, layout.binders.len()));
+                            layout.binders.push(binder);
+                            self.env.push(*param);
+                            work.push(Measure::Enter(body));
+                        }
+                        Term::Application(lhs, rhs) => {
+                            self.top_level = false;
+                            work.push(Measure::Exit(node, index, 0));
+                            work.push(Measure::Enter(rhs));
+                            work.push(Measure::Enter(lhs));
+                        }
+                        Term::TypeLambda(_, body) => {
+                            work.push(Measure::Exit(node, index, 0));
+                            work.push(Measure::Enter(body));
+                        }
+                        Term::TypeApplication(fun, ty) => {
+                            self.top_level = false;
+                            work.push(Measure::Exit(node, index, layout.types.len()));
+                            layout.types.push(ty.to_string());
+                            work.push(Measure::Enter(fun));
+                        }
+                        Term::Let(name, bound, body) => {
+                            self.top_level = false;
+                            work.push(Measure::Exit(node, index, 0));
+                            work.push(Measure::Enter(body));
+                            work.push(Measure::Bind(*name));
+                            work.push(Measure::Enter(bound));
+                        }
+                    }
+                    continue;
+                }
+                Measure::Bind(name) => {
+                    self.env.push(name);
+                    continue;
+                }
+                Measure::Exit(node, index, extra) => (node, index, extra),
+            };
+            let shape = match node {
+                Term::Lambda(..) => {
+                    self.env.pop();
+                    let body = done.pop().unwrap().wrap(Wrap::Long);
+                    let binder = &layout.binders[extra];
+                    Shape {
+                        len: 'λ'.len_utf8() + binder.len() + 2 + body.len,
+                        open: false,
+                        close: body.close,
+                    }
+                }
+                Term::Application(..) => {
+                    let rhs = done.pop().unwrap().wrap(Wrap::Always);
+                    let lhs = done.pop().unwrap().wrap(Wrap::Long);
+                    Shape {
+                        len: lhs.len + rhs.len,
+                        open: lhs.open,
+                        close: rhs.close,
+                    }
+                }
+                Term::TypeLambda(param, _) => {
+                    let body = done.pop().unwrap().wrap(Wrap::Long);
+                    Shape {
+                        len: 'Λ'.len_utf8() + param.as_str().len() + 2 + body.len,
+                        open: false,
+                        close: body.close,
+                    }
+                }
+                Term::TypeApplication(..) => {
+                    let fun = done.pop().unwrap().wrap(Wrap::Long);
+                    let ty = &layout.types[extra];
+                    Shape {
+                        len: fun.len + 1 + ty.len() + 1,
+                        open: fun.open,
+                        close: false,
+                    }
+                }
+                Term::Let(name, ..) => {
+                    self.env.pop();
+                    let body = done.pop().unwrap();
+                    let bound = done.pop().unwrap();
+                    Shape {
+                        len: "let  =  in ".len() + name.as_str().len() + bound.len + body.len,
+                        open: false,
+                        close: body.close,
+                    }
+                }
+                Term::Variable(_) | Term::Constant(_) => unreachable!("leaves have no exit"),
+            };
+            layout.shapes[index] = shape;
+            done.push(shape);
         }
+        layout
     }
 
-    fn print_lambda(&mut self, param: &String, body: &Term, free: &[String]) -> String {
-        self.env.push(param.clone());
-        let body_str = self.print_term(body, free);
-        self.env.pop();
-        let fmtbody = if body_str.len() > MAXLEN {
-            Self::addparen(&body_str)
-        } else {
-            body_str
-        };
-        format!("λ{}. {}", param, fmtbody)
+    fn emit(
+        &mut self,
+        out: &mut impl Write,
+        term: &Term,
+        binders: &[Symbol],
+        free: &[Symbol],
+        layout: &Layout,
+    ) -> fmt::Result {
+        self.env = binders.to_vec();
+        let mut shapes = layout.shapes.iter();
+        let mut lambdas = layout.binders.iter();
+        let mut type_arguments = 0..;
+        let mut work = vec![Emit::Enter(term, Wrap::Never)];
+        while let Some(frame) = work.pop() {
+            match frame {
+                Emit::Enter(node, wrap) => {
+                    let paren = wrap.applies(*shapes.next().unwrap());
+                    if paren {
+                        out.write_char('(')?;
+                    }
+                    work.push(Emit::Close(paren));
+                    match node {
+                        Term::Variable(var) => match self.var_name(*var, free) {
+                            Ok(bound) => out.write_str(bound)?,
+                            Err(free) => {
+                                out.write_char('$')?;
+                                out.write_str(free)?;
+                            }
+                        },
+                        Term::Constant(constant) => write!(out, "{}", constant)?,
+                        Term::Lambda(param, _, body) => {
+                            out.write_char('λ')?;
+                            out.write_str(lambdas.next().unwrap())?;
+                            out.write_str(". ")?;
+                            self.env.push(*param);
+                            work.push(Emit::Unbind);
+                            work.push(Emit::Enter(body, Wrap::Long));
+                        }
+                        Term::Application(lhs, rhs) => {
+                            work.push(Emit::Enter(rhs, Wrap::Always));
+                            work.push(Emit::Enter(lhs, Wrap::Long));
+                        }
+                        Term::TypeLambda(param, body) => {
+                            out.write_char('Λ')?;
+                            out.write_str(param.as_str())?;
+                            out.write_str(". ")?;
+                            work.push(Emit::Enter(body, Wrap::Long));
+                        }
+                        Term::TypeApplication(fun, _) => {
+                            work.push(Emit::TypeArgument(type_arguments.next().unwrap()));
+                            work.push(Emit::Enter(fun, Wrap::Long));
+                        }
+                        Term::Let(name, bound, body) => {
+                            out.write_str("let ")?;
+                            out.write_str(name.as_str())?;
+                            out.write_str(" = ")?;
+                            work.push(Emit::Unbind);
+                            work.push(Emit::Enter(body, Wrap::Never));
+                            work.push(Emit::Bind(*name));
+                            work.push(Emit::Text(" in "));
+                            work.push(Emit::Enter(bound, Wrap::Never));
+                        }
+                    }
+                }
+                Emit::Text(text) => out.write_str(text)?,
+                Emit::TypeArgument(index) => {
+                    out.write_char('[')?;
+                    out.write_str(&layout.types[index])?;
+                    out.write_char(']')?;
+                }
+                Emit::Close(paren) => {
+                    if paren {
+                        out.write_char(')')?;
+                    }
+                }
+                Emit::Bind(name) => self.env.push(name),
+                Emit::Unbind => {
+                    self.env.pop();
+                }
+            }
+        }
+        Ok(())
     }
 
-    fn addparen(s: &String) -> String {
-        if s.starts_with('(') && s.ends_with(')') {
-            s.clone()
+    // the name of a bound variable, or Err with the name of a free one
+    fn var_name(&self, index: i32, free: &[Symbol]) -> Result<&'static str, &'static str> {
+        if index < 0 {
+            Err(free[-(index + 1) as usize].as_str())
         } else {
-            format!("({})", s)
+            Ok(self.env[self.env.len() - (index as usize)].as_str())
         }
     }
 
-    fn print_application(&mut self, lhs: &Term, rhs: &Term, free: &[String]) -> String {
-        let lhs_str = self.print_term(lhs, free);
-        let rhs_str = self.print_term(rhs, free);
-        // add parentheses for lhs if len > MAXLEN
-        let fmtlhs = if lhs_str.len() > MAXLEN {
-            Self::addparen(&lhs_str)
-        } else {
-            lhs_str
-        };
-        format!("{}{}", fmtlhs, Self::addparen(&rhs_str))
+    // the binder of a lambda, annotated as configured
+    fn print_binder(&mut self, param: Symbol, annot: &Option<Type>) -> Cow<'static, str> {
+        let ty = self.binder_types.get(self.next_binder).or(annot.as_ref());
+        self.next_binder += 1;
+        match (self.annotate, ty) {
+            (Annotate::Full, Some(ty)) => format!("({} : {})", param, ty).into(),
+            (Annotate::TopLevel, Some(ty)) if self.top_level => {
+                format!("({} : {})", param, ty).into()
+            }
+            _ => param.as_str().into(),
+        }
     }
 }
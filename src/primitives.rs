@@ -0,0 +1,136 @@
+use crate::church;
+use crate::combinators::{app, lam, var};
+use crate::parser::{Constant, Term};
+use crate::traverse;
+
+pub fn from_name(name: &str) -> Option<Constant> {
+    match name {
+        "true" => Some(Constant::Bool(true)),
+        "false" => Some(Constant::Bool(false)),
+        "add" => Some(Constant::Add),
+        "mul" => Some(Constant::Mul),
+        "ite" => Some(Constant::Ite),
+        _ => None,
+    }
+}
+
+// δ-rules:
+//   <<add|m>|n>        -> m + n
+//   <<mul|m>|n>        -> m * n
+//   <<<ite|true>|t>|e> -> t
+//   <<<ite|false>|t>|e> -> e
+// arithmetic wraps around on overflow
+pub fn delta(term: &Term) -> Option<Term> {
+    let (op, args) = spine(term)?;
+    match (op, args.as_slice()) {
+        (
+            Constant::Add,
+            [
+                Term::Constant(Constant::Int(m)),
+                Term::Constant(Constant::Int(n)),
+            ],
+        ) => Some(Term::Constant(Constant::Int(m.wrapping_add(*n)))),
+        (
+            Constant::Mul,
+            [
+                Term::Constant(Constant::Int(m)),
+                Term::Constant(Constant::Int(n)),
+            ],
+        ) => Some(Term::Constant(Constant::Int(m.wrapping_mul(*n)))),
+        (Constant::Ite, [Term::Constant(Constant::Bool(cond)), then, other]) => Some(if *cond {
+            (*then).clone()
+        } else {
+            (*other).clone()
+        }),
+        _ => None,
+    }
+}
+
+// same as delta(term).is_some() without building the result
+pub fn is_delta_redex(term: &Term) -> bool {
+    let Some((op, args)) = spine(term) else {
+        return false;
+    };
+    matches!(
+        (op, args.as_slice()),
+        (
+            Constant::Add | Constant::Mul,
+            [
+                Term::Constant(Constant::Int(_)),
+                Term::Constant(Constant::Int(_))
+            ]
+        ) | (Constant::Ite, [Term::Constant(Constant::Bool(_)), _, _])
+    )
+}
+
+// the term with its constants as the Church encodings β-reduction computes
+// them by: numerals, booleans, add and mul, and ite as the conditional
+// λc. λt. λe. c t e. None with a negative integer, or one past
+// church::MAX_NUMERAL, in it
+pub fn to_church(term: &Term) -> Option<Term> {
+    let mut unrepresentable = false;
+    let lowered = traverse::map_leaves(term, |leaf, _| match leaf {
+        Term::Constant(Constant::Int(n)) if *n < 0 || *n as u64 > church::MAX_NUMERAL => {
+            unrepresentable = true;
+            leaf.clone()
+        }
+        Term::Constant(Constant::Int(n)) => church::numeral(*n as u64),
+        Term::Constant(Constant::Bool(true)) => church::tru(),
+        Term::Constant(Constant::Bool(false)) => church::fls(),
+        Term::Constant(Constant::Add) => church::add(),
+        Term::Constant(Constant::Mul) => church::mul(),
+        Term::Constant(Constant::Ite) => {
+            lam("c", lam("t", lam("e", app(app(var(3), var(2)), var(1)))))
+        }
+        _ => leaf.clone(),
+    });
+    (!unrepresentable).then_some(lowered)
+}
+
+// split <<<c|a>|b>|...> into c and its arguments, None unless headed by a constant
+fn spine(term: &Term) -> Option<(Constant, Vec<&Term>)> {
+    let mut args = Vec::new();
+    let mut node = term;
+    while let Term::Application(lhs, rhs) = node {
+        args.push(&**rhs);
+        node = lhs;
+    }
+    args.reverse();
+    match node {
+        Term::Constant(op) => Some((*op, args)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::apps;
+    use crate::reduce;
+
+    fn constant(constant: Constant) -> Term {
+        Term::Constant(constant)
+    }
+
+    #[test]
+    fn constants_lower_to_what_they_compute() {
+        let int = |n| constant(Constant::Int(n));
+        for (op, expected) in [(Constant::Add, 5), (Constant::Mul, 6)] {
+            let term = to_church(&apps(constant(op), [int(2), int(3)])).unwrap();
+            let (normal, _) = reduce::normalize(&term, 1000).unwrap();
+            assert_eq!(church::to_number(&normal), Some(expected));
+        }
+        let cond = |b| {
+            apps(
+                constant(Constant::Ite),
+                [constant(Constant::Bool(b)), var(-1), var(-2)],
+            )
+        };
+        for (b, expected) in [(true, -1), (false, -2)] {
+            let (normal, _) = reduce::normalize(&to_church(&cond(b)).unwrap(), 100).unwrap();
+            assert_eq!(normal, var(expected));
+        }
+        assert_eq!(to_church(&int(-1)), None);
+        assert_eq!(to_church(&int(church::MAX_NUMERAL as i64 + 1)), None);
+    }
+}
@@ -0,0 +1,168 @@
+// opt-in instrumentation of reduce::normalize_with: counters kept while reducing
+// and a hook called every so many steps
+use std::mem;
+
+use crate::parser::Term;
+use crate::reduce::{self, Strategy};
+use crate::types::Type;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EvalMetrics {
+    pub steps: usize,           // contractions: β, let, type and δ
+    pub beta_steps: usize,      // of which β and let
+    pub substitutions: usize,   // variable occurrences replaced by an argument
+    pub nodes_allocated: usize, // nodes of the terms built, each step rebuilds the whole term
+    pub max_size: usize,        // largest term on the way, in nodes
+    pub max_depth: usize,       // deepest term on the way, a lone variable has depth 1
+    // most heap held at once, see heap_bytes: during a step the term and its
+    // reduct are both alive, they share nothing
+    pub peak_bytes: usize,
+}
+
+type Hook<'a> = Box<dyn FnMut(&EvalMetrics, &Term) + 'a>;
+
+pub struct Profiler<'a> {
+    metrics: EvalMetrics,
+    every: usize,
+    hook: Option<Hook<'a>>,
+}
+
+impl Default for Profiler<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Profiler<'a> {
+    pub fn new() -> Self {
+        Self {
+            metrics: EvalMetrics::default(),
+            every: 0,
+            hook: None,
+        }
+    }
+
+    // call `hook` with the counters so far and the current term after every
+    // `every` steps
+    pub fn with_hook(mut self, every: usize, hook: impl FnMut(&EvalMetrics, &Term) + 'a) -> Self {
+        self.every = every.max(1);
+        self.hook = Some(Box::new(hook));
+        self
+    }
+
+    pub fn metrics(&self) -> &EvalMetrics {
+        &self.metrics
+    }
+
+    // as reduce::normalize_with, counters add up over calls
+    pub fn normalize(
+        &mut self,
+        term: &Term,
+        fuel: usize,
+        strategy: Strategy,
+    ) -> Option<(Term, usize)> {
+        let mut current = term.clone();
+        let (_, mut bytes) = self.observe(&current);
+        self.metrics.peak_bytes = self.metrics.peak_bytes.max(bytes);
+        for steps in 0..=fuel {
+            let Some(path) = reduce::next_redex(&current, strategy) else {
+                return Some((current, steps));
+            };
+            let redex = reduce::subterm(&current, &path);
+            if matches!(redex, Term::Let(..))
+                || matches!(redex, Term::Application(lhs, _) if matches!(**lhs, Term::Lambda(..)))
+            {
+                self.metrics.beta_steps += 1;
+            }
+            current = reduce::contract_counting(&current, &path, &mut self.metrics.substitutions);
+            self.metrics.steps += 1;
+            let (size, reduct_bytes) = self.observe(&current);
+            self.metrics.nodes_allocated += size;
+            self.metrics.peak_bytes = self.metrics.peak_bytes.max(bytes + reduct_bytes);
+            bytes = reduct_bytes;
+            if let Some(hook) = &mut self.hook
+                && self.metrics.steps.is_multiple_of(self.every)
+            {
+                hook(&self.metrics, &current);
+            }
+        }
+        None
+    }
+
+    // update the size and depth maxima, returning the size and heap_bytes
+    fn observe(&mut self, term: &Term) -> (usize, usize) {
+        let (mut size, mut depth, mut bytes) = (0, 0, 0);
+        let mut work = vec![(term, 1)];
+        while let Some((node, at)) = work.pop() {
+            size += 1;
+            depth = depth.max(at);
+            bytes += node_bytes(node);
+            match node {
+                Term::Variable(_) | Term::Constant(_) => {}
+                Term::Lambda(_, _, body)
+                | Term::TypeLambda(_, body)
+                | Term::TypeApplication(body, _) => work.push((body, at + 1)),
+                Term::Application(lhs, rhs) | Term::Let(_, lhs, rhs) => {
+                    work.push((rhs, at + 1));
+                    work.push((lhs, at + 1));
+                }
+            }
+        }
+        self.metrics.max_size = self.metrics.max_size.max(size);
+        self.metrics.max_depth = self.metrics.max_depth.max(depth);
+        (size, bytes - mem::size_of::<Term>())
+    }
+}
+
+// estimated heap footprint of a term: every node but the root is boxed, and
+// annotations own their types. allocator overhead is not counted
+pub fn heap_bytes(term: &Term) -> usize {
+    let mut bytes = 0;
+    let mut work = vec![term];
+    while let Some(node) = work.pop() {
+        bytes += node_bytes(node);
+        match node {
+            Term::Variable(_) | Term::Constant(_) => {}
+            Term::Lambda(_, _, body)
+            | Term::TypeLambda(_, body)
+            | Term::TypeApplication(body, _) => work.push(body),
+            Term::Application(lhs, rhs) | Term::Let(_, lhs, rhs) => {
+                work.push(rhs);
+                work.push(lhs);
+            }
+        }
+    }
+    bytes - mem::size_of::<Term>()
+}
+
+// same for a type, not counting the Type itself. names are interned, the
+// table is not counted
+pub fn type_heap_bytes(ty: &Type) -> usize {
+    let mut bytes = 0;
+    let mut work = vec![ty];
+    while let Some(ty) = work.pop() {
+        match ty {
+            Type::Base(_) | Type::Var(_) => {}
+            Type::Arrow(from, to) => {
+                bytes += 2 * mem::size_of::<Type>();
+                work.push(from);
+                work.push(to);
+            }
+            Type::Forall(_, body) => {
+                bytes += mem::size_of::<Type>();
+                work.push(body);
+            }
+        }
+    }
+    bytes
+}
+
+// the node in its box, with what it owns besides its children
+fn node_bytes(node: &Term) -> usize {
+    mem::size_of::<Term>()
+        + match node {
+            Term::Lambda(_, Some(ty), _) => type_heap_bytes(ty),
+            Term::TypeApplication(_, ty) => type_heap_bytes(ty),
+            _ => 0,
+        }
+}
@@ -0,0 +1,192 @@
+// Mogensen–Scott quoting, terms as data in higher-order abstract syntax:
+//
+// ⌜x⌝    = λa. λb. λc. a x
+// ⌜M N⌝  = λa. λb. λc. b ⌜M⌝ ⌜N⌝
+// ⌜λx.M⌝ = λa. λb. λc. c (λx. ⌜M⌝)
+//
+// binders of the quoted term stay binders, so a quoted variable still points at
+// its own λx. constants and free variables are quoted like variables, lets are
+// quoted as the redex (λx. body) bound and types are erased
+use crate::combinators::{app, apps, i, lam, var, y};
+use crate::parser::Term;
+use crate::symbol::Symbol;
+
+// what quote and unquote still have to do, over explicit stacks so deep terms
+// are bounded by the heap. `depth` is the number of binders around a node in
+// the output
+enum Frame<'a> {
+    Visit(Node<'a>, usize),
+    Lambda(Symbol), // the body is done
+    Application,    // both sides are done
+}
+
+// a let is quoted as the redex (λx. body) bound, its λ is only a parameter
+// and a body
+enum Node<'a> {
+    Term(&'a Term),
+    Lambda(Symbol, &'a Term),
+}
+
+fn case(body: Term) -> Term {
+    lam("a", lam("b", lam("c", body)))
+}
+
+pub fn quote(term: &Term) -> Term {
+    // for every enclosing binder of the term, the number of binders around it
+    // in the output, itself included
+    let mut levels: Vec<usize> = Vec::new();
+    let mut work = vec![Frame::Visit(Node::Term(term), 0)];
+    let mut done: Vec<Term> = Vec::new();
+    while let Some(frame) = work.pop() {
+        match frame {
+            Frame::Visit(Node::Lambda(param, body), depth) => {
+                levels.push(depth + 4);
+                work.push(Frame::Lambda(param));
+                work.push(Frame::Visit(Node::Term(body), depth + 4));
+            }
+            Frame::Visit(Node::Term(node), depth) => {
+                let inner = depth + 3;
+                match node {
+                    Term::Variable(index) if *index > 0 => {
+                        let level = levels[levels.len() - *index as usize];
+                        done.push(case(app(var(3), var((inner - level + 1) as i32))));
+                    }
+                    Term::Variable(_) | Term::Constant(_) => {
+                        done.push(case(app(var(3), node.clone())))
+                    }
+                    Term::Lambda(param, _, body) => {
+                        work.push(Frame::Visit(Node::Lambda(*param, body), depth))
+                    }
+                    Term::Application(lhs, rhs) => {
+                        work.push(Frame::Application);
+                        work.push(Frame::Visit(Node::Term(rhs), inner));
+                        work.push(Frame::Visit(Node::Term(lhs), inner));
+                    }
+                    Term::Let(name, bound, body) => {
+                        work.push(Frame::Application);
+                        work.push(Frame::Visit(Node::Term(bound), inner));
+                        work.push(Frame::Visit(Node::Lambda(*name, body), inner));
+                    }
+                    Term::TypeLambda(_, body) | Term::TypeApplication(body, _) => {
+                        work.push(Frame::Visit(Node::Term(body), depth))
+                    }
+                }
+            }
+            Frame::Lambda(param) => {
+                levels.pop();
+                let body = done.pop().expect("body quoted");
+                done.push(case(app(var(1), Term::Lambda(param, None, Box::new(body)))));
+            }
+            Frame::Application => {
+                let rhs = done.pop().expect("argument quoted");
+                let lhs = done.pop().expect("function quoted");
+                done.push(case(apps(var(2), [lhs, rhs])));
+            }
+        }
+    }
+    done.pop().expect("root quoted")
+}
+
+// the term a quotation stands for, None if `term` is not one
+pub fn unquote(term: &Term) -> Option<Term> {
+    // `levels` as in quote, read in the other direction
+    let mut levels: Vec<usize> = Vec::new();
+    let mut work = vec![Frame::Visit(Node::Term(term), 0)];
+    let mut done: Vec<Term> = Vec::new();
+    while let Some(frame) = work.pop() {
+        let (node, depth) = match frame {
+            Frame::Visit(Node::Term(node), depth) => (node, depth),
+            Frame::Visit(Node::Lambda(..), _) => unreachable!("quotations have no lets"),
+            Frame::Lambda(param) => {
+                levels.pop();
+                let body = done.pop().expect("body read");
+                done.push(Term::Lambda(param, None, Box::new(body)));
+                continue;
+            }
+            Frame::Application => {
+                let arg = done.pop().expect("argument read");
+                let fun = done.pop().expect("function read");
+                done.push(Term::Application(Box::new(fun), Box::new(arg)));
+                continue;
+            }
+        };
+        let Term::Lambda(_, _, b) = node else {
+            return None;
+        };
+        let Term::Lambda(_, _, c) = &**b else {
+            return None;
+        };
+        let Term::Lambda(_, _, body) = &**c else {
+            return None;
+        };
+        let inner = depth + 3;
+        let Term::Application(lhs, rhs) = &**body else {
+            return None;
+        };
+        match (&**lhs, &**rhs) {
+            (Term::Variable(3), Term::Variable(index)) if *index > 0 => {
+                let level = (inner + 1).checked_sub(*index as usize)?;
+                let pos = levels.iter().rposition(|&bound| bound == level)?;
+                done.push(Term::Variable((levels.len() - pos) as i32));
+            }
+            (Term::Variable(3), Term::Variable(_) | Term::Constant(_)) => {
+                done.push((**rhs).clone())
+            }
+            (Term::Variable(1), Term::Lambda(param, _, quoted)) => {
+                levels.push(inner + 1);
+                work.push(Frame::Lambda(*param));
+                work.push(Frame::Visit(Node::Term(quoted), inner + 1));
+            }
+            (Term::Application(head, fun), arg) if **head == Term::Variable(2) => {
+                work.push(Frame::Application);
+                work.push(Frame::Visit(Node::Term(arg), inner));
+                work.push(Frame::Visit(Node::Term(fun), inner));
+            }
+            _ => return None,
+        }
+    }
+    done.pop()
+}
+
+// Mogensen's self-interpreter, E ⌜M⌝ reduces to the normal form of M
+// Y (λe. λm. m (λx. x) (λm. λn. (e m) (e n)) (λm. λv. e (m v)))
+pub fn eval() -> Term {
+    let application = lam("m", lam("n", app(app(var(4), var(2)), app(var(4), var(1)))));
+    let abstraction = lam("m", lam("v", app(var(4), app(var(2), var(1)))));
+    let body = apps(var(1), [i(), application, abstraction]);
+    app(y(), lam("e", lam("m", body)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate;
+    use crate::reduce;
+
+    #[test]
+    fn quotations_round_trip() {
+        for term in generate::enumerate_closed_terms(8) {
+            assert_eq!(unquote(&quote(&term)), Some(term));
+        }
+        assert_eq!(unquote(&quote(&var(-1))), Some(var(-1)));
+        assert_eq!(unquote(&i()), None);
+    }
+
+    #[test]
+    fn eval_reduces_a_quotation() {
+        let term = app(lam("x", app(var(1), var(1))), i());
+        let (normal, _) = reduce::normalize(&app(eval(), quote(&term)), 1000).unwrap();
+        assert!(reduce::alpha_eq(&normal, &i()));
+    }
+
+    #[test]
+    fn deep_terms_are_quoted_without_recursion() {
+        let depth = 100_000;
+        let mut term = var(depth);
+        for _ in 0..depth {
+            term = lam("x", app(term, var(1)));
+        }
+        let quoted = quote(&term);
+        assert_eq!(unquote(&quoted), Some(term));
+    }
+}
@@ -0,0 +1,705 @@
+use crate::church;
+use crate::parser::Term;
+use crate::symbol::Symbol;
+use crate::traverse;
+use crate::types;
+
+// one step on the way from the root of a term down to a subterm
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dir {
+    Body, // into the body of a lambda or type abstraction
+    Fun,  // lhs of an application, or the term of a type application
+    Arg,  // rhs of an application
+}
+
+pub type Path = Vec<Dir>;
+
+// which redex a reduction step picks
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Strategy {
+    #[default]
+    Normal, // leftmost-outermost, finds the normal form whenever there is one
+    // leftmost-innermost outside of lambdas (call by value), arguments are
+    // reduced before they are passed and bodies only once they are applied
+    Applicative,
+}
+
+// add `d` to every bound variable pointing above `cutoff` binders
+// free variables are negative and never affected
+pub fn shift(term: &Term, d: i32, cutoff: i32) -> Term {
+    traverse::map_leaves(term, |leaf, depth| match leaf {
+        Term::Variable(index) if *index > cutoff + depth => Term::Variable(index + d),
+        Term::Variable(index) => Term::Variable(*index),
+        _ => leaf.clone(),
+    })
+}
+
+// replace the variable bound just outside `body` (sitting `depth` binders deep) with `arg`
+// variables bound further out lose one level since that binder disappears
+// `count` goes up by the number of occurrences replaced
+fn subst(body: &Term, arg: &Term, depth: i32, count: &mut usize) -> Term {
+    traverse::map_leaves(body, |leaf, inner| {
+        let depth = depth + inner;
+        match leaf {
+            Term::Variable(index) if *index == depth + 1 => {
+                *count += 1;
+                shift(arg, depth, 0)
+            }
+            Term::Variable(index) if *index > depth + 1 => Term::Variable(index - 1),
+            _ => leaf.clone(),
+        }
+    })
+}
+
+// contract <\x.{body}|arg> into body[x := arg]
+pub fn beta(body: &Term, arg: &Term) -> Term {
+    subst(body, arg, 0, &mut 0)
+}
+
+// beta redexes, type redexes <(/\A.{body}) [T]>, lets and δ-redexes of the primitives
+pub fn is_redex(term: &Term) -> bool {
+    match term {
+        Term::Let(..) => true,
+        Term::Application(lhs, _) if matches!(**lhs, Term::Lambda(..)) => true,
+        Term::TypeApplication(fun, _) => matches!(**fun, Term::TypeLambda(..)),
+        _ => is_delta_redex(term),
+    }
+}
+
+#[cfg(feature = "primitives")]
+fn is_delta_redex(term: &Term) -> bool {
+    crate::primitives::is_delta_redex(term)
+}
+
+#[cfg(not(feature = "primitives"))]
+fn is_delta_redex(_term: &Term) -> bool {
+    false
+}
+
+#[cfg(feature = "primitives")]
+fn delta(term: &Term) -> Term {
+    crate::primitives::delta(term).expect("Not a redex")
+}
+
+#[cfg(not(feature = "primitives"))]
+fn delta(_term: &Term) -> Term {
+    panic!("Not a redex")
+}
+
+// paths of all redexes, leftmost-outermost first
+pub fn redexes(term: &Term) -> Vec<Path> {
+    let mut found = Vec::new();
+    walk_redexes(term, |path| {
+        found.push(path.to_vec());
+        false
+    });
+    found
+}
+
+// visit the paths of the redexes in pre-order until `found` returns true
+fn walk_redexes(term: &Term, mut found: impl FnMut(&[Dir]) -> bool) {
+    walk_paths(term, |node, path| is_redex(node) && found(path))
+}
+
+// visit the subterms with their paths in pre-order until `visit` returns true
+pub(crate) fn walk_paths<'a>(term: &'a Term, mut visit: impl FnMut(&'a Term, &[Dir]) -> bool) {
+    let mut path = Vec::new();
+    // each node with the length of the path above it and the step into it
+    let mut work: Vec<(&'a Term, usize, Option<Dir>)> = vec![(term, 0, None)];
+    while let Some((node, above, dir)) = work.pop() {
+        path.truncate(above);
+        path.extend(dir);
+        if visit(node, &path) {
+            return;
+        }
+        let above = path.len();
+        match node {
+            Term::Variable(_) | Term::Constant(_) => {}
+            Term::Lambda(_, _, body) | Term::TypeLambda(_, body) => {
+                work.push((body, above, Some(Dir::Body)))
+            }
+            Term::TypeApplication(fun, _) => work.push((fun, above, Some(Dir::Fun))),
+            Term::Let(_, bound, body) => {
+                work.push((body, above, Some(Dir::Body)));
+                work.push((bound, above, Some(Dir::Arg)));
+            }
+            Term::Application(lhs, rhs) => {
+                work.push((rhs, above, Some(Dir::Arg)));
+                work.push((lhs, above, Some(Dir::Fun)));
+            }
+        }
+    }
+}
+
+// follow a path down to a subterm, panic if the path does not fit the term
+pub fn subterm<'a>(term: &'a Term, path: &[Dir]) -> &'a Term {
+    path.iter().fold(term, |node, dir| match (node, dir) {
+        (Term::Lambda(_, _, body), Dir::Body) => body,
+        (Term::Application(lhs, _), Dir::Fun) => lhs,
+        (Term::Application(_, rhs), Dir::Arg) => rhs,
+        (Term::TypeLambda(_, body), Dir::Body) => body,
+        (Term::TypeApplication(fun, _), Dir::Fun) => fun,
+        (Term::Let(_, bound, _), Dir::Arg) => bound,
+        (Term::Let(_, _, body), Dir::Body) => body,
+        _ => panic!("Path does not match term structure"),
+    })
+}
+
+// a path as the steps it takes, fun.arg.body, or root for the empty one
+pub fn show_path(path: &[Dir]) -> String {
+    if path.is_empty() {
+        return "root".to_string();
+    }
+    let steps: Vec<&str> = path
+        .iter()
+        .map(|dir| match dir {
+            Dir::Body => "body",
+            Dir::Fun => "fun",
+            Dir::Arg => "arg",
+        })
+        .collect();
+    steps.join(".")
+}
+
+// names of the binders crossed on the way down a path, outermost first
+pub fn binders_along(term: &Term, path: &[Dir]) -> Vec<Symbol> {
+    let mut names = Vec::new();
+    let mut node = term;
+    for dir in path {
+        match (node, dir) {
+            (Term::Lambda(name, _, _), _) | (Term::Let(name, _, _), Dir::Body) => names.push(*name),
+            _ => {}
+        }
+        node = subterm(node, &[*dir]);
+    }
+    names
+}
+
+// contract the redex at `path`, rebuilding the spine above it
+pub fn contract(term: &Term, path: &[Dir]) -> Term {
+    contract_counting(term, path, &mut 0)
+}
+
+// same, adding the variable occurrences substituted to `substitutions`
+pub(crate) fn contract_counting(term: &Term, path: &[Dir], substitutions: &mut usize) -> Term {
+    let mut above = Vec::with_capacity(path.len());
+    let mut node = term;
+    for dir in path {
+        above.push(node);
+        node = subterm(node, &[*dir]);
+    }
+    let result = match node {
+        Term::Application(lhs, arg) => match &**lhs {
+            Term::Lambda(_, _, body) => subst(body, arg, 0, substitutions),
+            _ => delta(node),
+        },
+        Term::TypeApplication(fun, ty) => match &**fun {
+            Term::TypeLambda(param, body) => types::subst_type_in_term(body, *param, ty),
+            _ => panic!("Not a redex"),
+        },
+        Term::Let(_, bound, body) => subst(body, bound, 0, substitutions),
+        _ => panic!("Not a redex"),
+    };
+    rebuild(above, path, result)
+}
+
+// `term` with the subterm at `path` swapped for `new`
+pub(crate) fn replace(term: &Term, path: &[Dir], new: Term) -> Term {
+    let mut above = Vec::with_capacity(path.len());
+    let mut node = term;
+    for dir in path {
+        above.push(node);
+        node = subterm(node, &[*dir]);
+    }
+    rebuild(above, path, new)
+}
+
+// put `result` back at the end of `path`, below the nodes `above` it
+fn rebuild(above: Vec<&Term>, path: &[Dir], mut result: Term) -> Term {
+    for (parent, dir) in above.into_iter().zip(path).rev() {
+        let child = Box::new(result);
+        result = match (parent, dir) {
+            (Term::Lambda(param, annot, _), Dir::Body) => {
+                Term::Lambda(*param, annot.clone(), child)
+            }
+            (Term::Application(_, rhs), Dir::Fun) => Term::Application(child, rhs.clone()),
+            (Term::Application(lhs, _), Dir::Arg) => Term::Application(lhs.clone(), child),
+            (Term::TypeLambda(param, _), Dir::Body) => Term::TypeLambda(*param, child),
+            (Term::TypeApplication(_, ty), Dir::Fun) => Term::TypeApplication(child, ty.clone()),
+            (Term::Let(name, _, body), Dir::Arg) => Term::Let(*name, child, body.clone()),
+            (Term::Let(name, bound, _), Dir::Body) => Term::Let(*name, bound.clone(), child),
+            _ => unreachable!("subterm followed the path"),
+        };
+    }
+    result
+}
+
+// one normal-order (leftmost-outermost) step, None if the term is in normal form
+pub fn step(term: &Term) -> Option<Term> {
+    step_with(term, Strategy::Normal)
+}
+
+// path of the redex `strategy` contracts next
+pub fn next_redex(term: &Term, strategy: Strategy) -> Option<Path> {
+    match strategy {
+        Strategy::Normal => {
+            let mut first = None;
+            walk_redexes(term, |path| {
+                first = Some(path.to_vec());
+                true
+            });
+            first
+        }
+        Strategy::Applicative => {
+            // redexes come in pre-order, each followed by the ones inside it:
+            // one inside the last found replaces it, the first one after them
+            // means the last found has none inside it
+            let mut innermost: Option<Path> = None;
+            walk_paths(term, |node, path| {
+                if path.contains(&Dir::Body) || !is_redex(node) {
+                    return false;
+                }
+                match &innermost {
+                    Some(outer) if !path.starts_with(outer) => true,
+                    _ => {
+                        innermost = Some(path.to_vec());
+                        false
+                    }
+                }
+            });
+            innermost
+        }
+    }
+}
+
+// one step under `strategy`, None if no redex is left for it
+pub fn step_with(term: &Term, strategy: Strategy) -> Option<Term> {
+    next_redex(term, strategy).map(|path| contract(term, &path))
+}
+
+// the head redex: the outermost redex on the spine below the leading
+// abstractions, None for a head normal form \x1.{...<<h|M1>|...>} whose head
+// h is a variable or a constant
+pub fn head_redex(term: &Term) -> Option<Path> {
+    let mut path = Vec::new();
+    let mut node = term;
+    loop {
+        if is_redex(node) {
+            return Some(path);
+        }
+        match node {
+            Term::Variable(_) | Term::Constant(_) => return None,
+            Term::Lambda(_, _, body) | Term::TypeLambda(_, body) => {
+                path.push(Dir::Body);
+                node = body;
+            }
+            Term::Application(fun, _) | Term::TypeApplication(fun, _) => {
+                path.push(Dir::Fun);
+                node = fun;
+            }
+            Term::Let(..) => unreachable!("lets are redexes"),
+        }
+    }
+}
+
+// head reduction to a head normal form in at most `fuel` steps, with the
+// steps taken. it finds one whenever the term has one, unlike weak head or
+// applicative reduction
+pub fn head_normalize(term: &Term, fuel: usize) -> Option<(Term, usize)> {
+    let mut current = term.clone();
+    for steps in 0..=fuel {
+        match head_redex(&current) {
+            Some(path) => current = contract(&current, &path),
+            None => return Some((current, steps)),
+        }
+    }
+    None
+}
+
+// what head reduction tells about a term within some fuel
+#[derive(Clone, Debug)]
+pub enum Solvability {
+    HeadNormal(Term, usize), // solvable: its head normal form and the steps to it
+    Unsolvable(usize),       // head reduction repeats itself, seen after these steps
+    Unknown,                 // neither within the fuel
+}
+
+// solvable terms are the ones with a head normal form. head reduction from
+// an unsolvable one never stops, it is caught when a head redex comes back
+// at the head of what it contracted to, which then happens forever: Ω at
+// once, <<ω3|ω3>|ω3>... and <Y|I> after a few steps. the redex to compare
+// with is renewed at powers of two steps, so loops entered late are caught
+pub fn is_solvable(term: &Term, fuel: usize) -> Solvability {
+    let mut current = term.clone();
+    let mut earlier: Option<(Path, Term)> = None;
+    for steps in 0..=fuel {
+        let Some(path) = head_redex(&current) else {
+            return Solvability::HeadNormal(current, steps);
+        };
+        let redex = subterm(&current, &path);
+        // everything since the earlier redex happened inside it
+        let inside = match &earlier {
+            Some((at, earlier)) if path.starts_with(at) => {
+                if path[at.len()..].iter().all(|dir| *dir == Dir::Fun) && alpha_eq(redex, earlier) {
+                    return Solvability::Unsolvable(steps);
+                }
+                true
+            }
+            _ => false,
+        };
+        if !inside || steps.is_power_of_two() {
+            earlier = Some((path.clone(), redex.clone()));
+        }
+        current = contract(&current, &path);
+    }
+    Solvability::Unknown
+}
+
+// what typability tells about reducing a term
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Termination {
+    Guaranteed, // well-typed, strongly normalizing
+    MayDiverge, // not typable, nothing is known
+}
+
+// typed terms (STLC, System F, let-polymorphism, primitives) strongly normalize
+pub fn termination(term: &Term) -> Termination {
+    match types::type_of(term) {
+        Ok(_) => Termination::Guaranteed,
+        Err(_) => Termination::MayDiverge,
+    }
+}
+
+// step limit for normalize: terminating terms get a generous budget, they only
+// run out on very large normal forms
+pub fn default_fuel(hint: Termination) -> usize {
+    match hint {
+        Termination::Guaranteed => 1_000_000,
+        Termination::MayDiverge => 10_000,
+    }
+}
+
+// reduce to normal form in at most `fuel` steps, returning it with the steps taken
+pub fn normalize(term: &Term, fuel: usize) -> Option<(Term, usize)> {
+    normalize_with(term, fuel, Strategy::Normal)
+}
+
+// same under `strategy`, applicative order stops at values: it leaves lambda bodies alone
+pub fn normalize_with(term: &Term, fuel: usize, strategy: Strategy) -> Option<(Term, usize)> {
+    let mut current = term.clone();
+    for steps in 0..=fuel {
+        match step_with(&current, strategy) {
+            Some(next) => current = next,
+            None => return Some((current, steps)),
+        }
+    }
+    None
+}
+
+// same, with known operations on numerals computed in a single step each
+// (church::arithmetic), rather than by the β-steps they stand for
+pub fn normalize_arithmetic(term: &Term, fuel: usize, strategy: Strategy) -> Option<(Term, usize)> {
+    let mut current = term.clone();
+    for steps in 0..=fuel {
+        match step_arithmetic(&current, strategy) {
+            Some(next) => current = next,
+            None => return Some((current, steps)),
+        }
+    }
+    None
+}
+
+// one step of normalize_arithmetic
+pub(crate) fn step_arithmetic(term: &Term, strategy: Strategy) -> Option<Term> {
+    rewrite_first(term, strategy, church::arithmetic).or_else(|| step_with(term, strategy))
+}
+
+// the term with its leftmost-outermost subterm that `rewrite` has a result
+// for replaced by that result, applicative order only looks outside of lambdas
+pub(crate) fn rewrite_first(
+    term: &Term,
+    strategy: Strategy,
+    mut rewrite: impl FnMut(&Term) -> Option<Term>,
+) -> Option<Term> {
+    let mut first = None;
+    walk_paths(term, |node, path| {
+        if strategy == Strategy::Applicative && path.contains(&Dir::Body) {
+            return false;
+        }
+        first = rewrite(node).map(|result| (path.to_vec(), result));
+        first.is_some()
+    });
+    let (path, result) = first?;
+    let mut above = Vec::with_capacity(path.len());
+    let mut node = term;
+    for dir in &path {
+        above.push(node);
+        node = subterm(node, &[*dir]);
+    }
+    Some(rebuild(above, &path, result))
+}
+
+// no redex anywhere
+pub fn is_normal_form(term: &Term) -> bool {
+    let mut work = vec![term];
+    while let Some(node) = work.pop() {
+        if is_redex(node) {
+            return false;
+        }
+        match node {
+            Term::Variable(_) | Term::Constant(_) => {}
+            Term::Lambda(_, _, body) | Term::TypeLambda(_, body) => work.push(body),
+            Term::TypeApplication(fun, _) => work.push(fun),
+            Term::Application(lhs, rhs) | Term::Let(_, lhs, rhs) => {
+                work.push(rhs);
+                work.push(lhs);
+            }
+        }
+    }
+    true
+}
+
+// weak head normal form: an abstraction, or a head that cannot reduce applied to anything
+pub fn is_whnf(term: &Term) -> bool {
+    let mut node = term;
+    loop {
+        match node {
+            Term::Variable(_) | Term::Constant(_) | Term::Lambda(..) | Term::TypeLambda(..) => {
+                return true;
+            }
+            Term::Let(..) => return false,
+            Term::Application(head, _) | Term::TypeApplication(head, _) => {
+                if is_redex(node) {
+                    return false;
+                }
+                node = head;
+            }
+        }
+    }
+}
+
+// values of call by value: abstractions, variables, constants and partly applied
+// primitives, that is a primitive applied to values short of a δ-redex
+pub fn is_value(term: &Term) -> bool {
+    let mut work = vec![term];
+    while let Some(node) = work.pop() {
+        match node {
+            Term::Variable(_) | Term::Constant(_) | Term::Lambda(..) | Term::TypeLambda(..) => {}
+            Term::Application(..) => {
+                let mut spine = node;
+                while let Term::Application(lhs, rhs) = spine {
+                    if is_redex(spine) {
+                        return false;
+                    }
+                    work.push(rhs);
+                    spine = lhs;
+                }
+                if !matches!(spine, Term::Constant(_)) {
+                    return false;
+                }
+            }
+            Term::TypeApplication(..) | Term::Let(..) => return false,
+        }
+    }
+    true
+}
+
+// no free variables, bound indices pointing past the root count as free too
+pub fn is_closed(term: &Term) -> bool {
+    let mut work = vec![(term, 0)];
+    while let Some((node, depth)) = work.pop() {
+        match node {
+            Term::Variable(index) if *index <= 0 || *index > depth => return false,
+            Term::Variable(_) | Term::Constant(_) => {}
+            Term::Lambda(_, _, body) => work.push((body, depth + 1)),
+            Term::TypeLambda(_, body) | Term::TypeApplication(body, _) => work.push((body, depth)),
+            Term::Application(lhs, rhs) => {
+                work.push((rhs, depth));
+                work.push((lhs, depth));
+            }
+            Term::Let(_, bound, body) => {
+                work.push((body, depth + 1));
+                work.push((bound, depth));
+            }
+        }
+    }
+    true
+}
+
+// a closed term of the pure calculus: only variables, abstractions and applications
+pub fn is_combinator(term: &Term) -> bool {
+    let mut work = vec![term];
+    while let Some(node) = work.pop() {
+        match node {
+            Term::Variable(_) => {}
+            Term::Lambda(_, _, body) => work.push(body),
+            Term::Application(lhs, rhs) => {
+                work.push(rhs);
+                work.push(lhs);
+            }
+            _ => return false,
+        }
+    }
+    is_closed(term)
+}
+
+// equal up to the names of binders and their annotations
+pub fn alpha_eq(a: &Term, b: &Term) -> bool {
+    traverse::zip_all(a, b, |a, b| match (a, b) {
+        (Term::Variable(x), Term::Variable(y)) => x == y,
+        (Term::Constant(x), Term::Constant(y)) => x == y,
+        (Term::TypeApplication(_, s), Term::TypeApplication(_, t)) => types::same_type(s, t),
+        (Term::Lambda(..), Term::Lambda(..))
+        | (Term::TypeLambda(..), Term::TypeLambda(..))
+        | (Term::Application(..), Term::Application(..))
+        | (Term::Let(..), Term::Let(..)) => true,
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::{app, big_omega, i, k, lam, var, y};
+    use crate::parser::{ParserConfig, parse_with_config};
+
+    #[test]
+    fn solvability_by_head_reduction() {
+        assert!(matches!(
+            is_solvable(&big_omega(), 100),
+            Solvability::Unsolvable(1)
+        ));
+        // <Y|I> loops only after a few steps
+        assert!(matches!(
+            is_solvable(&app(y(), i()), 100),
+            Solvability::Unsolvable(_)
+        ));
+        // Ω as an argument is never reduced by head reduction
+        let solvable = lam("x", app(var(1), big_omega()));
+        match is_solvable(&solvable, 100) {
+            Solvability::HeadNormal(normal, 0) => assert_eq!(normal, solvable),
+            other => panic!("{:?}", other),
+        }
+        let redex = app(lam("y", lam("x", app(var(1), var(2)))), big_omega());
+        assert!(matches!(
+            is_solvable(&redex, 100),
+            Solvability::HeadNormal(_, 1)
+        ));
+    }
+
+    #[test]
+    fn solvability_is_unknown_past_the_fuel() {
+        // <<ω3|ω3>|ω3>... is caught once the loop comes round, not before
+        let omega3 = lam("x", app(app(var(1), var(1)), var(1)));
+        let growing = app(omega3.clone(), omega3);
+        assert!(matches!(is_solvable(&growing, 0), Solvability::Unknown));
+        assert!(matches!(
+            is_solvable(&growing, 10),
+            Solvability::Unsolvable(_)
+        ));
+        // a head normal form ten steps away
+        let nested = (0..10).fold(var(-1), |term, _| app(i(), term));
+        assert!(matches!(is_solvable(&nested, 9), Solvability::Unknown));
+        assert!(matches!(
+            is_solvable(&nested, 10),
+            Solvability::HeadNormal(_, 10)
+        ));
+    }
+
+    #[test]
+    fn termination_is_checked_however_deep_the_term() {
+        assert_eq!(termination(&big_omega()), Termination::MayDiverge);
+        let deep = (0..20_000).fold(var(1), |body, _| lam("x", body));
+        assert_eq!(termination(&deep), Termination::Guaranteed);
+    }
+
+    #[test]
+    fn term_predicates() {
+        // normal form, weak head normal form, value, closed, combinator
+        let table = [
+            (r"\x.{x}", [true, true, true, true, true]),
+            (r"y", [true, true, true, false, false]),
+            (r"<y|\x.{x}>", [true, true, false, false, false]),
+            (r"\x.{<\y.{y}|x>}", [false, true, true, true, true]),
+            (r"<\x.{x}|y>", [false, false, false, false, false]),
+            (r"<<x|<\y.{y}|x>>|x>", [false, true, false, false, false]),
+            (r"let x = \y.{y} in x", [false, false, false, true, false]),
+            (r"\f.{<f|<f|f>>}", [true, true, true, true, true]),
+        ];
+        for (source, expected) in table {
+            let (term, _) = parse_with_config(source, ParserConfig::default()).unwrap();
+            let found = [
+                is_normal_form(&term),
+                is_whnf(&term),
+                is_value(&term),
+                is_closed(&term),
+                is_combinator(&term),
+            ];
+            assert_eq!(found, expected, "{}", source);
+        }
+        // an index pointing past the root is not bound by anything
+        assert!(!is_closed(&lam("x", var(2))));
+        assert!(!is_combinator(&lam("x", var(2))));
+    }
+
+    #[test]
+    fn alpha_equivalence() {
+        let parse = |source| {
+            parse_with_config(source, ParserConfig::default())
+                .unwrap()
+                .0
+        };
+        // binder names and annotations do not matter
+        assert!(alpha_eq(
+            &parse(r"\x.{\y.{<x|y>}}"),
+            &parse(r"\a.{\b.{<a|b>}}")
+        ));
+        assert!(alpha_eq(&parse(r"\x:A.{x}"), &parse(r"\y.{y}")));
+        assert!(alpha_eq(
+            &parse(r"let f = \x.{x} in f"),
+            &parse(r"let g = \y.{y} in g")
+        ));
+        // which binder a variable points to does
+        assert!(!alpha_eq(&parse(r"\x.{\y.{x}}"), &parse(r"\x.{\y.{y}}")));
+        // free variables are compared by index, y and z are both the first one
+        assert!(alpha_eq(&parse(r"\x.{<x|y>}"), &parse(r"\x.{<x|z>}")));
+        assert!(!alpha_eq(&app(var(-1), var(-2)), &app(var(-1), var(-1))));
+        assert!(!alpha_eq(&parse(r"\x.{x}"), &parse(r"<\x.{x}|y>")));
+    }
+
+    #[test]
+    fn applicative_order_reduces_arguments_first() {
+        let parse = |source| {
+            parse_with_config(source, ParserConfig::default())
+                .unwrap()
+                .0
+        };
+        // <<K|I>|Ω>: normal order drops Ω, applicative order reduces it forever
+        let term = app(app(k(), i()), big_omega());
+        assert_eq!(next_redex(&term, Strategy::Normal), Some(vec![Dir::Fun]));
+        assert_eq!(
+            next_redex(&term, Strategy::Applicative),
+            Some(vec![Dir::Fun])
+        );
+        let after = step_with(&term, Strategy::Applicative).unwrap();
+        assert_eq!(
+            next_redex(&after, Strategy::Applicative),
+            Some(vec![Dir::Arg])
+        );
+        assert!(alpha_eq(&normalize(&term, 10).unwrap().0, &i()));
+        assert!(normalize_with(&term, 100, Strategy::Applicative).is_none());
+        // the innermost redex of the leftmost, not the leftmost innermost of all
+        let nested = parse(r"<<\x.{x}|<\y.{y}|<\z.{z}|a>>>|<\w.{w}|b>>");
+        assert_eq!(
+            next_redex(&nested, Strategy::Applicative),
+            Some(vec![Dir::Fun, Dir::Arg, Dir::Arg])
+        );
+        // nothing under a lambda
+        assert_eq!(
+            next_redex(&parse(r"\x.{<\y.{y}|x>}"), Strategy::Applicative),
+            None
+        );
+        let (normal, _) = normalize_with(&nested, 10, Strategy::Applicative).unwrap();
+        assert_eq!(normal, parse("<a|b>"));
+    }
+}
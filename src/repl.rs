@@ -0,0 +1,344 @@
+use std::io::{self, BufRead, Write};
+
+use crate::bidir;
+use crate::diagnostic::Span;
+use crate::latex;
+use crate::parser::{Parser, ParserConfig, Term};
+use crate::prelude;
+use crate::pretty_printer::PrettyPrinter;
+use crate::reduce::{self, Dir, Path, Strategy, Termination};
+use crate::rewrite::{Rule, Rules};
+use crate::symbol::Symbol;
+use crate::tokenizer;
+use crate::types;
+use crate::unify;
+use crate::unparse::unparse;
+
+const HELP: &str = "\
+TERM          reduce TERM to normal form
+:type TERM    show the principal type of TERM without evaluating it
+:bidir TERM   same with the bidirectional checker, binders in inference position need annotations
+:hints on|off type check before reducing and report whether TERM must terminate (default on)
+:strategy S   normal (default) or applicative order, which stops at values; letrec follows it
+:arith on|off compute succ pred add mul pow sub on numerals in one step each (default on)
+:share N|off  print repeated subterms of at least N nodes once, let-bound (default off)
+:rule NAME: PATTERN => REPLACEMENT
+              rewrite matches of PATTERN before any β-step, names starting with an
+              uppercase letter are meta-variables, <<plus|X>|z> => X for example
+:rules        list the rules, :unrule NAME removes one
+:pick TERM    reduce TERM step by step, contracting the redex numbered N on :pick N;
+              :pick alone lists the redexes chosen so far with their paths
+:unify A == B solve for the meta-variables in A and B up to βη, applied to distinct
+              bound variables they are found, \\x.{<F|x>} == \\x.{<<g|x>|x>} for example
+:help         show this message
+
+TERM may also be written in LaTeX, \\lambda x.\\, x\\,y, and is echoed in this syntax first
+
+free variables named after a prelude definition stand for it: I K S B C Y omega
+true false succ add mul pow pred sub is_zero leq nil cons head tail is_nil map foldr pair fst snd,
+c0 c1 c2 ... for numerals, and the Scott encodings scott_zero scott_succ scott_pred
+scott_is_zero scott_nil scott_cons scott_head scott_tail scott_is_nil, s0 s1 s2 ...
+:quit         leave the REPL";
+
+struct Parsed {
+    term: Term,
+    free: Vec<Symbol>,
+    spans: Vec<Span>,
+}
+
+// parser::parse_with_config, keeping the node spans for type errors
+fn parse(source: &str, strategy: Strategy) -> Result<Parsed, String> {
+    let (tokens, token_spans) = tokenizer::try_tokenize_spanned(source).map_err(|(msg, _)| msg)?;
+    // the REPL accepts every language level
+    let config = ParserConfig {
+        system_f: true,
+        strategy,
+        ..Default::default()
+    };
+    let mut parser = Parser::with_config(&tokens, config).with_token_spans(&token_spans);
+    let (term, free) = parser.try_parse()?;
+    let (term, spans) = prelude::resolve_spanned(&term, &free, parser.node_spans());
+    Ok(Parsed { term, free, spans })
+}
+
+// LaTeX input in the syntax of the parser, anything else as it is
+fn transcribe(source: &str) -> Result<String, String> {
+    if !latex::is_latex(source) {
+        return Ok(source.to_string());
+    }
+    let (term, free) = latex::parse(source)?;
+    let native = unparse(&term, &free);
+    println!("= {}", native);
+    Ok(native)
+}
+
+fn show_unifier(source: &str) {
+    let (a, b, free) = match unify::parse(source) {
+        Ok(parsed) => parsed,
+        Err(msg) => return println!("error: {}", msg),
+    };
+    match unify::unify(&a, &b, &free) {
+        Some(subst) if subst.iter().next().is_none() => println!("equal"),
+        Some(subst) => {
+            for (meta, solution) in subst.iter() {
+                println!("{} := {}", meta, unparse(solution, &subst.names));
+            }
+        }
+        None => println!("no unifier"),
+    }
+}
+
+// a reduction where the user picks each redex, see :pick
+struct Picking {
+    term: Term,
+    free: Vec<Symbol>,
+    redexes: Vec<Path>,
+    chosen: Vec<(Path, String)>, // the redexes contracted so far, printed
+}
+
+impl Picking {
+    fn new(term: Term, free: Vec<Symbol>) -> Picking {
+        let redexes = reduce::redexes(&term);
+        Picking {
+            term,
+            free,
+            redexes,
+            chosen: Vec::new(),
+        }
+    }
+
+    fn show_redex(&self, path: &[Dir]) -> String {
+        let binders = reduce::binders_along(&self.term, path);
+        let redex = reduce::subterm(&self.term, path);
+        PrettyPrinter::new().format_under(redex, &binders, &self.free)
+    }
+
+    fn show(&self) {
+        println!("{}", PrettyPrinter::new().format(&self.term, &self.free));
+        if self.redexes.is_empty() {
+            println!("normal form after {} steps", self.chosen.len());
+        }
+        for (number, path) in self.redexes.iter().enumerate() {
+            println!(
+                "{:>3}. {}   at {}",
+                number + 1,
+                self.show_redex(path),
+                reduce::show_path(path)
+            );
+        }
+    }
+
+    fn contract(&mut self, number: usize) {
+        let Some(path) = number
+            .checked_sub(1)
+            .and_then(|at| self.redexes.get(at))
+            .cloned()
+        else {
+            return println!("no redex {}, there are {}", number, self.redexes.len());
+        };
+        let shown = self.show_redex(&path);
+        self.term = reduce::contract(&self.term, &path);
+        self.redexes = reduce::redexes(&self.term);
+        self.chosen.push((path, shown));
+        self.show();
+    }
+
+    fn history(&self) {
+        if self.chosen.is_empty() {
+            println!("no redex contracted yet");
+        }
+        for (step, (path, redex)) in self.chosen.iter().enumerate() {
+            println!(
+                "{:>3}. {}   at {}",
+                step + 1,
+                redex,
+                reduce::show_path(path)
+            );
+        }
+    }
+}
+
+// :pick, starting over on a term, or going on with a number
+fn pick(picking: &mut Option<Picking>, rest: &str, strategy: Strategy) {
+    let rest = rest.trim();
+    match (picking.as_mut(), rest.parse::<usize>()) {
+        (Some(current), Ok(number)) => current.contract(number),
+        (Some(current), _) if rest.is_empty() => current.history(),
+        (None, _) if rest.is_empty() => println!("usage: :pick TERM, then :pick N"),
+        _ => match transcribe(rest).and_then(|source| parse(&source, strategy)) {
+            Ok(parsed) => {
+                let started = picking.insert(Picking::new(parsed.term, parsed.free));
+                started.show();
+            }
+            Err(msg) => println!("error: {}", msg),
+        },
+    }
+}
+
+fn show_type(source: &str, bidirectional: bool) {
+    print!("{}", describe_type(source, bidirectional));
+}
+
+// what :type, or :bidir, shows for `source`
+fn describe_type(source: &str, bidirectional: bool) -> String {
+    let parsed = match parse(source, Strategy::Normal) {
+        Ok(parsed) => parsed,
+        Err(msg) => return format!("error: {}\n", msg),
+    };
+    let ty = if bidirectional {
+        bidir::infer(&parsed.term)
+    } else {
+        types::type_of(&parsed.term)
+    };
+    match ty {
+        Ok(ty) => format!("{} : {}\n", source, ty),
+        Err(err) => err.render(source, &parsed.spans),
+    }
+}
+
+// the printer for normal forms, sharing as set by :share
+fn printer(sharing: Option<usize>) -> PrettyPrinter {
+    match sharing {
+        Some(min_size) => PrettyPrinter::new().with_sharing(min_size),
+        None => PrettyPrinter::new(),
+    }
+}
+
+fn evaluate(
+    source: &str,
+    hints: bool,
+    arithmetic: bool,
+    strategy: Strategy,
+    rules: &Rules,
+    sharing: Option<usize>,
+) {
+    let mut parsed = match parse(source, strategy) {
+        Ok(parsed) => parsed,
+        Err(msg) => return println!("error: {}", msg),
+    };
+    // rules can make any term diverge, typing tells nothing then
+    let hint = if hints && rules.is_empty() {
+        let hint = reduce::termination(&parsed.term);
+        match hint {
+            Termination::Guaranteed => println!("guaranteed to terminate (well-typed)"),
+            Termination::MayDiverge => println!("may diverge (not typable)"),
+        }
+        hint
+    } else {
+        Termination::MayDiverge
+    };
+    let fuel = reduce::default_fuel(hint);
+    if !rules.is_empty() {
+        match rules.normalize(&parsed.term, &mut parsed.free, fuel, strategy, arithmetic) {
+            Ok((normal, steps)) => {
+                let shown = printer(sharing).format(&normal, &parsed.free);
+                println!("{}   ({} steps)", shown, steps);
+            }
+            Err(stopped) => println!("{}", stopped),
+        }
+        return;
+    }
+    let result = if arithmetic {
+        reduce::normalize_arithmetic(&parsed.term, fuel, strategy)
+    } else {
+        reduce::normalize_with(&parsed.term, fuel, strategy)
+    };
+    match result {
+        Some((normal, steps)) => {
+            let shown = printer(sharing).format(&normal, &parsed.free);
+            println!("{}   ({} steps)", shown, steps);
+        }
+        None => println!("no normal form within {} steps", fuel),
+    }
+}
+
+pub fn run() {
+    let mut stdin = io::stdin().lock();
+    let mut line = String::new();
+    let mut hints = true;
+    let mut arithmetic = true;
+    let mut strategy = Strategy::Normal;
+    let mut sharing = None;
+    let mut rules = Rules::default();
+    let mut picking = None;
+    loop {
+        print!("λ> ");
+        let _ = io::stdout().flush();
+        line.clear();
+        if stdin.read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        let input = line.trim();
+        let (command, rest) = input.split_once(' ').unwrap_or((input, ""));
+        match command {
+            "" => {}
+            ":type" | ":t" | ":bidir" => match transcribe(rest.trim()) {
+                Ok(source) => show_type(&source, command == ":bidir"),
+                Err(msg) => println!("error: {}", msg),
+            },
+            ":hints" => match rest.trim() {
+                "on" => hints = true,
+                "off" => hints = false,
+                _ => println!("usage: :hints on|off"),
+            },
+            ":share" => match rest.trim() {
+                "off" => sharing = None,
+                size => match size.parse() {
+                    Ok(min_size) => sharing = Some(min_size),
+                    Err(_) => println!("usage: :share N|off"),
+                },
+            },
+            ":arith" => match rest.trim() {
+                "on" => arithmetic = true,
+                "off" => arithmetic = false,
+                _ => println!("usage: :arith on|off"),
+            },
+            ":strategy" => match rest.trim() {
+                "normal" => strategy = Strategy::Normal,
+                "applicative" => strategy = Strategy::Applicative,
+                _ => println!("usage: :strategy normal|applicative"),
+            },
+            ":rule" => match Rule::parse(rest) {
+                Ok(rule) => rules.add(rule),
+                Err(msg) => println!("error: {}", msg),
+            },
+            ":rules" => {
+                for rule in rules.iter() {
+                    println!("{}", rule);
+                }
+            }
+            ":unrule" => {
+                if !rules.remove(rest.trim()) {
+                    println!("no rule named {}", rest.trim());
+                }
+            }
+            ":pick" => pick(&mut picking, rest, strategy),
+            ":unify" => show_unifier(rest),
+            ":help" | ":h" => println!("{}", HELP),
+            ":quit" | ":q" => break,
+            cmd if cmd.starts_with(':') => println!("unknown command {}, try :help", cmd),
+            _ => match transcribe(input) {
+                Ok(source) => evaluate(&source, hints, arithmetic, strategy, &rules, sharing),
+                Err(msg) => println!("error: {}", msg),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn types_of_deep_terms_are_shown() {
+        let n = 20_000;
+        let nested =
+            |binder: &str, body: &str| format!("{}{}{}", binder.repeat(n), body, "}".repeat(n));
+        let source = nested(r"\x.{", "x");
+        assert_eq!(describe_type(&source, false).matches(" → ").count(), n);
+        let source = nested(r"\x:A.{", "x");
+        assert!(describe_type(&source, true).ends_with(" → A → A\n"));
+        let source = nested(r"\x:A.{", "<x|x>");
+        assert!(describe_type(&source, true).starts_with("error: applied a non-function"));
+    }
+}
@@ -0,0 +1,234 @@
+// user-defined rewrite rules, `name: pattern => replacement`, applied by the
+// evaluator alongside β. both sides are patterns (see pattern): names
+// starting with an uppercase letter are meta-variables, other free names are
+// symbols, so `plus_z: <<plus|X>|z> => X` gives meaning to symbols of the
+// user's own. rules read names literally, the prelude is not applied to them
+//
+// the meta-variables of the replacement must occur in the pattern, and a
+// pattern that is a lone meta-variable is refused, it would rewrite every
+// term forever
+//
+// like arithmetic in reduce, the leftmost-outermost rule match is rewritten
+// before any β-step. the guard against rules that do not terminate is fuel
+// plus a bound on the size of the term, which rules can grow without end
+use std::fmt;
+
+use crate::parser::Term;
+use crate::pattern::{self, Pattern};
+use crate::reduce::{self, Strategy};
+use crate::symbol::Symbol;
+
+pub struct Rule {
+    pub name: String,
+    pattern: Pattern,
+    replacement: Pattern,
+    source: String, // the text after the name
+}
+
+impl Rule {
+    pub fn parse(text: &str) -> Result<Rule, String> {
+        let (name, sides) = text
+            .split_once(':')
+            .ok_or("expected `name: pattern => replacement`")?;
+        let name = name.trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("invalid rule name `{}`", name));
+        }
+        let (pattern, replacement) = sides
+            .split_once("=>")
+            .ok_or("expected `=>` between pattern and replacement")?;
+        let pattern = Pattern::parse(pattern)?;
+        let replacement = Pattern::parse(replacement)?;
+        if pattern.is_trivial() {
+            return Err("the pattern is a lone meta-variable, it matches every term".to_string());
+        }
+        if let Some(unknown) = replacement
+            .metas()
+            .find(|meta| !pattern.metas().any(|known| known == *meta))
+        {
+            return Err(format!(
+                "meta-variable {} of the replacement is not in the pattern",
+                unknown
+            ));
+        }
+        Ok(Rule {
+            name: name.to_string(),
+            pattern,
+            replacement,
+            source: sides.trim().to_string(),
+        })
+    }
+
+    // what `term` rewrites to when it matches the pattern at its root, free
+    // names the replacement brings in are added to `free`
+    pub fn apply(&self, term: &Term, free: &mut Vec<Symbol>) -> Option<Term> {
+        let substitution = pattern::match_term(&self.pattern, term, free)?;
+        self.replacement.instantiate(&substitution, free)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.source)
+    }
+}
+
+// why normalize gave up
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stopped {
+    Fuel(usize), // the steps taken
+    Size(usize), // the size the term reached
+}
+
+impl fmt::Display for Stopped {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stopped::Fuel(steps) => write!(f, "no normal form within {} steps", steps),
+            Stopped::Size(size) => write!(f, "the term grew to {} nodes", size),
+        }
+    }
+}
+
+// terms larger than this are taken for a rewriting that does not terminate
+pub const MAX_SIZE: usize = 1_000_000;
+
+#[derive(Default)]
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+impl Rules {
+    // a rule with the name of an earlier one replaces it, in its place
+    pub fn add(&mut self, rule: Rule) {
+        match self.rules.iter_mut().find(|known| known.name == rule.name) {
+            Some(known) => *known = rule,
+            None => self.rules.push(rule),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.rules.len();
+        self.rules.retain(|rule| rule.name != name);
+        self.rules.len() < before
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Rule> {
+        self.rules.iter()
+    }
+
+    // the first rule, in the order they were added, that applies at the root
+    pub fn rewrite(&self, term: &Term, free: &mut Vec<Symbol>) -> Option<Term> {
+        self.rules.iter().find_map(|rule| rule.apply(term, free))
+    }
+
+    // one step: a rule match, else arithmetic on numerals when `arithmetic`
+    // is set, else β
+    pub fn step(
+        &self,
+        term: &Term,
+        free: &mut Vec<Symbol>,
+        strategy: Strategy,
+        arithmetic: bool,
+    ) -> Option<Term> {
+        reduce::rewrite_first(term, strategy, |node| self.rewrite(node, free)).or_else(|| {
+            if arithmetic {
+                reduce::step_arithmetic(term, strategy)
+            } else {
+                reduce::step_with(term, strategy)
+            }
+        })
+    }
+
+    // to normal form in at most `fuel` steps, with the steps taken
+    pub fn normalize(
+        &self,
+        term: &Term,
+        free: &mut Vec<Symbol>,
+        fuel: usize,
+        strategy: Strategy,
+        arithmetic: bool,
+    ) -> Result<(Term, usize), Stopped> {
+        let mut current = term.clone();
+        for steps in 0..=fuel {
+            match self.step(&current, free, strategy, arithmetic) {
+                Some(next) if next.size() > MAX_SIZE => return Err(Stopped::Size(next.size())),
+                Some(next) => current = next,
+                None => return Ok((current, steps)),
+            }
+        }
+        Err(Stopped::Fuel(fuel))
+    }
+
+    // rules one per line, `--` comments and blank lines skipped
+    pub fn parse(source: &str) -> Result<Rules, String> {
+        let mut rules = Rules::default();
+        for (number, line) in source.lines().enumerate() {
+            let line = line.split_once("--").map_or(line, |(code, _)| code).trim();
+            if !line.is_empty() {
+                rules
+                    .add(Rule::parse(line).map_err(|msg| format!("line {}: {}", number + 1, msg))?);
+            }
+        }
+        Ok(rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{self, ParserConfig};
+    use crate::unparse::unparse;
+
+    const PEANO: &str = "
+        plus_z: <<plus|X>|z> => X
+        plus_s: <<plus|X>|<s|Y>> => <s|<<plus|X>|Y>> -- recursion on the right
+    ";
+
+    fn run(rules: &Rules, source: &str, fuel: usize) -> Result<String, Stopped> {
+        let (term, mut free) = parser::parse_with_config(source, ParserConfig::default()).unwrap();
+        let (normal, _) = rules.normalize(&term, &mut free, fuel, Strategy::Normal, false)?;
+        Ok(unparse(&normal, &free))
+    }
+
+    #[test]
+    fn rules_fire_until_none_applies() {
+        let rules = Rules::parse(PEANO).unwrap();
+        assert_eq!(
+            run(&rules, "<<plus|<s|z>>|<s|<s|z>>>", 100),
+            Ok("<s|<s|<s|z>>>".to_string())
+        );
+        // β still runs where no rule matches
+        assert_eq!(
+            run(&rules, r"<\x.{<<plus|x>|z>}|<s|z>>", 100),
+            Ok("<s|z>".to_string())
+        );
+    }
+
+    #[test]
+    fn bad_rules_are_refused() {
+        assert!(Rule::parse("all: X => z").is_err());
+        assert!(Rule::parse("new: <f|X> => Y").is_err());
+        assert!(Rule::parse("no arrow: <f|X>").is_err());
+        let error = Rules::parse("ok: <f|X> => X\n\nbad <f|X> => X").err();
+        assert_eq!(
+            error.as_deref(),
+            Some("line 3: expected `name: pattern => replacement`")
+        );
+    }
+
+    #[test]
+    fn rules_that_do_not_terminate_run_out() {
+        let mut rules = Rules::default();
+        rules.add(Rule::parse("loop: <f|X> => <f|<f|X>>").unwrap());
+        assert_eq!(run(&rules, "<f|z>", 50), Err(Stopped::Fuel(50)));
+        // a later rule of the same name replaces it
+        rules.add(Rule::parse("loop: <f|X> => X").unwrap());
+        assert_eq!(run(&rules, "<f|<f|z>>", 50), Ok("z".to_string()));
+        assert!(rules.remove("loop"));
+        assert!(rules.is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+// JSON-RPC 2.0 over stdin and stdout, one message per line, so another
+// process can keep the engine running and send it terms as it goes
+//
+// methods are parse, normalize, pretty and type, their params an object with
+// the "term" (normalize also takes "strategy" and "fuel", see api). results are
+// the answers of api without "ok", a term that fails gives an error with code
+// -32000 and the message. batches are answered with an array, notifications
+// not at all
+use std::io::{self, BufRead, Write};
+
+use crate::api;
+use crate::json::Json;
+
+pub fn run() {
+    let mut output = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(answer) = respond(&line) {
+            let _ = writeln!(output, "{}", answer);
+            let _ = output.flush();
+        }
+    }
+}
+
+// the reply to one line, None when it holds only notifications
+fn respond(line: &str) -> Option<Json> {
+    match Json::parse(line) {
+        Ok(Json::Array(batch)) if !batch.is_empty() => {
+            let answers: Vec<Json> = batch.iter().filter_map(answer).collect();
+            (!answers.is_empty()).then_some(Json::Array(answers))
+        }
+        Ok(message) => answer(&message),
+        Err(error) => Some(error_response(
+            Json::Null,
+            -32700,
+            format!("parse error: {}", error),
+        )),
+    }
+}
+
+fn error_response(id: Json, code: i32, message: String) -> Json {
+    Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("id", id),
+        (
+            "error",
+            Json::object([
+                ("code", Json::Number(code as f64)),
+                ("message", message.into()),
+            ]),
+        ),
+    ])
+}
+
+// the response to a request, None for a notification
+fn answer(message: &Json) -> Option<Json> {
+    let id = message.get("id").cloned();
+    let method = message.get("method").and_then(Json::as_str);
+    let (Some(method), Some(Json::String(_) | Json::Number(_) | Json::Null) | None) = (method, &id)
+    else {
+        return Some(error_response(
+            Json::Null,
+            -32600,
+            "invalid request".to_string(),
+        ));
+    };
+    let result = call(method, message.get("params").unwrap_or(&Json::Null));
+    let id = id?;
+    Some(match result {
+        Ok(result) => Json::object([("jsonrpc", "2.0".into()), ("id", id), ("result", result)]),
+        Err((code, message)) => error_response(id, code, message),
+    })
+}
+
+fn call(method: &str, params: &Json) -> Result<Json, (i32, String)> {
+    let invalid = |message: String| (-32602, message);
+    let term = || {
+        params
+            .get("term")
+            .and_then(Json::as_str)
+            .ok_or_else(|| invalid("\"term\" must be a string".to_string()))
+    };
+    let answer = match method {
+        "parse" => api::parse(term()?),
+        "normalize" => {
+            let (source, fuel, strategy) = api::normalize_params(params).map_err(invalid)?;
+            api::normalize_with(source, fuel, strategy)
+        }
+        "pretty" => api::pretty(term()?),
+        "type" => api::type_of(term()?),
+        _ => return Err((-32601, format!("unknown method {}", method))),
+    };
+    // api answers carry their own success flag, the protocol has its own
+    let Json::Object(fields) = answer else {
+        unreachable!("api answers are objects")
+    };
+    match fields.iter().find(|(key, _)| key == "ok") {
+        Some((_, Json::Bool(true))) => Ok(Json::Object(
+            fields.into_iter().filter(|(key, _)| key != "ok").collect(),
+        )),
+        _ => {
+            let message = fields
+                .into_iter()
+                .find(|(key, _)| key == "error")
+                .and_then(|(_, message)| message.as_str().map(str::to_string))
+                .unwrap_or_default();
+            Err((-32000, message))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_code(reply: &Json) -> Option<f64> {
+        reply.get("error")?.get("code")?.as_f64()
+    }
+
+    #[test]
+    fn malformed_and_invalid_messages_are_errors() {
+        let reply = respond("{\"jsonrpc\": \"2.0\", ").unwrap();
+        assert_eq!(reply.get("id"), Some(&Json::Null));
+        assert_eq!(error_code(&reply), Some(-32700.0));
+        let reply = respond(r#"{"jsonrpc": "2.0", "id": 1}"#).unwrap();
+        assert_eq!(error_code(&reply), Some(-32600.0));
+    }
+
+    #[test]
+    fn unknown_methods_and_bad_params_are_errors() {
+        let reply = respond(r#"{"jsonrpc": "2.0", "id": 1, "method": "evaluate"}"#).unwrap();
+        assert_eq!(reply.get("id"), Some(&Json::Number(1.0)));
+        assert_eq!(error_code(&reply), Some(-32601.0));
+        let reply =
+            respond(r#"{"jsonrpc": "2.0", "id": 2, "method": "pretty", "params": {"term": 3}}"#)
+                .unwrap();
+        assert_eq!(error_code(&reply), Some(-32602.0));
+        let reply = respond(
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "normalize", "params": {"term": "x", "fuel": -1}}"#,
+        )
+        .unwrap();
+        assert_eq!(error_code(&reply), Some(-32602.0));
+        let reply =
+            respond(r#"{"jsonrpc": "2.0", "id": 4, "method": "parse", "params": {"term": "<x|"}}"#)
+                .unwrap();
+        assert_eq!(error_code(&reply), Some(-32000.0));
+    }
+
+    #[test]
+    fn notifications_get_no_reply() {
+        let notification = r#"{"jsonrpc": "2.0", "method": "parse", "params": {"term": "x"}}"#;
+        assert_eq!(respond(notification), None);
+        assert_eq!(respond(&format!("[{0}, {0}]", notification)), None);
+        // the requests of a batch are answered, its notifications left out
+        let batch = format!(
+            r#"[{}, {{"jsonrpc": "2.0", "id": 7, "method": "nope"}}]"#,
+            notification
+        );
+        let reply = respond(&batch).unwrap();
+        let answers = reply.as_array().unwrap();
+        assert_eq!(answers.len(), 1);
+        assert_eq!(answers[0].get("id"), Some(&Json::Number(7.0)));
+    }
+
+    #[test]
+    fn normalize_answers_with_the_normal_form() {
+        let request = r#"{"jsonrpc": "2.0", "id": "a", "method": "normalize", "params": {"term": "<\\x.{x}|y>"}}"#;
+        let reply = respond(request).unwrap();
+        assert_eq!(reply.get("id"), Some(&Json::String("a".to_string())));
+        assert_eq!(reply.get("error"), None);
+        let result = reply.get("result").unwrap();
+        assert_eq!(result.get("term").and_then(Json::as_str), Some("y"));
+        assert_eq!(result.get("steps").and_then(Json::as_usize), Some(1));
+        assert_eq!(result.get("ok"), None);
+    }
+}
@@ -0,0 +1,196 @@
+// where the names in the source are bound, for editors: the parser's node
+// spans matched up with the tokens they start at
+//
+// only names written in the source show up, the binders and variables that
+// lists, tuples and letrec are encoded with have no token of their own
+use crate::diagnostic::Span;
+use crate::parser::{Parser, ParserConfig, Term};
+use crate::symbol::Symbol;
+use crate::tokenizer::{self, Token};
+
+// a lambda parameter or the name of a let
+#[derive(Clone, Debug, PartialEq)]
+pub struct Binder {
+    pub name: Symbol,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Occurrence {
+    // binder is an index into Scopes::binders, index the de Bruijn index
+    Bound {
+        span: Span,
+        binder: usize,
+        index: i32,
+    },
+    Free {
+        span: Span,
+        name: Symbol,
+    },
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Scopes {
+    pub binders: Vec<Binder>,
+    pub occurrences: Vec<Occurrence>, // in source order
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntaxError {
+    pub message: String,
+    pub span: Span, // the offending token, empty at the end of the input
+}
+
+pub struct Analysis {
+    pub term: Term,
+    pub free: Vec<Symbol>,
+    pub node_spans: Vec<Span>,
+    pub tokens: Vec<Token>,
+    pub token_spans: Vec<Span>,
+    pub scopes: Scopes,
+}
+
+enum Visit<'a> {
+    Node(&'a Term),
+    Bind(Option<usize>),
+    Unbind,
+}
+
+// parse `source` without panicking and find the binder of every variable
+pub fn analyze(source: &str, config: ParserConfig) -> Result<Analysis, SyntaxError> {
+    let (tokens, token_spans) = tokenizer::try_tokenize_spanned(source)
+        .map_err(|(message, span)| SyntaxError { message, span })?;
+    let mut parser = Parser::with_config(&tokens, config).with_token_spans(&token_spans);
+    let (term, free) = parser.try_parse().map_err(|message| SyntaxError {
+        message,
+        span: token_spans
+            .get(parser.position())
+            .copied()
+            .unwrap_or(Span::new(source.len(), source.len())),
+    })?;
+    let node_spans = parser.node_spans().to_vec();
+    let scopes = resolve(&term, &free, &node_spans, &tokens, &token_spans);
+    Ok(Analysis {
+        term,
+        free,
+        node_spans,
+        tokens,
+        token_spans,
+        scopes,
+    })
+}
+
+fn resolve(
+    term: &Term,
+    free: &[Symbol],
+    node_spans: &[Span],
+    tokens: &[Token],
+    token_spans: &[Span],
+) -> Scopes {
+    let token_at = |span: Span| {
+        token_spans
+            .binary_search_by_key(&span.start, |s| s.start)
+            .ok()
+    };
+    let is_keyword =
+        |at: usize, keyword: &str| matches!(&tokens[at], Token::Var(name) if name == keyword);
+    let mut scopes = Scopes::default();
+    // binders in scope, None for the ones the encodings add
+    let mut env: Vec<Option<usize>> = Vec::new();
+    let mut spans = node_spans.iter().copied();
+    let mut work = vec![Visit::Node(term)];
+    while let Some(visit) = work.pop() {
+        let node = match visit {
+            Visit::Node(node) => node,
+            Visit::Bind(binder) => {
+                env.push(binder);
+                continue;
+            }
+            Visit::Unbind => {
+                env.pop();
+                continue;
+            }
+        };
+        let span = spans.next().unwrap_or_default();
+        let first = token_at(span);
+        match node {
+            Term::Variable(index) => {
+                // a single name token, not one made up by an encoding
+                let Some(at) = first.filter(|&at| token_spans[at] == span) else {
+                    continue;
+                };
+                if !matches!(tokens[at], Token::Var(_)) {
+                    continue;
+                }
+                if *index < 0 {
+                    let name = free[(-index - 1) as usize];
+                    scopes.occurrences.push(Occurrence::Free { span, name });
+                } else if let Some(Some(binder)) =
+                    env.len().checked_sub(*index as usize).map(|at| env[at])
+                {
+                    scopes.occurrences.push(Occurrence::Bound {
+                        span,
+                        binder,
+                        index: *index,
+                    });
+                }
+            }
+            Term::Constant(_) => {}
+            Term::Lambda(param, _, body) => {
+                let binder = match first {
+                    Some(at)
+                        if tokens[at] == Token::Lambda
+                            && tokens.get(at + 1) == Some(&Token::Var(*param)) =>
+                    {
+                        bind(&mut scopes, *param, token_spans[at + 1])
+                    }
+                    _ => None,
+                };
+                work.push(Visit::Unbind);
+                work.push(Visit::Node(body));
+                work.push(Visit::Bind(binder));
+            }
+            Term::Let(name, bound, body) => {
+                let at = first.filter(|&at| is_keyword(at, "let") || is_keyword(at, "letrec"));
+                let binder = at.and_then(|at| bind(&mut scopes, *name, token_spans[at + 1]));
+                work.push(Visit::Unbind);
+                work.push(Visit::Node(body));
+                work.push(Visit::Bind(binder));
+                match (&**bound, at) {
+                    // letrec f = t is FIX (λf. t): the combinator is closed and all
+                    // its nodes carry the let's span, skip to the body of the λf
+                    (Term::Application(fix, lambda), Some(at)) if is_keyword(at, "letrec") => {
+                        let Term::Lambda(_, _, inner) = &**lambda else {
+                            unreachable!("letrec binds through a lambda")
+                        };
+                        for _ in 0..2 + fix.size() {
+                            spans.next();
+                        }
+                        work.push(Visit::Unbind);
+                        work.push(Visit::Node(inner));
+                        work.push(Visit::Bind(binder));
+                    }
+                    _ => work.push(Visit::Node(bound)),
+                }
+            }
+            Term::Application(lhs, rhs) => {
+                work.push(Visit::Node(rhs));
+                work.push(Visit::Node(lhs));
+            }
+            Term::TypeLambda(_, body) | Term::TypeApplication(body, _) => {
+                work.push(Visit::Node(body))
+            }
+        }
+    }
+    scopes
+        .occurrences
+        .sort_by_key(|occurrence| match occurrence {
+            Occurrence::Bound { span, .. } | Occurrence::Free { span, .. } => span.start,
+        });
+    scopes
+}
+
+fn bind(scopes: &mut Scopes, name: Symbol, span: Span) -> Option<usize> {
+    scopes.binders.push(Binder { name, span });
+    Some(scopes.binders.len() - 1)
+}
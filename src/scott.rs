@@ -0,0 +1,175 @@
+// Scott encodings: a value is the case analysis on itself, so pred, tail and
+// friends take a constant number of steps instead of rebuilding the whole value
+// booleans are the same as the Church ones, see church::tru and church::fls
+use crate::church::{self, fls, mentions, tru};
+use crate::combinators::{app, apps, lam, var};
+use crate::parser::Term;
+use crate::reduce::shift;
+
+// λz. λs. z
+pub fn zero() -> Term {
+    lam("z", lam("s", var(2)))
+}
+
+// λn. λz. λs. s n
+pub fn succ() -> Term {
+    lam("n", lam("z", lam("s", app(var(1), var(3)))))
+}
+
+// λz. λs. s (λz. λs. s (... zero)), n times
+pub fn numeral(n: u64) -> Term {
+    (0..n).fold(zero(), |acc, _| lam("z", lam("s", app(var(1), acc))))
+}
+
+// read a numeral back from its normal form
+pub fn to_number(term: &Term) -> Option<u64> {
+    let mut term = term;
+    let mut n = 0;
+    loop {
+        let Term::Lambda(_, _, inner) = term else {
+            return None;
+        };
+        let Term::Lambda(_, _, body) = &**inner else {
+            return None;
+        };
+        match &**body {
+            Term::Variable(2) => return Some(n),
+            Term::Application(lhs, pred) if **lhs == Term::Variable(1) => {
+                n += 1;
+                term = pred;
+            }
+            _ => return None,
+        }
+    }
+}
+
+// λn. n zero (λm. m), pred 0 is 0
+pub fn pred() -> Term {
+    lam("n", apps(var(1), [zero(), lam("m", var(1))]))
+}
+
+// λn. n true (λm. false)
+pub fn is_zero() -> Term {
+    lam("n", apps(var(1), [tru(), lam("m", fls())]))
+}
+
+// λn. λc. n
+pub fn nil() -> Term {
+    lam("n", lam("c", var(2)))
+}
+
+// λh. λt. λn. λc. c h t
+pub fn cons() -> Term {
+    lam(
+        "h",
+        lam("t", lam("n", lam("c", apps(var(1), [var(4), var(3)])))),
+    )
+}
+
+// cons a (cons b ... nil) in normal form
+pub fn list(items: impl IntoIterator<Item = Term>) -> Term {
+    let items: Vec<Term> = items.into_iter().collect();
+    items.into_iter().rev().fold(nil(), |rest, item| {
+        let body = apps(var(1), [shift(&item, 2, 0), shift(&rest, 2, 0)]);
+        lam("n", lam("c", body))
+    })
+}
+
+// read the items back from a list in normal form, shifted out of its binders
+pub fn to_list(term: &Term) -> Option<Vec<Term>> {
+    let mut term = term.clone();
+    let mut items = Vec::new();
+    loop {
+        let Term::Lambda(_, _, inner) = &term else {
+            return None;
+        };
+        let Term::Lambda(_, _, body) = &**inner else {
+            return None;
+        };
+        match &**body {
+            Term::Variable(2) => return Some(items),
+            Term::Application(lhs, rest) => match &**lhs {
+                Term::Application(head, item) if **head == Term::Variable(1) => {
+                    if mentions(item, 0) || mentions(rest, 0) {
+                        return None;
+                    }
+                    items.push(shift(item, -2, 0));
+                    term = shift(rest, -2, 0);
+                }
+                _ => return None,
+            },
+            _ => return None,
+        }
+    }
+}
+
+// λl. l nil (λh. λt. h), the head of nil is nil
+pub fn head() -> Term {
+    lam("l", apps(var(1), [nil(), lam("h", lam("t", var(2)))]))
+}
+
+// λl. l nil (λh. λt. t), the tail of nil is nil
+pub fn tail() -> Term {
+    lam("l", apps(var(1), [nil(), lam("h", lam("t", var(1)))]))
+}
+
+// λl. l true (λh. λt. false)
+pub fn is_nil() -> Term {
+    lam("l", apps(var(1), [tru(), lam("h", lam("t", fls()))]))
+}
+
+// the booleans are shared with the Church encoding
+pub fn to_bool(term: &Term) -> Option<bool> {
+    church::to_bool(term)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reduce;
+
+    fn normal(term: Term) -> Term {
+        reduce::normalize(&term, 10_000).expect("normalizes").0
+    }
+
+    #[test]
+    fn numerals_round_trip() {
+        for n in 0..5 {
+            assert_eq!(to_number(&numeral(n)), Some(n));
+            assert_eq!(normal(app(succ(), numeral(n))), numeral(n + 1));
+        }
+        assert_eq!(to_number(&church::numeral(2)), None);
+    }
+
+    #[test]
+    fn pred_and_case_take_constant_steps() {
+        for n in 0..5 {
+            let (pred_n, steps) = reduce::normalize(&app(pred(), numeral(n)), 100).unwrap();
+            assert_eq!(to_number(&pred_n), Some(n.saturating_sub(1)));
+            // the same few steps however large n is
+            assert!(steps <= 4);
+            assert_eq!(to_bool(&normal(app(is_zero(), numeral(n)))), Some(n == 0));
+        }
+        // a numeral is its own case analysis: n z s is z for 0 and s (n - 1) otherwise
+        let case = |n| normal(apps(numeral(n), [var(-1), lam("m", var(1))]));
+        assert_eq!(case(0), var(-1));
+        assert_eq!(case(3), numeral(2));
+    }
+
+    #[test]
+    fn lists_round_trip() {
+        let abc = || list((0..3).map(numeral));
+        let read: Vec<Option<u64>> = to_list(&abc()).unwrap().iter().map(to_number).collect();
+        assert_eq!(read, [Some(0), Some(1), Some(2)]);
+        let built = normal(apps(
+            cons(),
+            [numeral(0), apps(cons(), [numeral(1), nil()])],
+        ));
+        assert_eq!(built, list((0..2).map(numeral)));
+        assert_eq!(to_number(&normal(app(head(), abc()))), Some(0));
+        assert_eq!(normal(app(tail(), abc())), list((1..3).map(numeral)));
+        assert_eq!(to_bool(&normal(app(is_nil(), abc()))), Some(false));
+        assert_eq!(to_bool(&normal(app(is_nil(), nil()))), Some(true));
+        assert_eq!(to_list(&nil()), Some(Vec::new()));
+    }
+}
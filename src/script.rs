@@ -0,0 +1,182 @@
+// script files: every line is a definition `NAME = TERM` or a term to reduce
+// and print, a line starting with whitespace continues the one before, and `--`
+// comments out the rest of a line
+//
+// free variables named after an earlier definition stand for its normal form,
+// then prelude names are resolved as in the REPL. normal forms of definitions
+// can be kept in a Cache across runs.
+use std::collections::HashMap;
+
+use crate::cache::Cache;
+use crate::parser::{self, ParserConfig, Term};
+use crate::prelude;
+use crate::pretty_printer::PrettyPrinter;
+use crate::reduce;
+use crate::symbol::Symbol;
+use crate::traverse;
+
+enum Item {
+    Definition(String, String),
+    Term(String),
+}
+
+// the printed normal forms of the terms, or the first error with its line
+pub fn run(source: &str, cache: Option<&Cache>) -> Result<Vec<String>, String> {
+    let mut definitions: HashMap<String, (Term, Vec<Symbol>)> = HashMap::new();
+    let mut output = Vec::new();
+    for (line, item) in items(source) {
+        let fail = |msg: String| format!("line {}: {}", line, msg);
+        match item {
+            Item::Definition(name, text) => {
+                let (term, free) = parse(&text, &definitions).map_err(fail)?;
+                let normal = match cache.and_then(|cache| cache.get(&term, &free)) {
+                    Some(cached) => cached,
+                    None => {
+                        let normal = normalize(&term).map_err(fail)?;
+                        if let Some(cache) = cache {
+                            cache.put(&term, &free, &normal);
+                        }
+                        (normal, free)
+                    }
+                };
+                definitions.insert(name, normal);
+            }
+            Item::Term(text) => {
+                let (term, free) = parse(&text, &definitions).map_err(fail)?;
+                let normal = normalize(&term).map_err(fail)?;
+                output.push(PrettyPrinter::new().format(&normal, &free));
+            }
+        }
+    }
+    Ok(output)
+}
+
+// logical lines with the number of the line they start on
+fn items(source: &str) -> Vec<(usize, Item)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (number, raw) in source.lines().enumerate() {
+        let text = raw.split_once("--").map_or(raw, |(code, _)| code);
+        if text.trim().is_empty() {
+            continue;
+        }
+        match lines.last_mut() {
+            Some((_, last)) if text.starts_with(char::is_whitespace) => {
+                last.push(' ');
+                last.push_str(text.trim());
+            }
+            _ => lines.push((number + 1, text.trim().to_string())),
+        }
+    }
+    lines
+        .into_iter()
+        .map(|(number, text)| {
+            let item = match text.split_once('=') {
+                Some((name, term)) if is_name(name.trim()) => {
+                    Item::Definition(name.trim().to_string(), term.trim().to_string())
+                }
+                _ => Item::Term(text),
+            };
+            (number, item)
+        })
+        .collect()
+}
+
+pub(crate) fn is_name(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && text != "let"
+        && text != "letrec"
+}
+
+fn parse(
+    text: &str,
+    definitions: &HashMap<String, (Term, Vec<Symbol>)>,
+) -> Result<(Term, Vec<Symbol>), String> {
+    let config = ParserConfig {
+        system_f: true,
+        ..Default::default()
+    };
+    let (term, mut free) = parser::parse_with_config(text, config)?;
+    let names = free.clone();
+    let term = traverse::map_leaves(&term, |leaf, _| match leaf {
+        Term::Variable(index) if *index < 0 => {
+            match definitions.get(names[(-index - 1) as usize].as_str()) {
+                // normal forms of definitions are closed but for their free names
+                Some((definition, def_free)) => {
+                    traverse::map_leaves(definition, |leaf, _| match leaf {
+                        Term::Variable(index) if *index < 0 => {
+                            let name = def_free[(-index - 1) as usize];
+                            let at = match free.iter().position(|known| *known == name) {
+                                Some(at) => at,
+                                None => {
+                                    free.push(name);
+                                    free.len() - 1
+                                }
+                            };
+                            Term::Variable(-(at as i32 + 1))
+                        }
+                        _ => leaf.clone(),
+                    })
+                }
+                None => leaf.clone(),
+            }
+        }
+        _ => leaf.clone(),
+    });
+    let term = prelude::resolve(&term, &free);
+    Ok((term, free))
+}
+
+fn normalize(term: &Term) -> Result<Term, String> {
+    let fuel = reduce::default_fuel(reduce::termination(term));
+    reduce::normalize(term, fuel)
+        .map(|(normal, _)| normal)
+        .ok_or_else(|| format!("no normal form within {} steps", fuel))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn definitions_stand_for_their_normal_forms() {
+        let source = "two = <succ|c1> -- a comment\n\
+                      four = <succ\n  |<succ|two>>\n\
+                      four\n\
+                      <<pair|two>|z>\n";
+        let output = run(source, None).unwrap();
+        assert_eq!(output.len(), 2);
+        let (four, _) = parse("c4", &HashMap::new()).unwrap();
+        assert_eq!(
+            output[0],
+            PrettyPrinter::new().format(&normalize(&four).unwrap(), &[])
+        );
+        assert!(output[1].contains('z'));
+    }
+
+    #[test]
+    fn free_names_of_definitions_are_added_once() {
+        let mut definitions = HashMap::new();
+        definitions.insert("ab".to_string(), parse("<a|b>", &definitions).unwrap());
+        let (term, free) = parse("<<<ab|ab>|a>|ab>", &definitions).unwrap();
+        let used: Vec<&Symbol> = free.iter().filter(|name| *name != "ab").collect();
+        assert_eq!(used.len(), 2, "{:?}", free);
+        let (expected, expected_free) =
+            parse("<<<<a|b>|<a|b>>|a>|<a|b>>", &HashMap::new()).unwrap();
+        assert_eq!(
+            PrettyPrinter::new().format(&term, &free),
+            PrettyPrinter::new().format(&expected, &expected_free)
+        );
+    }
+
+    #[test]
+    fn errors_name_their_line() {
+        let error = run("ok = c1\n\n<ok|\n", None).unwrap_err();
+        assert!(error.starts_with("line 3: "), "{}", error);
+        let error = run("loop = <omega|omega>", None).unwrap_err();
+        assert!(error.starts_with("line 1: no normal form"), "{}", error);
+    }
+}
@@ -0,0 +1,292 @@
+// a small HTTP server for the JSON entry points of api, to back a web
+// playground or a grading service
+//
+// POST /normalize takes {"term": "...", "strategy": "normal"|"applicative",
+// "fuel": N}, only the term is required, and answers as api does. a fixed
+// set of workers takes the connections, each closed after one answer, and
+// those that find every worker busy and the queue full get a 503. request
+// lines, headers and bodies are capped in size and the whole request in the
+// time it takes to arrive, terms in nesting depth (by api) and reductions in
+// steps, in the size of the terms on the way and in time, so one request
+// cannot take the server down or keep a worker for long
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::api;
+use crate::json::Json;
+
+// bytes of a request body
+const MAX_BODY: usize = 1 << 20;
+// bytes of the request line and of each header
+const MAX_LINE: usize = 8 << 10;
+// headers of a request
+const MAX_HEADERS: usize = 64;
+// steps a request may ask for
+const MAX_FUEL: usize = 1_000_000;
+// nodes of a term on the way to the normal form
+const MAX_SIZE: usize = 25_000;
+// time a reduction may take
+const DEADLINE: Duration = Duration::from_secs(5);
+// time a client has to send the whole request
+const READ_DEADLINE: Duration = Duration::from_secs(10);
+// threads answering requests, and connections waiting for one
+const WORKERS: usize = 8;
+const QUEUE: usize = 64;
+
+pub fn run(port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    eprintln!("listening on http://127.0.0.1:{}", port);
+    let (queue, waiting) = mpsc::sync_channel::<TcpStream>(QUEUE);
+    let waiting = Arc::new(Mutex::new(waiting));
+    for _ in 0..WORKERS {
+        let waiting = Arc::clone(&waiting);
+        thread::spawn(move || {
+            loop {
+                let next = waiting.lock().unwrap().recv();
+                let Ok(stream) = next else {
+                    break;
+                };
+                let _ = handle(stream);
+            }
+        });
+    }
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        if let Err(TrySendError::Full(mut stream)) = queue.try_send(stream) {
+            let busy = failure("too many requests, try again later".to_string());
+            let _ = stream
+                .set_write_timeout(Some(Duration::from_secs(1)))
+                .and_then(|_| respond(&mut stream, "503 Service Unavailable", &busy));
+        }
+    }
+    Ok(())
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+fn handle(mut stream: TcpStream) -> io::Result<()> {
+    // a client that stops sending, or sends a byte at a time, does not keep
+    // its thread
+    let mut reader = BufReader::new(Deadline {
+        stream: &stream,
+        until: Instant::now() + READ_DEADLINE,
+    });
+    let request = match read_request(&mut reader) {
+        Ok(request) => request,
+        Err((status, message)) => return respond(&mut stream, status, &failure(message)),
+    };
+    match (request.method.as_str(), request.path.as_str()) {
+        // CORS preflight, so a page served from elsewhere can post
+        ("OPTIONS", _) => respond(&mut stream, "204 No Content", &Json::Null),
+        ("POST", "/normalize") => {
+            let answer = String::from_utf8(request.body)
+                .map_err(|_| "body is not UTF-8".to_string())
+                .and_then(|body| Json::parse(&body).map_err(|error| format!("bad JSON: {}", error)))
+                .and_then(|request| normalize(&request));
+            match answer {
+                Ok(answer) => respond(&mut stream, "200 OK", &answer),
+                Err(message) => respond(&mut stream, "400 Bad Request", &failure(message)),
+            }
+        }
+        (_, "/normalize") => respond(
+            &mut stream,
+            "405 Method Not Allowed",
+            &failure("use POST".to_string()),
+        ),
+        _ => respond(
+            &mut stream,
+            "404 Not Found",
+            &failure("no such endpoint".to_string()),
+        ),
+    }
+}
+
+// the answer of api::normalize_with within the server's limits, or what is
+// wrong with the request. errors of the term itself are part of the answer
+fn normalize(request: &Json) -> Result<Json, String> {
+    let (source, fuel, strategy) = api::normalize_params(request)?;
+    let fuel = fuel.map(|fuel| fuel.min(MAX_FUEL));
+    let limits = api::Limits {
+        max_size: Some(MAX_SIZE),
+        deadline: Some(Instant::now() + DEADLINE),
+    };
+    Ok(api::normalize_within(source, fuel, strategy, &limits))
+}
+
+fn failure(message: String) -> Json {
+    Json::object([("ok", false.into()), ("error", message.into())])
+}
+
+// reads from a stream that fail once `until` has passed, however the bytes
+// trickle in before it
+struct Deadline<'a> {
+    stream: &'a TcpStream,
+    until: Instant,
+}
+
+impl Read for Deadline<'_> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let left = self.until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buffer)
+    }
+}
+
+fn read_request(reader: &mut impl BufRead) -> Result<Request, (&'static str, String)> {
+    let bad = |message: &str| ("400 Bad Request", message.to_string());
+    // a timed out read is the client's doing, not a malformed request
+    let unreadable = |error: io::Error, message: &str| match error.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => (
+            "408 Request Timeout",
+            "request not received in time".to_string(),
+        ),
+        _ => bad(message),
+    };
+    let mut line = String::new();
+    read_line(reader, &mut line).map_err(|error| unreadable(error, "unreadable request"))?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(bad("malformed request line"));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+    let mut length = 0;
+    for headers in 0.. {
+        line.clear();
+        read_line(reader, &mut line).map_err(|error| unreadable(error, "unreadable headers"))?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if headers == MAX_HEADERS {
+            return Err((
+                "431 Request Header Fields Too Large",
+                format!("at most {} headers", MAX_HEADERS),
+            ));
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            length = value
+                .trim()
+                .parse()
+                .map_err(|_| bad("bad Content-Length"))?;
+        }
+    }
+    if length > MAX_BODY {
+        return Err((
+            "413 Payload Too Large",
+            format!("bodies are limited to {} bytes", MAX_BODY),
+        ));
+    }
+    let mut body = vec![0; length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|error| unreadable(error, "body shorter than Content-Length"))?;
+    Ok(Request { method, path, body })
+}
+
+// one line of at most MAX_LINE bytes, a longer one is an error
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> io::Result<()> {
+    reader.take(MAX_LINE as u64).read_line(line)?;
+    if !line.ends_with('\n') && line.len() == MAX_LINE {
+        return Err(io::Error::other("line too long"));
+    }
+    Ok(())
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &Json) -> io::Result<()> {
+    let body = match body {
+        Json::Null => String::new(),
+        body => body.to_string(),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\nAccess-Control-Allow-Methods: POST, OPTIONS\r\n\
+         Access-Control-Allow-Headers: Content-Type\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reduce::Strategy;
+
+    fn post(body: &str) -> Json {
+        normalize(&Json::parse(body).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn a_term_is_normalized() {
+        let answer = post(r#"{"term": "<\\x.{x}|y>"}"#);
+        assert_eq!(answer.get("ok"), Some(&Json::from(true)));
+        assert_eq!(answer.get("term").and_then(Json::as_str), Some("y"));
+        assert!(normalize(&Json::parse(r#"{"fuel": 3}"#).unwrap()).is_err());
+    }
+
+    #[test]
+    fn a_term_growing_too_large_is_given_up() {
+        let answer = post(r#"{"term": "<<<<c2|c2>|c2>|c2>|c2>", "fuel": 1000000}"#);
+        assert_eq!(answer.get("ok"), Some(&Json::from(false)));
+        let error = answer.get("error").and_then(Json::as_str).unwrap();
+        assert!(error.starts_with("the term grew past"), "{}", error);
+    }
+
+    #[test]
+    fn a_reduction_out_of_time_is_given_up() {
+        let limits = api::Limits {
+            max_size: None,
+            deadline: Some(Instant::now()),
+        };
+        let answer = api::normalize_within("<omega|omega>", None, Strategy::Normal, &limits);
+        let error = answer.get("error").and_then(Json::as_str);
+        assert_eq!(error, Some("out of time after 0 steps"));
+    }
+
+    #[test]
+    fn a_request_trickling_in_runs_out_of_time() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            let _ = stream.write_all(b"POST /normalize HTTP/1.1\r\nX-Slow: ");
+            // each byte well within any single read's timeout
+            for _ in 0..40 {
+                thread::sleep(Duration::from_millis(50));
+                if stream.write_all(b"a").is_err() {
+                    break;
+                }
+            }
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let start = Instant::now();
+        let mut reader = BufReader::new(Deadline {
+            stream: &stream,
+            until: start + Duration::from_millis(300),
+        });
+        let Err((status, _)) = read_request(&mut reader) else {
+            panic!("the request was read in full");
+        };
+        assert_eq!(status, "408 Request Timeout");
+        assert!(start.elapsed() < Duration::from_secs(1));
+        drop(stream);
+        client.join().unwrap();
+    }
+}
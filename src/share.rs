@@ -0,0 +1,222 @@
+// sharing for printing: a subterm of at least some size occurring more than
+// once is bound by a let at the lowest node above all its occurrences, each
+// occurrence becoming its name, `let t0 = ... in <t0|t0>`. occurrences must
+// be the same term under the same binders, so a subterm using a variable is
+// only shared below its binder. the group saving the most nodes goes first,
+// then the term is searched again, so shared terms share in turn
+//
+// only the pure calculus is shared, subterms with constants are fine, ones
+// with a let or System F nodes are left alone
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use crate::parser::Term;
+use crate::reduce::{self, Dir, Path};
+use crate::symbol::Symbol;
+
+struct Occurrence {
+    path: Path,
+    depth: usize, // the binders above it
+}
+
+// `term` with its repeated subterms of at least `min_size` nodes let-bound,
+// named t0, t1, ... unless a name of the term or of `free` is taken
+pub fn share(term: &Term, free: &[Symbol], min_size: usize) -> Term {
+    let mut taken: HashSet<String> = free.iter().map(|name| name.to_string()).collect();
+    reduce::walk_paths(term, |node, _| {
+        if let Term::Lambda(name, ..) | Term::Let(name, ..) = node {
+            taken.insert(name.as_str().to_string());
+        }
+        false
+    });
+    let mut names = (0..)
+        .map(|n| format!("t{}", n))
+        .filter(|name| !taken.contains(name));
+    let mut term = term.clone();
+    while let Some(group) = best(&term, min_size.max(2)) {
+        let name = Symbol::intern(&names.next().expect("names never run out"));
+        term = bind(&term, &group, name);
+    }
+    term
+}
+
+// the occurrences of the repeated subterm saving the most nodes, None if no
+// subterm of `min_size` nodes repeats often enough to be worth a let
+fn best(term: &Term, min_size: usize) -> Option<Vec<Occurrence>> {
+    let mut groups: HashMap<String, Vec<Occurrence>> = HashMap::new();
+    let mut order = Vec::new(); // keys by first occurrence, for ties
+    let (mut path, mut binders) = (Vec::new(), Vec::new());
+    let mut next_binder = 0;
+    // each node with the length of the path and binders above it, the step
+    // into it and the binder it is the body of
+    type Frame<'a> = (&'a Term, usize, Option<Dir>, usize, Option<usize>);
+    let mut work: Vec<Frame> = vec![(term, 0, None, 0, None)];
+    while let Some((node, above, dir, outer, binder)) = work.pop() {
+        path.truncate(above);
+        path.extend(dir);
+        binders.truncate(outer);
+        binders.extend(binder);
+        if node.size() >= min_size
+            && let Some(key) = key(node, &binders)
+        {
+            let occurrences = groups.entry(key).or_insert_with_key(|key| {
+                order.push(key.clone());
+                Vec::new()
+            });
+            occurrences.push(Occurrence {
+                path: path.clone(),
+                depth: binders.len(),
+            });
+        }
+        let (above, outer) = (path.len(), binders.len());
+        match node {
+            Term::Variable(_) | Term::Constant(_) => {}
+            Term::Lambda(_, _, body) => {
+                work.push((body, above, Some(Dir::Body), outer, Some(next_binder)));
+                next_binder += 1;
+            }
+            Term::TypeLambda(_, body) => work.push((body, above, Some(Dir::Body), outer, None)),
+            Term::TypeApplication(fun, _) => work.push((fun, above, Some(Dir::Fun), outer, None)),
+            Term::Let(_, bound, body) => {
+                work.push((body, above, Some(Dir::Body), outer, Some(next_binder)));
+                work.push((bound, above, Some(Dir::Arg), outer, None));
+                next_binder += 1;
+            }
+            Term::Application(lhs, rhs) => {
+                work.push((rhs, above, Some(Dir::Arg), outer, None));
+                work.push((lhs, above, Some(Dir::Fun), outer, None));
+            }
+        }
+    }
+    // n copies of a term of s nodes print as one, n variables and a let
+    let saving = |key: &String| {
+        let count = groups[key].len();
+        let size = reduce::subterm(term, &groups[key][0].path).size();
+        ((count - 1) * size).saturating_sub(count + 1)
+    };
+    let mut best: Option<(&String, usize)> = None;
+    for key in &order {
+        let saved = saving(key);
+        if saved > best.map_or(0, |(_, most)| most) {
+            best = Some((key, saved));
+        }
+    }
+    let key = best?.0.clone();
+    groups.remove(&key)
+}
+
+// the subterm in prefix form, variables bound outside it by the binder they
+// point at, the ones in `binders` (innermost last) or the ones further out.
+// None if it is not a pure term
+fn key(term: &Term, binders: &[usize]) -> Option<String> {
+    let mut out = String::new();
+    let mut work = vec![(term, 0)];
+    while let Some((node, depth)) = work.pop() {
+        match node {
+            Term::Variable(index) if *index < 0 => write!(out, "f{} ", -index),
+            Term::Variable(index) if *index <= depth => write!(out, "v{} ", index),
+            Term::Variable(index) => {
+                let up = (index - depth) as usize;
+                match binders.len().checked_sub(up) {
+                    Some(at) => write!(out, "b{} ", binders[at]),
+                    None => write!(out, "o{} ", up - binders.len()),
+                }
+            }
+            Term::Constant(constant) => write!(out, "c{} ", constant),
+            Term::Lambda(_, _, body) => {
+                work.push((body, depth + 1));
+                write!(out, "λ ")
+            }
+            Term::Application(lhs, rhs) => {
+                work.push((rhs, depth));
+                work.push((lhs, depth));
+                write!(out, "@ ")
+            }
+            _ => return None,
+        }
+        .expect("writing to a String cannot fail");
+    }
+    Some(out)
+}
+
+// let-bind the subterm at `occurrences` as `name` above all of them
+fn bind(term: &Term, occurrences: &[Occurrence], name: Symbol) -> Term {
+    let first = &occurrences[0];
+    let common = occurrences
+        .iter()
+        .map(|other| {
+            first
+                .path
+                .iter()
+                .zip(&other.path)
+                .take_while(|(a, b)| a == b)
+                .count()
+        })
+        .min()
+        .unwrap_or(0);
+    let at = &first.path[..common];
+    let depth = reduce::binders_along(term, at).len();
+    let value = reduce::shift(
+        reduce::subterm(term, &first.path),
+        depth as i32 - first.depth as i32,
+        0,
+    );
+    // the let is one more binder for everything below it
+    let mut body = reduce::shift(reduce::subterm(term, at), 1, 0);
+    for occurrence in occurrences {
+        let index = (occurrence.depth - depth) as i32 + 1;
+        body = reduce::replace(&body, &occurrence.path[common..], Term::Variable(index));
+    }
+    reduce::replace(term, at, Term::Let(name, Box::new(value), Box::new(body)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{self, ParserConfig};
+    use crate::unparse::unparse;
+
+    // every let contracted, which undoes the sharing
+    fn expand(term: &Term) -> Term {
+        let mut term = term.clone();
+        while let Some(path) = reduce::redexes(&term)
+            .into_iter()
+            .find(|path| matches!(reduce::subterm(&term, path), Term::Let(..)))
+        {
+            term = reduce::contract(&term, &path);
+        }
+        term
+    }
+
+    // the shared term printed, checked to expand back to the input
+    fn shared(source: &str) -> String {
+        let (term, free) = parser::parse_with_config(source, ParserConfig::default()).unwrap();
+        let shared = share(&term, &free, 4);
+        assert!(reduce::alpha_eq(&expand(&shared), &term), "{}", source);
+        unparse(&shared, &free)
+    }
+
+    #[test]
+    fn repeated_subterms_are_bound_once() {
+        let triple = r"\x.{<<x|x>|x>}";
+        let source = format!("<<{0}|{0}>|<a|{0}>>", triple);
+        let printed = shared(&source);
+        assert_eq!(printed, r"let t0 = \x.{<<x|x>|x>} in <<t0|t0>|<a|t0>>");
+    }
+
+    #[test]
+    fn sharing_stays_below_the_binders_it_uses() {
+        let printed = shared(r"<\t0.{\y.{<<<t0|y>|<<y|y>|y>>|<<y|y>|y>>}}|z>");
+        assert_eq!(
+            printed,
+            r"<\t0.{\y.{let t1 = <<y|y>|y> in <<<t0|y>|t1>|t1>}}|z>"
+        );
+    }
+
+    #[test]
+    fn small_or_unique_subterms_are_left_alone() {
+        let source = r"<<\x.{x}|\x.{x}>|\y.{<<y|y>|y>}>";
+        let (term, free) = parser::parse_with_config(source, ParserConfig::default()).unwrap();
+        assert_eq!(share(&term, &free, 4), term);
+    }
+}
@@ -0,0 +1,343 @@
+// SKI combinator terms: bracket abstraction compiles lambdas away, decompile
+// expands the combinators back into lambda terms
+use std::mem;
+
+use crate::combinators;
+use crate::parser::Term;
+use crate::reduce;
+use crate::symbol::Symbol;
+
+#[derive(Debug)]
+pub enum Ski {
+    S,
+    K,
+    I,
+    Var(i32), // negative for free variable, as in Term
+    App(Box<Ski>, Box<Ski>),
+}
+
+// clone, equality and drop walk the tree with a work stack, as Term's do
+impl Clone for Ski {
+    fn clone(&self) -> Self {
+        map(self, |leaf| leaf.leaf())
+    }
+}
+
+impl PartialEq for Ski {
+    fn eq(&self, other: &Self) -> bool {
+        let mut work = vec![(self, other)];
+        while let Some(pair) = work.pop() {
+            match pair {
+                (Ski::App(f1, a1), Ski::App(f2, a2)) => {
+                    work.push((a1, a2));
+                    work.push((f1, f2));
+                }
+                (Ski::S, Ski::S) | (Ski::K, Ski::K) | (Ski::I, Ski::I) => {}
+                (Ski::Var(x), Ski::Var(y)) if x == y => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+impl Drop for Ski {
+    fn drop(&mut self) {
+        let mut stack = Vec::new();
+        take_children(self, &mut stack);
+        // every popped tree is dropped childless
+        while let Some(mut ski) = stack.pop() {
+            take_children(&mut ski, &mut stack);
+        }
+    }
+}
+
+// move the children of an application onto `stack`, leaving leaves behind
+fn take_children(ski: &mut Ski, stack: &mut Vec<Ski>) {
+    if let Ski::App(fun, arg) = ski {
+        stack.push(mem::replace(&mut **fun, Ski::I));
+        stack.push(mem::replace(&mut **arg, Ski::I));
+    }
+}
+
+impl Ski {
+    // a copy of a leaf, the tree has no others
+    fn leaf(&self) -> Ski {
+        match self {
+            Ski::S => Ski::S,
+            Ski::K => Ski::K,
+            Ski::I => Ski::I,
+            Ski::Var(index) => Ski::Var(*index),
+            Ski::App(..) => unreachable!("not a leaf"),
+        }
+    }
+}
+
+// rebuild `ski` bottom-up over an explicit stack, `leaf` maps the leaves
+fn map(ski: &Ski, mut leaf: impl FnMut(&Ski) -> Ski) -> Ski {
+    enum Frame<'a> {
+        Visit(&'a Ski),
+        App,
+    }
+    let mut work = vec![Frame::Visit(ski)];
+    let mut done = Vec::new();
+    while let Some(frame) = work.pop() {
+        match frame {
+            Frame::Visit(Ski::App(fun, arg)) => {
+                work.push(Frame::App);
+                work.push(Frame::Visit(arg));
+                work.push(Frame::Visit(fun));
+            }
+            Frame::Visit(node) => done.push(leaf(node)),
+            Frame::App => {
+                let arg = done.pop().expect("argument built");
+                let fun = done.pop().expect("function built");
+                done.push(ski_app(fun, arg));
+            }
+        }
+    }
+    done.pop().expect("root built")
+}
+
+fn ski_app(lhs: Ski, rhs: Ski) -> Ski {
+    Ski::App(Box::new(lhs), Box::new(rhs))
+}
+
+// bracket abstraction with the K and η shortcuts, None for constants. the term
+// is walked bottom-up with an explicit stack, every lambda abstracts its body
+pub fn compile(term: &Term) -> Option<Ski> {
+    enum Frame<'a> {
+        Visit(&'a Term),
+        Lambda,
+        App,
+        Let,
+    }
+    let mut work = vec![Frame::Visit(term)];
+    let mut done = Vec::new();
+    while let Some(frame) = work.pop() {
+        match frame {
+            Frame::Visit(node) => match node {
+                Term::Variable(index) => done.push(Ski::Var(*index)),
+                Term::Constant(_) => return None,
+                Term::Lambda(_, _, body) => {
+                    work.push(Frame::Lambda);
+                    work.push(Frame::Visit(body));
+                }
+                Term::Application(lhs, rhs) => {
+                    work.push(Frame::App);
+                    work.push(Frame::Visit(rhs));
+                    work.push(Frame::Visit(lhs));
+                }
+                // lets become redexes, types are erased
+                Term::Let(_, bound, body) => {
+                    work.push(Frame::Let);
+                    work.push(Frame::Visit(body));
+                    work.push(Frame::Visit(bound));
+                }
+                Term::TypeLambda(_, body) | Term::TypeApplication(body, _) => {
+                    work.push(Frame::Visit(body))
+                }
+            },
+            Frame::Lambda => {
+                let body = done.pop().expect("body compiled");
+                done.push(abstract_var(&body));
+            }
+            Frame::App => {
+                let rhs = done.pop().expect("argument compiled");
+                let lhs = done.pop().expect("function compiled");
+                done.push(ski_app(lhs, rhs));
+            }
+            Frame::Let => {
+                let body = done.pop().expect("body compiled");
+                let bound = done.pop().expect("bound term compiled");
+                done.push(ski_app(abstract_var(&body), bound));
+            }
+        }
+    }
+    done.pop()
+}
+
+// a subtree of `abstract_var`'s input, taken apart: with the variable
+// abstracted where it occurs, lowered past the binder where it does not
+enum Abstracted {
+    Occurs(Ski),
+    Absent(Ski),
+}
+
+impl Abstracted {
+    // [x] t of the subtree, K t when x does not occur
+    fn abstracted(self) -> Ski {
+        match self {
+            Abstracted::Occurs(ski) => ski,
+            Abstracted::Absent(ski) => ski_app(Ski::K, ski),
+        }
+    }
+}
+
+// [x] t for the innermost bound variable x, in one bottom-up pass: whether x
+// occurs in a subtree is known when its parent is built
+fn abstract_var(ski: &Ski) -> Ski {
+    enum Frame<'a> {
+        Visit(&'a Ski),
+        App(&'a Ski), // the argument, for the η shortcut
+    }
+    let mut work = vec![Frame::Visit(ski)];
+    let mut done: Vec<Abstracted> = Vec::new();
+    while let Some(frame) = work.pop() {
+        match frame {
+            Frame::Visit(Ski::App(fun, arg)) => {
+                work.push(Frame::App(arg));
+                work.push(Frame::Visit(arg));
+                work.push(Frame::Visit(fun));
+            }
+            Frame::Visit(Ski::Var(1)) => done.push(Abstracted::Occurs(Ski::I)),
+            Frame::Visit(Ski::Var(index)) if *index > 1 => {
+                done.push(Abstracted::Absent(Ski::Var(index - 1)))
+            }
+            Frame::Visit(leaf) => done.push(Abstracted::Absent(leaf.leaf())),
+            Frame::App(arg) => {
+                let rhs = done.pop().expect("argument abstracted");
+                let lhs = done.pop().expect("function abstracted");
+                done.push(match (lhs, rhs) {
+                    (Abstracted::Absent(fun), Abstracted::Absent(arg)) => {
+                        Abstracted::Absent(ski_app(fun, arg))
+                    }
+                    (Abstracted::Absent(fun), _) if *arg == Ski::Var(1) => Abstracted::Occurs(fun),
+                    (lhs, rhs) => Abstracted::Occurs(ski_app(
+                        ski_app(Ski::S, lhs.abstracted()),
+                        rhs.abstracted(),
+                    )),
+                });
+            }
+        }
+    }
+    done.pop().expect("root abstracted").abstracted()
+}
+
+// S, K and I replaced by their lambda definitions, nothing reduced
+pub fn decompile(ski: &Ski) -> Term {
+    enum Frame<'a> {
+        Visit(&'a Ski),
+        App,
+    }
+    let mut work = vec![Frame::Visit(ski)];
+    let mut done = Vec::new();
+    while let Some(frame) = work.pop() {
+        match frame {
+            Frame::Visit(node) => match node {
+                Ski::S => done.push(combinators::s()),
+                Ski::K => done.push(combinators::k()),
+                Ski::I => done.push(combinators::i()),
+                Ski::Var(index) => done.push(Term::Variable(*index)),
+                Ski::App(fun, arg) => {
+                    work.push(Frame::App);
+                    work.push(Frame::Visit(arg));
+                    work.push(Frame::Visit(fun));
+                }
+            },
+            Frame::App => {
+                let arg = done.pop().expect("argument built");
+                let fun = done.pop().expect("function built");
+                done.push(Term::Application(Box::new(fun), Box::new(arg)));
+            }
+        }
+    }
+    done.pop().expect("root built")
+}
+
+// decompile and reduce to normal form, None if `fuel` steps do not suffice
+pub fn decompile_simplified(ski: &Ski, fuel: usize) -> Option<Term> {
+    reduce::normalize(&decompile(ski), fuel).map(|(term, _)| term)
+}
+
+// S(KS)K style, application is left associative and free variables print as $name
+pub fn format(ski: &Ski, free: &[Symbol]) -> String {
+    enum Piece<'a> {
+        Ski(&'a Ski, bool), // parenthesized if an application
+        Text(char),
+    }
+    let mut out = String::new();
+    let mut work = vec![Piece::Ski(ski, false)];
+    while let Some(piece) = work.pop() {
+        let atom = match piece {
+            Piece::Text(c) => {
+                out.push(c);
+                continue;
+            }
+            Piece::Ski(Ski::App(fun, arg), nested) => {
+                if nested {
+                    out.push('(');
+                    work.push(Piece::Text(')'));
+                }
+                work.push(Piece::Ski(arg, true));
+                work.push(Piece::Ski(fun, false));
+                continue;
+            }
+            Piece::Ski(Ski::S, _) => "S".to_string(),
+            Piece::Ski(Ski::K, _) => "K".to_string(),
+            Piece::Ski(Ski::I, _) => "I".to_string(),
+            Piece::Ski(Ski::Var(index), _) if *index < 0 => {
+                format!("${}", free[(-index - 1) as usize])
+            }
+            Piece::Ski(Ski::Var(index), _) => format!("#{}", index),
+        };
+        // variables are separated from their neighbours, SKI letters are not
+        let after_var = out
+            .rsplit(['(', ')', ' '])
+            .next()
+            .is_some_and(|last| last.starts_with(['$', '#']));
+        if after_var || (atom.starts_with(['$', '#']) && !out.is_empty() && !out.ends_with('(')) {
+            out.push(' ');
+        }
+        out.push_str(&atom);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::church;
+    use crate::combinators::{app, apps, i, lam, var};
+    use crate::generate;
+
+    // no variable is left once every binder is abstracted
+    fn combinators_only(ski: &Ski) -> bool {
+        match ski {
+            Ski::Var(_) => false,
+            Ski::App(fun, arg) => combinators_only(fun) && combinators_only(arg),
+            _ => true,
+        }
+    }
+
+    #[test]
+    fn closed_terms_compile_to_combinators() {
+        for term in generate::enumerate_closed_terms(8) {
+            assert!(combinators_only(&compile(&term).unwrap()));
+        }
+        // equal up to η, which the shortcut uses
+        let sum = apps(church::add(), [church::numeral(2), church::numeral(3)]);
+        let back = decompile_simplified(&compile(&sum).unwrap(), 10_000).unwrap();
+        assert_eq!(church::to_number(&back), Some(5));
+        let flip = lam("x", lam("y", app(var(1), var(2))));
+        assert_eq!(format(&compile(&flip).unwrap(), &[]), "S(K(SI))K");
+        assert_eq!(
+            format(
+                &compile(&app(var(-1), i())).unwrap(),
+                &[Symbol::intern("f")]
+            ),
+            "$f I"
+        );
+    }
+
+    #[test]
+    fn deep_terms_compile_and_print() {
+        let deep = (0..3000).fold(var(1), |body, _| lam("x", body));
+        let ski = compile(&deep).unwrap();
+        assert!(combinators_only(&ski));
+        assert_eq!(ski.clone(), ski);
+        let printed = format(&ski, &[]);
+        assert_eq!(printed.matches('K').count(), 2999);
+        assert!(decompile(&ski).depth() > 3000);
+    }
+}
@@ -0,0 +1,201 @@
+// evaluation state that outlives the process: the current term, the steps
+// taken to reach it and the strategy taking them, so a long normalization
+// can stop and go on later from where it was. the file is text, a version
+// line and one field per line, the term unparsed as in the cache:
+//
+//   lambda_rs snapshot 1
+//   strategy normal
+//   steps 1200
+//   free x y
+//   term \a.{<<x|a>|y>}
+//
+// free names keep their order, an index into them means the same after a
+// round trip even when the term no longer uses some of them
+use std::fs;
+use std::path::Path;
+
+use crate::parser::{self, ParserConfig, Term};
+use crate::reduce::{self, Strategy};
+use crate::symbol::Symbol;
+use crate::traverse;
+use crate::unparse::unparse;
+
+// bump when the layout or the meaning of a field changes
+const VERSION: &str = "1";
+
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub term: Term,
+    pub free: Vec<Symbol>,
+    pub steps: usize, // taken to reach `term`
+    pub strategy: Strategy,
+}
+
+impl Snapshot {
+    pub fn new(term: Term, free: Vec<Symbol>, strategy: Strategy) -> Self {
+        Self {
+            term,
+            free,
+            steps: 0,
+            strategy,
+        }
+    }
+
+    // one step under the strategy, false in normal form
+    pub fn step(&mut self) -> bool {
+        match reduce::step_with(&self.term, self.strategy) {
+            Some(next) => {
+                self.term = next;
+                self.steps += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // at most `fuel` steps, true once the normal form is reached
+    pub fn run(&mut self, fuel: usize) -> bool {
+        for _ in 0..fuel {
+            if !self.step() {
+                return true;
+            }
+        }
+        reduce::next_redex(&self.term, self.strategy).is_none()
+    }
+
+    pub fn to_text(&self) -> String {
+        let strategy = match self.strategy {
+            Strategy::Normal => "normal",
+            Strategy::Applicative => "applicative",
+        };
+        format!(
+            "lambda_rs snapshot {}\nstrategy {}\nsteps {}\nfree {}\nterm {}\n",
+            VERSION,
+            strategy,
+            self.steps,
+            self.free
+                .iter()
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+            unparse(&self.term, &self.free)
+        )
+    }
+
+    pub fn parse(text: &str) -> Result<Snapshot, String> {
+        let mut lines = text.lines();
+        let mut field = |name: &str| {
+            let line = lines.next().ok_or(format!("missing `{}`", name))?;
+            match line.split_once(' ') {
+                Some((key, value)) if key == name => Ok(value.to_string()),
+                None if line == name => Ok(String::new()),
+                _ => Err(format!("expected `{}`, found `{}`", name, line)),
+            }
+        };
+        if field("lambda_rs")? != format!("snapshot {}", VERSION) {
+            return Err(format!("not a version {} snapshot", VERSION));
+        }
+        let strategy = match field("strategy")?.as_str() {
+            "normal" => Strategy::Normal,
+            "applicative" => Strategy::Applicative,
+            other => return Err(format!("unknown strategy `{}`", other)),
+        };
+        let steps = field("steps")?;
+        let steps = steps
+            .parse()
+            .map_err(|_| format!("bad step count `{}`", steps))?;
+        let mut free: Vec<Symbol> = field("free")?
+            .split_whitespace()
+            .map(Symbol::intern)
+            .collect();
+        let source = field("term")?;
+        let config = ParserConfig {
+            system_f: true,
+            ..Default::default()
+        };
+        let (term, names) = parser::parse_with_config(&source, config)?;
+        // the parser numbers free names by first use, put them back in order
+        let term = traverse::map_leaves(&term, |leaf, _| match leaf {
+            Term::Variable(index) if *index < 0 => {
+                let name = names[(-index - 1) as usize];
+                let at = match free.iter().position(|known| *known == name) {
+                    Some(at) => at,
+                    None => {
+                        free.push(name);
+                        free.len() - 1
+                    }
+                };
+                Term::Variable(-(at as i32 + 1))
+            }
+            _ => leaf.clone(),
+        });
+        Ok(Snapshot {
+            term,
+            free,
+            steps,
+            strategy,
+        })
+    }
+
+    // written next to `path` first and renamed over it, so stopping halfway
+    // through keeps the previous snapshot
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let partial = path.with_extension("partial");
+        fs::write(&partial, self.to_text())
+            .and_then(|_| fs::rename(&partial, path))
+            .map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    pub fn load(path: &Path) -> Result<Snapshot, String> {
+        let text =
+            fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        Snapshot::parse(&text).map_err(|msg| format!("{}: {}", path.display(), msg))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::church;
+    use crate::combinators::{app, apps, lam, var};
+
+    #[test]
+    fn a_resumed_run_ends_where_an_uninterrupted_one_does() {
+        let product = apps(church::mul(), [church::numeral(3), church::numeral(4)]);
+        let (normal, steps) = reduce::normalize(&product, 1000).unwrap();
+        let mut first = Snapshot::new(product, Vec::new(), Strategy::Normal);
+        assert!(!first.run(5));
+        let mut resumed = Snapshot::parse(&first.to_text()).unwrap();
+        assert_eq!(resumed.steps, 5);
+        assert!(resumed.run(1000));
+        assert_eq!((resumed.term, resumed.steps), (normal, steps));
+    }
+
+    #[test]
+    fn free_names_keep_their_indices() {
+        // the term only uses y, which stays second
+        let term = lam("a", app(var(-2), var(1)));
+        let free = vec![Symbol::intern("x"), Symbol::intern("y")];
+        let snapshot = Snapshot::new(term.clone(), free.clone(), Strategy::Applicative);
+        let text = snapshot.to_text();
+        assert_eq!(
+            text,
+            "lambda_rs snapshot 1\nstrategy applicative\nsteps 0\nfree x y\nterm \\a.{<y|a>}\n"
+        );
+        let back = Snapshot::parse(&text).unwrap();
+        assert_eq!((back.term, back.free), (term, free));
+    }
+
+    #[test]
+    fn files_round_trip_and_bad_ones_are_reported() {
+        let path = std::env::temp_dir().join(format!("lambda_rs-{}.snapshot", std::process::id()));
+        let snapshot = Snapshot::new(church::numeral(2), Vec::new(), Strategy::Normal);
+        snapshot.save(&path).unwrap();
+        assert_eq!(Snapshot::load(&path).unwrap().term, snapshot.term);
+        fs::remove_file(&path).unwrap();
+        assert!(Snapshot::load(&path).is_err());
+        assert!(Snapshot::parse("lambda_rs snapshot 0\n").is_err());
+        let text = snapshot.to_text().replace("steps 0", "steps many");
+        assert_eq!(Snapshot::parse(&text).unwrap_err(), "bad step count `many`");
+    }
+}
@@ -0,0 +1,127 @@
+// interned identifiers: every distinct name is stored once and compared as an integer
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+// names are leaked on first use, they live as long as the program. interning
+// takes the lock, reading a name back does not: names sit in chunks that are
+// never moved or freed, chunk k holding FIRST_CHUNK << k of them, and each
+// slot is written once before its symbol is handed out
+const FIRST_CHUNK: usize = 64;
+const CHUNKS: usize = 27; // room for every name a u32 can number
+
+type Chunk = Box<[OnceLock<&'static str>]>;
+
+static NAMES: [OnceLock<Chunk>; CHUNKS] = [const { OnceLock::new() }; CHUNKS];
+
+fn ids() -> &'static Mutex<HashMap<&'static str, Symbol>> {
+    static IDS: OnceLock<Mutex<HashMap<&'static str, Symbol>>> = OnceLock::new();
+    IDS.get_or_init(Default::default)
+}
+
+// the chunk a symbol's name is in and its slot there
+fn slot(id: u32) -> (usize, usize) {
+    let blocks = id as usize / FIRST_CHUNK + 1;
+    let chunk = (usize::BITS - 1 - blocks.leading_zeros()) as usize;
+    (chunk, id as usize - FIRST_CHUNK * ((1 << chunk) - 1))
+}
+
+impl Symbol {
+    pub fn intern(name: &str) -> Symbol {
+        let mut ids = ids().lock().unwrap();
+        if let Some(&symbol) = ids.get(name) {
+            return symbol;
+        }
+        let symbol = Symbol(ids.len() as u32);
+        let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+        let (chunk, at) = slot(symbol.0);
+        let chunk = NAMES[chunk]
+            .get_or_init(|| (0..FIRST_CHUNK << chunk).map(|_| OnceLock::new()).collect());
+        chunk[at]
+            .set(name)
+            .expect("a symbol's slot is written once");
+        ids.insert(name, symbol);
+        symbol
+    }
+
+    pub fn as_str(self) -> &'static str {
+        let (chunk, at) = slot(self.0);
+        NAMES[chunk]
+            .get()
+            .and_then(|chunk| chunk[at].get())
+            .expect("symbols are only made by intern")
+    }
+
+    // position in the table, dense from 0
+    pub fn id(self) -> u32 {
+        self.0
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Symbol {
+        Symbol::intern(name)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn slots_fill_each_chunk_in_turn() {
+        assert_eq!(slot(0), (0, 0));
+        assert_eq!(slot(63), (0, 63));
+        assert_eq!(slot(64), (1, 0));
+        assert_eq!(slot(191), (1, 127));
+        assert_eq!(slot(192), (2, 0));
+        assert_eq!(slot(u32::MAX), (CHUNKS - 1, FIRST_CHUNK - 1));
+    }
+
+    #[test]
+    fn names_read_back_across_threads() {
+        let workers: Vec<_> = (0..4)
+            .map(|worker| {
+                thread::spawn(move || {
+                    (0..500)
+                        .map(|n| Symbol::intern(&format!("s{}_{}", n % 300, worker % 2)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for (worker, handle) in workers.into_iter().enumerate() {
+            for (n, symbol) in handle.join().unwrap().into_iter().enumerate() {
+                assert_eq!(symbol.as_str(), format!("s{}_{}", n % 300, worker % 2));
+                assert_eq!(Symbol::intern(symbol.as_str()), symbol);
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+// synthesis from examples: every closed term up to a size, smallest first, is
+// applied to the inputs of each example and kept when the normal form is the
+// output, up to α. nothing cleverer than generate::enumerate_closed_terms is
+// behind it, so sizes past 12 or so take long
+//
+// the search is over normal forms: a candidate with a redex is skipped, when
+// it normalizes its normal form matches as well and is usually no larger. a
+// candidate that runs out of fuel on an example does not match it, nor one
+// whose term grows past GROWTH times the size of the call and the output
+use crate::generate;
+use crate::parser::{self, ParserConfig, Term};
+use crate::prelude;
+use crate::reduce;
+use crate::symbol::Symbol;
+use crate::traverse;
+
+// most of the candidates that do not normalize blow up rather than loop, and
+// each step of those costs more than the last
+const GROWTH: usize = 8;
+
+#[derive(Clone, Debug)]
+pub struct Example {
+    pub inputs: Vec<Term>, // the arguments, in order
+    pub output: Term,
+}
+
+// the candidates of at most `max_size` nodes matching every example, lazily.
+// the examples share one list of free names, with the candidates closed they
+// can only be passed through
+pub fn synthesize(
+    examples: &[Example],
+    max_size: usize,
+    fuel: usize,
+) -> impl Iterator<Item = Term> + '_ {
+    // an output without a normal form is never matched
+    let expected: Option<Vec<Term>> = examples
+        .iter()
+        .map(|example| reduce::normalize(&example.output, fuel).map(|(normal, _)| normal))
+        .collect();
+    let expected = expected.unwrap_or_default();
+    let search = if expected.len() == examples.len() {
+        max_size
+    } else {
+        0
+    };
+    generate::enumerate_closed_terms(search)
+        .filter(reduce::is_normal_form)
+        .filter(move |candidate| {
+            examples
+                .iter()
+                .zip(&expected)
+                .all(|(example, output)| matches(candidate, example, output, fuel))
+        })
+}
+
+fn matches(candidate: &Term, example: &Example, output: &Term, fuel: usize) -> bool {
+    let call = example.inputs.iter().fold(candidate.clone(), |fun, input| {
+        Term::Application(Box::new(fun), Box::new(input.clone()))
+    });
+    let bound = GROWTH * (call.size() + output.size());
+    let mut current = call;
+    for _ in 0..=fuel {
+        match reduce::step(&current) {
+            Some(next) if next.size() <= bound => current = next,
+            Some(_) => return false,
+            None => return reduce::alpha_eq(&current, output),
+        }
+    }
+    false
+}
+
+// `IN; IN => OUT`, any number of inputs, with the prelude; free names are
+// added to `free`
+pub fn parse_example(source: &str, free: &mut Vec<Symbol>) -> Result<Example, String> {
+    let (inputs, output) = source
+        .rsplit_once("=>")
+        .ok_or("expected `input; ... => output`")?;
+    let mut parse = |source: &str| {
+        let (term, names) = parser::parse_with_config(source, ParserConfig::default())?;
+        let term = prelude::resolve(&term, &names);
+        Ok::<Term, String>(traverse::map_leaves(&term, |leaf, _| match leaf {
+            Term::Variable(index) if *index < 0 => {
+                let name = names[(-index - 1) as usize];
+                let at = match free.iter().position(|known| *known == name) {
+                    Some(at) => at,
+                    None => {
+                        free.push(name);
+                        free.len() - 1
+                    }
+                };
+                Term::Variable(-(at as i32 + 1))
+            }
+            _ => leaf.clone(),
+        }))
+    };
+    let inputs = inputs
+        .split(';')
+        .filter(|input| !input.trim().is_empty())
+        .map(&mut parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    let output = parse(output)?;
+    Ok(Example { inputs, output })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::{app, k, lam, var};
+
+    fn examples(sources: &[&str]) -> Vec<Example> {
+        let mut free = Vec::new();
+        sources
+            .iter()
+            .map(|source| parse_example(source, &mut free).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn the_smallest_matching_term_comes_first() {
+        let first = examples(&["a; b => a", "b; a => b"]);
+        let found = synthesize(&first, 6, 100).next().unwrap();
+        assert!(reduce::alpha_eq(&found, &k()));
+        let swap = examples(&["f; x => <x|f>"]);
+        let found = synthesize(&swap, 6, 100).next().unwrap();
+        let expected = lam("f", lam("x", app(var(1), var(2))));
+        assert!(reduce::alpha_eq(&found, &expected));
+    }
+
+    #[test]
+    fn outputs_without_a_normal_form_match_nothing() {
+        let diverging = examples(&["a => <omega|omega>"]);
+        assert_eq!(synthesize(&diverging, 6, 100).count(), 0);
+        // and candidates may not use the example's free names
+        let constant = examples(&["a => b"]);
+        assert_eq!(synthesize(&constant, 6, 100).count(), 0);
+    }
+
+    #[test]
+    fn examples_share_their_free_names() {
+        let mut free = Vec::new();
+        let first = parse_example("x; y => x", &mut free).unwrap();
+        let second = parse_example("y => <succ|y>", &mut free).unwrap();
+        assert_eq!(free, ["x", "y"]);
+        assert_eq!(first.inputs, [var(-1), var(-2)]);
+        assert_eq!(second.inputs, [var(-2)]);
+        assert!(parse_example("x; y", &mut free).is_err());
+    }
+}
@@ -1,9 +1,11 @@
-use core::panic;
-use std::{iter::Peekable, str::Chars};
+use std::{iter::Peekable, str::CharIndices};
+
+use crate::diagnostic::Span;
+use crate::symbol::Symbol;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Token {
-    Var(String), // any valid identifier
+    Var(Symbol), // any valid identifier
     Lambda,      // '\'
     Dot,         // '.'
     LBrace,      // '{'
@@ -11,8 +13,19 @@ pub enum Token {
     Bra,         // '<'
     Delim,       // '|'
     Ket,         // '>'
+    Colon,       // ':'
+    Arrow,       // '->'
+    LParen,      // '('
+    RParen,      // ')'
+    TypeLambda,  // '/\'
+    Forall,      // '\/'
+    LBracket,    // '['
+    RBracket,    // ']'
+    Int(i64),    // integer literal, primitives feature only
+    Equals,      // '='
+    Comma,       // ','
 }
-type PIter<'a> = Peekable<Chars<'a>>;
+type PIter<'a> = Peekable<CharIndices<'a>>;
 fn ident_start(c: char) -> bool {
     c.is_ascii_alphabetic() || c == '_'
 }
@@ -23,41 +36,93 @@ fn ident_body(c: char) -> bool {
 fn consume_identifier(iter: &mut PIter, chr1: char) -> Token {
     let mut varname = String::new();
     varname.push(chr1); // already consumed
-    while let Some(chr) = iter.next_if(|&c| ident_body(c)) {
+    while let Some((_, chr)) = iter.next_if(|&(_, c)| ident_body(c)) {
         varname.push(chr);
     }
-    Token::Var(varname)
+    Token::Var(Symbol::intern(&varname))
+}
+// extract an integer literal, an error if it does not fit in i64
+#[cfg(feature = "primitives")]
+fn consume_integer(iter: &mut PIter, chr1: char) -> Result<Token, String> {
+    let mut digits = String::new();
+    digits.push(chr1); // already consumed
+    while let Some((_, chr)) = iter.next_if(|&(_, c)| c.is_ascii_digit()) {
+        digits.push(chr);
+    }
+    match digits.parse() {
+        Ok(value) => Ok(Token::Int(value)),
+        Err(_) => Err(format!("Integer literal out of range: {}", digits)),
+    }
 }
 // extract 1 exact token from the input (ignore whitespaces)
 // returns None if EOF (ignoring whitespaces)
-// panic when unknown char encountered OR invalid identifier OR something went wrong with next_if
-fn consume_token(iter: &mut PIter) -> Option<Token> {
+// error when unknown char encountered OR integer literal out of range
+fn consume_token(iter: &mut PIter) -> Result<Option<Token>, String> {
     // loop until non-whitespace or EOF
-    while iter.next_if(|c| c.is_whitespace()).is_some() {}
+    while iter.next_if(|(_, c)| c.is_whitespace()).is_some() {}
     // now iter.next is either None/EOF or a non-WS char
-    match iter.next().unwrap_or_default() {
+    let token = match iter.next().map_or('\0', |(_, chr)| chr) {
         // trivial tokens
-        '\\' => Some(Token::Lambda),
-        '.' => Some(Token::Dot),
-        '{' => Some(Token::LBrace),
-        '}' => Some(Token::RBrace),
-        '<' => Some(Token::Bra),
-        '|' => Some(Token::Delim),
-        '>' => Some(Token::Ket),
+        '\\' if iter.next_if(|&(_, c)| c == '/').is_some() => Token::Forall,
+        '\\' => Token::Lambda,
+        '/' if iter.next_if(|&(_, c)| c == '\\').is_some() => Token::TypeLambda,
+        '.' => Token::Dot,
+        '{' => Token::LBrace,
+        '}' => Token::RBrace,
+        '<' => Token::Bra,
+        '|' => Token::Delim,
+        '>' => Token::Ket,
+        ':' => Token::Colon,
+        '=' => Token::Equals,
+        ',' => Token::Comma,
+        '(' => Token::LParen,
+        ')' => Token::RParen,
+        '[' => Token::LBracket,
+        ']' => Token::RBracket,
+        '-' if iter.next_if(|&(_, c)| c == '>').is_some() => Token::Arrow,
         // identifier
-        chr if ident_start(chr) => Some(consume_identifier(iter, chr)),
+        chr if ident_start(chr) => consume_identifier(iter, chr),
+        #[cfg(feature = "primitives")]
+        chr if chr.is_ascii_digit() => consume_integer(iter, chr)?,
         // EOF, reserve for later use
-        '\0' => None,
+        '\0' => return Ok(None),
         // unknown char otherwise
-        chr => panic!("Unknown character encountered during tokenization: {}", chr),
-    }
+        chr => {
+            return Err(format!(
+                "Unknown character encountered during tokenization: {}",
+                chr
+            ));
+        }
+    };
+    Ok(Some(token))
 }
 pub fn tokenize(input: &str) -> Vec<Token> {
-    let mut iter = input.chars().peekable();
+    tokenize_spanned(input).0
+}
+// same as tokenize, plus the source span of every token
+pub fn tokenize_spanned(input: &str) -> (Vec<Token>, Vec<Span>) {
+    try_tokenize_spanned(input).unwrap_or_else(|(msg, _)| panic!("{}", msg))
+}
+
+// same, the first error comes back with the span of the text it stopped at
+pub fn try_tokenize_spanned(input: &str) -> Result<(Vec<Token>, Vec<Span>), (String, Span)> {
+    let mut iter = input.char_indices().peekable();
     let mut tokens = Vec::new();
-    // consume token with extracted func
-    while let Some(token) = consume_token(&mut iter) {
-        tokens.push(token);
+    let mut spans = Vec::new();
+    loop {
+        while iter.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let start = iter.peek().map_or(input.len(), |&(idx, _)| idx);
+        // consume token with extracted func
+        let token = consume_token(&mut iter);
+        let end = iter.peek().map_or(input.len(), |&(idx, _)| idx);
+        match token {
+            Ok(Some(token)) => {
+                tokens.push(token);
+                spans.push(Span::new(start, end));
+            }
+            Ok(None) => break,
+            Err(msg) => return Err((msg, Span::new(start, end))),
+        }
     }
-    tokens
+    Ok((tokens, spans))
 }
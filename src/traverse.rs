@@ -0,0 +1,114 @@
+// traversals with an explicit work stack, so deep terms are bounded by the heap
+// rather than the call stack
+use crate::parser::Term;
+use crate::reduce::Dir;
+use crate::symbol::Symbol;
+
+enum Frame<'a> {
+    Visit(&'a Term, i32), // with the number of term binders around it
+    Build(&'a Term),      // children are done, their results on top of `done`
+}
+
+// rebuild `term` bottom-up, `leaf` maps variables and constants given their depth
+pub fn map_leaves(term: &Term, mut leaf: impl FnMut(&Term, i32) -> Term) -> Term {
+    let mut work = vec![Frame::Visit(term, 0)];
+    let mut done: Vec<Term> = Vec::new();
+    while let Some(frame) = work.pop() {
+        match frame {
+            Frame::Visit(node, depth) => match node {
+                Term::Variable(_) | Term::Constant(_) => done.push(leaf(node, depth)),
+                Term::Lambda(_, _, body) => {
+                    work.push(Frame::Build(node));
+                    work.push(Frame::Visit(body, depth + 1));
+                }
+                Term::TypeLambda(_, body) | Term::TypeApplication(body, _) => {
+                    work.push(Frame::Build(node));
+                    work.push(Frame::Visit(body, depth));
+                }
+                Term::Application(lhs, rhs) => {
+                    work.push(Frame::Build(node));
+                    work.push(Frame::Visit(rhs, depth));
+                    work.push(Frame::Visit(lhs, depth));
+                }
+                Term::Let(_, bound, body) => {
+                    work.push(Frame::Build(node));
+                    work.push(Frame::Visit(body, depth + 1));
+                    work.push(Frame::Visit(bound, depth));
+                }
+            },
+            Frame::Build(node) => {
+                let mut pop = || Box::new(done.pop().expect("child built"));
+                let built = match node {
+                    Term::Lambda(param, annot, _) => Term::Lambda(*param, annot.clone(), pop()),
+                    Term::TypeLambda(param, _) => Term::TypeLambda(*param, pop()),
+                    Term::TypeApplication(_, ty) => Term::TypeApplication(pop(), ty.clone()),
+                    Term::Application(..) => {
+                        let rhs = pop();
+                        Term::Application(pop(), rhs)
+                    }
+                    Term::Let(name, ..) => {
+                        let body = pop();
+                        Term::Let(*name, pop(), body)
+                    }
+                    Term::Variable(_) | Term::Constant(_) => unreachable!("leaves are not built"),
+                };
+                done.push(built);
+            }
+        }
+    }
+    done.pop().expect("root built")
+}
+
+// compare two terms node by node, `same` decides on the pairs of nodes themselves
+pub fn zip_all(a: &Term, b: &Term, mut same: impl FnMut(&Term, &Term) -> bool) -> bool {
+    let mut work = vec![(a, b)];
+    while let Some((a, b)) = work.pop() {
+        if !same(a, b) {
+            return false;
+        }
+        match (a, b) {
+            (Term::Lambda(_, _, x), Term::Lambda(_, _, y))
+            | (Term::TypeLambda(_, x), Term::TypeLambda(_, y))
+            | (Term::TypeApplication(x, _), Term::TypeApplication(y, _)) => work.push((x, y)),
+            (Term::Application(f, x), Term::Application(g, y))
+            | (Term::Let(_, f, x), Term::Let(_, g, y)) => {
+                work.push((x, y));
+                work.push((f, g));
+            }
+            _ => {}
+        }
+    }
+    true
+}
+
+// move the children of `term` onto `stack`, leaving cheap leaves behind
+pub(crate) fn take_children(term: &mut Term, stack: &mut Vec<Term>) {
+    let mut take = |child: &mut Box<Term>| {
+        stack.push(std::mem::replace(&mut **child, Term::Variable(0)));
+    };
+    match term {
+        Term::Variable(_) | Term::Constant(_) => {}
+        Term::Lambda(_, _, body) | Term::TypeLambda(_, body) | Term::TypeApplication(body, _) => {
+            take(body)
+        }
+        Term::Application(lhs, rhs) | Term::Let(_, lhs, rhs) => {
+            take(lhs);
+            take(rhs);
+        }
+    }
+}
+
+// the children of `term` in order, each with the direction down to it and the
+// name it brings into scope, if any
+pub fn children(term: &Term) -> Vec<(Dir, &Term, Option<Symbol>)> {
+    match term {
+        Term::Variable(_) | Term::Constant(_) => Vec::new(),
+        Term::Lambda(param, _, body) => vec![(Dir::Body, body, Some(*param))],
+        Term::TypeLambda(_, body) => vec![(Dir::Body, body, None)],
+        Term::Application(lhs, rhs) => vec![(Dir::Fun, lhs, None), (Dir::Arg, rhs, None)],
+        Term::TypeApplication(fun, _) => vec![(Dir::Fun, fun, None)],
+        Term::Let(name, bound, body) => {
+            vec![(Dir::Arg, bound, None), (Dir::Body, body, Some(*name))]
+        }
+    }
+}
@@ -0,0 +1,505 @@
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+
+use crate::parser::Term;
+use crate::pretty_printer::PrettyPrinter;
+use crate::reduce::{self, Dir, Path, Strategy};
+use crate::snapshot::Snapshot;
+use crate::symbol::Symbol;
+use crate::traverse;
+
+const REDEX_ROWS: usize = 8;
+const HISTORY_ROWS: usize = 6;
+// header, pretty line and four pane titles
+const FIXED_ROWS: usize = 6;
+// steps between the snapshots going back replays from
+const SNAPSHOT_EVERY: usize = 16;
+
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Char(char),
+    Other,
+}
+
+struct Stepper {
+    free: Vec<Symbol>,
+    current: Term,
+    // the term after every SNAPSHOT_EVERY steps, the initial one first;
+    // earlier terms are replayed from them
+    snapshots: Vec<Snapshot>,
+    // the redex (printed) contracted by step i + 1, and where it was
+    contracted: Vec<(String, Path)>,
+    redexes: Vec<Path>,
+    selected: usize,
+    printer: PrettyPrinter,
+}
+
+impl Stepper {
+    fn new(term: Term, free: Vec<Symbol>) -> Self {
+        let snapshot = Snapshot::new(term.clone(), free.clone(), Strategy::Normal);
+        let mut stepper = Self {
+            free,
+            current: term,
+            snapshots: vec![snapshot],
+            contracted: Vec::new(),
+            redexes: Vec::new(),
+            selected: 0,
+            printer: PrettyPrinter::new(),
+        };
+        stepper.refresh();
+        stepper
+    }
+
+    fn current(&self) -> &Term {
+        &self.current
+    }
+
+    // the term after `step` steps, contracting the recorded redexes again
+    // from the last snapshot before it
+    fn replay(&self, step: usize) -> Term {
+        let snapshot = &self.snapshots[step / SNAPSHOT_EVERY];
+        self.contracted[snapshot.steps..step]
+            .iter()
+            .fold(snapshot.term.clone(), |term, (_, path)| {
+                reduce::contract(&term, path)
+            })
+    }
+
+    // recompute the redex list after the current term changed
+    fn refresh(&mut self) {
+        self.redexes = reduce::redexes(self.current());
+        self.selected = 0;
+    }
+
+    fn show_redex(&mut self, path: &[Dir]) -> String {
+        let term = &self.current;
+        let binders = reduce::binders_along(term, path);
+        let redex = reduce::subterm(term, path);
+        self.printer.format_under(redex, &binders, &self.free)
+    }
+
+    fn forward(&mut self) {
+        if let Some(path) = self.redexes.get(self.selected).cloned() {
+            let shown = self.show_redex(&path);
+            self.current = reduce::contract(self.current(), &path);
+            self.contracted.push((shown, path));
+            let steps = self.contracted.len();
+            if steps.is_multiple_of(SNAPSHOT_EVERY) {
+                let mut snapshot =
+                    Snapshot::new(self.current.clone(), self.free.clone(), Strategy::Normal);
+                snapshot.steps = steps;
+                self.snapshots.push(snapshot);
+            }
+            self.refresh();
+        }
+    }
+
+    fn backward(&mut self) {
+        if self.contracted.pop().is_some() {
+            let steps = self.contracted.len();
+            self.snapshots.truncate(steps / SNAPSHOT_EVERY + 1);
+            self.current = self.replay(steps);
+            self.refresh();
+        }
+    }
+
+    // contract the redex numbered `index + 1` in the list
+    fn pick(&mut self, index: usize) {
+        if index < self.redexes.len() {
+            self.selected = index;
+            self.forward();
+        }
+    }
+
+    fn select(&mut self, delta: isize) {
+        if !self.redexes.is_empty() {
+            let count = self.redexes.len() as isize;
+            self.selected = (self.selected as isize + delta).rem_euclid(count) as usize;
+        }
+    }
+
+    fn draw(&mut self, rows: usize, cols: usize) -> String {
+        let mut lines: Vec<String> = Vec::new();
+        let step = self.contracted.len();
+        let pretty = self.printer.format(&self.current, &self.free);
+        lines.push(title(&format!("Term (step {})", step), cols));
+        lines.push(clip(&pretty, cols));
+
+        // budget rows: redexes and history get capped panes, the tree takes the rest
+        let redex_rows = self.redexes.len().clamp(1, REDEX_ROWS);
+        let history_rows = (step + 1).min(HISTORY_ROWS);
+        let tree_rows = rows
+            .saturating_sub(FIXED_ROWS + redex_rows + history_rows)
+            .max(3);
+
+        lines.push(title("Tree", cols));
+        let highlight = self.redexes.get(self.selected).cloned();
+        // one line per node, only those that fit are built
+        let mut tree = tree_lines(&self.current, &self.free, highlight.as_deref());
+        let mut shown = 0;
+        while shown < tree_rows
+            && let Some(line) = tree.next()
+        {
+            let text = clip(&format!("{}{}", tree.prefix(cols), line.label), cols);
+            lines.push(if line.marked {
+                format!("\x1b[7m{}\x1b[0m", text)
+            } else {
+                text
+            });
+            shown += 1;
+        }
+        let size = self.current.size();
+        if size > tree_rows {
+            lines.push(clip(&format!("... {} more lines", size - tree_rows), cols));
+        }
+
+        lines.push(title(&format!("Redexes ({})", self.redexes.len()), cols));
+        if self.redexes.is_empty() {
+            lines.push("  normal form reached".to_string());
+        }
+        // keep the selection visible when there are more redexes than rows
+        let first = self.selected.saturating_sub(redex_rows - 1);
+        for idx in first..(first + redex_rows).min(self.redexes.len()) {
+            let path = self.redexes[idx].clone();
+            let marker = if idx == self.selected { '>' } else { ' ' };
+            let shown = self.show_redex(&path);
+            lines.push(clip(&format!("{} {}. {}", marker, idx + 1, shown), cols));
+        }
+
+        lines.push(title("History", cols));
+        let first = step + 1 - history_rows;
+        let mut term = self.replay(first);
+        for idx in first..=step {
+            if idx > first {
+                term = reduce::contract(&term, &self.contracted[idx - 1].1);
+            }
+            let shown = self.printer.format(&term, &self.free);
+            let entry = match self.contracted.get(idx) {
+                Some((redex, path)) => format!(
+                    "{:>3}: {}   [contracted {} at {}]",
+                    idx,
+                    shown,
+                    redex,
+                    reduce::show_path(path)
+                ),
+                None => format!("{:>3}: {}", idx, shown),
+            };
+            lines.push(clip(&entry, cols));
+        }
+
+        lines.push(title(
+            "←/b back  →/n/space step  1-9 contract that redex  ↑↓/jk select  q quit",
+            cols,
+        ));
+        let mut screen = String::from("\x1b[H\x1b[2J");
+        screen.push_str(&lines.join("\r\n"));
+        screen
+    }
+}
+
+fn clip(line: &str, cols: usize) -> String {
+    line.chars().take(cols).collect()
+}
+
+fn title(text: &str, cols: usize) -> String {
+    let head = format!("─── {} ", text);
+    let fill = cols.saturating_sub(head.chars().count());
+    clip(&format!("{}{}", head, "─".repeat(fill)), cols)
+}
+
+// one line of a term drawn as an indented tree: the node `depth` below the
+// root, whether it is its parent's last child, its label and whether it is
+// the node at the highlighted path
+pub struct TreeLine {
+    pub depth: usize,
+    pub last: bool,
+    pub label: String,
+    pub marked: bool,
+}
+
+// the lines of a term's tree in pre-order, each built when it is taken, so a
+// pane that shows a few rows costs a few rows. the nodes are drawn from a work
+// stack, see traverse, and the indentation is carried one rail per level
+pub struct TreeLines<'a> {
+    free: &'a [Symbol],
+    highlight: Option<&'a [Dir]>,
+    work: Vec<Draw<'a>>,
+    env: Vec<Symbol>,
+    path: Path,
+    // for each ancestor of the line last taken, root excluded, whether a
+    // later sibling follows it and so a rail runs down past the line
+    rails: Vec<bool>,
+    last: bool,
+}
+
+pub fn tree_lines<'a>(
+    term: &'a Term,
+    free: &'a [Symbol],
+    highlight: Option<&'a [Dir]>,
+) -> TreeLines<'a> {
+    TreeLines {
+        free,
+        highlight,
+        work: vec![Draw::Node {
+            term,
+            dir: None,
+            binder: None,
+            depth: 0,
+            last: true,
+        }],
+        env: Vec::new(),
+        path: Vec::new(),
+        rails: Vec::new(),
+        last: true,
+    }
+}
+
+impl TreeLines<'_> {
+    // the rails and connector in front of the line last taken, as much as
+    // fills `width` characters
+    pub fn prefix(&self, width: usize) -> String {
+        let Some((_, above)) = self.rails.split_last() else {
+            return String::new();
+        };
+        let mut prefix = String::new();
+        for &rail in above.iter().take(width.div_ceil(3)) {
+            prefix.push_str(if rail { "│  " } else { "   " });
+        }
+        prefix.push_str(if self.last { "└─ " } else { "├─ " });
+        prefix
+    }
+}
+
+impl Iterator for TreeLines<'_> {
+    type Item = TreeLine;
+
+    fn next(&mut self) -> Option<TreeLine> {
+        let (term, dir, binder, depth, last) = loop {
+            match self.work.pop()? {
+                Draw::Node {
+                    term,
+                    dir,
+                    binder,
+                    depth,
+                    last,
+                } => break (term, dir, binder, depth, last),
+                // the node's subtree is drawn
+                Draw::Leave { dir, binder } => {
+                    if dir {
+                        self.path.pop();
+                    }
+                    if binder {
+                        self.env.pop();
+                    }
+                }
+            }
+        };
+        self.path.extend(dir);
+        self.env.extend(binder);
+        self.work.push(Draw::Leave {
+            dir: dir.is_some(),
+            binder: binder.is_some(),
+        });
+        let label = match term {
+            Term::Variable(index) if *index < 0 => {
+                format!("${}", self.free[(-(index + 1)) as usize])
+            }
+            Term::Variable(index) => self.env[self.env.len() - (*index as usize)].to_string(),
+            Term::Lambda(param, _, _) => format!("λ{}", param),
+            Term::Application(..) => "@".to_string(),
+            Term::TypeLambda(param, _) => format!("Λ{}", param),
+            Term::TypeApplication(_, ty) => format!("@[{}]", ty),
+            Term::Constant(constant) => constant.to_string(),
+            Term::Let(name, _, _) => format!("let {}", name),
+        };
+        if depth > 0 {
+            self.rails.truncate(depth - 1);
+            self.rails.push(!last);
+        }
+        self.last = last;
+        let marked = self.highlight == Some(self.path.as_slice());
+        let children = traverse::children(term);
+        let count = children.len();
+        // the first child is drawn first
+        for (idx, (dir, child, binder)) in children.into_iter().enumerate().rev() {
+            self.work.push(Draw::Node {
+                term: child,
+                dir: Some(dir),
+                binder,
+                depth: depth + 1,
+                last: idx + 1 == count,
+            });
+        }
+        Some(TreeLine {
+            depth,
+            last,
+            label,
+            marked,
+        })
+    }
+}
+
+// a node to draw with what it adds to the path and the scope, then taking
+// those off again once its subtree is drawn
+enum Draw<'a> {
+    // `last` is whether this node is its parent's last child, true for the root
+    Node {
+        term: &'a Term,
+        dir: Option<Dir>,
+        binder: Option<Symbol>,
+        depth: usize,
+        last: bool,
+    },
+    Leave {
+        dir: bool,
+        binder: bool,
+    },
+}
+
+// ask the terminal for its size, falling back to 80x24
+fn terminal_size() -> (usize, usize) {
+    let output = Command::new("stty")
+        .arg("size")
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .output();
+    if let Ok(output) = output {
+        let text = String::from_utf8_lossy(&output.stdout);
+        let dims: Vec<usize> = text
+            .split_whitespace()
+            .filter_map(|n| n.parse().ok())
+            .collect();
+        if let [rows, cols] = dims[..] {
+            return (rows, cols);
+        }
+    }
+    (24, 80)
+}
+
+fn set_raw(raw: bool) {
+    let args: &[&str] = if raw {
+        &["-icanon", "-echo", "min", "1"]
+    } else {
+        &["icanon", "echo"]
+    };
+    let _ = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .status();
+}
+
+// the terminal in raw mode on the alternate screen for as long as this lives,
+// given back as it was when dropped, on a panic too
+struct Screen;
+
+impl Screen {
+    fn enter() -> Screen {
+        set_raw(true);
+        // alternate screen, hidden cursor
+        print!("\x1b[?1049h\x1b[?25l");
+        Screen
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        set_raw(false);
+    }
+}
+
+fn read_key(input: &mut impl Read) -> Option<Key> {
+    let mut byte = [0u8; 1];
+    input.read_exact(&mut byte).ok()?;
+    Some(match byte[0] {
+        // arrow keys arrive as ESC [ A..D
+        0x1b => {
+            let mut seq = [0u8; 2];
+            input.read_exact(&mut seq).ok()?;
+            match seq {
+                [b'[', b'A'] => Key::Up,
+                [b'[', b'B'] => Key::Down,
+                [b'[', b'C'] => Key::Right,
+                [b'[', b'D'] => Key::Left,
+                _ => Key::Other,
+            }
+        }
+        chr => Key::Char(chr as char),
+    })
+}
+
+pub fn run(term: Term, free: Vec<Symbol>) {
+    let mut stepper = Stepper::new(term, free);
+    let mut stdout = io::stdout();
+    let mut stdin = io::stdin().lock();
+    let _screen = Screen::enter();
+    loop {
+        let (rows, cols) = terminal_size();
+        print!("{}", stepper.draw(rows, cols));
+        let _ = stdout.flush();
+        match read_key(&mut stdin) {
+            Some(Key::Right | Key::Char('n' | ' ')) => stepper.forward(),
+            Some(Key::Left | Key::Char('b')) => stepper.backward(),
+            Some(Key::Up | Key::Char('k')) => stepper.select(-1),
+            Some(Key::Down | Key::Char('j')) => stepper.select(1),
+            Some(Key::Char(digit @ '1'..='9')) => stepper.pick(digit as usize - '1' as usize),
+            Some(Key::Char('q')) | None => break,
+            Some(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{self, ParserConfig};
+
+    fn parse(source: &str) -> (Term, Vec<Symbol>) {
+        parser::parse_with_config(source, ParserConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn tree_lines_draw_every_node_and_mark_the_highlight() {
+        let (term, free) = parse(r"<\x.{<x|y>}|z>");
+        let mut tree = tree_lines(&term, &free, Some(&[Dir::Fun, Dir::Body]));
+        let mut text = Vec::new();
+        let mut marked = Vec::new();
+        while let Some(line) = tree.next() {
+            if line.marked {
+                marked.push(text.len());
+            }
+            text.push(format!("{}{}", tree.prefix(80), line.label));
+        }
+        assert_eq!(
+            text,
+            [
+                "@",
+                "├─ λx",
+                "│  └─ @",
+                "│     ├─ x",
+                "│     └─ $y",
+                "└─ $z"
+            ]
+        );
+        assert_eq!(marked, [2]);
+    }
+
+    #[test]
+    fn deep_trees_draw_only_what_is_shown() {
+        let n = 100_000;
+        let (term, free) = parse(&format!("{}a{}", r"\a.{".repeat(n), "}".repeat(n)));
+        let mut tree = tree_lines(&term, &free, None);
+        let line = tree.by_ref().last().unwrap();
+        assert_eq!((line.depth, line.label.as_str()), (n, "a"));
+        assert_eq!(tree.prefix(6), "      └─ ");
+        // a normal form, so one row each for the redexes and the history
+        let screen = Stepper::new(term, free).draw(24, 80);
+        let tree_rows = 24 - FIXED_ROWS - 2;
+        assert!(screen.contains(&format!("... {} more lines", n + 1 - tree_rows)));
+    }
+}
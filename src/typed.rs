@@ -0,0 +1,83 @@
+use crate::parser::{Constant, Term};
+use crate::symbol::Symbol;
+use crate::types::{Checker, Type, TypeError};
+
+// elaborated term: every node carries its type, as returned by Checker::elaborate
+#[derive(Clone, Debug, PartialEq)]
+pub struct Typed {
+    pub ty: Type,
+    pub node: TypedNode,
+}
+
+// mirrors Term, binder types live in the type of the node (A → B for a lambda)
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypedNode {
+    Variable(i32),
+    Constant(Constant),
+    Lambda(Symbol, Box<Typed>),
+    Application(Box<Typed>, Box<Typed>),
+    TypeLambda(Symbol, Box<Typed>),
+    TypeApplication(Box<Typed>, Type),
+    Let(Symbol, Box<Typed>, Box<Typed>),
+}
+
+impl Checker {
+    // infer the type of every subterm at once
+    pub fn elaborate(&mut self, term: &Term) -> Result<Typed, TypeError> {
+        self.record_nodes = true;
+        self.infer(term)?;
+        let mut types = self.node_types().iter().cloned();
+        Ok(build(term, &mut types))
+    }
+}
+
+// pair the nodes of `term` with their types, both walked in pre-order
+fn build(term: &Term, types: &mut impl Iterator<Item = Type>) -> Typed {
+    let ty = types.next().expect("one type per node");
+    let mut sub = |child: &Term| Box::new(build(child, types));
+    let node = match term {
+        Term::Variable(index) => TypedNode::Variable(*index),
+        Term::Constant(constant) => TypedNode::Constant(*constant),
+        Term::Lambda(param, _, body) => TypedNode::Lambda(*param, sub(body)),
+        Term::Application(lhs, rhs) => {
+            let lhs = sub(lhs);
+            TypedNode::Application(lhs, sub(rhs))
+        }
+        Term::TypeLambda(param, body) => TypedNode::TypeLambda(*param, sub(body)),
+        Term::TypeApplication(fun, arg) => TypedNode::TypeApplication(sub(fun), arg.clone()),
+        Term::Let(name, bound, body) => {
+            let bound = sub(bound);
+            TypedNode::Let(*name, bound, sub(body))
+        }
+    };
+    Typed { ty, node }
+}
+
+impl Typed {
+    // back to a plain term, with every lambda annotated (Church style)
+    pub fn to_term(&self) -> Term {
+        match &self.node {
+            TypedNode::Variable(index) => Term::Variable(*index),
+            TypedNode::Constant(constant) => Term::Constant(*constant),
+            TypedNode::Lambda(param, body) => {
+                let annot = match &self.ty {
+                    Type::Arrow(arg, _) => Some((**arg).clone()),
+                    _ => None,
+                };
+                Term::Lambda(*param, annot, Box::new(body.to_term()))
+            }
+            TypedNode::Application(lhs, rhs) => {
+                Term::Application(Box::new(lhs.to_term()), Box::new(rhs.to_term()))
+            }
+            TypedNode::TypeLambda(param, body) => {
+                Term::TypeLambda(*param, Box::new(body.to_term()))
+            }
+            TypedNode::TypeApplication(fun, arg) => {
+                Term::TypeApplication(Box::new(fun.to_term()), arg.clone())
+            }
+            TypedNode::Let(name, bound, body) => {
+                Term::Let(*name, Box::new(bound.to_term()), Box::new(body.to_term()))
+            }
+        }
+    }
+}
@@ -0,0 +1,1171 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+
+use crate::diagnostic::{self, Span};
+use crate::parser::{Constant, Style, Term};
+use crate::symbol::Symbol;
+
+#[derive(Debug)]
+pub enum Type {
+    Base(Symbol),                // named type from an annotation, e.g. A
+    Var(usize),                  // unknown type, solved by unification
+    Arrow(Box<Type>, Box<Type>), // A->B
+    Forall(Symbol, Box<Type>),   // \/A.T, binds Base(A) inside T
+}
+
+// clone, equality and drop walk the type with a work stack, as Term's do
+impl Clone for Type {
+    fn clone(&self) -> Self {
+        rebuild(
+            self,
+            |_| Step::Descend,
+            |param, body| Type::Forall(param, Box::new(body)),
+        )
+    }
+}
+
+impl PartialEq for Type {
+    fn eq(&self, other: &Self) -> bool {
+        let (mut lhs, mut rhs) = (walk(self), walk(other));
+        loop {
+            match (lhs.next(), rhs.next()) {
+                (None, None) => return true,
+                (Some(a), Some(b)) => {
+                    let same = match (a, b) {
+                        (Type::Base(x), Type::Base(y)) => x == y,
+                        (Type::Var(x), Type::Var(y)) => x == y,
+                        (Type::Arrow(..), Type::Arrow(..)) => true,
+                        (Type::Forall(x, _), Type::Forall(y, _)) => x == y,
+                        _ => false,
+                    };
+                    if !same {
+                        return false;
+                    }
+                }
+                _ => return false,
+            }
+        }
+    }
+}
+
+impl Drop for Type {
+    fn drop(&mut self) {
+        let mut stack = Vec::new();
+        take_children(self, &mut stack);
+        // every popped type is dropped childless
+        while let Some(mut ty) = stack.pop() {
+            take_children(&mut ty, &mut stack);
+        }
+    }
+}
+
+// move the children of `ty` onto `stack` in order, leaving cheap leaves behind
+fn take_children(ty: &mut Type, stack: &mut Vec<Type>) {
+    match ty {
+        Type::Arrow(lhs, rhs) => {
+            stack.push(mem::replace(&mut **lhs, Type::Var(0)));
+            stack.push(mem::replace(&mut **rhs, Type::Var(0)));
+        }
+        Type::Forall(_, body) => stack.push(mem::replace(&mut **body, Type::Var(0))),
+        Type::Base(_) | Type::Var(_) => {}
+    }
+}
+
+// the nodes of `ty` in pre-order, an arrow's left side before its right
+pub(crate) fn walk(ty: &Type) -> impl Iterator<Item = &Type> {
+    let mut work = vec![ty];
+    std::iter::from_fn(move || {
+        let node = work.pop()?;
+        match node {
+            Type::Arrow(lhs, rhs) => {
+                work.push(rhs);
+                work.push(lhs);
+            }
+            Type::Forall(_, body) => work.push(body),
+            Type::Base(_) | Type::Var(_) => {}
+        }
+        Some(node)
+    })
+}
+
+// what `rebuild` does at a node
+enum Step<'a> {
+    Keep(Type),      // this in place of the node, its children not visited
+    Visit(&'a Type), // visit that in place of the node, e.g. a solved unknown
+    Descend,         // the node around its rebuilt children
+}
+
+// rebuild `ty` bottom-up over an explicit stack: `step` decides at every node
+// in pre-order, `forall` puts each \/ back around its rebuilt body
+fn rebuild<'a>(
+    ty: &'a Type,
+    mut step: impl FnMut(&'a Type) -> Step<'a>,
+    mut forall: impl FnMut(Symbol, Type) -> Type,
+) -> Type {
+    enum Frame<'a> {
+        Visit(&'a Type),
+        Arrow,
+        Forall(Symbol),
+    }
+    let mut work = vec![Frame::Visit(ty)];
+    let mut done: Vec<Type> = Vec::new();
+    while let Some(frame) = work.pop() {
+        match frame {
+            Frame::Visit(node) => match step(node) {
+                Step::Keep(ty) => done.push(ty),
+                Step::Visit(other) => work.push(Frame::Visit(other)),
+                Step::Descend => match node {
+                    Type::Base(name) => done.push(Type::Base(*name)),
+                    Type::Var(var) => done.push(Type::Var(*var)),
+                    Type::Arrow(lhs, rhs) => {
+                        work.push(Frame::Arrow);
+                        work.push(Frame::Visit(rhs));
+                        work.push(Frame::Visit(lhs));
+                    }
+                    Type::Forall(param, body) => {
+                        work.push(Frame::Forall(*param));
+                        work.push(Frame::Visit(body));
+                    }
+                },
+            },
+            Frame::Arrow => {
+                let rhs = done.pop().expect("right side built");
+                let lhs = done.pop().expect("left side built");
+                done.push(Type::Arrow(Box::new(lhs), Box::new(rhs)));
+            }
+            Frame::Forall(param) => {
+                let body = done.pop().expect("body built");
+                done.push(forall(param, body));
+            }
+        }
+    }
+    done.pop().expect("root built")
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeError {
+    pub kind: TypeErrorKind,
+    pub node: usize, // pre-order index of the offending subterm, see Parser::node_spans
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypeErrorKind {
+    Mismatch { expected: Type, actual: Type },
+    NotAFunction(Type),
+    NotPolymorphic(Type),   // type application to a non-\/ type
+    Infinite(usize, Type),  // occurs check failed: var = ...var...
+    NeedsAnnotation,        // bidirectional only: nothing to infer the type from
+    UnexpectedLambda(Type), // bidirectional only: lambda checked against a non-function type
+    Escapes(Symbol),        // a \/-bound type reaches a binder outside its \/
+    Captured(Symbol),       // bidirectional only: a \/ binds a type name already in scope
+}
+
+const GREEK: [char; 12] = ['α', 'β', 'γ', 'δ', 'ε', 'ζ', 'η', 'θ', 'ι', 'κ', 'μ', 'ν'];
+
+fn var_name(var: usize) -> String {
+    let letter = GREEK[var % GREEK.len()];
+    match var / GREEK.len() {
+        0 => letter.to_string(),
+        n => format!("{}{}", letter, n),
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        enum Piece<'a> {
+            Type(&'a Type),
+            Text(&'static str),
+        }
+        let mut work = vec![Piece::Type(self)];
+        while let Some(piece) = work.pop() {
+            match piece {
+                Piece::Text(text) => f.write_str(text)?,
+                Piece::Type(Type::Base(name)) => f.write_str(name.as_str())?,
+                Piece::Type(Type::Var(var)) => f.write_str(&var_name(*var))?,
+                Piece::Type(Type::Arrow(lhs, rhs)) => {
+                    work.push(Piece::Type(rhs));
+                    work.push(Piece::Text(" → "));
+                    if matches!(**lhs, Type::Arrow(..) | Type::Forall(..)) {
+                        work.extend([Piece::Text(")"), Piece::Type(lhs), Piece::Text("(")]);
+                    } else {
+                        work.push(Piece::Type(lhs));
+                    }
+                }
+                Piece::Type(Type::Forall(param, body)) => {
+                    write!(f, "∀{}. ", param)?;
+                    work.push(Piece::Type(body));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TypeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeErrorKind::Mismatch { expected, actual } => {
+                write!(
+                    f,
+                    "type mismatch: expected `{}`, found `{}`",
+                    expected, actual
+                )
+            }
+            TypeErrorKind::NotAFunction(ty) => write!(f, "`{}` is not a function type", ty),
+            TypeErrorKind::NotPolymorphic(ty) => {
+                write!(
+                    f,
+                    "`{}` is not a polymorphic type, cannot apply it to a type",
+                    ty
+                )
+            }
+            TypeErrorKind::Infinite(var, ty) => {
+                write!(
+                    f,
+                    "cannot construct infinite type `{} = {}`",
+                    var_name(*var),
+                    ty
+                )
+            }
+            TypeErrorKind::NeedsAnnotation => write!(f, "type annotation needed"),
+            TypeErrorKind::UnexpectedLambda(ty) => {
+                write!(f, "a function cannot have type `{}`", ty)
+            }
+            TypeErrorKind::Escapes(name) => {
+                write!(f, "type variable `{}` escapes its \\/", name)
+            }
+            TypeErrorKind::Captured(name) => {
+                write!(
+                    f,
+                    "`\\/{}` captures the type `{}` already in scope",
+                    name, name
+                )
+            }
+        }
+    }
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl TypeError {
+    // rustc-like report pointing into `source`, `spans` as returned by the parser
+    pub fn render(&self, source: &str, spans: &[Span]) -> String {
+        let (title, label, note) = match &self.kind {
+            TypeErrorKind::Mismatch { expected, actual } => (
+                "mismatched types".to_string(),
+                format!("expected `{}`, found `{}`", expected, actual),
+                Some(format!(
+                    "expected type `{}`\n   found type `{}`",
+                    expected, actual
+                )),
+            ),
+            TypeErrorKind::NotAFunction(ty) => (
+                "applied a non-function".to_string(),
+                format!("this has type `{}`, which is not a function", ty),
+                None,
+            ),
+            TypeErrorKind::NotPolymorphic(ty) => (
+                "type applied to a non-polymorphic term".to_string(),
+                format!("this has type `{}`, which is not \\/-quantified", ty),
+                None,
+            ),
+            TypeErrorKind::Infinite(..) => (
+                self.kind.to_string(),
+                "the occurs check fails here".to_string(),
+                None,
+            ),
+            TypeErrorKind::NeedsAnnotation => (
+                self.kind.to_string(),
+                "cannot infer the type of this".to_string(),
+                Some(
+                    "free variables and unannotated lambdas need their type from the context"
+                        .to_string(),
+                ),
+            ),
+            TypeErrorKind::UnexpectedLambda(ty) => (
+                "mismatched types".to_string(),
+                format!("expected `{}`, found a function", ty),
+                None,
+            ),
+            TypeErrorKind::Escapes(_) => (
+                self.kind.to_string(),
+                "the type variable is bound here".to_string(),
+                Some("a binder outside the \\/ ends up with a type mentioning it".to_string()),
+            ),
+            TypeErrorKind::Captured(_) => (
+                self.kind.to_string(),
+                "this \\/ shadows the type".to_string(),
+                Some("rename the type variable".to_string()),
+            ),
+        };
+        let span = spans.get(self.node).copied().unwrap_or_default();
+        diagnostic::render(source, span, &title, &label, note.as_deref())
+    }
+}
+
+// a type with some unknowns quantified, ML style; lambda-bound names quantify none
+struct Scheme {
+    vars: Vec<usize>,
+    ty: Type,
+}
+
+pub struct Checker {
+    // solution of each unknown, None while unsolved
+    solved: Vec<Option<Type>>,
+    // types of the enclosing binders, innermost last
+    env: Vec<Scheme>,
+    // free variables get one unknown each, shared by all occurrences
+    free: HashMap<i32, Type>,
+    // counter for the names used when comparing \/ types and opening \/s
+    rigid: usize,
+    // the names of the enclosing \/s and the rigid names standing for them,
+    // innermost last
+    scope: Vec<(Symbol, Type)>,
+    // the written name of each rigid name a \/ was opened with
+    written: HashMap<Symbol, Symbol>,
+    // pre-order index of the next node to visit
+    node: usize,
+    // type of every lambda binder in pre-order, declared or inferred
+    binders: Vec<Type>,
+    // type of every node in pre-order, filled in as nodes finish
+    nodes: Vec<Type>,
+    // whether to fill in `nodes`: a copy of the type at every node is
+    // quadratic in the depth, only elaboration and export want it
+    pub(crate) record_nodes: bool,
+    // Church requires binder annotations, Curry ignores them
+    style: Style,
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        Self {
+            solved: Vec::new(),
+            env: Vec::new(),
+            free: HashMap::new(),
+            rigid: 0,
+            scope: Vec::new(),
+            written: HashMap::new(),
+            node: 0,
+            binders: Vec::new(),
+            nodes: Vec::new(),
+            record_nodes: false,
+            style: Style::Mixed,
+        }
+    }
+
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    // keep the type of every node for node_types
+    pub fn with_node_types(mut self) -> Self {
+        self.record_nodes = true;
+        self
+    }
+
+    // principal type of a term, unannotated binders are inferred
+    pub fn infer(&mut self, term: &Term) -> Result<Type, TypeError> {
+        self.solved.clear();
+        self.env.clear();
+        self.free.clear();
+        self.scope.clear();
+        self.written.clear();
+        self.rigid = 0;
+        self.node = 0;
+        self.binders.clear();
+        self.nodes.clear();
+        let ty = self
+            .infer_term(term)
+            .map_err(|err| self.finish_error(err))?;
+        // number unknowns by the whole type first so binders agree with it
+        let mut names = HashMap::new();
+        let ty = rename(&self.resolve(&ty), &mut names);
+        let binders = std::mem::take(&mut self.binders);
+        self.binders = binders
+            .iter()
+            .map(|binder| self.restore(&rename(&self.resolve(binder), &mut names)))
+            .collect();
+        let nodes = std::mem::take(&mut self.nodes);
+        self.nodes = nodes
+            .iter()
+            .map(|node| self.restore(&rename(&self.resolve(node), &mut names)))
+            .collect();
+        Ok(ty)
+    }
+
+    // binder types of the last successfully checked term, in pre-order of the lambdas
+    pub fn binder_types(&self) -> &[Type] {
+        &self.binders
+    }
+
+    // type of every node of the last successfully checked term, in pre-order,
+    // on a checker made with_node_types
+    pub fn node_types(&self) -> &[Type] {
+        &self.nodes
+    }
+
+    // accept the term if it can be given `expected`, report a mismatch otherwise
+    pub fn check(&mut self, term: &Term, expected: &Type) -> Result<Type, TypeError> {
+        let actual = self.infer(term)?;
+        // infer renumbered its unknowns from 0, move them past any in `expected`
+        let offset = max_var(expected).map_or(0, |var| var + 1);
+        let actual = offset_vars(&actual, offset);
+        self.solved = vec![None; offset + max_var(&actual).map_or(0, |var| var + 1)];
+        self.unify(expected, &actual)
+            .map_err(|kind| self.finish_error(TypeError { kind, node: 0 }))?;
+        // specialise the recorded types to `expected` as well
+        let mut names = HashMap::new();
+        let mut tidy =
+            |ty: &Type| self.restore(&rename(&self.resolve(&offset_vars(ty, offset)), &mut names));
+        let nodes: Vec<Type> = self.nodes.iter().map(&mut tidy).collect();
+        let binders: Vec<Type> = self.binders.iter().map(&mut tidy).collect();
+        self.nodes = nodes;
+        self.binders = binders;
+        Ok(expected.clone())
+    }
+
+    fn fresh(&mut self) -> Type {
+        self.solved.push(None);
+        Type::Var(self.solved.len() - 1)
+    }
+
+    // type of `term`, its nodes numbered in pre-order from self.node. walks the
+    // term with an explicit stack: a frame per node still waiting on children
+    fn infer_term(&mut self, term: &Term) -> Result<Type, TypeError> {
+        enum Frame<'a> {
+            Visit(&'a Term),
+            Lambda(usize, Type),               // the parameter's type
+            Argument(usize, &'a Term),         // the function's type is done
+            Application(usize, usize),         // node of the argument
+            Bound(usize, &'a Term),            // the bound term's type is done
+            Let(usize),                        // the body's type is done
+            TypeLambda(usize, Symbol, Symbol), // rigid name, written name
+            TypeApplication(usize, Type),      // the type argument in scope
+        }
+        let mut work = vec![Frame::Visit(term)];
+        let mut done: Vec<Type> = Vec::new();
+        while let Some(frame) = work.pop() {
+            let (node, ty) = match frame {
+                Frame::Visit(term) => {
+                    let node = self.node;
+                    self.node += 1;
+                    let at = |kind| TypeError { kind, node };
+                    match term {
+                        Term::Constant(constant) => (node, self.constant_type(constant)),
+                        Term::Variable(index) if *index < 0 => match self.free.get(index) {
+                            Some(ty) => (node, ty.clone()),
+                            None => {
+                                let ty = self.fresh();
+                                self.free.insert(*index, ty.clone());
+                                (node, ty)
+                            }
+                        },
+                        Term::Variable(index) => {
+                            let scheme = &self.env[self.env.len() - (*index as usize)];
+                            let (vars, ty) = (scheme.vars.clone(), scheme.ty.clone());
+                            (node, self.instantiate(&vars, &ty))
+                        }
+                        Term::Lambda(_, annot, body) => {
+                            let param = match (annot, self.style) {
+                                (_, Style::Curry) => self.fresh(),
+                                (Some(ty), _) => self.in_scope(ty),
+                                (None, Style::Church) => {
+                                    return Err(at(TypeErrorKind::NeedsAnnotation));
+                                }
+                                (None, Style::Mixed) => self.fresh(),
+                            };
+                            self.binders.push(param.clone());
+                            self.env.push(Scheme {
+                                vars: Vec::new(),
+                                ty: param.clone(),
+                            });
+                            work.push(Frame::Lambda(node, param));
+                            work.push(Frame::Visit(body));
+                            continue;
+                        }
+                        Term::Application(lhs, rhs) => {
+                            work.push(Frame::Argument(node, rhs));
+                            work.push(Frame::Visit(lhs));
+                            continue;
+                        }
+                        Term::Let(_, bound, body) => {
+                            work.push(Frame::Bound(node, body));
+                            work.push(Frame::Visit(bound));
+                            continue;
+                        }
+                        // the body is checked with `param` standing for a name of its
+                        // own, so neither a type of the same name from outside nor an
+                        // unknown solved later is captured by the \/. nothing outside
+                        // may come to mention it
+                        Term::TypeLambda(param, body) => {
+                            self.rigid += 1;
+                            let rigid = Symbol::intern(&format!("{}'{}", param, self.rigid));
+                            self.written.insert(rigid, *param);
+                            self.scope.push((*param, Type::Base(rigid)));
+                            work.push(Frame::TypeLambda(node, rigid, *param));
+                            work.push(Frame::Visit(body));
+                            continue;
+                        }
+                        Term::TypeApplication(fun, arg) => {
+                            let arg = self.in_scope(arg);
+                            work.push(Frame::TypeApplication(node, arg));
+                            work.push(Frame::Visit(fun));
+                            continue;
+                        }
+                    }
+                }
+                Frame::Lambda(node, param) => {
+                    self.env.pop();
+                    let body = done.pop().expect("body inferred");
+                    (node, Type::Arrow(Box::new(param), Box::new(body)))
+                }
+                Frame::Argument(node, rhs) => {
+                    work.push(Frame::Application(node, self.node));
+                    work.push(Frame::Visit(rhs));
+                    continue;
+                }
+                Frame::Application(node, arg_node) => {
+                    let arg = done.pop().expect("argument inferred");
+                    let fun = done.pop().expect("function inferred");
+                    let mut resolved = self.resolve(&fun);
+                    match resolved {
+                        Type::Arrow(..) => {
+                            let mut sides = Vec::new();
+                            take_children(&mut resolved, &mut sides);
+                            let ret = sides.pop().expect("arrow result");
+                            let param = sides.pop().expect("arrow parameter");
+                            self.unify(&param, &arg).map_err(|kind| TypeError {
+                                kind,
+                                node: arg_node,
+                            })?;
+                            (node, ret)
+                        }
+                        Type::Var(_) => {
+                            let ret = self.fresh();
+                            self.unify(&fun, &Type::Arrow(Box::new(arg), Box::new(ret.clone())))
+                                .map_err(|kind| TypeError { kind, node })?;
+                            (node, ret)
+                        }
+                        other => {
+                            return Err(TypeError {
+                                kind: TypeErrorKind::NotAFunction(other),
+                                node: node + 1,
+                            });
+                        }
+                    }
+                }
+                // let-polymorphism: unknowns left open by the bound term are generalized
+                Frame::Bound(node, body) => {
+                    let bound = done.pop().expect("bound term inferred");
+                    let scheme = self.generalize(&bound);
+                    self.env.push(scheme);
+                    work.push(Frame::Let(node));
+                    work.push(Frame::Visit(body));
+                    continue;
+                }
+                Frame::Let(node) => {
+                    self.env.pop();
+                    (node, done.pop().expect("body inferred"))
+                }
+                Frame::TypeLambda(node, rigid, param) => {
+                    self.scope.pop();
+                    if self.mentions(rigid) {
+                        return Err(TypeError {
+                            kind: TypeErrorKind::Escapes(param),
+                            node,
+                        });
+                    }
+                    let body = done.pop().expect("body inferred");
+                    (node, Type::Forall(rigid, Box::new(body)))
+                }
+                Frame::TypeApplication(node, arg) => {
+                    let fun = done.pop().expect("function inferred");
+                    let resolved = self.resolve(&fun);
+                    let Type::Forall(param, body) = &resolved else {
+                        return Err(TypeError {
+                            kind: TypeErrorKind::NotPolymorphic(resolved),
+                            node: node + 1,
+                        });
+                    };
+                    (node, subst_type(body, *param, &arg))
+                }
+            };
+            if self.record_nodes {
+                if self.nodes.len() <= node {
+                    self.nodes.resize(node + 1, Type::Var(0));
+                }
+                self.nodes[node] = ty.clone();
+            }
+            done.push(ty);
+        }
+        Ok(done.pop().expect("root inferred"))
+    }
+
+    // a written type with the names of the enclosing \/s replaced by theirs
+    fn in_scope(&self, ty: &Type) -> Type {
+        self.scope
+            .iter()
+            .rev()
+            .fold(ty.clone(), |ty, (name, rigid)| {
+                subst_type(&ty, *name, rigid)
+            })
+    }
+
+    // whether the type of a binder in scope or of a free variable mentions
+    // the rigid name `name`, solved unknowns followed
+    fn mentions(&self, name: Symbol) -> bool {
+        let env = self.env.iter().map(|scheme| &scheme.ty);
+        env.chain(self.free.values())
+            .any(|ty| free_type_names(&self.resolve(ty)).contains(&name))
+    }
+
+    // rigid names of opened \/s back to the names they were written with
+    fn restore(&self, ty: &Type) -> Type {
+        free_type_names(ty)
+            .into_iter()
+            .fold(ty.clone(), |ty, name| match self.written.get(&name) {
+                Some(written) => subst_type(&ty, name, &Type::Base(*written)),
+                None => ty,
+            })
+    }
+
+    // quantify the unknowns of `ty` that nothing in scope refers to
+    fn generalize(&self, ty: &Type) -> Scheme {
+        let mut in_scope = Vec::new();
+        for scheme in &self.env {
+            let mut vars = Vec::new();
+            type_vars(&self.resolve(&scheme.ty), &mut vars);
+            in_scope.extend(vars.into_iter().filter(|var| !scheme.vars.contains(var)));
+        }
+        for ty in self.free.values() {
+            type_vars(&self.resolve(ty), &mut in_scope);
+        }
+        let ty = self.resolve(ty);
+        let mut vars = Vec::new();
+        type_vars(&ty, &mut vars);
+        vars.retain(|var| !in_scope.contains(var));
+        Scheme { vars, ty }
+    }
+
+    // fresh unknowns for the quantified ones
+    fn instantiate(&mut self, vars: &[usize], ty: &Type) -> Type {
+        if vars.is_empty() {
+            return ty.clone();
+        }
+        let fresh: HashMap<usize, Type> = vars.iter().map(|var| (*var, self.fresh())).collect();
+        replace_vars(&self.resolve(ty), &fresh)
+    }
+
+    fn constant_type(&mut self, constant: &Constant) -> Type {
+        let int = || Type::Base(Symbol::intern("Int"));
+        let arrow = |lhs, rhs| Type::Arrow(Box::new(lhs), Box::new(rhs));
+        match constant {
+            Constant::Int(_) => int(),
+            Constant::Bool(_) => Type::Base(Symbol::intern("Bool")),
+            Constant::Add | Constant::Mul => arrow(int(), arrow(int(), int())),
+            // every occurrence of ite gets its own result type
+            Constant::Ite => {
+                let branch = self.fresh();
+                let tail = arrow(branch.clone(), arrow(branch.clone(), branch));
+                arrow(Type::Base(Symbol::intern("Bool")), tail)
+            }
+        }
+    }
+
+    // make `actual` equal to `expected`, reporting both in full on failure
+    fn unify(&mut self, expected: &Type, actual: &Type) -> Result<(), TypeErrorKind> {
+        match self.unify_inner(expected, actual) {
+            Err(TypeErrorKind::Mismatch { .. }) => Err(TypeErrorKind::Mismatch {
+                expected: self.resolve(expected),
+                actual: self.resolve(actual),
+            }),
+            other => other,
+        }
+    }
+
+    // works through the pairs still to make equal with an explicit stack
+    fn unify_inner(&mut self, lhs: &Type, rhs: &Type) -> Result<(), TypeErrorKind> {
+        enum Goal {
+            Equal(Type, Type),
+            // the bodies of two \/ types are equal: no unknown unsolved before
+            // may have been solved with the rigid name they were opened with
+            Opened {
+                rigid: Symbol,
+                unsolved: Vec<usize>,
+                lhs: Type,
+                rhs: Type,
+            },
+        }
+        let mut goals = vec![Goal::Equal(lhs.clone(), rhs.clone())];
+        while let Some(goal) = goals.pop() {
+            let (mut lhs, mut rhs) = match goal {
+                Goal::Equal(lhs, rhs) => (self.shallow(lhs), self.shallow(rhs)),
+                Goal::Opened {
+                    rigid,
+                    unsolved,
+                    lhs,
+                    rhs,
+                } => {
+                    let escaped = unsolved.iter().any(|var| {
+                        let solution = self.resolve(&Type::Var(*var));
+                        free_type_names(&solution).contains(&rigid)
+                    });
+                    if escaped {
+                        for var in unsolved {
+                            self.solved[var] = None;
+                        }
+                        return Err(TypeErrorKind::Mismatch {
+                            expected: lhs,
+                            actual: rhs,
+                        });
+                    }
+                    continue;
+                }
+            };
+            match (&lhs, &rhs) {
+                (Type::Var(a), Type::Var(b)) if a == b => {}
+                (Type::Var(var), other) | (other, Type::Var(var)) => {
+                    let other = self.resolve(other);
+                    if occurs(*var, &other) {
+                        return Err(TypeErrorKind::Infinite(*var, other));
+                    }
+                    self.solved[*var] = Some(other);
+                }
+                (Type::Base(a), Type::Base(b)) if a == b => {}
+                (Type::Arrow(..), Type::Arrow(..)) => {
+                    let mut sides = Vec::new();
+                    take_children(&mut lhs, &mut sides);
+                    take_children(&mut rhs, &mut sides);
+                    let (r2, a2) = (sides.pop().unwrap(), sides.pop().unwrap());
+                    let (r1, a1) = (sides.pop().unwrap(), sides.pop().unwrap());
+                    goals.push(Goal::Equal(r1, r2));
+                    goals.push(Goal::Equal(a1, a2));
+                }
+                // equal up to renaming: open both bodies with the same rigid name,
+                // which no unknown may be solved with, it means nothing outside
+                (Type::Forall(p1, b1), Type::Forall(p2, b2)) => {
+                    self.rigid += 1;
+                    let rigid = Symbol::intern(&format!("{}'{}", p1, self.rigid));
+                    let name = Type::Base(rigid);
+                    let unsolved: Vec<usize> = (0..self.solved.len())
+                        .filter(|var| self.solved[*var].is_none())
+                        .collect();
+                    let bodies =
+                        Goal::Equal(subst_type(b1, *p1, &name), subst_type(b2, *p2, &name));
+                    goals.push(Goal::Opened {
+                        rigid,
+                        unsolved,
+                        lhs,
+                        rhs,
+                    });
+                    goals.push(bodies);
+                }
+                _ => {
+                    return Err(TypeErrorKind::Mismatch {
+                        expected: lhs,
+                        actual: rhs,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // follow solved unknowns at the top of a type only
+    fn shallow(&self, mut ty: Type) -> Type {
+        while let Type::Var(var) = ty
+            && let Some(solution) = &self.solved[var]
+        {
+            ty = solution.clone();
+        }
+        ty
+    }
+
+    // apply the current solution everywhere in a type
+    fn resolve(&self, ty: &Type) -> Type {
+        rebuild(
+            ty,
+            |node| match node {
+                Type::Var(var) => match &self.solved[*var] {
+                    Some(solution) => Step::Visit(solution),
+                    None => Step::Keep(Type::Var(*var)),
+                },
+                _ => Step::Descend,
+            },
+            |param, body| Type::Forall(param, Box::new(body)),
+        )
+    }
+
+    // resolve and renumber the types carried by an error so it reads on its own
+    fn finish_error(&self, err: TypeError) -> TypeError {
+        let mut names = HashMap::new();
+        let mut tidy = |ty: &Type| self.restore(&rename(&self.resolve(ty), &mut names));
+        let kind = match err.kind {
+            TypeErrorKind::Mismatch { expected, actual } => TypeErrorKind::Mismatch {
+                expected: tidy(&expected),
+                actual: tidy(&actual),
+            },
+            TypeErrorKind::NotAFunction(ty) => TypeErrorKind::NotAFunction(tidy(&ty)),
+            TypeErrorKind::NotPolymorphic(ty) => TypeErrorKind::NotPolymorphic(tidy(&ty)),
+            TypeErrorKind::NeedsAnnotation => TypeErrorKind::NeedsAnnotation,
+            TypeErrorKind::UnexpectedLambda(ty) => TypeErrorKind::UnexpectedLambda(tidy(&ty)),
+            TypeErrorKind::Escapes(name) => TypeErrorKind::Escapes(name),
+            TypeErrorKind::Captured(name) => TypeErrorKind::Captured(name),
+            TypeErrorKind::Infinite(var, ty) => {
+                let var = match tidy(&Type::Var(var)) {
+                    Type::Var(var) => var,
+                    _ => var,
+                };
+                TypeErrorKind::Infinite(var, tidy(&ty))
+            }
+        };
+        TypeError {
+            kind,
+            node: err.node,
+        }
+    }
+}
+
+impl Default for Checker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// shorthand for a one-off inference
+pub fn type_of(term: &Term) -> Result<Type, TypeError> {
+    Checker::new().infer(term)
+}
+
+fn occurs(var: usize, ty: &Type) -> bool {
+    walk(ty).any(|node| matches!(node, Type::Var(other) if *other == var))
+}
+
+// unknowns of a type in order of first appearance, without duplicates
+fn type_vars(ty: &Type, vars: &mut Vec<usize>) {
+    for node in walk(ty) {
+        if let Type::Var(var) = node
+            && !vars.contains(var)
+        {
+            vars.push(*var);
+        }
+    }
+}
+
+// `ty` with its unknowns replaced as `var` says
+fn map_vars(ty: &Type, mut var: impl FnMut(usize) -> Type) -> Type {
+    rebuild(
+        ty,
+        |node| match node {
+            Type::Var(v) => Step::Keep(var(*v)),
+            _ => Step::Descend,
+        },
+        |param, body| Type::Forall(param, Box::new(body)),
+    )
+}
+
+fn replace_vars(ty: &Type, with: &HashMap<usize, Type>) -> Type {
+    map_vars(ty, |var| with.get(&var).cloned().unwrap_or(Type::Var(var)))
+}
+
+fn max_var(ty: &Type) -> Option<usize> {
+    walk(ty)
+        .filter_map(|node| match node {
+            Type::Var(var) => Some(*var),
+            _ => None,
+        })
+        .max()
+}
+
+fn offset_vars(ty: &Type, offset: usize) -> Type {
+    map_vars(ty, |var| Type::Var(var + offset))
+}
+
+// number unknowns 0, 1, ... in order of first appearance, and give the \/s
+// the checker opened the names they were written with where that captures
+// nothing
+pub fn canonical(ty: &Type) -> Type {
+    rename(ty, &mut HashMap::new())
+}
+
+fn rename(ty: &Type, names: &mut HashMap<usize, usize>) -> Type {
+    rebuild(
+        ty,
+        |node| match node {
+            Type::Var(var) => {
+                let next = names.len();
+                Step::Keep(Type::Var(*names.entry(*var).or_insert(next)))
+            }
+            _ => Step::Descend,
+        },
+        |param, body| {
+            let Some((written, _)) = param.as_str().split_once('\'') else {
+                return Type::Forall(param, Box::new(body));
+            };
+            let free = free_type_names(&body);
+            let name = if free.iter().any(|name| *name == written) {
+                fresh_name(written, &free)
+            } else {
+                Symbol::intern(written)
+            };
+            let body = subst_type(&body, param, &Type::Base(name));
+            Type::Forall(name, Box::new(body))
+        },
+    )
+}
+
+// structural equality up to renaming of \/-bound names: a name bound on both
+// sides matches if the same \/ on the way down binds it
+pub fn same_type(lhs: &Type, rhs: &Type) -> bool {
+    // the \/s enclosing the pair, as (left name, right name)
+    let mut bound: Vec<(Symbol, Symbol)> = Vec::new();
+    let mut work = vec![(lhs, rhs, 0)];
+    while let Some((lhs, rhs, depth)) = work.pop() {
+        bound.truncate(depth);
+        match (lhs, rhs) {
+            (Type::Forall(p1, b1), Type::Forall(p2, b2)) => {
+                bound.push((*p1, *p2));
+                work.push((b1, b2, depth + 1));
+            }
+            (Type::Arrow(a1, r1), Type::Arrow(a2, r2)) => {
+                work.push((r1, r2, depth));
+                work.push((a1, a2, depth));
+            }
+            (Type::Base(a), Type::Base(b)) => {
+                let left = bound.iter().rposition(|(p, _)| p == a);
+                let right = bound.iter().rposition(|(_, p)| p == b);
+                if left != right || (left.is_none() && a != b) {
+                    return false;
+                }
+            }
+            (Type::Var(a), Type::Var(b)) if a == b => {}
+            _ => return false,
+        }
+    }
+    true
+}
+
+// names of Base types not bound by an enclosing \/, in order of appearance
+pub fn free_type_names(ty: &Type) -> Vec<Symbol> {
+    let mut names = Vec::new();
+    // the \/s enclosing the node
+    let mut bound: Vec<Symbol> = Vec::new();
+    let mut work = vec![(ty, 0)];
+    while let Some((node, depth)) = work.pop() {
+        bound.truncate(depth);
+        match node {
+            Type::Base(name) if !bound.contains(name) => names.push(*name),
+            Type::Base(_) | Type::Var(_) => {}
+            Type::Arrow(lhs, rhs) => {
+                work.push((rhs, depth));
+                work.push((lhs, depth));
+            }
+            Type::Forall(param, body) => {
+                bound.push(*param);
+                work.push((body, depth + 1));
+            }
+        }
+    }
+    names
+}
+
+// a variant of `name` that does not clash with anything in `avoid`
+fn fresh_name(name: &str, avoid: &[Symbol]) -> Symbol {
+    let mut fresh = format!("{}'", name);
+    while avoid.iter().any(|taken| *taken == fresh.as_str()) {
+        fresh.push('\'');
+    }
+    Symbol::intern(&fresh)
+}
+
+// capture-avoiding ty[name := with], only renaming a capturing \/ starts a
+// nested walk
+pub fn subst_type(ty: &Type, name: Symbol, with: &Type) -> Type {
+    let free = free_type_names(with);
+    rebuild(
+        ty,
+        |node| match node {
+            Type::Base(base) if *base == name => Step::Keep(with.clone()),
+            Type::Forall(param, _) if *param == name => Step::Keep(node.clone()),
+            Type::Forall(param, body) if free.contains(param) => {
+                let fresh = fresh_name(param.as_str(), &free);
+                let body = subst_type(body, *param, &Type::Base(fresh));
+                Step::Keep(Type::Forall(fresh, Box::new(subst_type(&body, name, with))))
+            }
+            _ => Step::Descend,
+        },
+        |param, body| Type::Forall(param, Box::new(body)),
+    )
+}
+
+// substitute a type for a type variable in every annotation of a term
+// walks the term with an explicit stack, only renaming a capturing type
+// abstraction starts a nested walk
+pub fn subst_type_in_term(term: &Term, name: Symbol, with: &Type) -> Term {
+    enum Frame<'a> {
+        Visit(&'a Term),
+        Build(&'a Term),
+    }
+    let mut work = vec![Frame::Visit(term)];
+    let mut done: Vec<Term> = Vec::new();
+    while let Some(frame) = work.pop() {
+        match frame {
+            Frame::Visit(node) => match node {
+                Term::Variable(_) | Term::Constant(_) => done.push(node.clone()),
+                Term::TypeLambda(param, _) if *param == name => done.push(node.clone()),
+                Term::TypeLambda(param, body) if free_type_names(with).contains(param) => {
+                    let fresh = fresh_name(param.as_str(), &free_type_names(with));
+                    let body = subst_type_in_term(body, *param, &Type::Base(fresh));
+                    let body = subst_type_in_term(&body, name, with);
+                    done.push(Term::TypeLambda(fresh, Box::new(body)));
+                }
+                Term::Lambda(_, _, body)
+                | Term::TypeLambda(_, body)
+                | Term::TypeApplication(body, _) => {
+                    work.push(Frame::Build(node));
+                    work.push(Frame::Visit(body));
+                }
+                Term::Application(lhs, rhs) | Term::Let(_, lhs, rhs) => {
+                    work.push(Frame::Build(node));
+                    work.push(Frame::Visit(rhs));
+                    work.push(Frame::Visit(lhs));
+                }
+            },
+            Frame::Build(node) => {
+                let mut pop = || Box::new(done.pop().expect("child built"));
+                let built = match node {
+                    Term::Lambda(param, annot, _) => Term::Lambda(
+                        *param,
+                        annot.as_ref().map(|ty| subst_type(ty, name, with)),
+                        pop(),
+                    ),
+                    Term::TypeLambda(param, _) => Term::TypeLambda(*param, pop()),
+                    Term::TypeApplication(_, arg) => {
+                        Term::TypeApplication(pop(), subst_type(arg, name, with))
+                    }
+                    Term::Application(..) => {
+                        let rhs = pop();
+                        Term::Application(pop(), rhs)
+                    }
+                    Term::Let(var, ..) => {
+                        let body = pop();
+                        Term::Let(*var, pop(), body)
+                    }
+                    Term::Variable(_) | Term::Constant(_) => unreachable!("leaves are not built"),
+                };
+                done.push(built);
+            }
+        }
+    }
+    done.pop().expect("root built")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::{app, lam, var};
+    use crate::parser::{self, ParserConfig};
+    use crate::unparse;
+
+    fn infer(source: &str) -> Result<String, TypeErrorKind> {
+        let config = ParserConfig {
+            system_f: true,
+            ..Default::default()
+        };
+        let (term, _) = parser::parse_with_config(source, config).unwrap();
+        type_of(&term)
+            .map(|ty| ty.to_string())
+            .map_err(|err| err.kind)
+    }
+
+    #[test]
+    fn type_abstraction_and_application() {
+        assert_eq!(infer(r"/\A.{\x:A.{x}}"), Ok("∀A. A → A".to_string()));
+        assert_eq!(
+            infer(r"\y:B.{</\A.{\x:A.{x}}[B]|y>}"),
+            Ok("B → B".to_string())
+        );
+        assert_eq!(
+            infer(r"<\f:\/A.A->A.{f}|/\B.{\x:B.{x}}>"),
+            Ok("∀A. A → A".to_string())
+        );
+        assert!(matches!(
+            infer(r"\x:A.{x[B]}"),
+            Err(TypeErrorKind::NotPolymorphic(_))
+        ));
+    }
+
+    #[test]
+    fn a_type_abstraction_captures_nothing_from_outside() {
+        assert_eq!(infer(r"\x:A.{/\A.{x}}"), Ok("A → ∀A'. A".to_string()));
+        assert_eq!(
+            infer(r"\y:A.{<\x:A.{/\A.{x}}|y>[B]}"),
+            Ok("A → A".to_string())
+        );
+        // an unknown solved after the \/ is closed
+        assert_eq!(
+            infer(r"\y:A.{<\x.{/\A.{x}}|y>}"),
+            Ok("A → ∀A'. A".to_string())
+        );
+    }
+
+    #[test]
+    fn a_bound_type_does_not_escape() {
+        assert_eq!(
+            infer(r"\x.{/\A.{<x|\y:A.{y}>}}"),
+            Err(TypeErrorKind::Escapes(Symbol::intern("A")))
+        );
+        // nor does the name two \/ types are compared under
+        assert!(matches!(
+            infer(r"\y.{<\f:\/A.A->A.{f}|/\B.{\x:B.{y}}>}"),
+            Err(TypeErrorKind::Mismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn lets_generalize_what_lambdas_do_not() {
+        assert_eq!(
+            infer(r"\f.{\x.{<f|<f|x>>}}"),
+            Ok("(α → α) → α → α".to_string())
+        );
+        assert_eq!(
+            infer(r"let id = \x.{x} in <id|id>"),
+            Ok("α → α".to_string())
+        );
+        assert!(matches!(
+            infer(r"<\id.{<id|id>}|\x.{x}>"),
+            Err(TypeErrorKind::Infinite(..))
+        ));
+        assert_eq!(
+            infer(r"let k = \x.{\y.{x}} in <<k|k>|\z.{z}>"),
+            Ok("α → β → α".to_string())
+        );
+        // the type of a variable bound outside stays one type
+        assert_eq!(
+            infer(r"\y.{let f = \x.{y} in <f|f>}"),
+            Ok("α → α".to_string())
+        );
+        assert!(matches!(
+            infer(r"\y.{let f = y in <f|f>}"),
+            Err(TypeErrorKind::Infinite(..))
+        ));
+    }
+
+    #[test]
+    fn deep_terms_and_types_need_no_deep_stack() {
+        let deep = (0..20_000).fold(var(1), |body, _| lam("x", body));
+        let ty = type_of(&deep).unwrap();
+        assert_eq!(ty.to_string().matches('→').count(), 20_000);
+        // unify the deep type with an unknown, then print and compare it
+        let applied = type_of(&app(lam("f", var(1)), deep)).unwrap();
+        assert_eq!(applied, ty);
+        assert!(same_type(&applied, &ty.clone()));
+        assert_eq!(unparse::unparse_type(&ty).matches("->").count(), 20_000);
+    }
+}
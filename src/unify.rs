@@ -0,0 +1,455 @@
+// higher-order pattern unification (Miller): solving a = b for meta-variables,
+// free names starting with an uppercase letter as in pattern, that may be
+// applied to arguments. a meta-variable applied to distinct bound variables,
+// <<M|x>|y>, is a pattern and gets the most general solution \x.{\y.{...}};
+// equations with other applications of it wait until a solution elsewhere
+// turns them into patterns, and fail if none does
+//
+// terms are compared up to β (each side is normalized once the solutions so
+// far are substituted, which only needs β₀ on patterns) and η, a lambda
+// against anything else is compared with the η-expansion of the other side.
+// the occurs check rejects M = <f|M> outright, and bound variables a solution
+// cannot mention are pruned from the meta-variables that would receive them
+use crate::parser::{self, ParserConfig, Term};
+use crate::pattern;
+use crate::reduce::{self, Termination};
+use crate::symbol::Symbol;
+use crate::traverse;
+use crate::types;
+
+// the solutions, by free name. `names` extends the free names given to unify
+// with the meta-variables it introduced
+#[derive(Clone, Debug)]
+pub struct MetaSubst {
+    pub names: Vec<Symbol>,
+    solutions: Vec<Option<Term>>, // by free index
+    given: usize,                 // how many names were given to unify
+}
+
+impl MetaSubst {
+    // a name given more than once is one meta-variable or constant, the
+    // indices the terms use are mapped onto `names` without the repeats
+    fn new(names: &[Symbol]) -> (MetaSubst, Vec<i32>) {
+        let mut merged: Vec<Symbol> = Vec::new();
+        let renumbered = names
+            .iter()
+            .map(|name| -(index_of(&mut merged, *name) as i32 + 1))
+            .collect();
+        let subst = MetaSubst {
+            solutions: vec![None; merged.len()],
+            given: merged.len(),
+            names: merged,
+        };
+        (subst, renumbered)
+    }
+
+    fn is_meta(&self, index: i32) -> bool {
+        index < 0 && pattern::is_meta_name(self.names[(-index - 1) as usize].as_str())
+    }
+
+    pub fn get(&self, meta: &str) -> Option<&Term> {
+        let at = self.names.iter().position(|name| *name == meta)?;
+        self.solutions[at].as_ref()
+    }
+
+    // the meta-variables of the original problem that got a solution, in
+    // order. solutions only mention meta-variables that are still open
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Term)> {
+        self.names
+            .iter()
+            .zip(&self.solutions)
+            .take(self.given)
+            .filter_map(|(name, solution)| Some((name.as_str(), solution.as_ref()?)))
+    }
+
+    // a fresh meta-variable, as a free variable
+    fn fresh(&mut self) -> Term {
+        let name = (1..)
+            .map(|n| Symbol::intern(&format!("M{}", n)))
+            .find(|name| !self.names.contains(name))
+            .unwrap();
+        self.names.push(name);
+        self.solutions.push(None);
+        Term::Variable(-(self.names.len() as i32))
+    }
+
+    fn solve(&mut self, meta: i32, solution: Term) {
+        // the redexes this makes apply a solution to bound variables, their
+        // β-normal forms are no larger
+        let fuel = reduce::default_fuel(Termination::Guaranteed);
+        for known in self.solutions.iter_mut().flatten() {
+            let substituted = substitute(known, meta, &solution);
+            *known = match reduce::normalize(&substituted, fuel) {
+                Some((normal, _)) => normal,
+                None => substituted,
+            };
+        }
+        self.solutions[(-meta - 1) as usize] = Some(solution);
+    }
+
+    // `term` with the solutions in, in β-normal form if it has one within
+    // the step limit of untyped terms
+    pub fn apply(&self, term: &Term) -> Option<Term> {
+        let term = traverse::map_leaves(term, |leaf, _| match leaf {
+            Term::Variable(index) if *index < 0 => match &self.solutions[(-index - 1) as usize] {
+                Some(solution) => solution.clone(),
+                None => leaf.clone(),
+            },
+            _ => leaf.clone(),
+        });
+        let fuel = reduce::default_fuel(Termination::MayDiverge);
+        reduce::normalize(&term, fuel).map(|(normal, _)| normal)
+    }
+}
+
+// where `name` is in `names`, added at the end if it is not
+fn index_of(names: &mut Vec<Symbol>, name: Symbol) -> usize {
+    match names.iter().position(|known| *known == name) {
+        Some(at) => at,
+        None => {
+            names.push(name);
+            names.len() - 1
+        }
+    }
+}
+
+// the free variables of `term` numbered as `renumbered` says
+fn renumber(term: &Term, renumbered: &[i32]) -> Term {
+    traverse::map_leaves(term, |leaf, _| match leaf {
+        Term::Variable(index) if *index < 0 => Term::Variable(renumbered[(-index - 1) as usize]),
+        _ => leaf.clone(),
+    })
+}
+
+// `solution` for the free variable `meta`, solutions are closed so no shifting
+fn substitute(term: &Term, meta: i32, solution: &Term) -> Term {
+    traverse::map_leaves(term, |leaf, _| match leaf {
+        Term::Variable(index) if *index == meta => solution.clone(),
+        _ => leaf.clone(),
+    })
+}
+
+// head and arguments of an application spine
+fn spine(term: &Term) -> (&Term, Vec<&Term>) {
+    let mut args = Vec::new();
+    let mut head = term;
+    while let Term::Application(fun, arg) = head {
+        args.push(&**arg);
+        head = fun;
+    }
+    args.reverse();
+    (head, args)
+}
+
+// the arguments as bound variables if they are distinct ones
+fn pattern_args(args: &[&Term]) -> Option<Vec<i32>> {
+    let mut vars = Vec::with_capacity(args.len());
+    for arg in args {
+        match arg {
+            Term::Variable(index) if *index > 0 && !vars.contains(index) => vars.push(*index),
+            _ => return None,
+        }
+    }
+    Some(vars)
+}
+
+fn lambdas(count: usize, body: Term) -> Term {
+    (0..count).fold(body, |body, _| {
+        Term::Lambda(Symbol::intern("x"), None, Box::new(body))
+    })
+}
+
+// <<head|a1>|...> with bound variables as arguments, seen under `depth`
+// binders of the solution
+fn applied(head: Term, args: impl IntoIterator<Item = i32>) -> Term {
+    args.into_iter().fold(head, |fun, arg| {
+        Term::Application(Box::new(fun), Box::new(Term::Variable(arg)))
+    })
+}
+
+// where the solution of `meta` applied to `vars` must not go: Err for an
+// occurrence of `meta` itself, or the variables outside `vars` that have to
+// be pruned from the meta-variables they are passed to
+enum Blocked {
+    Occurs,
+    Prune(i32, Vec<bool>), // the meta-variable and which arguments it keeps
+    Escapes,               // a bound variable no pruning can remove
+}
+
+// `term` as the body of \vars.{...}: its variables bound outside of it mapped
+// to the new binders, or why that is impossible
+fn abstract_over(
+    term: &Term,
+    meta: i32,
+    vars: &[i32],
+    subst: &MetaSubst,
+    depth: i32,
+) -> Result<Term, Blocked> {
+    let n = vars.len() as i32;
+    let (head, args) = spine(term);
+    if let Term::Variable(index) = head
+        && subst.is_meta(*index)
+    {
+        if *index == meta {
+            return Err(Blocked::Occurs);
+        }
+        // arguments that are variables from outside and not among `vars`
+        // can be pruned if the other meta-variable's arguments are a pattern
+        let outside = |arg: &&Term| matches!(arg, Term::Variable(i) if *i > depth && !vars.contains(&(i - depth)));
+        if args.iter().any(outside) {
+            return match pattern_args(&args) {
+                Some(_) => Err(Blocked::Prune(
+                    *index,
+                    args.iter().map(|arg| !outside(arg)).collect(),
+                )),
+                None => Err(Blocked::Escapes),
+            };
+        }
+    }
+    match term {
+        Term::Variable(index) if *index > depth => {
+            match vars.iter().position(|var| *var == index - depth) {
+                Some(at) => Ok(Term::Variable(depth + n - at as i32)),
+                None => Err(Blocked::Escapes),
+            }
+        }
+        Term::Variable(_) | Term::Constant(_) => Ok(term.clone()),
+        Term::Lambda(param, annot, body) => Ok(Term::Lambda(
+            *param,
+            annot.clone(),
+            Box::new(abstract_over(body, meta, vars, subst, depth + 1)?),
+        )),
+        Term::TypeLambda(param, body) => Ok(Term::TypeLambda(
+            *param,
+            Box::new(abstract_over(body, meta, vars, subst, depth)?),
+        )),
+        Term::Application(lhs, rhs) => Ok(Term::Application(
+            Box::new(abstract_over(lhs, meta, vars, subst, depth)?),
+            Box::new(abstract_over(rhs, meta, vars, subst, depth)?),
+        )),
+        Term::TypeApplication(fun, ty) => Ok(Term::TypeApplication(
+            Box::new(abstract_over(fun, meta, vars, subst, depth)?),
+            ty.clone(),
+        )),
+        Term::Let(name, bound, body) => Ok(Term::Let(
+            *name,
+            Box::new(abstract_over(bound, meta, vars, subst, depth)?),
+            Box::new(abstract_over(body, meta, vars, subst, depth + 1)?),
+        )),
+    }
+}
+
+// what to do with one equation
+enum Step {
+    Done,
+    Split(Vec<(Term, Term)>),
+    Stuck, // a meta-variable applied to something other than a pattern
+    Fail,
+}
+
+fn step(a: &Term, b: &Term, subst: &mut MetaSubst) -> Step {
+    if reduce::alpha_eq(a, b) {
+        return Step::Done;
+    }
+    let eta = |term: &Term| {
+        Term::Application(
+            Box::new(reduce::shift(term, 1, 0)),
+            Box::new(Term::Variable(1)),
+        )
+    };
+    match (a, b) {
+        (Term::Lambda(_, _, a), Term::Lambda(_, _, b)) => {
+            return Step::Split(vec![(*a.clone(), *b.clone())]);
+        }
+        (Term::Lambda(_, _, a), b) | (b, Term::Lambda(_, _, a)) => {
+            return Step::Split(vec![(*a.clone(), eta(b))]);
+        }
+        (Term::TypeLambda(_, a), Term::TypeLambda(_, b)) => {
+            return Step::Split(vec![(*a.clone(), *b.clone())]);
+        }
+        (Term::TypeApplication(a, s), Term::TypeApplication(b, t)) => {
+            return match types::same_type(s, t) {
+                true => Step::Split(vec![(*a.clone(), *b.clone())]),
+                false => Step::Fail,
+            };
+        }
+        _ => {}
+    }
+    let (head_a, args_a) = spine(a);
+    let (head_b, args_b) = spine(b);
+    let flex = |head: &Term| matches!(head, Term::Variable(index) if subst.is_meta(*index));
+    match (flex(head_a), flex(head_b)) {
+        (false, false) => {
+            let same_head = match (head_a, head_b) {
+                (Term::Variable(x), Term::Variable(y)) => x == y,
+                (Term::Constant(x), Term::Constant(y)) => x == y,
+                _ => false,
+            };
+            if !same_head || args_a.len() != args_b.len() {
+                return Step::Fail;
+            }
+            let pairs = args_a.into_iter().zip(args_b);
+            Step::Split(pairs.map(|(a, b)| (a.clone(), b.clone())).collect())
+        }
+        (true, true) => {
+            let (Term::Variable(m), Term::Variable(n)) = (head_a, head_b) else {
+                unreachable!("flex heads are variables")
+            };
+            let (Some(xs), Some(ys)) = (pattern_args(&args_a), pattern_args(&args_b)) else {
+                return Step::Stuck;
+            };
+            let fresh = subst.fresh();
+            if m == n {
+                // M xs = M ys: M keeps the arguments where both agree
+                if xs.len() != ys.len() {
+                    return Step::Fail;
+                }
+                let kept = (0..xs.len()).filter(|&at| xs[at] == ys[at]);
+                let body = applied(fresh, kept.map(|at| (xs.len() - at) as i32));
+                subst.solve(*m, lambdas(xs.len(), body));
+            } else {
+                // M xs = N ys: both become the fresh one on the variables they share
+                let shared: Vec<i32> = xs.iter().copied().filter(|x| ys.contains(x)).collect();
+                let under = |vars: &[i32]| {
+                    let at = |x: &i32| vars.iter().position(|var| var == x).unwrap();
+                    let args = shared.iter().map(|x| (vars.len() - at(x)) as i32);
+                    lambdas(vars.len(), applied(fresh.clone(), args))
+                };
+                let (for_m, for_n) = (under(&xs), under(&ys));
+                subst.solve(*m, for_m);
+                subst.solve(*n, for_n);
+            }
+            Step::Done
+        }
+        (true, false) | (false, true) => {
+            let (meta, args, other) = match flex(head_a) {
+                true => (head_a, args_a, b),
+                false => (head_b, args_b, a),
+            };
+            let Term::Variable(meta) = meta else {
+                unreachable!("flex heads are variables")
+            };
+            let Some(vars) = pattern_args(&args) else {
+                return Step::Stuck;
+            };
+            match abstract_over(other, *meta, &vars, subst, 0) {
+                Ok(body) => {
+                    subst.solve(*meta, lambdas(vars.len(), body));
+                    Step::Done
+                }
+                Err(Blocked::Prune(pruned, keep)) => {
+                    // N ys := \ys.{N' kept}, then the same equation again
+                    let fresh = subst.fresh();
+                    let count = keep.len();
+                    let kept = (0..count).filter(|&at| keep[at]);
+                    let body = applied(fresh, kept.map(|at| (count - at) as i32));
+                    subst.solve(pruned, lambdas(count, body));
+                    Step::Split(vec![(a.clone(), b.clone())])
+                }
+                Err(Blocked::Occurs | Blocked::Escapes) => Step::Fail,
+            }
+        }
+    }
+}
+
+// the most general solution of a = b, both with the free names `free`, None
+// if there is none or it lies outside the pattern fragment
+pub fn unify(a: &Term, b: &Term, free: &[Symbol]) -> Option<MetaSubst> {
+    let (mut subst, renumbered) = MetaSubst::new(free);
+    let mut equations = vec![(renumber(a, &renumbered), renumber(b, &renumbered))];
+    // equations waiting for other solutions, and whether any came since
+    let mut stuck: Vec<(Term, Term)> = Vec::new();
+    let mut progress = false;
+    loop {
+        let Some((a, b)) = equations.pop() else {
+            if stuck.is_empty() {
+                return Some(subst);
+            }
+            if !progress {
+                return None;
+            }
+            progress = false;
+            equations = std::mem::take(&mut stuck);
+            continue;
+        };
+        let (a, b) = (subst.apply(&a)?, subst.apply(&b)?);
+        let solved = subst.solutions.iter().flatten().count();
+        match step(&a, &b, &mut subst) {
+            Step::Done => {}
+            Step::Split(more) => equations.extend(more),
+            Step::Stuck => stuck.push((a, b)),
+            Step::Fail => return None,
+        }
+        progress |= subst.solutions.iter().flatten().count() > solved;
+    }
+}
+
+// `a == b` parsed as two terms of any language level sharing their free
+// names, without the prelude like rules
+pub fn parse(source: &str) -> Result<(Term, Term, Vec<Symbol>), String> {
+    let (a, b) = source.split_once("==").ok_or("expected `term == term`")?;
+    let parse = |source: &str| {
+        let config = ParserConfig {
+            system_f: true,
+            ..Default::default()
+        };
+        parser::parse_with_config(source, config)
+    };
+    // the parser numbers each occurrence of a free name on its own, both
+    // sides get one index per name
+    let mut free = Vec::new();
+    let mut by_name = |(term, names): (Term, Vec<Symbol>)| {
+        let renumbered: Vec<i32> = names
+            .iter()
+            .map(|name| -(index_of(&mut free, *name) as i32 + 1))
+            .collect();
+        renumber(&term, &renumbered)
+    };
+    let a = by_name(parse(a)?);
+    let b = by_name(parse(b)?);
+    Ok((a, b, free))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unparse::unparse;
+
+    fn solve(source: &str) -> Option<Vec<(String, String)>> {
+        let (a, b, free) = parse(source).unwrap();
+        let subst = unify(&a, &b, &free)?;
+        let solutions = subst
+            .iter()
+            .map(|(meta, solution)| (meta.to_string(), unparse(solution, &subst.names)))
+            .collect();
+        Some(solutions)
+    }
+
+    #[test]
+    fn a_term_unifies_with_itself() {
+        assert_eq!(solve("<<g|f>|f> == <<g|f>|f>"), Some(vec![]));
+        assert_eq!(solve(r"\x.{<<f|x>|f>} == \y.{<<f|y>|f>}"), Some(vec![]));
+    }
+
+    #[test]
+    fn a_repeated_meta_is_one_meta() {
+        assert_eq!(solve("<<g|M>|M> == <<g|a>|b>"), None);
+        assert_eq!(
+            solve("<<g|M>|M> == <<g|a>|a>"),
+            Some(vec![("M".to_string(), "a".to_string())])
+        );
+    }
+
+    #[test]
+    fn repeated_names_given_to_unify_are_merged() {
+        let free = ["M", "a", "M", "a"].map(Symbol::intern);
+        let a = Term::Application(Box::new(Term::Variable(-1)), Box::new(Term::Variable(-3)));
+        let b = Term::Application(Box::new(Term::Variable(-2)), Box::new(Term::Variable(-4)));
+        assert!(unify(&a, &b, &free).is_none());
+        let b = Term::Application(Box::new(Term::Variable(-4)), Box::new(Term::Variable(-2)));
+        assert!(unify(&a, &b, &free).is_none());
+        let subst = unify(&Term::Variable(-1), &Term::Variable(-4), &free).unwrap();
+        assert_eq!(subst.names, ["M", "a"]);
+        assert!(matches!(subst.get("M"), Some(Term::Variable(-2))));
+    }
+}
@@ -0,0 +1,254 @@
+// print a term back in the input syntax, so that parsing the result gives the
+// same term and free names again
+//
+// binders are renamed where they would capture: no binder reuses the name of an
+// enclosing one or of a free variable, and none is called let, letrec or in
+use std::collections::{HashMap, HashSet};
+
+use crate::parser::Term;
+use crate::symbol::Symbol;
+use crate::types::Type;
+
+enum Frame<'a> {
+    Enter(&'a Term),
+    Text(&'static str),
+    TypeArgument(&'a Type),
+    Bind(Bound), // the name scopes over what follows
+    Unbind,
+}
+
+const KEYWORDS: [&str; 3] = ["let", "letrec", "in"];
+
+pub fn unparse(term: &Term, free: &[Symbol]) -> String {
+    let mut out = String::new();
+    let mut env = Scope {
+        names: Vec::new(),
+        free: free.iter().map(|name| name.as_str()).collect(),
+        live: HashMap::new(),
+        next: HashMap::new(),
+    };
+    let mut work = vec![Frame::Enter(term)];
+    while let Some(frame) = work.pop() {
+        match frame {
+            Frame::Text(text) => out.push_str(text),
+            Frame::TypeArgument(ty) => {
+                out.push('[');
+                out.push_str(&unparse_type(ty));
+                out.push(']');
+            }
+            Frame::Bind(bound) => env.bind(bound),
+            Frame::Unbind => env.unbind(),
+            Frame::Enter(node) => match node {
+                Term::Variable(index) if *index < 0 => {
+                    out.push_str(free[(-index - 1) as usize].as_str())
+                }
+                Term::Variable(index) => out.push_str(env.get(*index)),
+                Term::Constant(constant) => out.push_str(&constant.to_string()),
+                Term::Lambda(param, annot, body) => {
+                    let bound = env.fresh(param.as_str());
+                    out.push('\\');
+                    out.push_str(&bound.name);
+                    if let Some(ty) = annot {
+                        out.push(':');
+                        out.push_str(&unparse_type(ty));
+                    }
+                    out.push_str(".{");
+                    env.bind(bound);
+                    work.push(Frame::Unbind);
+                    work.push(Frame::Text("}"));
+                    work.push(Frame::Enter(body));
+                }
+                Term::Application(lhs, rhs) => {
+                    out.push('<');
+                    work.push(Frame::Text(">"));
+                    work.push(Frame::Enter(rhs));
+                    work.push(Frame::Text("|"));
+                    work.push(Frame::Enter(lhs));
+                }
+                Term::TypeLambda(param, body) => {
+                    out.push_str("/\\");
+                    out.push_str(param.as_str());
+                    out.push_str(".{");
+                    work.push(Frame::Text("}"));
+                    work.push(Frame::Enter(body));
+                }
+                Term::TypeApplication(fun, ty) => {
+                    work.push(Frame::TypeArgument(ty));
+                    work.push(Frame::Enter(fun));
+                }
+                Term::Let(name, bound, body) => {
+                    let binder = env.fresh(name.as_str());
+                    out.push_str("let ");
+                    out.push_str(&binder.name);
+                    out.push_str(" = ");
+                    work.push(Frame::Unbind);
+                    work.push(Frame::Enter(body));
+                    work.push(Frame::Bind(binder));
+                    work.push(Frame::Text(" in "));
+                    work.push(Frame::Enter(bound));
+                }
+            },
+        }
+    }
+    out
+}
+
+// the names of the enclosing binders, innermost last. `live` counts the
+// binders holding each name, `next` is the first suffix to try for a base name
+struct Scope<'a> {
+    names: Vec<Bound>,
+    free: HashSet<&'a str>,
+    live: HashMap<String, usize>,
+    next: HashMap<String, usize>,
+}
+
+// a binder's name, and what `next` of its base name was before it was chosen
+struct Bound {
+    name: String,
+    base: String,
+    next: Option<usize>,
+}
+
+impl Scope<'_> {
+    fn get(&self, index: i32) -> &str {
+        &self.names[self.names.len() - index as usize].name
+    }
+
+    fn taken(&self, candidate: &str) -> bool {
+        KEYWORDS.contains(&candidate)
+            || self.free.contains(candidate)
+            || self.live.get(candidate).is_some_and(|&n| n > 0)
+    }
+
+    // `name`, or `name_1`, `name_2`, ... if it is taken. suffixes go on from
+    // the last one given to the same name, so a chain of shadowing binders
+    // takes linear time
+    fn fresh(&mut self, name: &str) -> Bound {
+        let next = self.next.get(name).copied();
+        if !self.taken(name) {
+            return Bound {
+                name: name.to_string(),
+                base: name.to_string(),
+                next,
+            };
+        }
+        let mut n = next.unwrap_or(1);
+        while self.taken(&format!("{}_{}", name, n)) {
+            n += 1;
+        }
+        self.next.insert(name.to_string(), n + 1);
+        Bound {
+            name: format!("{}_{}", name, n),
+            base: name.to_string(),
+            next,
+        }
+    }
+
+    fn bind(&mut self, bound: Bound) {
+        *self.live.entry(bound.name.clone()).or_default() += 1;
+        self.names.push(bound);
+    }
+
+    // leave the innermost binder, its suffix can be given out again
+    fn unbind(&mut self) {
+        let bound = self.names.pop().expect("a binder to leave");
+        *self.live.get_mut(&bound.name).expect("live name") -= 1;
+        match bound.next {
+            Some(next) => self.next.insert(bound.base, next),
+            None => self.next.remove(&bound.base),
+        };
+    }
+}
+
+// same order as Display for Type, with an explicit stack
+pub fn unparse_type(ty: &Type) -> String {
+    enum Piece<'a> {
+        Type(&'a Type),
+        Text(&'static str),
+    }
+    let mut out = String::new();
+    let mut work = vec![Piece::Type(ty)];
+    while let Some(piece) = work.pop() {
+        match piece {
+            Piece::Text(text) => out.push_str(text),
+            Piece::Type(Type::Base(name)) => out.push_str(name.as_str()),
+            // unknowns left by inference, as fresh base types
+            Piece::Type(Type::Var(var)) => out.push_str(&format!("t{}", var)),
+            Piece::Type(Type::Arrow(lhs, rhs)) => {
+                work.push(Piece::Type(rhs));
+                work.push(Piece::Text("->"));
+                if matches!(**lhs, Type::Arrow(..) | Type::Forall(..)) {
+                    work.extend([Piece::Text(")"), Piece::Type(lhs), Piece::Text("(")]);
+                } else {
+                    work.push(Piece::Type(lhs));
+                }
+            }
+            Piece::Type(Type::Forall(param, body)) => {
+                out.push_str(&format!("\\/{}.", param));
+                work.push(Piece::Type(body));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::{app, lam, var};
+    use crate::parser::{self, ParserConfig};
+    use crate::reduce;
+
+    fn round_trip(source: &str) -> String {
+        let config = || ParserConfig {
+            system_f: true,
+            ..Default::default()
+        };
+        let (term, free) = parser::parse_with_config(source, config()).unwrap();
+        let printed = unparse(&term, &free);
+        let (again, free_again) = parser::parse_with_config(&printed, config()).unwrap();
+        // binder names may differ
+        assert!(reduce::alpha_eq(&again, &term) && free_again == free);
+        printed
+    }
+
+    #[test]
+    fn printed_terms_parse_back_to_themselves() {
+        for source in [
+            r"<\x.{<x|y>}|\z.{z}>",
+            r"\x:A->A.{x}",
+            r"let id = \x.{x} in <id|id>",
+            r"/\A.{\x:A.{x}}[B->B]",
+        ] {
+            assert_eq!(round_trip(source), source);
+        }
+    }
+
+    #[test]
+    fn shadowing_binders_take_the_next_free_suffix() {
+        assert_eq!(round_trip(r"\x.{\x.{x}}"), r"\x.{\x_1.{x_1}}");
+        assert_eq!(round_trip(r"\x.{\x.{\x.{x}}}"), r"\x.{\x_1.{\x_2.{x_2}}}");
+        // siblings reuse the suffix
+        assert_eq!(
+            round_trip(r"\x.{<\x.{x}|\x.{x}>}"),
+            r"\x.{<\x_1.{x_1}|\x_1.{x_1}>}"
+        );
+        // a free name is never captured, keywords are never binders
+        let term = lam("y", app(var(1), var(-1)));
+        assert_eq!(unparse(&term, &[Symbol::intern("y")]), r"\y_1.{<y_1|y>}");
+        assert_eq!(unparse(&lam("in", var(1)), &[]), r"\in_1.{in_1}");
+    }
+
+    #[test]
+    fn long_chains_of_shadowing_binders_print_quickly() {
+        let n = 20_000;
+        let source = format!("{}x{}", r"\x.{".repeat(n), "}".repeat(n));
+        let printed = round_trip(&source);
+        assert!(printed.ends_with(&format!(
+            r"\x_{}.{{x_{}}}{}",
+            n - 1,
+            n - 1,
+            "}".repeat(n - 1)
+        )));
+    }
+}
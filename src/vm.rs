@@ -0,0 +1,410 @@
+// bytecode for the lazy Krivine machine, with accumulators to normalize under
+// binders (Grégoire and Leroy, "A compiled implementation of strong reduction")
+//
+//   Access n   continue with the n-th variable of the environment
+//   Free i     the free variable i, a head that takes any arguments
+//   Push a     push a thunk of the code at a with the current environment
+//   Grab b     take an argument into the environment, or stop: a lambda in
+//              weak head normal form. b names the binder for the readback
+//
+// a term compiles to straight-line blocks: the spine of an application pushes
+// its arguments, then runs its head. arguments are shared, a thunk is updated
+// with its value the first time it is entered. only terms without types and
+// constants compile, a let becomes the redex it stands for.
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::parser::Term;
+use crate::symbol::Symbol;
+use crate::types::Type;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instr {
+    Access(u32),
+    Free(i32),
+    Push(u32),
+    Grab(u32),
+}
+
+pub struct Program {
+    code: Vec<Instr>,
+    // names and annotations of the lambdas and lets, indexed by Grab
+    binders: Vec<(Symbol, Option<Type>)>,
+}
+
+type Env = Option<Rc<EnvNode>>;
+
+struct EnvNode {
+    thunk: Rc<Thunk>,
+    next: Env,
+}
+
+// environments can be as long as the term is deep, drop them a node at a time
+impl Drop for EnvNode {
+    fn drop(&mut self) {
+        let mut next = self.next.take();
+        while let Some(node) = next {
+            next = match Rc::try_unwrap(node) {
+                Ok(mut node) => node.next.take(),
+                Err(_) => None,
+            };
+        }
+    }
+}
+
+type Thunk = RefCell<State>;
+
+enum State {
+    Code(u32, Env),
+    Evaluating,
+    Value(Value),
+}
+
+#[derive(Clone)]
+enum Value {
+    Closure(u32, Env), // at a Grab
+    Accumulator(Head, Vec<Rc<Thunk>>),
+}
+
+#[derive(Clone, Copy)]
+enum Head {
+    Level(usize), // bound by the readback, counted from the outside
+    Free(i32),
+}
+
+enum Frame {
+    Argument(Rc<Thunk>),
+    Update(Rc<Thunk>),
+}
+
+enum Readback {
+    Value(Value, usize),
+    Thunk(Rc<Thunk>, usize),
+    Lambda(u32),
+    Application,
+}
+
+struct OutOfFuel;
+
+type Step = Result<Option<Value>, OutOfFuel>;
+
+struct Machine<'a> {
+    program: &'a Program,
+    steps: usize,
+    fuel: usize,
+}
+
+// None if the term has type abstractions, type applications or constants
+pub fn compile(term: &Term) -> Option<Program> {
+    let mut program = Program {
+        code: Vec::new(),
+        binders: Vec::new(),
+    };
+    // blocks still to compile, with the Push waiting for their address
+    let mut blocks: Vec<(&Term, Option<usize>)> = vec![(term, None)];
+    while let Some((mut node, pusher)) = blocks.pop() {
+        if let Some(at) = pusher {
+            program.code[at] = Instr::Push(program.code.len() as u32);
+        }
+        loop {
+            match node {
+                Term::Variable(index) if *index < 0 => {
+                    program.code.push(Instr::Free(*index));
+                    break;
+                }
+                Term::Variable(index) => {
+                    program.code.push(Instr::Access(*index as u32));
+                    break;
+                }
+                Term::Application(fun, arg) => {
+                    blocks.push((arg, Some(program.code.len())));
+                    program.code.push(Instr::Push(0));
+                    node = fun;
+                }
+                Term::Lambda(param, annot, body) => {
+                    program.code.push(Instr::Grab(program.binders.len() as u32));
+                    program.binders.push((*param, annot.clone()));
+                    node = body;
+                }
+                // let x = bound in body runs as <\x.{body}|bound>
+                Term::Let(name, bound, body) => {
+                    blocks.push((bound, Some(program.code.len())));
+                    program.code.push(Instr::Push(0));
+                    program.code.push(Instr::Grab(program.binders.len() as u32));
+                    program.binders.push((*name, None));
+                    node = body;
+                }
+                Term::Constant(_) | Term::TypeLambda(..) | Term::TypeApplication(..) => {
+                    return None;
+                }
+            }
+        }
+    }
+    Some(program)
+}
+
+impl Program {
+    pub fn code(&self) -> &[Instr] {
+        &self.code
+    }
+
+    // reduce to normal form in at most `fuel` β-steps, returning it with the
+    // steps taken; a shared argument is only reduced once
+    pub fn normalize(&self, fuel: usize) -> Option<(Term, usize)> {
+        let mut machine = Machine {
+            program: self,
+            steps: 0,
+            fuel,
+        };
+        let normal = machine.normalize().ok()?;
+        Some((normal, machine.steps))
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (at, instr) in self.code.iter().enumerate() {
+            match instr {
+                Instr::Access(index) => writeln!(f, "{:5}  access {}", at, index)?,
+                Instr::Free(index) => writeln!(f, "{:5}  free {}", at, index)?,
+                Instr::Push(target) => writeln!(f, "{:5}  push {}", at, target)?,
+                Instr::Grab(binder) => {
+                    writeln!(f, "{:5}  grab {}", at, self.binders[*binder as usize].0)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn thunk(state: State) -> Rc<Thunk> {
+    Rc::new(RefCell::new(state))
+}
+
+fn extend(thunk: Rc<Thunk>, env: &Env) -> Env {
+    Some(Rc::new(EnvNode {
+        thunk,
+        next: env.clone(),
+    }))
+}
+
+fn lookup(env: &Env, index: u32) -> Rc<Thunk> {
+    let mut node = env.as_ref().expect("Unbound variable");
+    for _ in 1..index {
+        node = node.next.as_ref().expect("Unbound variable");
+    }
+    node.thunk.clone()
+}
+
+// where the machine is: the next instruction, its environment and the stack
+struct Registers {
+    pc: u32,
+    env: Env,
+    stack: Vec<Frame>,
+}
+
+impl<'a> Machine<'a> {
+    // the instructions one at a time, each returns the weak head normal form
+    // once the stack is used up, None to go on at `regs.pc`
+    fn push(&mut self, regs: &mut Registers, target: u32) {
+        let arg = thunk(State::Code(target, regs.env.clone()));
+        regs.stack.push(Frame::Argument(arg));
+        regs.pc += 1;
+    }
+
+    fn access(&mut self, regs: &mut Registers, index: u32) -> Step {
+        let entered = lookup(&regs.env, index);
+        let state = std::mem::replace(&mut *entered.borrow_mut(), State::Evaluating);
+        match state {
+            State::Code(target, inner) => {
+                regs.stack.push(Frame::Update(entered));
+                regs.pc = target;
+                regs.env = inner;
+                Ok(None)
+            }
+            State::Value(value) => {
+                *entered.borrow_mut() = State::Value(value.clone());
+                self.feed(regs, value)
+            }
+            // it needs its own value
+            State::Evaluating => Err(OutOfFuel),
+        }
+    }
+
+    fn free(&mut self, regs: &mut Registers, index: i32) -> Step {
+        self.feed(regs, Value::Accumulator(Head::Free(index), Vec::new()))
+    }
+
+    fn grab(&mut self, regs: &mut Registers) -> Step {
+        let closure = Value::Closure(regs.pc, regs.env.clone());
+        self.feed(regs, closure)
+    }
+
+    // feed a weak head normal form what is on the stack
+    fn feed(&mut self, regs: &mut Registers, value: Value) -> Step {
+        match value {
+            Value::Closure(at, inner) => match regs.stack.pop() {
+                None => Ok(Some(Value::Closure(at, inner))),
+                Some(Frame::Update(updated)) => {
+                    *updated.borrow_mut() = State::Value(Value::Closure(at, inner.clone()));
+                    regs.pc = at;
+                    regs.env = inner;
+                    Ok(None)
+                }
+                Some(Frame::Argument(arg)) => {
+                    if self.steps == self.fuel {
+                        return Err(OutOfFuel);
+                    }
+                    self.steps += 1;
+                    regs.pc = at + 1;
+                    regs.env = extend(arg, &inner);
+                    Ok(None)
+                }
+            },
+            Value::Accumulator(head, mut args) => loop {
+                match regs.stack.pop() {
+                    None => return Ok(Some(Value::Accumulator(head, args))),
+                    Some(Frame::Argument(arg)) => args.push(arg),
+                    Some(Frame::Update(updated)) => {
+                        *updated.borrow_mut() =
+                            State::Value(Value::Accumulator(head, args.clone()));
+                    }
+                }
+            },
+        }
+    }
+
+    // run from `pc` until a weak head normal form is left with an empty stack
+    fn run(&mut self, pc: u32, env: Env, stack: Vec<Frame>) -> Result<Value, OutOfFuel> {
+        let mut regs = Registers { pc, env, stack };
+        loop {
+            let done = match self.program.code[regs.pc as usize] {
+                Instr::Push(target) => {
+                    self.push(&mut regs, target);
+                    None
+                }
+                Instr::Access(index) => self.access(&mut regs, index)?,
+                Instr::Free(index) => self.free(&mut regs, index)?,
+                Instr::Grab(_) => self.grab(&mut regs)?,
+            };
+            if let Some(value) = done {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn force(&mut self, forced: &Rc<Thunk>) -> Result<Value, OutOfFuel> {
+        let state = std::mem::replace(&mut *forced.borrow_mut(), State::Evaluating);
+        match state {
+            State::Code(pc, env) => self.run(pc, env, vec![Frame::Update(forced.clone())]),
+            State::Value(value) => {
+                *forced.borrow_mut() = State::Value(value.clone());
+                Ok(value)
+            }
+            State::Evaluating => Err(OutOfFuel),
+        }
+    }
+
+    fn normalize(&mut self) -> Result<Term, OutOfFuel> {
+        let root = self.run(0, None, Vec::new())?;
+        let mut work = vec![Readback::Value(root, 0)];
+        let mut done: Vec<Term> = Vec::new();
+        while let Some(frame) = work.pop() {
+            match frame {
+                Readback::Thunk(arg, depth) => {
+                    let value = self.force(&arg)?;
+                    work.push(Readback::Value(value, depth));
+                }
+                // run the body against a fresh variable
+                Readback::Value(Value::Closure(at, env), depth) => {
+                    let Instr::Grab(binder) = self.program.code[at as usize] else {
+                        unreachable!("closures start at a grab")
+                    };
+                    let bound = thunk(State::Value(Value::Accumulator(
+                        Head::Level(depth),
+                        Vec::new(),
+                    )));
+                    let body = self.run(at + 1, extend(bound, &env), Vec::new())?;
+                    work.push(Readback::Lambda(binder));
+                    work.push(Readback::Value(body, depth + 1));
+                }
+                Readback::Value(Value::Accumulator(head, args), depth) => {
+                    for arg in args.into_iter().rev() {
+                        work.push(Readback::Application);
+                        work.push(Readback::Thunk(arg, depth));
+                    }
+                    done.push(match head {
+                        Head::Level(level) => Term::Variable((depth - level) as i32),
+                        Head::Free(index) => Term::Variable(index),
+                    });
+                }
+                Readback::Lambda(binder) => {
+                    let (param, annot) = &self.program.binders[binder as usize];
+                    let body = done.pop().expect("body read back");
+                    done.push(Term::Lambda(*param, annot.clone(), Box::new(body)));
+                }
+                Readback::Application => {
+                    let rhs = done.pop().expect("argument read back");
+                    let lhs = done.pop().expect("function read back");
+                    done.push(Term::Application(Box::new(lhs), Box::new(rhs)));
+                }
+            }
+        }
+        Ok(done.pop().expect("root read back"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::church;
+    use crate::combinators::{app, apps, lam, omega, var};
+    use crate::reduce;
+
+    fn normalize(term: &Term, fuel: usize) -> Option<(Term, usize)> {
+        compile(term).unwrap().normalize(fuel)
+    }
+
+    #[test]
+    fn normal_forms_and_steps() {
+        let sum = apps(church::add(), [church::numeral(2), church::numeral(3)]);
+        let (normal, steps) = normalize(&sum, 1000).unwrap();
+        assert_eq!(church::to_number(&normal), Some(5));
+        assert_eq!(
+            Some(steps),
+            reduce::normalize(&sum, 1000).map(|(_, steps)| steps)
+        );
+        assert_eq!(normalize(&app(omega(), omega()), 1000), None);
+        // under a binder, with a free variable at the head
+        let eta = lam("x", app(lam("y", app(var(-1), var(1))), var(1)));
+        assert_eq!(
+            normalize(&eta, 10).unwrap(),
+            (lam("x", app(var(-1), var(1))), 1)
+        );
+    }
+
+    #[test]
+    fn a_shared_argument_is_reduced_once() {
+        let twice = lam("x", app(app(var(1), var(1)), var(1)));
+        let arg = app(lam("y", var(1)), lam("z", var(1)));
+        let term = app(twice, arg);
+        // normal order contracts <y|z> once for every x
+        assert_eq!(normalize(&term, 100).unwrap().1, 4);
+        assert_eq!(reduce::normalize(&term, 100).unwrap().1, 6);
+    }
+
+    #[test]
+    fn terms_with_types_or_constants_do_not_compile() {
+        let abstraction = Term::TypeLambda(Symbol::intern("A"), Box::new(lam("x", var(1))));
+        assert!(compile(&abstraction).is_none());
+    }
+
+    #[test]
+    fn deep_terms_are_read_back() {
+        let n = 100_000;
+        let deep = (0..n).fold(var(n), |body, _| lam("a", body));
+        let (normal, steps) = normalize(&deep, 10).unwrap();
+        assert_eq!((normal.depth(), steps), (n as usize + 1, 0));
+    }
+}